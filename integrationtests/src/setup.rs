@@ -2,7 +2,7 @@ use std::time::Duration;
 
 use crate::{bitcoin_client::BitcoinClient, lnd_client::LndClient};
 use mokshamint::{
-    config::{BtcOnchainConfig, DatabaseConfig, ServerConfig},
+    config::{BtcOnchainConfig, DatabaseConfig, Network, ServerConfig},
     lightning::LightningType,
     mint::MintBuilder,
 };
@@ -67,6 +67,7 @@ pub async fn start_mint(
         .with_lightning(ln)
         .with_btc_onchain(btc_onchain)
         .with_fee(Some((0.0, 0).into()))
+        .with_network(Network::Regtest)
         .build();
 
     mokshamint::server::run_server(mint.await.expect("Can not connect to lightning backend"))
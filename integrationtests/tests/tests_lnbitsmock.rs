@@ -67,7 +67,9 @@ pub async fn test_bolt11_lnbitsmock() -> anyhow::Result<()> {
 
     // mint some tokens
     let mint_amount = 6_000;
-    let mint_quote = wallet.create_quote_bolt11(&mint_url, mint_amount).await?;
+    let mint_quote = wallet
+        .create_quote_bolt11(&mint_url, mint_amount, CurrencyUnit::Sat)
+        .await?;
     let hash = mint_quote.clone().quote;
 
     sleep_until(Instant::now() + Duration::from_millis(1_000)).await;
@@ -87,10 +89,10 @@ pub async fn test_bolt11_lnbitsmock() -> anyhow::Result<()> {
     // pay ln-invoice
     let invoice_1000 = read_fixture("invoice_1000.txt")?;
     let quote = wallet
-        .get_melt_quote_bolt11(&mint_url, invoice_1000.clone(), CurrencyUnit::Sat)
+        .get_melt_quote_bolt11(&mint_url, invoice_1000.clone(), CurrencyUnit::Sat, None)
         .await?;
     let result_pay_invoice = wallet
-        .pay_invoice(wallet_keyset, &quote, invoice_1000)
+        .pay_invoice(wallet_keyset, &quote, invoice_1000, false)
         .await;
     if result_pay_invoice.is_err() {
         println!("error in pay_invoice{:?}", result_pay_invoice);
@@ -101,13 +103,13 @@ pub async fn test_bolt11_lnbitsmock() -> anyhow::Result<()> {
 
     // receive 10 sats
     let token_10: moksha_core::token::TokenV3 = read_fixture("token_10.cashu")?.try_into()?;
-    let result_receive = wallet.receive_tokens(wallet_keyset, &token_10).await;
+    let result_receive = wallet.receive_tokens(wallet_keyset, &token_10, false).await;
     assert!(result_receive.is_ok());
     let balance = wallet.get_balance().await?;
     assert_eq!(5_010, balance);
 
     // send 10 tokens
-    let result_send = wallet.send_tokens(wallet_keyset, 10).await;
+    let result_send = wallet.send_tokens(wallet_keyset, 10, false).await;
     assert!(result_send.is_ok());
     assert_eq!(10, result_send.unwrap().total_amount());
     let balance = wallet.get_balance().await?;
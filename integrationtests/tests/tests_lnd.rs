@@ -113,7 +113,9 @@ async fn test_btc_onchain_mint_melt() -> anyhow::Result<()> {
         .await?;
 
     let first_quote = melt_quotes.first().expect("No quote returned from mint");
-    let result = wallet.pay_onchain(wallet_keyset, first_quote).await?;
+    let result = wallet
+        .pay_onchain(wallet_keyset, first_quote, false)
+        .await?;
 
     // FIXME check for all states in the melt_quotes
     //assert!(!result.state);
@@ -184,7 +186,9 @@ async fn test_bolt11_mint() -> anyhow::Result<()> {
 
     // mint some tokens
     let mint_amount = 6_000;
-    let mint_quote = wallet.create_quote_bolt11(&mint_url, mint_amount).await?;
+    let mint_quote = wallet
+        .create_quote_bolt11(&mint_url, mint_amount, CurrencyUnit::Sat)
+        .await?;
     let hash = mint_quote.clone().quote;
 
     sleep_until(Instant::now() + Duration::from_millis(1_000)).await;
@@ -205,10 +209,10 @@ async fn test_bolt11_mint() -> anyhow::Result<()> {
     let wallet_lnd = LndClient::new_wallet_lnd().await?;
     let invoice_1000 = wallet_lnd.create_invoice(1_000).await?;
     let quote = wallet
-        .get_melt_quote_bolt11(&mint_url, invoice_1000.clone(), CurrencyUnit::Sat)
+        .get_melt_quote_bolt11(&mint_url, invoice_1000.clone(), CurrencyUnit::Sat, None)
         .await?;
     let result_pay_invoice = wallet
-        .pay_invoice(wallet_keyset, &quote, invoice_1000)
+        .pay_invoice(wallet_keyset, &quote, invoice_1000, false)
         .await;
     if result_pay_invoice.is_err() {
         println!("error in pay_invoice{:?}", result_pay_invoice);
@@ -218,7 +222,7 @@ async fn test_bolt11_mint() -> anyhow::Result<()> {
     assert_eq!(5_000, balance);
 
     // send tokens
-    let exported_tokens = wallet.send_tokens(wallet_keyset, 100).await?;
+    let exported_tokens = wallet.send_tokens(wallet_keyset, 100, false).await?;
     assert_eq!(100, exported_tokens.total_amount());
     let balance = wallet.get_balance().await?;
     assert_eq!(4_900, balance);
@@ -280,7 +284,9 @@ async fn test_bolt11_send() -> anyhow::Result<()> {
 
     // mint some tokens
     let mint_amount = 2_000;
-    let mint_quote = wallet.create_quote_bolt11(&mint_url, mint_amount).await?;
+    let mint_quote = wallet
+        .create_quote_bolt11(&mint_url, mint_amount, CurrencyUnit::Sat)
+        .await?;
     let hash = mint_quote.clone().quote;
 
     sleep_until(Instant::now() + Duration::from_millis(1_000)).await;
@@ -298,7 +304,7 @@ async fn test_bolt11_send() -> anyhow::Result<()> {
     assert_eq!(2_000, balance);
 
     // send tokens
-    let exported_tokens = wallet.send_tokens(wallet_keyset, 100).await?;
+    let exported_tokens = wallet.send_tokens(wallet_keyset, 100, false).await?;
     assert_eq!(100, exported_tokens.total_amount());
     let balance = wallet.get_balance().await?;
     assert_eq!(1_900, balance);
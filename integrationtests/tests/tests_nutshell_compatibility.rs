@@ -39,7 +39,9 @@ pub async fn test_nutshell_compatibility() -> anyhow::Result<()> {
 
     // mint some tokens
     let mint_amount = 6_000;
-    let mint_quote = wallet.create_quote_bolt11(&mint_url, mint_amount).await?;
+    let mint_quote = wallet
+        .create_quote_bolt11(&mint_url, mint_amount, CurrencyUnit::Sat)
+        .await?;
     let hash = mint_quote.clone().quote;
 
     sleep_until(Instant::now() + Duration::from_millis(1_000)).await;
@@ -59,11 +61,11 @@ pub async fn test_nutshell_compatibility() -> anyhow::Result<()> {
     // pay ln-invoice (10_000 invoice + 10 sats fee_reserve / 9 sats get returned)
     let invoice_1000 = read_fixture("invoice_1000.txt")?;
     let quote = wallet
-        .get_melt_quote_bolt11(&mint_url, invoice_1000.clone(), CurrencyUnit::Sat)
+        .get_melt_quote_bolt11(&mint_url, invoice_1000.clone(), CurrencyUnit::Sat, None)
         .await?;
     assert_eq!(10, quote.fee_reserve);
     let result_pay_invoice = wallet
-        .pay_invoice(wallet_keyset, &quote, invoice_1000)
+        .pay_invoice(wallet_keyset, &quote, invoice_1000, false)
         .await;
 
     if result_pay_invoice.is_err() {
@@ -0,0 +1,105 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use moksha_core::primitives::{CurrencyUnit, PaymentMethod};
+use moksha_core::token::TokenV3;
+use moksha_wallet::http::CrossPlatformHttpClient;
+use moksha_wallet::localstore::sqlite::SqliteLocalStore;
+use moksha_wallet::wallet::WalletBuilder;
+use mokshacli::cli::{parse_batch_file, send_batch_entry};
+
+use itests::setup::start_mint;
+use mokshamint::lightning::{lnbits::LnbitsLightningSettings, LightningType};
+use reqwest::Url;
+use testcontainers::runners::AsyncRunner;
+use testcontainers::ImageExt;
+use testcontainers_modules::postgres::Postgres;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+pub async fn test_send_batch() -> anyhow::Result<()> {
+    let node = Postgres::default()
+        .with_host_auth()
+        .with_tag("16.6-alpine")
+        .start()
+        .await?;
+    let host_port = node.get_host_port_ipv4(5432).await?;
+
+    let _lnbits_thread = tokio::spawn(async {
+        let _ = itests::lnbitsmock::run_server(6100).await;
+    });
+
+    let _server_thread = tokio::spawn(async move {
+        let ln = LightningType::Lnbits(LnbitsLightningSettings::new(
+            "my_admin_key",
+            "http://127.0.0.1:6100",
+        ));
+
+        start_mint(host_port, ln, None)
+            .await
+            .expect("Could not start mint server");
+    });
+
+    // Wait for the server to start
+    tokio::time::sleep(Duration::from_millis(800)).await;
+
+    let mint_url = Url::parse("http://127.0.0.1:8686")?;
+    let wallet = WalletBuilder::default()
+        .with_client(CrossPlatformHttpClient::new())
+        .with_localstore(SqliteLocalStore::with_in_memory().await?)
+        .build()
+        .await?;
+    let wallet_keysets = wallet.add_mint_keysets(&mint_url).await?;
+    let wallet_keyset = wallet_keysets.first().unwrap(); // FIXME
+
+    let mint_quote = wallet
+        .create_quote_bolt11(&mint_url, 1_000, CurrencyUnit::Sat)
+        .await?;
+    tokio::time::sleep(Duration::from_millis(1_000)).await;
+    wallet
+        .mint_tokens(
+            wallet_keyset,
+            &PaymentMethod::Bolt11,
+            1_000.into(),
+            mint_quote.quote,
+        )
+        .await?;
+
+    let pubkey = "0249098aa8b9d2fbec49ff8598feb17b592b986e62319a4fa488a3dc36387157a7";
+    let entries = parse_batch_file(&format!("100 alice\n21 bob {pubkey}\n42\n"))?;
+    let mut output = Vec::new();
+    let mut tokens = vec![];
+    for (label, entry) in ["alice", "bob", "#3"].iter().zip(entries.iter()) {
+        tokens.push(send_batch_entry(&wallet, wallet_keyset, label, entry, &mut output).await?);
+    }
+
+    // every token is written to the output with its label and amount
+    let lines = String::from_utf8(output)?;
+    let lines = lines.lines().collect::<Vec<_>>();
+    assert_eq!(3, lines.len());
+    for (((line, token), label), amount) in lines
+        .iter()
+        .zip(tokens.iter())
+        .zip(["alice", "bob", "#3"])
+        .zip([100, 21, 42])
+    {
+        assert_eq!(format!("{label} {amount} {token}"), *line);
+        let token = TokenV3::from_str(token)?;
+        assert_eq!(amount, token.total_amount());
+        assert_eq!(Some(mint_url.clone()), token.mint());
+    }
+
+    // only the token of bob is locked to the pubkey
+    let is_locked = |token: &str| -> anyhow::Result<bool> {
+        Ok(TokenV3::from_str(token)?
+            .proofs()
+            .proofs()
+            .into_iter()
+            .all(|proof| proof.secret.contains(pubkey)))
+    };
+    assert!(!is_locked(&tokens[0])?);
+    assert!(is_locked(&tokens[1])?);
+    assert!(!is_locked(&tokens[2])?);
+
+    assert_eq!(1_000 - 100 - 21 - 42, wallet.get_balance().await?);
+    Ok(())
+}
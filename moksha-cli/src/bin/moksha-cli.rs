@@ -1,23 +1,27 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use console::{style, Term};
 use dialoguer::{theme::ColorfulTheme, Confirm, Select};
 use moksha_core::primitives::{
-    CurrencyUnit, MeltBtcOnchainState, PaymentMethod, PostMeltBtcOnchainResponse,
+    CurrencyUnit, KeysResponse, MeltBtcOnchainState, PaymentMethod, PostMeltBtcOnchainResponse,
     PostMintQuoteBolt11Response, PostMintQuoteBtcOnchainResponse,
 };
 use moksha_core::token::TokenV3;
 use moksha_wallet::client::CashuClient;
 
-use moksha_wallet::http::CrossPlatformHttpClient;
+use moksha_wallet::http::{CrossPlatformHttpClient, HttpClientConfig};
 
-use moksha_wallet::localstore::WalletKeysetFilter;
-use mokshacli::cli::{self, choose_mint, get_mints_with_balance};
+use moksha_wallet::localstore::{Operation, WalletKeysetFilter, DEFAULT_ACCOUNT};
+use moksha_wallet::wallet::Bolt11ReceiveState;
+use mokshacli::cli::{self, choose_mint};
 use num_format::{Locale, ToFormattedString};
 use qrcode::render::unicode;
 use qrcode::QrCode;
+use secp256k1::PublicKey;
 
+use std::io::Write;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use url::Url;
 
@@ -27,35 +31,235 @@ struct Opts {
     #[clap(short, long)]
     db_dir: Option<PathBuf>,
 
+    /// Account to use. Each account has its own seed, mints and balance
+    #[clap(long, env = "MOKSHA_ACCOUNT", default_value = DEFAULT_ACCOUNT)]
+    account: String,
+
+    #[clap(flatten)]
+    http: HttpOpts,
+
     #[clap(subcommand)]
     command: Command,
 }
 
+// connection settings of the http client, see `HttpClientConfig`
+#[derive(Args)]
+struct HttpOpts {
+    /// Seconds after which idle connections to a mint are closed. 0 keeps them open
+    #[clap(long, env = "MOKSHA_POOL_IDLE_TIMEOUT", default_value_t = 90)]
+    pool_idle_timeout: u64,
+
+    /// Max number of idle connections kept open per mint
+    #[clap(long, env = "MOKSHA_POOL_MAX_IDLE_PER_HOST")]
+    pool_max_idle_per_host: Option<usize>,
+
+    /// Interval of HTTP/2 keep-alive pings in seconds. Omit it to disable the pings
+    #[clap(long, env = "MOKSHA_HTTP2_KEEP_ALIVE_INTERVAL")]
+    http2_keep_alive_interval: Option<u64>,
+
+    /// Number of retries of GET requests after network errors and 5xx responses
+    #[clap(long, env = "MOKSHA_MAX_RETRIES", default_value_t = 2)]
+    max_retries: u32,
+
+    /// Delay before the first retry in milliseconds, doubled for each further retry
+    #[clap(long, env = "MOKSHA_RETRY_BASE_DELAY", default_value_t = 200)]
+    retry_base_delay: u64,
+}
+
+impl From<&HttpOpts> for HttpClientConfig {
+    fn from(opts: &HttpOpts) -> Self {
+        let default = Self::default();
+        Self {
+            pool_idle_timeout: (opts.pool_idle_timeout > 0)
+                .then(|| Duration::from_secs(opts.pool_idle_timeout)),
+            pool_max_idle_per_host: opts
+                .pool_max_idle_per_host
+                .unwrap_or(default.pool_max_idle_per_host),
+            http2_keep_alive_interval: opts.http2_keep_alive_interval.map(Duration::from_secs),
+            max_retries: opts.max_retries,
+            retry_base_delay: Duration::from_millis(opts.retry_base_delay),
+        }
+    }
+}
+
 #[derive(Subcommand, Clone)]
 enum Command {
     /// Mint tokens
-    Mint { amount: u64 },
-
-    /// Pay Lightning invoice
-    Pay { invoice: String },
+    Mint {
+        amount: u64,
+        /// Currency unit of the tokens, the mint needs a keyset for this unit. Asks if the mints
+        /// offer more than one unit
+        #[clap(long)]
+        unit: Option<CurrencyUnit>,
+        /// Let a LNURL-withdraw service pay the invoice of the mint quote
+        #[clap(long)]
+        from_lnurl: Option<String>,
+        /// Tag the minted tokens for coin control, e.g. `savings`
+        #[clap(long)]
+        tag: Option<String>,
+    },
+
+    /// Mint tokens and print them as a token to send without adding them to the balance
+    MintSend {
+        amount: u64,
+        /// Currency unit of the tokens, the mint needs a keyset for this unit. Asks if the mints
+        /// offer more than one unit
+        #[clap(long)]
+        unit: Option<CurrencyUnit>,
+        /// Id of a bolt11 mint quote that is already paid
+        #[clap(long)]
+        quote: Option<String>,
+        /// Lock the tokens to this pubkey, so only its owner can receive them
+        #[clap(long)]
+        lock_to: Option<PublicKey>,
+    },
+
+    /// Pay Lightning invoice or lightning address (user@domain)
+    Pay {
+        invoice: String,
+        /// Amount in sats, required for invoices without amount and lightning addresses
+        #[clap(long)]
+        amount: Option<u64>,
+        /// Currency unit of the tokens to melt. Asks if the mints offer more than one unit
+        #[clap(long)]
+        unit: Option<CurrencyUnit>,
+        /// Pay even if the amount exceeds the configured max send amount
+        #[clap(long)]
+        force: bool,
+    },
 
     /// Pay Bitcoin on chain
-    PayOnchain { address: String, amount: u64 },
+    PayOnchain {
+        address: String,
+        amount: u64,
+        /// Pay even if the amount exceeds the configured max send amount
+        #[clap(long)]
+        force: bool,
+    },
 
     /// Send tokens
-    Send { amount: u64 },
-
-    /// Receive tokens
-    Receive { token: String },
+    Send {
+        amount: u64,
+        /// Currency unit of the tokens to send. Asks if the mints offer more than one unit
+        #[clap(long)]
+        unit: Option<CurrencyUnit>,
+        /// Send even if the amount exceeds the configured max send amount
+        #[clap(long)]
+        force: bool,
+        /// Only send tokens with this tag
+        #[clap(long)]
+        from_tag: Option<String>,
+    },
+
+    /// Send one token per line of a batch file with `<amount> [label] [pubkey]` entries. Tokens are
+    /// locked to the pubkey if given
+    SendBatch {
+        file: PathBuf,
+        /// File the tokens are appended to as soon as they are created. Defaults to the batch file
+        /// with the extension `tokens`
+        #[clap(long)]
+        output: Option<PathBuf>,
+        /// Send even if an amount exceeds the configured max send amount
+        #[clap(long)]
+        force: bool,
+    },
+
+    /// Set the max amount for a single send or payment. Omit the amount to remove the limit
+    SetMaxSendAmount { amount: Option<u64> },
+
+    /// Set the max claim fee in percent of a token's value that is accepted without confirmation. Omit the percent to restore the default of 100
+    SetMaxReceiveFee { percent: Option<u64> },
+
+    /// Set the max fee reserve of a lightning payment as a ratio of its amount that is accepted without confirmation. Omit the ratio to restore the default of 1.0
+    SetMaxFeeReserve { ratio: Option<f64> },
+
+    /// Receive tokens or a lightning payment into the mint
+    Receive {
+        #[clap(required_unless_present = "lightning")]
+        token: Option<String>,
+        /// Create a lightning invoice for the amount in sats and mint the tokens once it is paid
+        #[clap(long, conflicts_with = "token")]
+        lightning: Option<u64>,
+        /// Tag the received tokens for coin control, e.g. `savings`
+        #[clap(long)]
+        tag: Option<String>,
+        /// Fail instead of asking to add the mint if the token is from an unknown mint
+        #[clap(long, conflicts_with = "lightning")]
+        no_add_mint: bool,
+    },
 
     /// Show local balance
     Balance,
 
+    /// Export the whole balance as one token per mint and unit into a file
+    Backup {
+        /// File the tokens are written to, one token per line
+        #[clap(long)]
+        tokens: PathBuf,
+        /// Remove the exported tokens from the wallet
+        #[clap(long)]
+        withdraw: bool,
+    },
+
+    /// Receive all tokens from a file created with `backup --tokens`
+    Restore {
+        #[clap(long)]
+        tokens: PathBuf,
+    },
+
     /// Show version and configuration
     Info,
 
     /// Add a new mint to the wallet
-    AddMint { mint_url: Url },
+    AddMint {
+        mint_url: Url,
+        /// Only add the keysets of this unit. Fails if the mint has none
+        #[clap(long)]
+        unit: Option<CurrencyUnit>,
+    },
+
+    /// Add a mint without network access from the JSON of its /v1/keys response copied from an online device
+    AddMintOffline { mint_url: Url, keysets_json: String },
+
+    /// Add all reachable mints from a JSON list of mint urls
+    AddMints {
+        #[clap(long)]
+        from: Url,
+    },
+
+    /// Print diagnostic information about the wallet and check if the mints are reachable
+    Doctor,
+
+    /// Check the state of all proofs with their mints and report balance discrepancies
+    Audit {
+        /// Remove proofs from the wallet that the mint considers spent
+        #[clap(long)]
+        prune: bool,
+    },
+
+    /// Show past mint, melt, send and receive transactions
+    History {
+        /// Number of transactions to show, newest first
+        #[clap(long, default_value_t = 20)]
+        limit: u64,
+    },
+
+    /// Print the operation log, a low level audit trail of all state changing calls
+    Log {
+        /// Number of entries to show, newest first
+        #[clap(long, default_value_t = 20)]
+        limit: u64,
+    },
+
+    /// List the accounts of the wallet
+    Accounts,
+
+    /// Move to the next P2PK signing key and lock the tokens of the current key to the new one
+    RotateP2pkKey,
+
+    /// Set or change the passphrase of the wallet database
+    #[cfg(feature = "sqlcipher")]
+    Passphrase,
 }
 
 #[tokio::main]
@@ -74,8 +278,26 @@ async fn main() -> anyhow::Result<()> {
     };
 
     let term = Term::stdout();
+
+    #[cfg(feature = "sqlcipher")]
+    let passphrase = cli::db_passphrase(&db_path)?;
+
+    #[cfg(feature = "sqlcipher")]
+    if matches!(cli.command, Command::Passphrase) {
+        return cli::change_passphrase(&db_path, passphrase.as_deref()).await;
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    let localstore = match passphrase {
+        Some(ref passphrase) => {
+            SqliteLocalStore::with_encrypted_path(db_path.clone(), passphrase).await?
+        }
+        None => SqliteLocalStore::with_path(db_path.clone()).await?,
+    };
+    #[cfg(not(feature = "sqlcipher"))]
     let localstore = SqliteLocalStore::with_path(db_path.clone()).await?;
-    let client = CrossPlatformHttpClient::new();
+    let localstore = localstore.with_account(&cli.account);
+    let client = CrossPlatformHttpClient::with_config(&HttpClientConfig::from(&cli.http))?;
     let wallet = moksha_wallet::wallet::WalletBuilder::default()
         .with_client(client)
         .with_localstore(localstore)
@@ -93,35 +315,357 @@ async fn main() -> anyhow::Result<()> {
             e
         })?;
 
+    // an unreachable mint must not block offline commands, the next start tries again
+    match wallet.reconcile().await {
+        Ok(0) => {}
+        Ok(count) => term.write_line(&format!("Reconciled {count} interrupted swap(s)"))?,
+        Err(e) => term.write_line(&format!(
+            "{} reconciling interrupted swaps failed: {}",
+            style("Warning").yellow(),
+            e
+        ))?,
+    }
+    match wallet.recover_pending().await {
+        Ok(restored) if restored.is_empty() => {}
+        Ok(restored) => term.write_line(&format!(
+            "Restored {} proof(s) of interrupted payments",
+            restored.len()
+        ))?,
+        Err(e) => term.write_line(&format!(
+            "{} recovering interrupted payments failed: {}",
+            style("Warning").yellow(),
+            e
+        ))?,
+    }
+
     match cli.command {
-        Command::AddMint { mint_url } => {
-            wallet.add_mint_keysets(&mint_url).await?;
+        #[cfg(feature = "sqlcipher")]
+        Command::Passphrase => unreachable!("handled before the wallet is opened"),
+        Command::AddMint { mint_url, unit } => {
+            wallet
+                .add_mint_keysets_for_unit(&mint_url, unit.as_ref())
+                .await?;
+            cli::confirm_tls_fingerprint(&term, &wallet, &mint_url).await?;
             term.write_line("Mint added successfully ")?;
         }
+        Command::AddMintOffline {
+            mint_url,
+            keysets_json,
+        } => {
+            let keys = serde_json::from_str::<KeysResponse>(&keysets_json)?;
+            let keysets = wallet.add_mint_keysets_offline(&mint_url, &keys).await?;
+            term.write_line(&format!(
+                "Mint added successfully with {} keyset(s)",
+                keysets.len()
+            ))?;
+        }
+        Command::AddMints { from } => {
+            let result = wallet.add_mints_from_list(&from).await?;
+            for mint_url in result.added {
+                term.write_line(&format!("{} {}", style("Added").green(), mint_url))?;
+            }
+            for mint_url in result.skipped {
+                term.write_line(&format!(
+                    "{} {} (already added)",
+                    style("Skipped").yellow(),
+                    mint_url
+                ))?;
+            }
+            for (mint_url, e) in result.failed {
+                term.write_line(&format!("{} {}: {}", style("Failed").red(), mint_url, e))?;
+            }
+            for entry in result.invalid {
+                term.write_line(&format!(
+                    "{} {} (not a valid url)",
+                    style("Invalid").red(),
+                    cli::sanitize(&entry)
+                ))?;
+            }
+        }
         Command::Info => {
             let wallet_version = style(env!("CARGO_PKG_VERSION")).cyan();
             let mint_urls = wallet.get_mint_urls().await?;
             let db_path = style(db_path).cyan();
             term.write_line(&format!("Version: {wallet_version}"))?;
             term.write_line(&format!("DB: {db_path}"))?;
+            term.write_line(&format!(
+                "P2PK pubkey: {}",
+                style(wallet.p2pk_pubkey().await?).cyan()
+            ))?;
 
             if mint_urls.is_empty() {
                 term.write_line("No mints found.")?;
             } else {
                 term.write_line("Mints:")?;
-                for mint in mint_urls {
-                    term.write_line(&format!(" - {}", mint))?;
+                let client = wallet.client();
+                for mint_url in mint_urls {
+                    let info = match client.get_info(&mint_url).await {
+                        Ok(info) => info,
+                        Err(_) => {
+                            term.write_line(&format!(
+                                " - {} {}",
+                                mint_url,
+                                style("unreachable").red()
+                            ))?;
+                            continue;
+                        }
+                    };
+                    term.write_line(&format!(" - {}", mint_url))?;
+                    term.write_line(&format!(
+                        "   Name: {}",
+                        style(info.name.as_deref().map_or("-".to_owned(), cli::sanitize)).cyan()
+                    ))?;
+                    term.write_line(&format!(
+                        "   Version: {}",
+                        style(
+                            info.version
+                                .as_deref()
+                                .map_or("-".to_owned(), cli::sanitize)
+                        )
+                        .cyan()
+                    ))?;
+                    let fingerprint =
+                        cli::confirm_tls_fingerprint(&term, &wallet, &mint_url).await?;
+                    term.write_line(&format!(
+                        "   TLS certificate: {}",
+                        style(fingerprint.as_deref().unwrap_or("-")).cyan()
+                    ))?;
+                    term.write_line("   NUTs:")?;
+                    for (nut, description, supported) in cli::nut_support(&info.nuts) {
+                        let state = match supported {
+                            true => style("supported").green(),
+                            false => style("unsupported").red(),
+                        };
+                        term.write_line(&format!("     {nut:>2} {description:<33} {state}"))?;
+                    }
+                }
+            }
+        }
+        Command::RotateP2pkKey => {
+            let pubkey = wallet.rotate_p2pk_key().await?;
+            term.write_line(&format!("New P2PK pubkey: {}", style(pubkey).cyan()))?;
+        }
+        Command::Accounts => {
+            for account in wallet.list_accounts().await? {
+                match account == cli.account {
+                    true => term.write_line(&format!("{} (current)", style(account).bold()))?,
+                    false => term.write_line(&account)?,
+                }
+            }
+        }
+        Command::History { limit } => {
+            let transactions = wallet.get_transactions(limit).await?;
+            if transactions.is_empty() {
+                term.write_line("No transactions found")?;
+            }
+            for transaction in transactions {
+                let amount = transaction.amount.to_formatted_string(&Locale::en);
+                let amount = match transaction.operation {
+                    Operation::Mint | Operation::Receive => style(format!("+{amount}")).green(),
+                    _ => style(format!("-{amount}")).red(),
+                };
+                term.write_line(&format!(
+                    "{} {:<8} {:>12} {:<4} {}{}",
+                    transaction.created_at.unwrap_or_default(),
+                    transaction.operation,
+                    amount,
+                    transaction.unit.to_string(),
+                    transaction.mint_url,
+                    transaction
+                        .memo
+                        .map(|memo| format!(" \"{}\"", cli::sanitize(&memo)))
+                        .unwrap_or_default()
+                ))?;
+            }
+        }
+        Command::Log { limit } => {
+            let entries = wallet.get_operation_log(limit).await?;
+            if entries.is_empty() {
+                term.write_line("The operation log is empty")?;
+            }
+            for entry in entries {
+                let result = match entry.error {
+                    Some(error) => style(format!("failed: {error}")).red(),
+                    None => style("ok".to_owned()).green(),
+                };
+                term.write_line(&format!(
+                    "#{} {} {} {}",
+                    entry.id.unwrap_or_default(),
+                    entry.created_at.unwrap_or_default(),
+                    style(entry.operation).cyan(),
+                    result
+                ))?;
+                term.write_line(&format!("  inputs:  {}", entry.inputs))?;
+                term.write_line(&format!("  outputs: {}", entry.outputs))?;
+            }
+        }
+        Command::Doctor => {
+            let mint_urls = wallet.get_mint_urls().await?;
+            for line in cli::doctor_report(
+                &db_path,
+                mint_urls.len(),
+                wallet.get_wallet_keysets().await?.len(),
+                &wallet.get_proofs().await?,
+                wallet.has_seed().await?,
+            ) {
+                term.write_line(&line)?;
+            }
+
+            let client = wallet.client();
+            let mut all_reachable = true;
+            for mint_url in mint_urls {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+                match cli::mint_reachability(
+                    &mint_url,
+                    client.get_info(&mint_url).await,
+                    client.get_keysets(&mint_url).await,
+                    now,
+                ) {
+                    Ok(lines) => {
+                        for line in lines {
+                            term.write_line(&line)?;
+                        }
+                        cli::confirm_tls_fingerprint(&term, &wallet, &mint_url).await?;
+                    }
+                    Err(line) => {
+                        all_reachable = false;
+                        term.write_line(&line)?;
+                    }
                 }
             }
+
+            if !all_reachable {
+                std::process::exit(1);
+            }
+        }
+        Command::Audit { prune } => {
+            let mut consistent = true;
+            for mint_url in wallet.get_mint_urls().await? {
+                let audit = match wallet.audit_balance(&mint_url, prune).await {
+                    Ok(audit) => audit,
+                    Err(e) => {
+                        consistent = false;
+                        term.write_line(&format!(
+                            " - {} {} ({})",
+                            mint_url,
+                            style("failed").red(),
+                            e
+                        ))?;
+                        continue;
+                    }
+                };
+                let status = match audit.is_consistent() {
+                    true => style("consistent").green(),
+                    false => style("inconsistent").red(),
+                };
+                term.write_line(&format!(
+                    " - {} {} (local: {} sat, verified: {} sat)",
+                    mint_url,
+                    status,
+                    audit.local_balance.to_formatted_string(&Locale::en),
+                    audit.verified_balance.to_formatted_string(&Locale::en)
+                ))?;
+                for (label, proofs) in [
+                    ("spent", &audit.spent),
+                    ("pending", &audit.pending),
+                    ("unknown to the mint", &audit.unknown),
+                ] {
+                    if !proofs.is_empty() {
+                        term.write_line(&format!(
+                            "   {} proofs {}: {} sat",
+                            proofs.len(),
+                            label,
+                            proofs.total_amount().to_formatted_string(&Locale::en)
+                        ))?;
+                    }
+                }
+                if audit.pruned {
+                    term.write_line(&format!(
+                        "   removed {} spent proofs",
+                        style(audit.spent.len()).cyan()
+                    ))?;
+                }
+                consistent &= audit.is_consistent();
+            }
+
+            if !consistent {
+                std::process::exit(1);
+            }
         }
         // checks if the mints keyset is already in the wallet, if not it adds it and then imports the tokens
-        Command::Receive { token } => {
+        Command::Receive {
+            lightning: Some(amount),
+            tag,
+            ..
+        } => {
+            let currency = CurrencyUnit::Sat;
+            let mint_url = choose_mint(&wallet, &currency).await?.0;
+            let wallet_keysets = wallet.get_wallet_keysets().await?;
+            let wallet_keyset = wallet_keysets
+                .get_active(&mint_url, &currency)
+                .ok_or_else(|| anyhow::anyhow!("no active sat keyset for {mint_url}"))?;
+
+            // a new invoice is created if the last one expired unpaid and the user wants to retry
+            loop {
+                let PostMintQuoteBolt11Response {
+                    payment_request,
+                    quote,
+                    expiry,
+                    ..
+                } = wallet
+                    .create_quote_bolt11(&mint_url, amount, currency.clone())
+                    .await?;
+
+                term.write_line(&format!(
+                    "Pay lightning invoice to receive {} (sat):\n\n{payment_request}",
+                    amount.to_formatted_string(&Locale::en)
+                ))?;
+                let image = QrCode::new(payment_request)?
+                    .render::<unicode::Dense1x2>()
+                    .quiet_zone(true)
+                    .build();
+                term.write_line(&image)?;
+
+                let progress_bar = cli::progress_bar()?;
+                progress_bar.set_message("Waiting for payment ...");
+                let state =
+                    cli::wait_for_bolt11_payment(&wallet, &mint_url, &quote, expiry, &currency)
+                        .await?;
+
+                if state == Bolt11ReceiveState::Expired {
+                    progress_bar.finish_with_message("Invoice expired\n");
+                    if Confirm::new()
+                        .with_prompt("Create a new invoice?")
+                        .interact()?
+                    {
+                        continue;
+                    }
+                    return Ok(());
+                }
+
+                progress_bar.set_message("Payment received, minting tokens ...");
+                let tokens = wallet
+                    .mint_tokens(wallet_keyset, &PaymentMethod::Bolt11, amount.into(), quote)
+                    .await?;
+                if let Some(ref tag) = tag {
+                    wallet.set_proof_labels(&tokens.proofs(), Some(tag)).await?;
+                }
+                progress_bar.finish_with_message("Tokens minted successfully.\n");
+                cli::show_total_balance(&wallet).await?;
+                break;
+            }
+        }
+        Command::Receive { token: None, .. } => {
+            unreachable!("clap requires a token or --lightning")
+        }
+        Command::Receive {
+            token: Some(token),
+            tag,
+            no_add_mint,
+            ..
+        } => {
             let token: TokenV3 = TokenV3::from_str(&token)?;
             let mint_urls = wallet.get_mint_urls().await?;
-            let currency = match &token.currency_unit {
-                Some(currency) => currency,
-                None => &CurrencyUnit::Sat,
-            };
 
             let token_mint_url = match token.mint() {
                 Some(url) => url,
@@ -132,6 +676,13 @@ async fn main() -> anyhow::Result<()> {
             };
 
             if !mint_urls.contains(&token_mint_url) {
+                if no_add_mint {
+                    return Err(moksha_wallet::error::MokshaWalletError::UnknownMint(
+                        token_mint_url.to_string(),
+                    )
+                    .into());
+                }
+
                 let add_mint = Confirm::new()
                     .with_prompt(format!(
                         "New mint found {:?} . Do you want to add it?",
@@ -143,27 +694,111 @@ async fn main() -> anyhow::Result<()> {
                     return Ok(());
                 }
 
-                let is_valid_mint = CrossPlatformHttpClient::new()
-                    .is_v1_supported(&token_mint_url)
-                    .await?;
+                let is_valid_mint = wallet.client().is_v1_supported(&token_mint_url).await?;
                 if !is_valid_mint {
                     term.write_line("Error: Invalid mint url")?;
                     return Ok(());
                 }
 
+                if !wallet.can_redeem(&token).await? {
+                    term.write_line(
+                        "Warning: The token can not be redeemed at this mint. The mint does not know its keysets or amounts",
+                    )?;
+                    return Ok(());
+                }
+
                 wallet.add_mint_keysets(&token_mint_url).await?;
             }
 
-            let wallet_keysets = wallet.get_wallet_keysets().await?;
-            let wallet_keyset = wallet_keysets
-                .get_active(&token_mint_url, currency)
-                .expect("no active keyset found");
+            let currency = wallet.get_token_unit(&token).await?;
+            match wallet.check_receive_fee(&token_mint_url, &token).await {
+                Err(moksha_wallet::error::MokshaWalletError::FeeExceedsValue(fee, amount)) => {
+                    let message = match fee >= amount {
+                        true => "claiming this costs more in fees than it's worth",
+                        false => "claiming this costs more in fees than your configured max",
+                    };
+                    term.write_line(&format!(
+                        "Warning: {message} (fee {fee} of {amount} {currency})"
+                    ))?;
+                    if !Confirm::new().with_prompt("Receive anyway?").interact()? {
+                        return Ok(());
+                    }
+                }
+                result => result?,
+            }
 
-            wallet.receive_tokens(wallet_keyset, &token).await?;
+            let received = wallet.receive_tokens_from_known_mint(&token, true).await?;
+            if let Some(tag) = tag {
+                wallet
+                    .set_proof_labels(&received.proofs(), Some(&tag))
+                    .await?;
+            }
+            if let Some(memo) = token.display_memo() {
+                term.write_line(&format!("Memo: {}", style(memo).cyan()))?;
+            }
             cli::show_total_balance(&wallet).await?;
         }
-        Command::Send { amount } => {
-            let currency_unit = CurrencyUnit::Sat;
+        Command::Backup { tokens, withdraw } => {
+            let exported = wallet.export_tokens().await?;
+            if exported.is_empty() {
+                term.write_line("No tokens to export")?;
+                return Ok(());
+            }
+
+            let total: u64 = exported.iter().map(|token| token.total_amount()).sum();
+            let lines = exported
+                .iter()
+                .cloned()
+                .map(String::try_from)
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut file = std::fs::File::create_new(&tokens)?;
+            file.write_all(format!("{}\n", lines.join("\n")).as_bytes())?;
+            file.sync_all()?;
+
+            term.write_line(&format!(
+                "Exported {} tokens with a total of {} to {}",
+                exported.len(),
+                total.to_formatted_string(&Locale::en),
+                tokens.display()
+            ))?;
+            if withdraw {
+                wallet.withdraw_tokens(&exported).await?;
+                term.write_line("The exported tokens were removed from the wallet")?;
+            } else {
+                term.write_line(
+                    "Warning: The exported tokens are still in the wallet. Spending them from either place makes the other copy invalid",
+                )?;
+            }
+        }
+        Command::Restore { tokens } => {
+            let content = std::fs::read_to_string(tokens)?;
+            let mint_urls = wallet.get_mint_urls().await?;
+            for (index, line) in content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .enumerate()
+            {
+                match cli::receive_token_line(&wallet, &mint_urls, line).await {
+                    Ok(amount) => term.write_line(&format!(
+                        "#{} received {}",
+                        index + 1,
+                        amount.to_formatted_string(&Locale::en)
+                    ))?,
+                    Err(e) => {
+                        term.write_line(&format!("#{} {} ({e})", index + 1, style("failed").red()))?
+                    }
+                }
+            }
+            cli::show_total_balance(&wallet).await?;
+        }
+        Command::Send {
+            amount,
+            unit,
+            force,
+            from_tag,
+        } => {
+            let currency_unit = cli::choose_unit(&wallet, unit).await?;
             let mint_url = choose_mint(&wallet, &currency_unit).await?;
 
             if mint_url.1 < amount {
@@ -178,34 +813,266 @@ async fn main() -> anyhow::Result<()> {
                 .get_active(&mint_url, &currency_unit)
                 .expect("no active keyset found");
 
+            if !force && !cli::confirm_max_send_amount(&wallet, amount).await? {
+                return Ok(());
+            }
+
+            cli::warn_expired_keysets(&wallet, &mint_url).await?;
             term.write_line(&format!("Using tokens from mint: {mint_url}"))?;
-            let result = wallet.send_tokens(wallet_keyset, amount).await?;
+            let result = match from_tag {
+                Some(tag) => {
+                    wallet
+                        .send_tokens_from_label(wallet_keyset, &tag, amount, true)
+                        .await?
+                }
+                None => wallet.send_tokens(wallet_keyset, amount, true).await?,
+            };
+            let claim_fee = wallet.claim_fee_estimate(&mint_url, &result).await?;
             let tokens: String = result.try_into()?;
 
-            term.write_line(&format!("Result {amount} (sat):\n{tokens}"))?;
+            term.write_line(&format!("Result {amount} ({currency_unit}):\n{tokens}"))?;
+            if claim_fee > 0 {
+                term.write_line(&format!(
+                    "The recipient will pay {claim_fee} ({currency_unit}) in mint fees to claim this token"
+                ))?;
+            }
+            cli::show_total_balance(&wallet).await?;
+        }
+        Command::MintSend {
+            amount,
+            unit,
+            quote,
+            lock_to,
+        } => {
+            let unit = cli::choose_unit(&wallet, unit).await?;
+            let mint_url = choose_mint(&wallet, &unit).await?.0;
+            if lock_to.is_some()
+                && !cli::supported_nuts(&wallet.get_mint_info(&mint_url).await?.nuts).contains(&11)
+            {
+                term.write_line("Error: The mint doesn't support locking tokens to pubkeys")?;
+                return Ok(());
+            }
+            let quote = match quote {
+                Some(quote) => {
+                    if !wallet
+                        .is_quote_paid(&mint_url, &PaymentMethod::Bolt11, quote.clone())
+                        .await?
+                    {
+                        term.write_line("Error: The quote is not paid")?;
+                        return Ok(());
+                    }
+                    quote
+                }
+                None => {
+                    let PostMintQuoteBolt11Response {
+                        payment_request,
+                        quote,
+                        expiry,
+                        ..
+                    } = wallet
+                        .create_quote_bolt11(&mint_url, amount, unit.clone())
+                        .await?;
+                    term.write_line(&format!(
+                        "Pay lightning invoice to mint tokens:\n\n{payment_request}"
+                    ))?;
+                    let image = QrCode::new(payment_request)?
+                        .render::<unicode::Dense1x2>()
+                        .quiet_zone(true)
+                        .build();
+                    term.write_line(&image)?;
+
+                    let progress_bar = cli::progress_bar()?;
+                    progress_bar.set_message("Waiting for payment ...");
+                    if cli::wait_for_bolt11_payment(&wallet, &mint_url, &quote, expiry, &unit)
+                        .await?
+                        == Bolt11ReceiveState::Expired
+                    {
+                        progress_bar.finish_with_message("Invoice expired\n");
+                        return Ok(());
+                    }
+                    progress_bar.finish_and_clear();
+                    quote
+                }
+            };
+
+            let wallet_keysets = wallet.get_wallet_keysets().await?;
+            let wallet_keyset = wallet_keysets
+                .get_active(&mint_url, &unit)
+                .expect("Keyset not found");
+            let result = wallet
+                .mint_and_send(
+                    wallet_keyset,
+                    &PaymentMethod::Bolt11,
+                    amount.into(),
+                    quote,
+                    lock_to.as_ref(),
+                )
+                .await?;
+            let tokens: String = result.try_into()?;
+            term.write_line(&format!("Result {amount} ({unit}):\n{tokens}"))?;
+        }
+        Command::SendBatch {
+            file,
+            output,
+            force,
+        } => {
+            let entries = cli::parse_batch_file(&std::fs::read_to_string(&file)?)?;
+            let total: u64 = entries.iter().map(|entry| entry.amount).sum();
+
+            let currency_unit = CurrencyUnit::Sat;
+            let (mint_url, mint_balance) = choose_mint(&wallet, &currency_unit).await?;
+            if mint_balance < total {
+                term.write_line(&format!(
+                    "Warning: Batch needs {} (sat) but the selected mint only has {} (sat)",
+                    total.to_formatted_string(&Locale::en),
+                    mint_balance.to_formatted_string(&Locale::en)
+                ))?;
+            }
+
+            if entries.iter().any(|entry| entry.lock.is_some())
+                && !cli::supported_nuts(&wallet.get_mint_info(&mint_url).await?.nuts).contains(&11)
+            {
+                term.write_line("Error: The mint doesn't support locking tokens to pubkeys")?;
+                return Ok(());
+            }
+
+            let wallet_keysets = wallet.get_wallet_keysets().await?;
+            let wallet_keyset = wallet_keysets
+                .get_active(&mint_url, &currency_unit)
+                .expect("no active keyset found");
+
+            // every token is stored before it is shown, so no token is lost if the batch fails
+            let output = output.unwrap_or_else(|| file.with_extension("tokens"));
+            let mut output_file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&output)?;
+
+            term.write_line(&format!("Using tokens from mint: {mint_url}"))?;
+            let mut sent = 0;
+            let mut succeeded = 0;
+            for (index, entry) in entries.iter().enumerate() {
+                let label = entry
+                    .label
+                    .clone()
+                    .unwrap_or_else(|| format!("#{}", index + 1));
+                if !force && !cli::confirm_max_send_amount(&wallet, entry.amount).await? {
+                    term.write_line("Stopping batch, the remaining lines were not sent")?;
+                    break;
+                }
+                match cli::send_batch_entry(&wallet, wallet_keyset, &label, entry, &mut output_file)
+                    .await
+                {
+                    Ok(token) => {
+                        output_file.sync_data()?;
+                        term.write_line(&format!("{label} {} (sat): {token}", entry.amount))?;
+                        sent += entry.amount;
+                        succeeded += 1;
+                    }
+                    Err(e) => {
+                        term.write_line(&format!(
+                            "{label} {} (sat): {} ({e})",
+                            entry.amount,
+                            style("failed").red()
+                        ))?;
+                        term.write_line("Stopping batch, the remaining lines were not sent")?;
+                        break;
+                    }
+                }
+            }
+
+            term.write_line(&format!(
+                "Sent {} (sat) in {succeeded} of {} tokens, stored in {}",
+                style(sent.to_formatted_string(&Locale::en)).cyan(),
+                entries.len(),
+                output.display()
+            ))?;
             cli::show_total_balance(&wallet).await?;
         }
+        Command::SetMaxSendAmount { amount } => {
+            wallet.set_max_send_amount(amount).await?;
+            match amount {
+                Some(amount) => term.write_line(&format!(
+                    "Max send amount set to {} (sat)",
+                    amount.to_formatted_string(&Locale::en)
+                ))?,
+                None => term.write_line("Max send amount removed")?,
+            }
+        }
+        Command::SetMaxFeeReserve { ratio } => {
+            wallet.set_max_fee_reserve_ratio(ratio).await?;
+            match ratio {
+                Some(ratio) => term.write_line(&format!(
+                    "Max fee reserve set to {ratio} times the payment amount"
+                ))?,
+                None => term.write_line("Max fee reserve reset to 1.0 times the payment amount")?,
+            }
+        }
+        Command::SetMaxReceiveFee { percent } => {
+            wallet.set_max_receive_fee_percent(percent).await?;
+            match percent {
+                Some(percent) => term.write_line(&format!(
+                    "Max receive fee set to {percent}% of the token value"
+                ))?,
+                None => term.write_line("Max receive fee reset to 100% of the token value")?,
+            }
+        }
         Command::Balance => {
-            let total_balance = wallet.get_balance().await?;
-            if total_balance > 0 {
-                let mints = get_mints_with_balance(&wallet, &CurrencyUnit::Sat).await?;
+            let balances = cli::mint_unit_balances(
+                &wallet.get_wallet_keysets().await?,
+                &wallet.get_balances().await?,
+            );
+            let mut mints = balances
+                .iter()
+                .map(|(mint_url, _, _)| mint_url)
+                .collect::<Vec<_>>();
+            mints.dedup();
+            if !balances.is_empty() {
                 term.write_line(&format!(
                     "You have balances in {} mints",
                     style(mints.len()).cyan()
                 ))?;
 
-                for mint in mints {
+                for (mint_url, unit, balance) in balances.iter() {
                     term.write_line(&format!(
-                        " - {} {} (sat)",
-                        mint.0,
-                        style(mint.1.to_formatted_string(&Locale::en)).cyan()
+                        " - {} {} ({})",
+                        mint_url,
+                        style(balance.to_formatted_string(&Locale::en)).cyan(),
+                        unit
                     ))?;
                 }
             }
-            cli::show_total_balance(&wallet).await?;
+            let totals = cli::unit_totals(&balances);
+            if totals.is_empty() {
+                term.write_line(&format!("Total balance {} (sat)", style(0).cyan()))?;
+            }
+            for (unit, total) in totals {
+                term.write_line(&format!(
+                    "Total balance {} ({})",
+                    style(total.to_formatted_string(&Locale::en)).cyan(),
+                    unit
+                ))?;
+            }
         }
-        Command::Pay { invoice } => {
-            let currency_unit = CurrencyUnit::Sat;
+        Command::Pay {
+            invoice,
+            amount,
+            unit,
+            force,
+        } => {
+            let (invoice, amount) = match (invoice.contains('@'), amount) {
+                (true, Some(amount)) => (
+                    wallet.resolve_lightning_address(&invoice, amount).await?,
+                    None,
+                ),
+                (true, None) => {
+                    term.write_line("Error: --amount is required to pay a lightning address")?;
+                    return Ok(());
+                }
+                (false, amount) => (invoice, amount),
+            };
+
+            let currency_unit = cli::choose_unit(&wallet, unit).await?;
             let mint_url = choose_mint(&wallet, &currency_unit).await?.0;
             let wallet_keysets = wallet.get_wallet_keysets().await?;
             let wallet_keyset = wallet_keysets
@@ -213,12 +1080,13 @@ async fn main() -> anyhow::Result<()> {
                 .expect("no active keyset found");
 
             let quote = wallet
-                .get_melt_quote_bolt11(&mint_url, invoice.clone(), currency_unit)
+                .get_melt_quote_bolt11(&mint_url, invoice.clone(), currency_unit.clone(), amount)
                 .await?;
+            cli::warn_expired_keysets(&wallet, &mint_url).await?;
 
             let pay_confirmed = Confirm::new()
                 .with_prompt(format!(
-                    "Pay lightning invoice: amount {} + fee {} = {} (sat)?",
+                    "Pay lightning invoice: amount {} + fee {} = {} ({currency_unit})?",
                     quote.amount,
                     quote.fee_reserve,
                     quote.amount + quote.fee_reserve
@@ -229,24 +1097,37 @@ async fn main() -> anyhow::Result<()> {
                 return Ok(());
             }
 
-            let response = wallet.pay_invoice(wallet_keyset, &quote, invoice).await?;
+            if !force
+                && (!cli::confirm_fee_reserve(&wallet, quote.amount, quote.fee_reserve).await?
+                    || !cli::confirm_max_send_amount(&wallet, quote.amount + quote.fee_reserve)
+                        .await?)
+            {
+                return Ok(());
+            }
+
+            let response = wallet
+                .pay_invoice(wallet_keyset, &quote, invoice, true)
+                .await?;
 
             // FIXME handle not enough tokens error
 
             if response.0.paid {
-                if response.1 > 0 {
-                    term.write_line(&format!(
-                        "Returned fees {} (sat)",
-                        response.1.to_formatted_string(&Locale::en)
-                    ))?;
-                }
+                term.write_line(&cli::fee_summary(
+                    quote.fee_reserve,
+                    response.0.fee_paid,
+                    response.1,
+                ))?;
                 term.write_line("\nInvoice has been paid: Tokens melted successfully")?;
                 cli::show_total_balance(&wallet).await?;
             } else {
                 term.write_line("Error: Tokens not melted")?;
             }
         }
-        Command::PayOnchain { address, amount } => {
+        Command::PayOnchain {
+            address,
+            amount,
+            force,
+        } => {
             // FIXME remove redundant code
             let currency = CurrencyUnit::Sat;
             let mint_url = choose_mint(&wallet, &currency).await?;
@@ -278,7 +1159,29 @@ async fn main() -> anyhow::Result<()> {
                 return Ok(());
             }
 
-            let quote = quotes.first().expect("No quotes found");
+            // the mint offers a quote per confirmation target, so the user can trade fee for speed
+            let quote = match quotes.len() {
+                1 => &quotes[0],
+                _ => {
+                    let selections = quotes
+                        .iter()
+                        .map(|quote| {
+                            format!(
+                                "fee {} (sat) - {}",
+                                quote.fee,
+                                quote.description.clone().unwrap_or_default()
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    let selection = Select::with_theme(&ColorfulTheme::default())
+                        .with_prompt("Choose a fee:")
+                        .default(0)
+                        .items(&selections[..])
+                        .interact()
+                        .expect("Selection failed");
+                    &quotes[selection]
+                }
+            };
 
             term.write_line(&format!(
                 "Create onchain transaction to melt tokens: amount {} + fee {} = {} (sat)\n{}\n",
@@ -294,8 +1197,12 @@ async fn main() -> anyhow::Result<()> {
                 return Ok(());
             }
 
+            if !force && !cli::confirm_max_send_amount(&wallet, amount + quote.fee).await? {
+                return Ok(());
+            }
+
             let PostMeltBtcOnchainResponse { state, txid } =
-                wallet.pay_onchain(wallet_keyset, quote).await?;
+                wallet.pay_onchain(wallet_keyset, quote, true).await?;
             if let Some(txid) = txid.clone() {
                 term.write_line(&format!("Created transaction: {}\n", &txid))?;
             }
@@ -320,38 +1227,55 @@ async fn main() -> anyhow::Result<()> {
                 }
             }
         }
-        Command::Mint { amount } => {
-            let currency = CurrencyUnit::Sat;
+        Command::Mint {
+            amount,
+            unit,
+            from_lnurl,
+            tag,
+        } => {
+            let currency = match from_lnurl {
+                Some(_) => unit.unwrap_or(CurrencyUnit::Sat),
+                None => cli::choose_unit(&wallet, unit).await?,
+            };
+            if from_lnurl.is_some() && currency != CurrencyUnit::Sat {
+                term.write_line("Minting from a LNURL-withdraw is only supported for sat")?;
+                return Ok(());
+            }
             let mint_url = choose_mint(&wallet, &currency).await?.0;
 
             let info = wallet.get_mint_info(&mint_url).await?;
 
-            let payment_method = info.nuts.nut18.as_ref().map_or_else(
-                || {
-                    term.write_line("Only bolt11 minting is supported")
-                        .expect("write_line failed");
-                    PaymentMethod::Bolt11
-                },
-                |nut17| {
-                    if !nut17.supported {
+            let payment_method = if from_lnurl.is_some() {
+                PaymentMethod::Bolt11
+            } else {
+                info.nuts.nut18.as_ref().map_or_else(
+                    || {
                         term.write_line("Only bolt11 minting is supported")
                             .expect("write_line failed");
                         PaymentMethod::Bolt11
-                    } else {
-                        let selections = &[PaymentMethod::BtcOnchain, PaymentMethod::Bolt11];
-
-                        let selection = Select::with_theme(&ColorfulTheme::default())
-                            .with_prompt("Choose a payment method:")
-                            .default(0)
-                            .items(&selections[..])
-                            .interact()
-                            .expect("Selection failed");
-                        selections[selection].clone()
-                    }
-                },
-            );
+                    },
+                    |nut17| {
+                        // onchain minting is only supported for sat
+                        if !nut17.supported || currency != CurrencyUnit::Sat {
+                            term.write_line("Only bolt11 minting is supported")
+                                .expect("write_line failed");
+                            PaymentMethod::Bolt11
+                        } else {
+                            let selections = &[PaymentMethod::BtcOnchain, PaymentMethod::Bolt11];
+
+                            let selection = Select::with_theme(&ColorfulTheme::default())
+                                .with_prompt("Choose a payment method:")
+                                .default(0)
+                                .items(&selections[..])
+                                .interact()
+                                .expect("Selection failed");
+                            selections[selection].clone()
+                        }
+                    },
+                )
+            };
 
-            let quote = match payment_method {
+            let (quote, expiry) = match payment_method {
                 PaymentMethod::BtcOnchain => {
                     let nut17 = info.nuts.nut18.expect("nut17 is None");
                     let payment_method = nut17.payment_methods.first().expect("no payment methods");
@@ -376,6 +1300,10 @@ async fn main() -> anyhow::Result<()> {
                         wallet.create_quote_onchain(&mint_url, amount).await?;
 
                     term.write_line(&format!("Pay onchain to mint tokens:\n\n{address}"))?;
+                    term.write_line(&format!(
+                        "The deposit needs {} confirmation(s) before the tokens can be minted",
+                        payment_method.required_confirmations(amount)
+                    ))?;
 
                     let amount_btc = amount as f64 / 100_000_000.0;
                     let bip21_code = format!("bitcoin:{}?amount={}", address, amount_btc);
@@ -384,14 +1312,24 @@ async fn main() -> anyhow::Result<()> {
                         .quiet_zone(true)
                         .build();
                     term.write_line(&image)?;
-                    quote
+                    (quote, None)
+                }
+                PaymentMethod::Bolt11 if from_lnurl.is_some() => {
+                    let lnurl = from_lnurl.expect("lnurl is None");
+                    let PostMintQuoteBolt11Response { quote, expiry, .. } = wallet
+                        .mint_from_lnurl_withdraw(&mint_url, &lnurl, amount)
+                        .await?;
+                    (quote, expiry)
                 }
                 PaymentMethod::Bolt11 => {
                     let PostMintQuoteBolt11Response {
                         payment_request,
                         quote,
+                        expiry,
                         ..
-                    } = wallet.create_quote_bolt11(&mint_url, amount).await?;
+                    } = wallet
+                        .create_quote_bolt11(&mint_url, amount, currency.clone())
+                        .await?;
 
                     term.write_line(&format!(
                         "Pay lightning invoice to mint tokens:\n\n{payment_request}"
@@ -403,8 +1341,9 @@ async fn main() -> anyhow::Result<()> {
                         .build();
                     term.write_line(&image)?;
 
-                    quote
+                    (quote, expiry)
                 }
+                PaymentMethod::Bolt12 => unreachable!("bolt12 minting is never selected"),
             };
 
             let wallet_keysets = wallet.get_wallet_keysets().await?;
@@ -415,6 +1354,15 @@ async fn main() -> anyhow::Result<()> {
             let progress_bar = cli::progress_bar()?;
             progress_bar.set_message("Waiting for payment ...");
 
+            if payment_method == PaymentMethod::Bolt11
+                && cli::wait_for_bolt11_payment(&wallet, &mint_url, &quote, expiry, &currency)
+                    .await?
+                    == Bolt11ReceiveState::Expired
+            {
+                progress_bar.finish_with_message("Invoice expired\n");
+                return Ok(());
+            }
+
             loop {
                 tokio::time::sleep_until(
                     tokio::time::Instant::now() + std::time::Duration::from_millis(500),
@@ -435,7 +1383,10 @@ async fn main() -> anyhow::Result<()> {
                     .await;
 
                 match mint_result {
-                    Ok(_) => {
+                    Ok(tokens) => {
+                        if let Some(ref tag) = tag {
+                            wallet.set_proof_labels(&tokens.proofs(), Some(tag)).await?;
+                        }
                         progress_bar.finish_with_message("Tokens minted successfully.\n");
                         cli::show_total_balance(&wallet).await?;
                         break;
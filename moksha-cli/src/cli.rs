@@ -1,16 +1,28 @@
-use std::{process::exit, time::Duration};
+use std::{
+    collections::HashMap,
+    io::Write,
+    process::exit,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use console::{style, Term};
-use dialoguer::{theme::ColorfulTheme, Select};
+use dialoguer::{theme::ColorfulTheme, Confirm, Select};
 use indicatif::{ProgressBar, ProgressStyle};
 
-use moksha_core::primitives::CurrencyUnit;
+use moksha_core::keyset::{KeysetId, Keysets};
+use moksha_core::primitives::{CurrencyUnit, MintInfoResponse, Nuts};
+use moksha_core::proof::Proofs;
+use moksha_core::token::TokenV3;
 use moksha_wallet::{
-    error::MokshaWalletError, http::CrossPlatformHttpClient, localstore::sqlite::SqliteLocalStore,
-    wallet::Wallet,
+    error::MokshaWalletError,
+    http::CrossPlatformHttpClient,
+    localstore::{sqlite::SqliteLocalStore, WalletKeyset},
+    wallet::{Bolt11ReceiveState, TlsFingerprint, Wallet},
 };
 use num_format::Locale;
 use num_format::ToFormattedString;
+use secp256k1::PublicKey;
 use url::Url;
 
 pub fn progress_bar() -> anyhow::Result<ProgressBar> {
@@ -39,7 +51,7 @@ pub async fn choose_mint(
         .iter()
         .map(|(url, balance)| {
             format!(
-                "{} - {} (sat)",
+                "{} - {} ({currency_unit})",
                 url,
                 balance.to_formatted_string(&Locale::en)
             )
@@ -55,6 +67,42 @@ pub async fn choose_mint(
     Ok(mints[selection].clone())
 }
 
+/// Returns the unit if given. Otherwise asks for one if the mints of the wallet offer more than
+/// one unit, sat is used if there is only one.
+pub async fn choose_unit(
+    wallet: &Wallet<SqliteLocalStore, CrossPlatformHttpClient>,
+    unit: Option<CurrencyUnit>,
+) -> Result<CurrencyUnit, MokshaWalletError> {
+    if let Some(unit) = unit {
+        return Ok(unit);
+    }
+
+    let units = available_units(&wallet.get_wallet_keysets().await?);
+    if units.len() <= 1 {
+        return Ok(units.into_iter().next().unwrap_or(CurrencyUnit::Sat));
+    }
+
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Choose a currency:")
+        .default(0)
+        .items(&units)
+        .interact()
+        .unwrap();
+    Ok(units[selection].clone())
+}
+
+/// Units of the active keysets, sat first and the others in the order of the keysets
+pub fn available_units(keysets: &[WalletKeyset]) -> Vec<CurrencyUnit> {
+    let mut units: Vec<CurrencyUnit> = vec![];
+    for keyset in keysets.iter().filter(|keyset| keyset.active) {
+        if !units.contains(&keyset.currency_unit) {
+            units.push(keyset.currency_unit.clone());
+        }
+    }
+    units.sort_by_key(|unit| *unit != CurrencyUnit::Sat);
+    units
+}
+
 pub async fn get_mints_with_balance(
     wallet: &Wallet<SqliteLocalStore, CrossPlatformHttpClient>,
     currency_unit: &CurrencyUnit,
@@ -82,9 +130,875 @@ pub async fn show_total_balance(
     wallet: &Wallet<SqliteLocalStore, CrossPlatformHttpClient>,
 ) -> anyhow::Result<()> {
     let term = Term::stdout();
+    let balances = mint_unit_balances(
+        &wallet.get_wallet_keysets().await?,
+        &wallet.get_balances().await?,
+    );
+    let totals = unit_totals(&balances);
+    if totals.is_empty() {
+        term.write_line(&format!("New total balance {} (sat)", style(0).cyan()))?;
+    }
+    for (unit, total) in totals {
+        term.write_line(&format!(
+            "New total balance {} ({})",
+            style(total.to_formatted_string(&Locale::en)).cyan(),
+            unit
+        ))?;
+    }
+    Ok(())
+}
+
+/// Sums the non-zero balances per mint and unit, sorted by mint with sat first
+pub fn mint_unit_balances(
+    keysets: &[WalletKeyset],
+    balances: &HashMap<(Url, KeysetId), u64>,
+) -> Vec<(Url, CurrencyUnit, u64)> {
+    let mut result: Vec<(Url, CurrencyUnit, u64)> = vec![];
+    for keyset in keysets {
+        let balance = balances
+            .get(&(keyset.mint_url.clone(), keyset.keyset_id.clone()))
+            .copied()
+            .unwrap_or_default();
+        if balance == 0 {
+            continue;
+        }
+        match result.iter_mut().find(|(mint_url, unit, _)| {
+            mint_url == &keyset.mint_url && unit == &keyset.currency_unit
+        }) {
+            Some((_, _, total)) => *total += balance,
+            None => result.push((
+                keyset.mint_url.clone(),
+                keyset.currency_unit.clone(),
+                balance,
+            )),
+        }
+    }
+    result.sort_by_key(|(mint_url, unit, _)| {
+        (
+            mint_url.clone(),
+            *unit != CurrencyUnit::Sat,
+            unit.to_string(),
+        )
+    });
+    result
+}
+
+/// Sums the balances per unit, sat first
+pub fn unit_totals(balances: &[(Url, CurrencyUnit, u64)]) -> Vec<(CurrencyUnit, u64)> {
+    let mut totals: Vec<(CurrencyUnit, u64)> = vec![];
+    for (_, unit, balance) in balances {
+        match totals.iter_mut().find(|(total_unit, _)| total_unit == unit) {
+            Some((_, total)) => *total += balance,
+            None => totals.push((unit.clone(), *balance)),
+        }
+    }
+    totals.sort_by_key(|(unit, _)| (*unit != CurrencyUnit::Sat, unit.to_string()));
+    totals
+}
+
+/// Warns if the wallet holds proofs of keysets the mint has already replaced
+pub async fn warn_expired_keysets(
+    wallet: &Wallet<SqliteLocalStore, CrossPlatformHttpClient>,
+    mint_url: &Url,
+) -> anyhow::Result<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let expired = wallet.get_expired_keysets(mint_url, now).await?;
+    if !expired.is_empty() {
+        Term::stdout().write_line(&format!(
+            "{} spending proofs of keysets the mint has replaced: {}",
+            style("Warning:").yellow(),
+            expired.join(", ")
+        ))?;
+    }
+    Ok(())
+}
+
+/// clock differences to the mint up to this many seconds are not reported
+const MAX_CLOCK_SKEW_SECS: u64 = 60;
+
+/// Returns a warning if the local clock is more than [`MAX_CLOCK_SKEW_SECS`] ahead of or behind the
+/// clock of the mint. `skew` is the offset of the local clock in seconds
+pub fn clock_skew_warning(skew: i64) -> Option<String> {
+    let direction = match skew > 0 {
+        true => "ahead of",
+        false => "behind",
+    };
+    (skew.unsigned_abs() > MAX_CLOCK_SKEW_SECS).then(|| {
+        format!(
+            "{} your clock is off by {} seconds ({} the mint). Quotes may seem to expire too early or too late",
+            style("Warning:").yellow(),
+            skew.unsigned_abs(),
+            direction
+        )
+    })
+}
+
+/// Lines of the `doctor` command about the local state of the wallet
+pub fn doctor_report(
+    db_path: &str,
+    mint_count: usize,
+    keyset_count: usize,
+    proofs: &Proofs,
+    has_seed: bool,
+) -> Vec<String> {
+    let seed = match has_seed {
+        true => "present",
+        false => "missing",
+    };
+    vec![
+        format!("Version: {}", style(env!("CARGO_PKG_VERSION")).cyan()),
+        format!("DB: {}", style(db_path).cyan()),
+        format!("Mints: {}", style(mint_count).cyan()),
+        format!("Keysets: {}", style(keyset_count).cyan()),
+        format!(
+            "Proofs: {} ({} sat)",
+            style(proofs.len()).cyan(),
+            style(proofs.total_amount().to_formatted_string(&Locale::en)).cyan()
+        ),
+        format!("Seed: {}", style(seed).cyan()),
+    ]
+}
+
+/// Lines of the `doctor` command about a mint. Returns `Err` with the line to print if the info
+/// or the keysets of the mint could not be fetched. `now` is the local unix time
+pub fn mint_reachability(
+    mint_url: &Url,
+    info: Result<MintInfoResponse, MokshaWalletError>,
+    keysets: Result<Keysets, MokshaWalletError>,
+    now: u64,
+) -> Result<Vec<String>, String> {
+    let (info, keysets) = match (info, keysets) {
+        (Ok(info), Ok(keysets)) => (info, keysets),
+        (Err(e), _) | (_, Err(e)) => {
+            return Err(format!(
+                " - {} {} ({})",
+                mint_url,
+                style("unreachable").red(),
+                e
+            ))
+        }
+    };
+
+    let nuts = supported_nuts(&info.nuts)
+        .iter()
+        .map(|nut| nut.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut lines = vec![format!(
+        " - {} {} (keysets: {}, nuts: {})",
+        mint_url,
+        style("reachable").green(),
+        keysets.keysets.len(),
+        nuts
+    )];
+    if let Some(warning) = info
+        .time
+        .and_then(|time| clock_skew_warning(now as i64 - time as i64))
+    {
+        lines.push(format!("   {warning}"));
+    }
+    Ok(lines)
+}
+
+/// Removes control characters, e.g. terminal escape sequences, from text that was set by a mint or
+/// the sender of a token before it is printed
+pub fn sanitize(text: &str) -> String {
+    text.chars().filter(|c| !c.is_control()).collect()
+}
+
+/// Returns a warning if the TLS certificate of the mint differs from the trusted one
+pub fn tls_fingerprint_warning(fingerprint: &TlsFingerprint) -> Option<String> {
+    fingerprint.previous.as_ref().map(|previous| {
+        format!(
+            "{} the TLS certificate of the mint differs from the trusted one ({}). \
+             This is expected after a renewal, otherwise it can be an attack",
+            style("Warning:").yellow(),
+            previous
+        )
+    })
+}
+
+/// Checks the TLS certificate of the mint and asks the user to trust it if it has changed since the
+/// last connection. Failures are printed as warning, so one mint doesn't abort a command that
+/// handles several. Returns the fingerprint of the current certificate.
+pub async fn confirm_tls_fingerprint(
+    term: &Term,
+    wallet: &Wallet<SqliteLocalStore, CrossPlatformHttpClient>,
+    mint_url: &Url,
+) -> anyhow::Result<Option<String>> {
+    let fingerprint = match wallet.check_tls_fingerprint(mint_url).await {
+        Ok(fingerprint) => fingerprint,
+        Err(e) => {
+            term.write_line(&format!(
+                "   {} checking the TLS certificate failed: {}",
+                style("Warning:").yellow(),
+                e
+            ))?;
+            return Ok(None);
+        }
+    };
+    if let (Some(warning), Some(current)) =
+        (tls_fingerprint_warning(&fingerprint), &fingerprint.current)
+    {
+        term.write_line(&format!("   {warning}"))?;
+        if Confirm::new()
+            .with_prompt("Trust the new certificate?")
+            .interact()?
+        {
+            wallet.accept_tls_fingerprint(mint_url, current).await?;
+        }
+    }
+    Ok(fingerprint.current)
+}
+
+/// Describes the lightning fee of a paid invoice. `fee_paid` is `None` for mints that don't
+/// report the fee they actually paid
+pub fn fee_summary(fee_reserve: u64, fee_paid: Option<u64>, returned: u64) -> String {
+    let sat = |amount: u64| amount.to_formatted_string(&Locale::en);
+    match fee_paid {
+        Some(fee_paid) => format!(
+            "Fee reserved {}, actually paid {}, returned {} (sat)",
+            sat(fee_reserve),
+            sat(fee_paid),
+            sat(returned)
+        ),
+        None => format!(
+            "Fee reserved {}, returned {} (sat)",
+            sat(fee_reserve),
+            sat(returned)
+        ),
+    }
+}
+
+/// seconds without a subscription update after which the quote is checked over http
+const SUBSCRIPTION_RECHECK_SECS: u64 = 30;
+
+/// Waits until the invoice of a bolt11 mint quote is paid or expired. Uses a websocket
+/// subscription if the mint supports it and polls the quote otherwise. `expiry` is the expiry
+/// of the quote when it was created and is used if the mint leaves it out of later updates.
+pub async fn wait_for_bolt11_payment(
+    wallet: &Wallet<SqliteLocalStore, CrossPlatformHttpClient>,
+    mint_url: &Url,
+    quote: &str,
+    expiry: Option<u64>,
+    unit: &CurrencyUnit,
+) -> anyhow::Result<Bolt11ReceiveState> {
+    // the expiry of the quote is compared with the clock of the mint
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let skew = wallet
+        .check_time_sync(mint_url, now)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    if let Some(warning) = clock_skew_warning(skew) {
+        Term::stdout().write_line(&warning)?;
+    }
+    let mint_now = || -> anyhow::Result<u64> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        Ok(now.saturating_add_signed(-skew))
+    };
+
+    // polling still works if the subscription fails
+    let subscription = wallet
+        .subscribe_mint_quote(mint_url, quote, unit)
+        .await
+        .unwrap_or_default();
+
+    let mut expiry = expiry;
+    if let Some(mut updates) = subscription {
+        let mut idle_secs = 0;
+        loop {
+            let update = tokio::time::timeout(Duration::from_secs(1), updates.recv()).await;
+            let now = mint_now()?;
+            match update {
+                Ok(Some(update)) => {
+                    idle_secs = 0;
+                    expiry = update.expiry.or(expiry);
+                    match Bolt11ReceiveState::from_quote(&update, now) {
+                        Bolt11ReceiveState::Waiting => continue,
+                        state => return Ok(state),
+                    }
+                }
+                // the connection was closed, continue by polling
+                Ok(None) => break,
+                Err(_) => {
+                    idle_secs += 1;
+                    // the mint sends no update when the quote expires and an update might have
+                    // been missed, so the quote is checked before it's reported as expired and
+                    // regularly while the subscription is quiet
+                    let expired = expiry.is_some_and(|expiry| expiry < now);
+                    if expired || idle_secs % SUBSCRIPTION_RECHECK_SECS == 0 {
+                        match wallet
+                            .get_bolt11_receive_state(mint_url, quote, now)
+                            .await?
+                        {
+                            Bolt11ReceiveState::Waiting if expired => {
+                                return Ok(Bolt11ReceiveState::Expired)
+                            }
+                            Bolt11ReceiveState::Waiting => continue,
+                            state => return Ok(state),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let now = mint_now()?;
+        match wallet
+            .get_bolt11_receive_state(mint_url, quote, now)
+            .await?
+        {
+            Bolt11ReceiveState::Waiting if expiry.is_some_and(|expiry| expiry < now) => {
+                return Ok(Bolt11ReceiveState::Expired)
+            }
+            Bolt11ReceiveState::Waiting => continue,
+            state => return Ok(state),
+        }
+    }
+}
+
+/// Returns the number, a short description and the support state of every nut in the mint info
+pub fn nut_support(nuts: &Nuts) -> Vec<(u8, &'static str, bool)> {
+    vec![
+        (4, "Minting tokens", !nuts.nut4.disabled),
+        (5, "Melting tokens", !nuts.nut5.disabled),
+        (
+            7,
+            "Token state check",
+            nuts.nut7.as_ref().is_some_and(|nut| nut.supported),
+        ),
+        (
+            8,
+            "Overpaid lightning fees",
+            nuts.nut8.as_ref().is_some_and(|nut| nut.supported),
+        ),
+        (
+            9,
+            "Signature restore",
+            nuts.nut9.as_ref().is_some_and(|nut| nut.supported),
+        ),
+        (
+            10,
+            "Spending conditions",
+            nuts.nut10.as_ref().is_some_and(|nut| nut.supported),
+        ),
+        (
+            11,
+            "Pay-to-Pubkey (P2PK)",
+            nuts.nut11.as_ref().is_some_and(|nut| nut.supported),
+        ),
+        (
+            12,
+            "DLEQ proofs",
+            nuts.nut12.as_ref().is_some_and(|nut| nut.supported),
+        ),
+        (
+            13,
+            "Deterministic secrets",
+            nuts.nut13.as_ref().is_some_and(|nut| nut.supported),
+        ),
+        (
+            14,
+            "Hashed timelock contracts (HTLC)",
+            nuts.nut14.as_ref().is_some_and(|nut| nut.supported),
+        ),
+        (
+            15,
+            "Partial multi-path payments",
+            nuts.nut15.as_ref().is_some_and(|nut| nut.supported),
+        ),
+        (
+            16,
+            "Animated QR codes",
+            nuts.nut16.as_ref().is_some_and(|nut| nut.supported),
+        ),
+        (
+            17,
+            "WebSocket subscriptions",
+            nuts.nut17
+                .as_ref()
+                .is_some_and(|nut| !nut.supported.is_empty()),
+        ),
+        (
+            18,
+            "Onchain minting",
+            nuts.nut18.as_ref().is_some_and(|nut| nut.supported),
+        ),
+        (
+            19,
+            "Onchain melting",
+            nuts.nut19.as_ref().is_some_and(|nut| nut.supported),
+        ),
+    ]
+}
+
+/// Returns the numbers of all nuts the mint reports as supported
+pub fn supported_nuts(nuts: &Nuts) -> Vec<u8> {
+    nut_support(nuts)
+        .into_iter()
+        .filter_map(|(nut, _, supported)| supported.then_some(nut))
+        .collect()
+}
+
+/// Asks for confirmation if the amount exceeds the configured max send amount.
+/// Returns true if the amount is within the limit or the user confirmed.
+pub async fn confirm_max_send_amount(
+    wallet: &Wallet<SqliteLocalStore, CrossPlatformHttpClient>,
+    amount: u64,
+) -> anyhow::Result<bool> {
+    match wallet.get_max_send_amount().await? {
+        Some(max) if amount > max => Ok(Confirm::new()
+            .with_prompt(format!(
+                "This sends more than your configured max ({} sat). Continue?",
+                max.to_formatted_string(&Locale::en)
+            ))
+            .interact()?),
+        _ => Ok(true),
+    }
+}
+
+pub async fn confirm_fee_reserve(
+    wallet: &Wallet<SqliteLocalStore, CrossPlatformHttpClient>,
+    amount: u64,
+    fee_reserve: u64,
+) -> anyhow::Result<bool> {
+    match wallet.check_fee_reserve(amount, fee_reserve, false).await {
+        Err(MokshaWalletError::FeeReserveTooHigh(_, _)) => Ok(Confirm::new()
+            .with_prompt(format!(
+                "The fee reserve of {} sat exceeds your configured max ratio of the amount. Continue?",
+                fee_reserve.to_formatted_string(&Locale::en)
+            ))
+            .interact()?),
+        Err(e) => Err(e.into()),
+        Ok(()) => Ok(true),
+    }
+}
+
+/// Environment variable with the passphrase of the encrypted wallet database
+#[cfg(feature = "sqlcipher")]
+pub const PASSPHRASE_ENV: &str = "MOKSHA_DB_PASSPHRASE";
+
+/// Returns the passphrase of the wallet database from `MOKSHA_DB_PASSPHRASE` or asks for it if the
+/// database is encrypted. There is no command line option, since arguments are visible to other
+/// users of the system.
+#[cfg(feature = "sqlcipher")]
+pub fn db_passphrase(db_path: &str) -> anyhow::Result<Option<String>> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(Some(passphrase));
+    }
+    if !is_encrypted_db(db_path)? {
+        return Ok(None);
+    }
+    Ok(Some(
+        dialoguer::Password::new()
+            .with_prompt("Wallet passphrase")
+            .interact()?,
+    ))
+}
+
+/// An unencrypted SQLite database starts with a well-known header, an encrypted one doesn't.
+/// Missing and empty files are not encrypted.
+#[cfg(feature = "sqlcipher")]
+fn is_encrypted_db(db_path: &str) -> anyhow::Result<bool> {
+    use std::io::Read;
+
+    let mut header = [0_u8; 16];
+    match std::fs::File::open(db_path) {
+        Ok(mut file) => Ok(file.read_exact(&mut header).is_ok() && &header != b"SQLite format 3\0"),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Sets a new passphrase for the wallet database. An unencrypted database gets encrypted in place after confirmation.
+#[cfg(feature = "sqlcipher")]
+pub async fn change_passphrase(db_path: &str, passphrase: Option<&str>) -> anyhow::Result<()> {
+    let term = Term::stdout();
+    if passphrase.is_none()
+        && !Confirm::new()
+            .with_prompt("The wallet database is not encrypted. Encrypt it now?")
+            .interact()?
+    {
+        return Ok(());
+    }
+
+    let new_passphrase = dialoguer::Password::new()
+        .with_prompt("New passphrase")
+        .with_confirmation("Confirm passphrase", "Passphrases do not match")
+        .interact()?;
+    SqliteLocalStore::change_passphrase(db_path, passphrase, &new_passphrase).await?;
     term.write_line(&format!(
-        "New total balance {} (sat)",
-        style(wallet.get_balance().await?.to_formatted_string(&Locale::en)).cyan()
+        "Passphrase changed. Set {PASSPHRASE_ENV} or enter it when asked"
     ))?;
     Ok(())
 }
+
+/// A single line of a batch file passed to `send-batch`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchEntry {
+    pub amount: u64,
+    pub label: Option<String>,
+    /// pubkey the token is locked to (NUT-11)
+    pub lock: Option<PublicKey>,
+}
+
+/// Sends the token of a batch entry and appends `<label> <amount> <token>` to the output before
+/// the token is returned, so no token is lost if a later entry fails.
+pub async fn send_batch_entry(
+    wallet: &Wallet<SqliteLocalStore, CrossPlatformHttpClient>,
+    wallet_keyset: &WalletKeyset,
+    label: &str,
+    entry: &BatchEntry,
+    output: &mut impl Write,
+) -> anyhow::Result<String> {
+    let token = match entry.lock {
+        Some(pubkey) => {
+            wallet
+                .send_tokens_locked(wallet_keyset, entry.amount, &[pubkey], 1, None, true)
+                .await?
+        }
+        None => {
+            wallet
+                .send_tokens(wallet_keyset, entry.amount, true)
+                .await?
+        }
+    };
+    let token = String::try_from(token)?;
+    writeln!(output, "{label} {} {token}", entry.amount)?;
+    output.flush()?;
+    Ok(token)
+}
+
+/// Receives a single token from a token file and adds its mint if it is not known yet.
+/// Returns the received amount.
+pub async fn receive_token_line(
+    wallet: &Wallet<SqliteLocalStore, CrossPlatformHttpClient>,
+    mint_urls: &[Url],
+    line: &str,
+) -> anyhow::Result<u64> {
+    let token = line.parse::<TokenV3>()?;
+    let mint_url = token
+        .mint()
+        .ok_or_else(|| anyhow::anyhow!("missing mint url"))?;
+    if !mint_urls.contains(&mint_url) {
+        wallet.add_mint_keysets(&mint_url).await?;
+    }
+
+    wallet.receive_tokens_from_known_mint(&token, false).await?;
+    Ok(token.total_amount())
+}
+
+/// Parses a batch file with one `<amount> [label] [pubkey]` entry per line. A trailing hex encoded
+/// pubkey locks the token to it. Empty lines and lines starting with `#` are ignored.
+pub fn parse_batch_file(content: &str) -> anyhow::Result<Vec<BatchEntry>> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_number, line)| {
+            let (amount, rest) = match line.split_once(char::is_whitespace) {
+                Some((amount, rest)) => (amount, rest.trim()),
+                None => (line, ""),
+            };
+            let (label, lock) = match rest.rsplit_once(char::is_whitespace) {
+                Some((label, pubkey)) => match PublicKey::from_str(pubkey) {
+                    Ok(lock) => (label.trim(), Some(lock)),
+                    Err(_) => (rest, None),
+                },
+                None => match PublicKey::from_str(rest) {
+                    Ok(lock) => ("", Some(lock)),
+                    Err(_) => (rest, None),
+                },
+            };
+            let label = (!label.is_empty()).then(|| label.to_owned());
+            let amount = amount
+                .parse::<u64>()
+                .map_err(|_| anyhow::anyhow!("invalid amount in line {line_number}: {amount}"))?;
+            if amount == 0 {
+                anyhow::bail!("amount must be greater than 0 in line {line_number}");
+            }
+            Ok(BatchEntry {
+                amount,
+                label,
+                lock,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, str::FromStr};
+
+    use moksha_core::{
+        keyset::{KeysetId, Keysets},
+        primitives::{CurrencyUnit, MintInfoResponse, Nut11, Nuts},
+        proof::Proofs,
+    };
+    use moksha_wallet::error::MokshaWalletError;
+    use secp256k1::PublicKey;
+    use url::Url;
+
+    use super::{
+        available_units, clock_skew_warning, doctor_report, fee_summary, mint_reachability,
+        mint_unit_balances, nut_support, parse_batch_file, sanitize, supported_nuts,
+        tls_fingerprint_warning, unit_totals, BatchEntry, TlsFingerprint, WalletKeyset,
+    };
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!("coffee", sanitize("coffee"));
+        assert_eq!("[31mred[0m", sanitize("\x1b[31mred\x1b[0m"));
+        assert_eq!("twolines", sanitize("two\nlines\r"));
+    }
+
+    #[test]
+    fn test_available_units() -> anyhow::Result<()> {
+        let mint_url = Url::parse("http://127.0.0.1:3338")?;
+        let keyset = |unit: CurrencyUnit, active: bool| -> anyhow::Result<WalletKeyset> {
+            Ok(WalletKeyset::new(
+                &KeysetId::new("009a1f293253e41e")?,
+                &mint_url,
+                &unit,
+                0,
+                HashMap::new(),
+                active,
+            ))
+        };
+
+        assert!(available_units(&[]).is_empty());
+        let keysets = vec![
+            keyset(CurrencyUnit::Usd, true)?,
+            keyset(CurrencyUnit::Sat, true)?,
+            keyset(CurrencyUnit::Usd, true)?,
+            keyset(CurrencyUnit::MSat, false)?,
+        ];
+        assert_eq!(
+            vec![CurrencyUnit::Sat, CurrencyUnit::Usd],
+            available_units(&keysets)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mint_unit_balances() -> anyhow::Result<()> {
+        let first_mint = Url::parse("http://127.0.0.1:3338")?;
+        let second_mint = Url::parse("http://127.0.0.1:3339")?;
+        let keyset =
+            |id: &str, mint_url: &Url, unit: CurrencyUnit| -> anyhow::Result<WalletKeyset> {
+                Ok(WalletKeyset::new(
+                    &KeysetId::new(id)?,
+                    mint_url,
+                    &unit,
+                    0,
+                    HashMap::new(),
+                    true,
+                ))
+            };
+        let keysets = vec![
+            keyset("00000000000000a1", &second_mint, CurrencyUnit::Sat)?,
+            keyset("00000000000000a2", &first_mint, CurrencyUnit::Usd)?,
+            keyset("00000000000000a3", &first_mint, CurrencyUnit::Sat)?,
+            keyset("00000000000000a4", &first_mint, CurrencyUnit::Sat)?,
+            keyset("00000000000000a5", &second_mint, CurrencyUnit::Usd)?,
+        ];
+        let balances = keysets
+            .iter()
+            .zip([8, 100, 16, 4, 0])
+            .map(|(keyset, balance)| ((keyset.mint_url.clone(), keyset.keyset_id.clone()), balance))
+            .collect::<HashMap<_, _>>();
+
+        let mint_balances = mint_unit_balances(&keysets, &balances);
+        assert_eq!(
+            vec![
+                (first_mint.clone(), CurrencyUnit::Sat, 20),
+                (first_mint, CurrencyUnit::Usd, 100),
+                (second_mint, CurrencyUnit::Sat, 8),
+            ],
+            mint_balances
+        );
+        assert_eq!(
+            vec![(CurrencyUnit::Sat, 28), (CurrencyUnit::Usd, 100)],
+            unit_totals(&mint_balances)
+        );
+        assert!(unit_totals(&mint_unit_balances(&keysets, &HashMap::new())).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_doctor_report() {
+        let report = doctor_report("/tmp/wallet.db", 2, 3, &Proofs::empty(), false);
+        assert_eq!(6, report.len());
+        assert!(report[1].contains("/tmp/wallet.db"));
+        assert!(report[2].contains('2'));
+        assert!(report[3].contains('3'));
+        assert!(report[4].contains("0 (0 sat)"));
+        assert!(report[5].contains("missing"));
+    }
+
+    #[test]
+    fn test_mint_reachability() -> anyhow::Result<()> {
+        let mint_url = Url::parse("http://127.0.0.1:3338")?;
+        let info = MintInfoResponse {
+            name: None,
+            pubkey: PublicKey::from_str(
+                "0249098aa8b9d2fbec49ff8598feb17b592b986e62319a4fa488a3dc36387157a7",
+            )?,
+            version: None,
+            description: None,
+            description_long: None,
+            contact: None,
+            motd: None,
+            time: Some(10_000),
+            nuts: Nuts::default(),
+        };
+
+        let lines = mint_reachability(&mint_url, Ok(info.clone()), Ok(Keysets::default()), 10_000)
+            .expect("mint not reachable");
+        assert_eq!(1, lines.len());
+        assert!(lines[0].contains("reachable (keysets: 0, nuts: 4, 5"));
+
+        // the clock of the wallet is an hour ahead of the mint
+        let lines = mint_reachability(&mint_url, Ok(info.clone()), Ok(Keysets::default()), 13_600)
+            .expect("mint not reachable");
+        assert_eq!(2, lines.len());
+        assert!(lines[1].contains("off by 3600 seconds (ahead of the mint)"));
+
+        let line = mint_reachability(
+            &mint_url,
+            Ok(info),
+            Err(MokshaWalletError::UnsupportedApiVersion),
+            10_000,
+        )
+        .expect_err("mint reachable");
+        assert!(line.contains("unreachable"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_clock_skew_warning() {
+        assert_eq!(None, clock_skew_warning(60));
+        assert_eq!(None, clock_skew_warning(-60));
+        let warning = clock_skew_warning(-3_600).expect("skew not reported");
+        assert!(warning.contains("off by 3600 seconds (behind the mint)"));
+    }
+
+    #[test]
+    fn test_tls_fingerprint_warning() {
+        let mut fingerprint = TlsFingerprint {
+            current: Some("new".to_owned()),
+            previous: None,
+        };
+        assert!(tls_fingerprint_warning(&fingerprint).is_none());
+        fingerprint.previous = Some("old".to_owned());
+        assert!(tls_fingerprint_warning(&fingerprint).is_some_and(|w| w.contains("old")));
+    }
+
+    #[test]
+    fn test_fee_summary() {
+        assert_eq!(
+            "Fee reserved 1,000, actually paid 2, returned 998 (sat)",
+            fee_summary(1_000, Some(2), 998)
+        );
+        assert_eq!(
+            "Fee reserved 10, returned 8 (sat)",
+            fee_summary(10, None, 8)
+        );
+    }
+
+    #[test]
+    fn test_nut_support() {
+        let nuts = Nuts {
+            nut11: Some(Nut11 { supported: false }),
+            ..Default::default()
+        };
+        let support = nut_support(&nuts);
+        assert_eq!(
+            (4..=19).filter(|nut| *nut != 6).collect::<Vec<u8>>(),
+            support.iter().map(|(nut, _, _)| *nut).collect::<Vec<_>>()
+        );
+        assert_eq!(Some(&(11, "Pay-to-Pubkey (P2PK)", false)), support.get(6));
+        assert!(!supported_nuts(&nuts).contains(&11));
+    }
+
+    #[test]
+    fn test_parse_batch_file() -> anyhow::Result<()> {
+        let content = "# payroll\n100 alice\n\n  21   bob smith \n42\n";
+        let entries = parse_batch_file(content)?;
+        assert_eq!(
+            vec![
+                BatchEntry {
+                    amount: 100,
+                    label: Some("alice".to_owned()),
+                    lock: None,
+                },
+                BatchEntry {
+                    amount: 21,
+                    label: Some("bob smith".to_owned()),
+                    lock: None,
+                },
+                BatchEntry {
+                    amount: 42,
+                    label: None,
+                    lock: None,
+                },
+            ],
+            entries
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_batch_file_lock() -> anyhow::Result<()> {
+        let pubkey = "0249098aa8b9d2fbec49ff8598feb17b592b986e62319a4fa488a3dc36387157a7";
+        let entries = parse_batch_file(&format!("100 alice {pubkey}\n21 {pubkey}\n"))?;
+        let lock = Some(PublicKey::from_str(pubkey)?);
+        assert_eq!(
+            vec![
+                BatchEntry {
+                    amount: 100,
+                    label: Some("alice".to_owned()),
+                    lock,
+                },
+                BatchEntry {
+                    amount: 21,
+                    label: None,
+                    lock,
+                },
+            ],
+            entries
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn test_is_encrypted_db() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wallet.db");
+        let path_str = path.to_string_lossy();
+        assert!(!super::is_encrypted_db(&path_str)?);
+
+        std::fs::write(&path, b"SQLite format 3\0 and some pages")?;
+        assert!(!super::is_encrypted_db(&path_str)?);
+
+        std::fs::write(&path, [0x5a_u8; 64])?;
+        assert!(super::is_encrypted_db(&path_str)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_batch_file_invalid_amount() {
+        let result = parse_batch_file("100 alice\nabc bob\n");
+        assert_eq!(
+            "invalid amount in line 2: abc",
+            result.unwrap_err().to_string()
+        );
+        assert!(parse_batch_file("0 carol").is_err());
+    }
+}
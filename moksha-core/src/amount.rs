@@ -13,6 +13,15 @@ impl Amount {
     pub fn split(&self) -> SplitAmount {
         split_amount(self.0).into()
     }
+
+    /// Number of blank outputs that are needed to return any change up to this fee reserve, which
+    /// is `max(ceil(log2(fee_reserve)), 1)` as described in [Nut-08](https://github.com/cashubtc/nuts/blob/main/08.md)
+    pub fn blank_outputs_count(&self) -> usize {
+        match self.0 {
+            0 => 0,
+            fee_reserve => ((u64::BITS - (fee_reserve - 1).leading_zeros()) as usize).max(1),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -66,8 +75,29 @@ fn split_amount(amount: u64) -> Vec<u64> {
 
 #[cfg(test)]
 mod tests {
+    use super::Amount;
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn test_blank_outputs_count() {
+        assert_eq!(0, Amount(0).blank_outputs_count());
+        assert_eq!(1, Amount(1).blank_outputs_count());
+        assert_eq!(1, Amount(2).blank_outputs_count());
+        assert_eq!(10, Amount(1000).blank_outputs_count());
+        assert_eq!(64, Amount(u64::MAX).blank_outputs_count());
+
+        // every change up to the fee reserve fits into the blank outputs
+        for fee_reserve in [1, 2, 3, 4, 5, 7, 8, 9, 1023, 1024, 1025, u64::MAX] {
+            for change in fee_reserve.saturating_sub(2048)..=fee_reserve {
+                assert!(
+                    Amount(change).split().len() <= Amount(fee_reserve).blank_outputs_count(),
+                    "change {change} doesn't fit into the blanks of fee reserve {fee_reserve}"
+                );
+            }
+        }
+        assert_eq!(64, Amount(u64::MAX).split().len());
+    }
+
     #[test]
     fn test_split_amount() -> anyhow::Result<()> {
         let bits = super::split_amount(13);
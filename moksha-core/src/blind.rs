@@ -62,17 +62,25 @@ impl BlindingFactor {
 }
 
 pub trait TotalAmount {
-    fn total_amount(&self) -> u64;
+    /// Returns the sum of all amounts or `None` if it overflows
+    fn checked_total_amount(&self) -> Option<u64>;
+
+    /// Returns the sum of all amounts, saturating at `u64::MAX`
+    fn total_amount(&self) -> u64 {
+        self.checked_total_amount().unwrap_or(u64::MAX)
+    }
 }
 
 impl TotalAmount for Vec<BlindedSignature> {
-    fn total_amount(&self) -> u64 {
-        self.iter().fold(0, |acc, x| acc + x.amount)
+    fn checked_total_amount(&self) -> Option<u64> {
+        self.iter()
+            .try_fold(0u64, |acc, x| acc.checked_add(x.amount))
     }
 }
 
 impl TotalAmount for Vec<BlindedMessage> {
-    fn total_amount(&self) -> u64 {
-        self.iter().fold(0, |acc, x| acc + x.amount)
+    fn checked_total_amount(&self) -> Option<u64> {
+        self.iter()
+            .try_fold(0u64, |acc, x| acc.checked_add(x.amount))
     }
 }
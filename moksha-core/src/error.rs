@@ -23,6 +23,12 @@ pub enum MokshaCoreError {
     #[error("Invalid Keysetid")]
     InvalidKeysetid,
 
+    #[error("Invalid keyset signature")]
+    InvalidKeysetSignature,
+
+    #[error("Invalid denomination limit {0}, expected <unit>:<min_amount>:<max_amount>")]
+    InvalidDenominationLimit(String),
+
     #[error("Not enough tokens")]
     NotEnoughTokens,
 
@@ -37,4 +43,19 @@ pub enum MokshaCoreError {
 
     #[error("Invalid Keyset-ID")]
     Slice(#[from] std::array::TryFromSliceError),
+
+    #[error("Unknown currency unit {0}")]
+    UnknownCurrencyUnit(String),
+
+    #[error("Invalid confirmation tier {0}. Expected <min_amount>:<min_confirmations>")]
+    InvalidConfirmationTier(String),
+
+    #[error("Unsupported spending condition {0}")]
+    UnsupportedSpendingCondition(String),
+
+    #[error("Proof is locked to a pubkey, but has no witness")]
+    MissingWitness,
+
+    #[error("Witness doesn't contain enough valid signatures of the locked pubkeys")]
+    InvalidWitness,
 }
@@ -10,15 +10,16 @@
 //!
 //! The module also defines a `generate_hash` function for generating a random hash, and several helper functions for deriving keys and keyset IDs.
 
+use base64::{engine::general_purpose, Engine as _};
 use hex::ToHex;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fmt::Display};
+use std::{collections::HashMap, fmt::Display, str::FromStr};
 use utoipa::ToSchema;
 
-use bitcoin_hashes::{sha256, Hash};
+use bitcoin_hashes::{sha256, Hash, HashEngine};
 
 use itertools::Itertools;
-use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, SecretKey};
 
 use crate::{error::MokshaCoreError, primitives::CurrencyUnit};
 
@@ -30,6 +31,11 @@ pub struct MintKeyset {
     pub public_keys: HashMap<u64, PublicKey>,
     pub keyset_id: String,
     pub mint_pubkey: PublicKey,
+    pub unit: CurrencyUnit,
+    /// smallest denomination the keyset issues, all denominations are allowed if not set
+    pub min_amount: Option<u64>,
+    /// largest denomination the keyset issues, all denominations are allowed if not set
+    pub max_amount: Option<u64>,
 }
 
 impl MintKeyset {
@@ -41,14 +47,94 @@ impl MintKeyset {
             keyset_id: derive_keyset_id(&pub_keys),
             public_keys: pub_keys,
             mint_pubkey: derive_pubkey(seed).expect("invalid seed"),
+            unit: CurrencyUnit::Sat,
+            min_amount: None,
+            max_amount: None,
+        }
+    }
+
+    /// Creates a keyset for the given unit. The keys of units other than sat are derived from
+    /// the derivation path extended by the unit, so every unit has its own keyset.
+    pub fn with_unit(seed: &str, derivation_path: &str, unit: CurrencyUnit) -> Self {
+        let derivation_path = match unit {
+            CurrencyUnit::Sat => derivation_path.to_owned(),
+            _ => format!("{derivation_path}/{unit}"),
+        };
+        Self {
+            unit,
+            ..Self::new(seed, &derivation_path)
+        }
+    }
+
+    /// Creates a sat keyset whose id is derived with the legacy (pre v1) scheme. Used to keep
+    /// resolving proofs of keysets that were created before the mint switched to v1 ids.
+    pub fn legacy(seed: &str, derivation_path: &str) -> Self {
+        let keyset = Self::new(seed, derivation_path);
+        Self {
+            keyset_id: derive_keyset_id_legacy(&keyset.public_keys),
+            ..keyset
         }
     }
+
+    /// Applies the denomination limit for the unit of the keyset, if there is one
+    pub fn with_denomination_limits(self, limits: &[DenominationLimit]) -> Self {
+        match limits.iter().find(|limit| limit.unit == self.unit) {
+            Some(limit) => Self {
+                min_amount: Some(limit.min_amount),
+                max_amount: Some(limit.max_amount),
+                ..self
+            },
+            None => self,
+        }
+    }
+
+    /// Returns true if the keyset issues signatures for the amount
+    pub fn allows_amount(&self, amount: u64) -> bool {
+        self.min_amount.map_or(true, |min| amount >= min)
+            && self.max_amount.map_or(true, |max| amount <= max)
+    }
+}
+
+/// Restricts the denominations of the keyset of `unit` to the range `min_amount..=max_amount`
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DenominationLimit {
+    pub unit: CurrencyUnit,
+    pub min_amount: u64,
+    pub max_amount: u64,
+}
+
+impl FromStr for DenominationLimit {
+    type Err = MokshaCoreError;
+
+    /// Parses a limit in the format `<unit>:<min_amount>:<max_amount>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || MokshaCoreError::InvalidDenominationLimit(s.to_owned());
+        let mut parts = s.split(':').map(str::trim);
+        let (Some(unit), Some(min_amount), Some(max_amount), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let limit = Self {
+            unit: CurrencyUnit::from_str(unit).map_err(|_| invalid())?,
+            min_amount: min_amount.parse().map_err(|_| invalid())?,
+            max_amount: max_amount.parse().map_err(|_| invalid())?,
+        };
+        if limit.min_amount > limit.max_amount {
+            return Err(invalid());
+        }
+        Ok(limit)
+    }
 }
 
 // FIXME rename to keysets
 #[derive(Clone, Debug, Serialize, Deserialize, Default, ToSchema, PartialEq, Eq)]
 pub struct Keysets {
     pub keysets: Vec<Keyset>,
+    /// hex encoded ECDSA signature of the keyset list by the mint pubkey. Only set if the mint
+    /// is configured to sign its keysets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
 }
 
 // FIXME rename to keyset
@@ -57,13 +143,75 @@ pub struct Keyset {
     pub id: String, // FIXME use KeysetId
     pub unit: CurrencyUnit,
     pub active: bool,
+    /// fee in parts per thousand sats the mint charges per input proof
+    #[serde(default)]
+    pub input_fee_ppk: u64,
+    /// smallest denomination the keyset issues
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_amount: Option<u64>,
+    /// largest denomination the keyset issues
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_amount: Option<u64>,
+    /// unix timestamp of when the keyset became active
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_from: Option<u64>,
+    /// unix timestamp of when the keyset was replaced by a new one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_to: Option<u64>,
 }
 
 impl Keysets {
     pub fn new(id: String, unit: CurrencyUnit, active: bool) -> Self {
         Self {
-            keysets: vec![Keyset { id, unit, active }],
+            keysets: vec![Keyset {
+                id,
+                unit,
+                active,
+                input_fee_ppk: 0,
+                min_amount: None,
+                max_amount: None,
+                valid_from: None,
+                valid_to: None,
+            }],
+            signature: None,
+        }
+    }
+
+    /// Signs the keyset list with the secret key of the mint pubkey
+    pub fn sign(self, secret_key: &SecretKey) -> Self {
+        let secp = Secp256k1::new();
+        let signature = secp.sign_ecdsa(&self.signature_message(), secret_key);
+        Self {
+            signature: Some(signature.serialize_compact().encode_hex()),
+            ..self
+        }
+    }
+
+    /// Verifies that the keyset list was signed by the mint pubkey
+    pub fn verify_signature(&self, mint_pubkey: &PublicKey) -> Result<(), MokshaCoreError> {
+        let signature = self
+            .signature
+            .as_ref()
+            .ok_or(MokshaCoreError::InvalidKeysetSignature)?;
+        let signature = Signature::from_compact(&hex::decode(signature)?)?;
+        Secp256k1::verification_only()
+            .verify_ecdsa(&self.signature_message(), &signature, mint_pubkey)
+            .map_err(|_| MokshaCoreError::InvalidKeysetSignature)
+    }
+
+    /// sha256 of every keyset as `id:unit:active:input_fee_ppk`, one per line
+    fn signature_message(&self) -> Message {
+        let mut engine = sha256::Hash::engine();
+        for keyset in self.keysets.iter() {
+            engine.input(
+                format!(
+                    "{}:{}:{}:{}\n",
+                    keyset.id, keyset.unit, keyset.active, keyset.input_fee_ppk
+                )
+                .as_bytes(),
+            );
         }
+        Message::from_digest(sha256::Hash::from_engine(engine).to_byte_array())
     }
 
     pub fn current_keyset(
@@ -79,13 +227,13 @@ impl Keysets {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, ToSchema)]
 pub struct KeysetId(KeysetIdType, String);
 
 impl KeysetId {
     // FIXME implement fromString
     pub fn new(id: &str) -> Result<Self, MokshaCoreError> {
-        if id.len() != 16 {
+        if id.len() != 16 || !id.starts_with("00") {
             return Err(MokshaCoreError::InvalidKeysetid);
         }
         let id_type = KeysetIdType::from(id[0..2].to_string());
@@ -111,7 +259,7 @@ impl Display for KeysetId {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, ToSchema)]
 pub enum KeysetIdType {
     V1,
 }
@@ -169,7 +317,8 @@ pub fn derive_pubkeys(keys: &HashMap<u64, SecretKey>) -> HashMap<u64, PublicKey>
         .collect()
 }
 
-fn derive_keyset_id(keys: &HashMap<u64, PublicKey>) -> String {
+/// Derives the v1 keyset id from the public keys of a keyset
+pub fn derive_keyset_id(keys: &HashMap<u64, PublicKey>) -> String {
     let pubkeys = keys
         .iter()
         .sorted_by(|(amt_a, _), (amt_b, _)| amt_a.cmp(amt_b))
@@ -179,6 +328,19 @@ fn derive_keyset_id(keys: &HashMap<u64, PublicKey>) -> String {
     let hashed_pubkeys: String = sha256::Hash::hash(pubkeys.as_slice()).encode_hex();
     format!("00{}", &hashed_pubkeys[0..14])
 }
+
+/// Derives the legacy keyset id, which is the base64 encoded sha256 hash of the concatenated
+/// hex encoded public keys sorted by amount
+pub fn derive_keyset_id_legacy(keys: &HashMap<u64, PublicKey>) -> String {
+    let pubkeys = keys
+        .iter()
+        .sorted_by(|(amt_a, _), (amt_b, _)| amt_a.cmp(amt_b))
+        .map(|(_, pubkey)| pubkey.to_string())
+        .collect::<String>();
+
+    let hashed_pubkeys = sha256::Hash::hash(pubkeys.as_bytes());
+    general_purpose::STANDARD.encode(hashed_pubkeys.as_byte_array())[..12].to_owned()
+}
 ///
 /// # Arguments
 ///
@@ -188,18 +350,25 @@ fn derive_keyset_id(keys: &HashMap<u64, PublicKey>) -> String {
 ///
 /// Returns a `Result` containing the derived `PublicKey` or a `MokshaCoreError` if an error occurs.
 pub fn derive_pubkey(seed: &str) -> Result<PublicKey, MokshaCoreError> {
-    let hash = sha256::Hash::hash(seed.as_bytes());
-    let key = SecretKey::from_slice(hash.as_byte_array())?;
+    let key = derive_secret_key(seed)?;
     let secp = Secp256k1::new();
     Ok(key.public_key(&secp))
 }
 
+/// Derives the secret key of the mint pubkey from the seed
+pub fn derive_secret_key(seed: &str) -> Result<SecretKey, MokshaCoreError> {
+    let hash = sha256::Hash::hash(seed.as_bytes());
+    Ok(SecretKey::from_slice(hash.as_byte_array())?)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::keyset::{derive_pubkey, KeysetId};
+    use crate::keyset::{derive_pubkey, derive_secret_key, DenominationLimit, KeysetId, Keysets};
+    use crate::primitives::CurrencyUnit;
     use pretty_assertions::assert_eq;
     use secp256k1::PublicKey;
     use std::collections::HashMap;
+    use std::str::FromStr;
 
     #[test]
     fn test_keyset_id() -> anyhow::Result<()> {
@@ -218,6 +387,42 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_keysets_signature() -> anyhow::Result<()> {
+        let secret_key = derive_secret_key("supersecretprivatekey")?;
+        let mint_pubkey = derive_pubkey("supersecretprivatekey")?;
+        let keysets =
+            Keysets::new("00f545318e4fad2b".to_owned(), CurrencyUnit::Sat, true).sign(&secret_key);
+        keysets.verify_signature(&mint_pubkey)?;
+
+        let mut tampered = keysets.clone();
+        tampered.keysets[0].id = "00d31cecf59d18c0".to_owned();
+        assert!(tampered.verify_signature(&mint_pubkey).is_err());
+
+        let unsigned = Keysets {
+            signature: None,
+            ..keysets.clone()
+        };
+        assert!(unsigned.verify_signature(&mint_pubkey).is_err());
+
+        let other_pubkey = derive_pubkey("otherprivatekey")?;
+        assert!(keysets.verify_signature(&other_pubkey).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_denomination_limit_from_str() -> anyhow::Result<()> {
+        let limit = DenominationLimit::from_str("usd:1:1048576")?;
+        assert_eq!(CurrencyUnit::Usd, limit.unit);
+        assert_eq!(1, limit.min_amount);
+        assert_eq!(1_048_576, limit.max_amount);
+
+        assert!(DenominationLimit::from_str("usd:1").is_err());
+        assert!(DenominationLimit::from_str("usd:8:4").is_err());
+        assert!(DenominationLimit::from_str("usd:1:2:3").is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_derive_keys_master_v1() -> anyhow::Result<()> {
         let keys = super::derive_keys("supersecretprivatekey", "");
@@ -230,6 +435,16 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_derive_keyset_id_legacy() -> anyhow::Result<()> {
+        let keys = super::derive_keys("supersecretprivatekey", "");
+        let pub_keys = super::derive_pubkeys(&keys);
+        let id = super::derive_keyset_id_legacy(&pub_keys);
+        assert_eq!("PfXEw6/WbR9o", id);
+        assert_eq!(id.len(), 12);
+        Ok(())
+    }
+
     #[test]
     fn test_derive_keyset_id() -> anyhow::Result<()> {
         let keys: &str = r#"
@@ -4,6 +4,9 @@ pub mod dhke;
 pub mod error;
 pub mod fixture;
 pub mod keyset;
+pub mod p2pk;
 pub mod primitives;
 pub mod proof;
+pub mod spent_digest;
+pub mod subscription;
 pub mod token;
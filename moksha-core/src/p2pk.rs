@@ -0,0 +1,517 @@
+//! This module implements Pay-to-Pubkey spending conditions as described in [Nut-11](https://github.com/cashubtc/nuts/blob/main/11.md)
+//!
+//! A P2PK locked proof carries a well-known secret of the form `["P2PK", {"nonce": .., "data": .., "tags": ..}]`
+//! as described in [Nut-10](https://github.com/cashubtc/nuts/blob/main/10.md), where `data` is the pubkey the proof is locked to.
+//! To spend the proof the `witness` must contain a schnorr signature of the secret created with the matching secret key.
+//!
+//! A proof can be locked to several pubkeys with the `pubkeys` tag, the `n_sigs` tag sets how many of them have to sign
+//! (n-of-m multisig). Only the `SIG_INPUTS` signature flag is supported.
+//!
+//! After the unix time of the `locktime` tag has passed, a signature of one of the pubkeys from the `refund` tag unlocks
+//! the proof as well. Without `refund` tag the proof can be spent by anyone after the locktime.
+
+use bitcoin_hashes::{sha256, Hash};
+use secp256k1::{schnorr::Signature, Keypair, Message, PublicKey, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::str::FromStr;
+
+use crate::error::MokshaCoreError;
+
+const P2PK_KIND: &str = "P2PK";
+
+/// Maximum number of pubkeys (signers and refund pubkeys) a secret may lock a proof to, so
+/// verifying a witness stays cheap
+pub const MAX_P2PK_PUBKEYS: usize = 20;
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+struct SecretData {
+    nonce: String,
+    data: String,
+    tags: Option<Vec<Vec<String>>>,
+}
+
+/// Secret of a proof that is locked to one or more pubkeys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct P2PKSecret {
+    pub nonce: String,
+    pub pubkey: PublicKey,
+    /// additional pubkeys from the `pubkeys` tag that may sign
+    pub pubkeys: Vec<PublicKey>,
+    /// number of distinct pubkeys that have to sign, from the `n_sigs` tag
+    pub n_sigs: usize,
+    /// unix time after which the refund pubkeys may sign, from the `locktime` tag
+    pub locktime: Option<u64>,
+    /// pubkeys that may sign after the locktime, from the `refund` tag
+    pub refund: Vec<PublicKey>,
+    pub tags: Vec<Vec<String>>,
+}
+
+impl P2PKSecret {
+    /// Creates a secret with a random nonce that locks a proof to the pubkey
+    pub fn new(pubkey: PublicKey) -> Self {
+        let nonce: [u8; 32] = secp256k1::rand::random();
+        Self {
+            nonce: hex::encode(nonce),
+            pubkey,
+            pubkeys: vec![],
+            n_sigs: 1,
+            locktime: None,
+            refund: vec![],
+            tags: vec![],
+        }
+    }
+
+    /// Creates a secret with a random nonce that requires signatures of `n_sigs` of the pubkeys
+    pub fn new_multisig(pubkeys: &[PublicKey], n_sigs: usize) -> Result<Self, MokshaCoreError> {
+        let Some((pubkey, others)) = pubkeys.split_first() else {
+            return Err(MokshaCoreError::UnsupportedSpendingCondition(
+                "no pubkeys".to_owned(),
+            ));
+        };
+        if pubkeys.len() > MAX_P2PK_PUBKEYS {
+            return Err(MokshaCoreError::UnsupportedSpendingCondition(format!(
+                "more than {MAX_P2PK_PUBKEYS} pubkeys"
+            )));
+        }
+        if n_sigs == 0 || n_sigs > pubkeys.len() {
+            return Err(MokshaCoreError::UnsupportedSpendingCondition(format!(
+                "n_sigs {n_sigs} of {} pubkeys",
+                pubkeys.len()
+            )));
+        }
+
+        let mut secret = Self::new(*pubkey);
+        if !others.is_empty() {
+            secret.tags.push(
+                std::iter::once("pubkeys".to_owned())
+                    .chain(others.iter().map(PublicKey::to_string))
+                    .collect(),
+            );
+        }
+        if n_sigs > 1 {
+            secret
+                .tags
+                .push(vec!["n_sigs".to_owned(), n_sigs.to_string()]);
+        }
+        Ok(Self {
+            pubkeys: others.to_vec(),
+            n_sigs,
+            ..secret
+        })
+    }
+
+    /// Adds a locktime after which a signature of one of the refund pubkeys unlocks the proof. If
+    /// `refund` is empty anyone can spend the proof after the locktime.
+    pub fn with_locktime(mut self, locktime: u64, refund: &[PublicKey]) -> Self {
+        self.tags
+            .push(vec!["locktime".to_owned(), locktime.to_string()]);
+        if !refund.is_empty() {
+            self.tags.push(
+                std::iter::once("refund".to_owned())
+                    .chain(refund.iter().map(PublicKey::to_string))
+                    .collect(),
+            );
+        }
+        Self {
+            locktime: Some(locktime),
+            refund: refund.to_vec(),
+            ..self
+        }
+    }
+
+    /// Returns a copy of the secret with a new random nonce, so several proofs can be locked with
+    /// the same conditions
+    pub fn with_new_nonce(&self) -> Self {
+        let nonce: [u8; 32] = secp256k1::rand::random();
+        Self {
+            nonce: hex::encode(nonce),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the distinct pubkeys that may sign, the `data` pubkey first
+    pub fn signers(&self) -> Vec<PublicKey> {
+        let mut signers = vec![self.pubkey];
+        for pubkey in &self.pubkeys {
+            if !signers.contains(pubkey) {
+                signers.push(*pubkey);
+            }
+        }
+        signers
+    }
+
+    /// Parses a proof secret. Returns `None` for ordinary random secrets and an error for well-known
+    /// secrets that are malformed or use spending conditions that are not supported.
+    pub fn from_secret(secret: &str) -> Result<Option<Self>, MokshaCoreError> {
+        let Ok((kind, data)) = serde_json::from_str::<(String, serde_json::Value)>(secret) else {
+            return Ok(None);
+        };
+        if kind != P2PK_KIND {
+            return Err(MokshaCoreError::UnsupportedSpendingCondition(kind));
+        }
+
+        let data: SecretData = serde_json::from_value(data)?;
+        let tags = data.tags.unwrap_or_default();
+        let (mut pubkeys, mut n_sigs, mut locktime, mut refund) = (vec![], 1, None, vec![]);
+        for tag in &tags {
+            match tag.as_slice() {
+                [key, value] if key == "sigflag" && value != "SIG_INPUTS" => {
+                    return Err(MokshaCoreError::UnsupportedSpendingCondition(format!(
+                        "sigflag {value}"
+                    )));
+                }
+                [key, value] if key == "n_sigs" => {
+                    n_sigs = value.parse().map_err(|_| {
+                        MokshaCoreError::UnsupportedSpendingCondition(format!("n_sigs {value}"))
+                    })?;
+                }
+                [key, values @ ..] if key == "pubkeys" => {
+                    pubkeys = values
+                        .iter()
+                        .map(|value| PublicKey::from_str(value))
+                        .collect::<Result<_, _>>()?;
+                }
+                [key, value] if key == "locktime" => {
+                    locktime = Some(value.parse().map_err(|_| {
+                        MokshaCoreError::UnsupportedSpendingCondition(format!("locktime {value}"))
+                    })?);
+                }
+                [key, values @ ..] if key == "refund" => {
+                    refund = values
+                        .iter()
+                        .map(|value| PublicKey::from_str(value))
+                        .collect::<Result<_, _>>()?;
+                }
+                _ => {}
+            }
+        }
+
+        let secret = Self {
+            nonce: data.nonce,
+            pubkey: PublicKey::from_str(&data.data)?,
+            pubkeys,
+            n_sigs,
+            locktime,
+            refund,
+            tags,
+        };
+        // a proof that needs more signatures than there are signers could never be spent
+        if secret.n_sigs == 0 || secret.n_sigs > secret.signers().len() {
+            return Err(MokshaCoreError::UnsupportedSpendingCondition(format!(
+                "n_sigs {} of {} pubkeys",
+                secret.n_sigs,
+                secret.signers().len()
+            )));
+        }
+        Ok(Some(secret))
+    }
+
+    /// Returns the well-known secret that is used as secret of the proof
+    pub fn to_secret(&self) -> Result<String, MokshaCoreError> {
+        let data = SecretData {
+            nonce: self.nonce.clone(),
+            data: self.pubkey.to_string(),
+            tags: (!self.tags.is_empty()).then(|| self.tags.clone()),
+        };
+        Ok(serde_json::to_string(&(P2PK_KIND, data))?)
+    }
+}
+
+/// Witness of a P2PK locked proof, serialized as json string in the `witness` field of the proof
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct P2PKWitness {
+    /// hex encoded schnorr signatures of the secret
+    pub signatures: Vec<String>,
+}
+
+fn secret_message(secret: &str) -> Message {
+    Message::from_digest(sha256::Hash::hash(secret.as_bytes()).to_byte_array())
+}
+
+/// Signs the secret of a proof and adds the signature to the witness. Multisig proofs are signed
+/// once with each key
+pub fn sign_secret(
+    secret: &str,
+    key: &SecretKey,
+    witness: Option<&str>,
+) -> Result<String, MokshaCoreError> {
+    let mut witness: P2PKWitness = match witness {
+        Some(witness) => serde_json::from_str(witness)?,
+        None => P2PKWitness::default(),
+    };
+    let secp = Secp256k1::new();
+    let keypair = Keypair::from_secret_key(&secp, key);
+    let signature = secp.sign_schnorr_no_aux_rand(&secret_message(secret), &keypair);
+    witness.signatures.push(signature.to_string());
+    Ok(serde_json::to_string(&witness)?)
+}
+
+/// Checks that the witness contains valid signatures of the secret from at least `n_sigs` of the
+/// locked pubkeys. Witnesses with duplicate signatures or more signatures than pubkeys are
+/// rejected. After the locktime a signature of one refund pubkey is enough, or no signature if
+/// the secret has no refund pubkeys.
+pub fn verify_witness(
+    p2pk: &P2PKSecret,
+    secret: &str,
+    witness: Option<&str>,
+    now: u64,
+) -> Result<(), MokshaCoreError> {
+    let unlocked = p2pk.locktime.is_some_and(|locktime| now >= locktime);
+    if unlocked && p2pk.refund.is_empty() {
+        return Ok(());
+    }
+
+    let signers = p2pk.signers();
+    if signers.len() + p2pk.refund.len() > MAX_P2PK_PUBKEYS {
+        return Err(MokshaCoreError::UnsupportedSpendingCondition(format!(
+            "more than {MAX_P2PK_PUBKEYS} pubkeys"
+        )));
+    }
+
+    let witness: P2PKWitness =
+        serde_json::from_str(witness.ok_or(MokshaCoreError::MissingWitness)?)?;
+    // every pubkey signs once, so a witness can't carry more signatures than there are pubkeys
+    if witness.signatures.len() > signers.len() + p2pk.refund.len() {
+        return Err(MokshaCoreError::InvalidWitness);
+    }
+    let message = secret_message(secret);
+    let secp = Secp256k1::verification_only();
+    let mut signatures: Vec<Signature> = Vec::with_capacity(witness.signatures.len());
+    for signature in witness.signatures.iter() {
+        let Ok(signature) = Signature::from_str(signature) else {
+            continue;
+        };
+        if signatures.contains(&signature) {
+            return Err(MokshaCoreError::InvalidWitness);
+        }
+        signatures.push(signature);
+    }
+    let valid = |signers: &[PublicKey]| {
+        signers
+            .iter()
+            .filter(|signer| {
+                let (pubkey, _) = signer.x_only_public_key();
+                signatures
+                    .iter()
+                    .any(|signature| secp.verify_schnorr(signature, &message, &pubkey).is_ok())
+            })
+            .count()
+    };
+
+    match valid(&signers) >= p2pk.n_sigs || (unlocked && valid(&p2pk.refund) > 0) {
+        true => Ok(()),
+        false => Err(MokshaCoreError::InvalidWitness),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+    use super::{sign_secret, verify_witness, P2PKSecret, MAX_P2PK_PUBKEYS};
+    use crate::error::MokshaCoreError;
+
+    fn keypair(byte: u8) -> (SecretKey, PublicKey) {
+        let secret_key = SecretKey::from_slice(&[byte; 32]).expect("invalid key");
+        (secret_key, secret_key.public_key(&Secp256k1::new()))
+    }
+
+    #[test]
+    fn test_secret_roundtrip() -> anyhow::Result<()> {
+        let (_, pubkey) = keypair(1);
+        let secret = P2PKSecret::new(pubkey);
+
+        let serialized = secret.to_secret()?;
+        assert!(serialized.starts_with("[\"P2PK\",{\"nonce\":"));
+        assert_eq!(Some(secret), P2PKSecret::from_secret(&serialized)?);
+        assert_eq!(
+            None,
+            P2PKSecret::from_secret("407915bc212be61a77e3e6d2aeb4c727")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_secret_from_nut11() -> anyhow::Result<()> {
+        let secret = r#"["P2PK",{"nonce":"859d4935c4907062a6297cf4e663e2835d90d97ecdd510745d32f6816323a41f","data":"0249098aa8b9d2fbec49ff8598feb17b592b986e62319a4fa488a3dc36387157a7","tags":[["sigflag","SIG_INPUTS"]]}]"#;
+        let p2pk = P2PKSecret::from_secret(secret)?.expect("not a p2pk secret");
+        assert_eq!(
+            "0249098aa8b9d2fbec49ff8598feb17b592b986e62319a4fa488a3dc36387157a7",
+            p2pk.pubkey.to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_secret_unsupported_conditions() {
+        let sig_all = r#"["P2PK",{"nonce":"00","data":"0249098aa8b9d2fbec49ff8598feb17b592b986e62319a4fa488a3dc36387157a7","tags":[["sigflag","SIG_ALL"]]}]"#;
+        let htlc = r#"["HTLC",{"nonce":"00","data":"00"}]"#;
+        for secret in [sig_all, htlc] {
+            assert!(matches!(
+                P2PKSecret::from_secret(secret),
+                Err(MokshaCoreError::UnsupportedSpendingCondition(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_verify_witness() -> anyhow::Result<()> {
+        let (secret_key, pubkey) = keypair(1);
+        let (other_key, _) = keypair(2);
+        let p2pk = P2PKSecret::new(pubkey);
+        let secret = p2pk.to_secret()?;
+
+        let witness = sign_secret(&secret, &secret_key, None)?;
+        assert!(verify_witness(&p2pk, &secret, Some(&witness), 0).is_ok());
+
+        let witness = sign_secret(&secret, &other_key, None)?;
+        assert!(matches!(
+            verify_witness(&p2pk, &secret, Some(&witness), 0),
+            Err(MokshaCoreError::InvalidWitness)
+        ));
+        assert!(matches!(
+            verify_witness(&p2pk, &secret, None, 0),
+            Err(MokshaCoreError::MissingWitness)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_multisig_secret_roundtrip() -> anyhow::Result<()> {
+        let pubkeys = [keypair(1).1, keypair(2).1, keypair(3).1];
+        let secret = P2PKSecret::new_multisig(&pubkeys, 2)?;
+        assert_eq!(pubkeys.to_vec(), secret.signers());
+
+        let parsed = P2PKSecret::from_secret(&secret.to_secret()?)?.expect("not a p2pk secret");
+        assert_eq!(secret, parsed);
+        assert_eq!(2, parsed.n_sigs);
+
+        assert!(matches!(
+            P2PKSecret::new_multisig(&pubkeys, 4),
+            Err(MokshaCoreError::UnsupportedSpendingCondition(_))
+        ));
+        let unspendable = format!(
+            r#"["P2PK",{{"nonce":"00","data":"{}","tags":[["n_sigs","2"]]}}]"#,
+            pubkeys[0]
+        );
+        assert!(matches!(
+            P2PKSecret::from_secret(&unspendable),
+            Err(MokshaCoreError::UnsupportedSpendingCondition(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_witness_multisig() -> anyhow::Result<()> {
+        let keys = [keypair(1), keypair(2), keypair(3)];
+        let p2pk = P2PKSecret::new_multisig(&keys.map(|(_, pubkey)| pubkey), 2)?;
+        let secret = p2pk.to_secret()?;
+
+        // 2 of 3
+        let witness = sign_secret(&secret, &keys[0].0, None)?;
+        let witness = sign_secret(&secret, &keys[2].0, Some(&witness))?;
+        assert!(verify_witness(&p2pk, &secret, Some(&witness), 0).is_ok());
+
+        // 1 of 3, a duplicate signature of the same key is rejected
+        let witness = sign_secret(&secret, &keys[1].0, None)?;
+        assert!(matches!(
+            verify_witness(&p2pk, &secret, Some(&witness), 0),
+            Err(MokshaCoreError::InvalidWitness)
+        ));
+        let witness = sign_secret(&secret, &keys[1].0, Some(&witness))?;
+        assert!(matches!(
+            verify_witness(&p2pk, &secret, Some(&witness), 0),
+            Err(MokshaCoreError::InvalidWitness)
+        ));
+
+        // more signatures than pubkeys
+        let witness = keys.iter().chain(&[keypair(4)]).try_fold(
+            None,
+            |witness: Option<String>, (key, _)| {
+                sign_secret(&secret, key, witness.as_deref()).map(Some)
+            },
+        )?;
+        assert!(matches!(
+            verify_witness(&p2pk, &secret, witness.as_deref(), 0),
+            Err(MokshaCoreError::InvalidWitness)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_witness_too_many_pubkeys() -> anyhow::Result<()> {
+        let keys = (1..=MAX_P2PK_PUBKEYS as u8 + 1)
+            .map(|byte| keypair(byte).1)
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            P2PKSecret::new_multisig(&keys, 1),
+            Err(MokshaCoreError::UnsupportedSpendingCondition(_))
+        ));
+        let p2pk = P2PKSecret {
+            pubkeys: keys[1..].to_vec(),
+            ..P2PKSecret::new(keys[0])
+        };
+        let secret = p2pk.to_secret()?;
+
+        let witness = sign_secret(&secret, &keypair(1).0, None)?;
+        assert!(matches!(
+            verify_witness(&p2pk, &secret, Some(&witness), 0),
+            Err(MokshaCoreError::UnsupportedSpendingCondition(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_locktime_secret_roundtrip() -> anyhow::Result<()> {
+        let (_, pubkey) = keypair(1);
+        let (_, refund) = keypair(2);
+        let secret = P2PKSecret::new(pubkey).with_locktime(1_700_000_000, &[refund]);
+
+        let parsed = P2PKSecret::from_secret(&secret.to_secret()?)?.expect("not a p2pk secret");
+        assert_eq!(secret, parsed);
+        assert_eq!(Some(1_700_000_000), parsed.locktime);
+        assert_eq!(vec![refund], parsed.refund);
+        assert_ne!(secret.nonce, secret.with_new_nonce().nonce);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_witness_refund() -> anyhow::Result<()> {
+        let (secret_key, pubkey) = keypair(1);
+        let (refund_key, refund_pubkey) = keypair(2);
+        let p2pk = P2PKSecret::new(pubkey).with_locktime(100, &[refund_pubkey]);
+        let secret = p2pk.to_secret()?;
+
+        let witness = sign_secret(&secret, &refund_key, None)?;
+        assert!(matches!(
+            verify_witness(&p2pk, &secret, Some(&witness), 99),
+            Err(MokshaCoreError::InvalidWitness)
+        ));
+        assert!(verify_witness(&p2pk, &secret, Some(&witness), 100).is_ok());
+
+        // the locked pubkey can still sign after the locktime
+        let witness = sign_secret(&secret, &secret_key, None)?;
+        assert!(verify_witness(&p2pk, &secret, Some(&witness), 100).is_ok());
+        assert!(matches!(
+            verify_witness(&p2pk, &secret, None, 100),
+            Err(MokshaCoreError::MissingWitness)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_witness_locktime_without_refund() -> anyhow::Result<()> {
+        let (_, pubkey) = keypair(1);
+        let p2pk = P2PKSecret::new(pubkey).with_locktime(100, &[]);
+        let secret = p2pk.to_secret()?;
+
+        assert!(matches!(
+            verify_witness(&p2pk, &secret, None, 99),
+            Err(MokshaCoreError::MissingWitness)
+        ));
+        assert!(verify_witness(&p2pk, &secret, None, 100).is_ok());
+        Ok(())
+    }
+}
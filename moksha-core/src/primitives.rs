@@ -3,7 +3,8 @@
 
 use std::{collections::HashMap, fmt::Display, str::FromStr};
 
-use secp256k1::PublicKey;
+use bitcoin_hashes::{sha256, Hash};
+use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, SecretKey};
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 use utoipa::ToSchema;
@@ -11,7 +12,9 @@ use uuid::Uuid;
 
 use crate::{
     blind::{BlindedMessage, BlindedSignature},
+    error::MokshaCoreError,
     proof::Proofs,
+    subscription::SubscriptionKind,
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -42,12 +45,62 @@ pub struct PostSwapResponse {
     pub signatures: Vec<BlindedSignature>,
 }
 
+/// Checks the state of proofs by their Y (nut07)
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct PostCheckStateRequest {
+    #[serde(rename = "Ys")]
+    #[schema(value_type = Vec<String>)]
+    pub ys: Vec<PublicKey>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Default, ToSchema)]
+pub struct PostCheckStateResponse {
+    pub states: Vec<ProofState>,
+}
+
+/// Requests the signatures of outputs that were signed before (nut09)
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct PostRestoreRequest {
+    pub outputs: Vec<BlindedMessage>,
+}
+
+/// Contains only the outputs the mint has signed, in the same order as their signatures
+#[derive(Clone, Debug, Serialize, Deserialize, Default, ToSchema)]
+pub struct PostRestoreResponse {
+    pub outputs: Vec<BlindedMessage>,
+    pub signatures: Vec<BlindedSignature>,
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct ProofState {
+    #[serde(rename = "Y")]
+    #[schema(value_type = String)]
+    pub y: PublicKey,
+    pub state: SpendState,
+    pub witness: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum SpendState {
+    Unspent,
+    /// the proof is an input of a melt that is not finished yet
+    Pending,
+    Spent,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct CashuErrorResponse {
     pub code: u64,
     pub detail: String,
 }
 
+impl CashuErrorResponse {
+    /// error code of proofs that have already been spent
+    pub const PROOF_ALREADY_USED: u64 = 11001;
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default, ToSchema)]
 pub struct KeysResponse {
     pub keysets: Vec<KeyResponse>,
@@ -88,6 +141,19 @@ impl From<String> for CurrencyUnit {
     }
 }
 
+impl FromStr for CurrencyUnit {
+    type Err = MokshaCoreError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sat" => Ok(Self::Sat),
+            "msat" => Ok(Self::MSat),
+            "usd" => Ok(Self::Usd),
+            _ => Err(MokshaCoreError::UnknownCurrencyUnit(s.to_owned())),
+        }
+    }
+}
+
 impl Display for CurrencyUnit {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -102,6 +168,7 @@ impl Display for CurrencyUnit {
 #[serde(rename_all = "lowercase")]
 pub enum PaymentMethod {
     Bolt11,
+    Bolt12,
     BtcOnchain,
 }
 
@@ -109,6 +176,7 @@ impl Display for PaymentMethod {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Bolt11 => write!(f, "Lightning"),
+            Self::Bolt12 => write!(f, "Lightning (Bolt12)"),
             Self::BtcOnchain => write!(f, "Onchain"),
         }
     }
@@ -125,6 +193,7 @@ pub struct PostMintQuoteBolt11Response {
     pub quote: String,
     #[serde(rename = "request")]
     pub payment_request: String,
+    pub amount: u64,
     pub paid: bool,
     pub expiry: Option<u64>,
 }
@@ -134,6 +203,7 @@ impl From<Bolt11MintQuote> for PostMintQuoteBolt11Response {
         Self {
             quote: quote.quote_id.to_string(),
             payment_request: quote.payment_request,
+            amount: quote.amount,
             paid: quote.paid,
             expiry: Some(quote.expiry),
         }
@@ -151,11 +221,38 @@ pub struct PostMintBolt11Response {
     pub signatures: Vec<BlindedSignature>,
 }
 
+#[skip_serializing_none]
 #[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
 pub struct PostMeltQuoteBolt11Request {
     /// payment request
     pub request: String,
     pub unit: CurrencyUnit,
+    /// amount to pay in sats, required if the invoice has no amount
+    #[serde(default)]
+    pub amount: Option<u64>,
+    #[serde(default)]
+    pub options: Option<MeltOptions>,
+}
+
+impl PostMeltQuoteBolt11Request {
+    /// Returns the partial amount in msat if only a part of the invoice is paid (nut15)
+    pub fn mpp_amount(&self) -> Option<u64> {
+        self.options.as_ref()?.mpp.as_ref().map(|mpp| mpp.amount)
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq, ToSchema)]
+pub struct MeltOptions {
+    #[serde(default)]
+    pub mpp: Option<MppOption>,
+}
+
+/// Pays a part of a multi-path payment. The rest of the invoice is paid by other mints (nut15)
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct MppOption {
+    /// partial amount in msat
+    pub amount: u64,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
@@ -167,12 +264,33 @@ pub struct PostMeltQuoteBolt11Response {
     pub expiry: Option<u64>,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum MeltProbeState {
+    /// the mint found a route to the payee
+    Routable,
+    /// the mint didn't find a route to the payee
+    Unroutable,
+    /// the lightning backend of the mint can't probe routes
+    Unknown,
+}
+
+#[skip_serializing_none]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct PostMeltProbeBolt11Response {
+    pub state: MeltProbeState,
+    /// estimated routing fee in sats, only set if a route was found
+    pub fee: Option<u64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Bolt11MintQuote {
     pub quote_id: Uuid,
     pub payment_request: String,
+    pub amount: u64,
     pub expiry: u64,
     pub paid: bool,
+    pub unit: CurrencyUnit,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -183,6 +301,14 @@ pub struct Bolt11MeltQuote {
     pub payment_request: String,
     pub expiry: u64,
     pub paid: bool,
+    /// the payment failed and the inputs got refunded
+    pub failed: bool,
+    /// amount in sats the payee received, known after settlement
+    pub amount_paid: Option<u64>,
+    /// lightning fee in sats paid by the mint, known after settlement
+    pub fee_paid: Option<u64>,
+    /// unit of the inputs and the change
+    pub unit: CurrencyUnit,
 }
 
 impl From<Bolt11MeltQuote> for PostMeltQuoteBolt11Response {
@@ -209,6 +335,12 @@ pub struct PostMeltBolt11Response {
     pub paid: bool,
     pub payment_preimage: Option<String>,
     pub change: Vec<BlindedSignature>,
+    /// amount in sats the payee received. Only set if the invoice has been paid
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount_paid: Option<u64>,
+    /// lightning fee in sats the mint actually paid. Only set if the invoice has been paid
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_paid: Option<u64>,
 }
 
 #[skip_serializing_none]
@@ -222,6 +354,8 @@ pub struct MintInfoResponse {
     pub description_long: Option<String>,
     pub contact: Option<Vec<ContactInfoResponse>>,
     pub motd: Option<String>,
+    /// current unix time of the mint, so wallets can detect a skewed clock
+    pub time: Option<u64>,
     pub nuts: Nuts,
 }
 
@@ -254,6 +388,71 @@ impl ContactInfoResponse {
     }
 }
 
+/// Outstanding ecash of the mint at a point in time, used as a proof of liabilities
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct LiabilitiesSnapshot {
+    /// unix timestamp in seconds
+    pub timestamp: u64,
+    pub keysets: Vec<KeysetLiabilities>,
+    pub units: Vec<UnitLiabilities>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct KeysetLiabilities {
+    pub id: String,
+    pub unit: CurrencyUnit,
+    pub issued: u64,
+    pub redeemed: u64,
+    pub outstanding: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct UnitLiabilities {
+    pub unit: CurrencyUnit,
+    pub outstanding: u64,
+}
+
+impl LiabilitiesSnapshot {
+    /// sha256 of the json serialized snapshot, this is the message that gets signed
+    pub fn digest(&self) -> Result<[u8; 32], MokshaCoreError> {
+        Ok(sha256::Hash::hash(&serde_json::to_vec(self)?).to_byte_array())
+    }
+
+    /// Signs the snapshot and returns the hex encoded compact ecdsa signature
+    pub fn sign(&self, key: &SecretKey) -> Result<String, MokshaCoreError> {
+        let message = Message::from_digest(self.digest()?);
+        let signature = Secp256k1::new().sign_ecdsa(&message, key);
+        Ok(hex::encode(signature.serialize_compact()))
+    }
+
+    /// Verifies a signature created by [`LiabilitiesSnapshot::sign`] against the pubkey of the mint
+    pub fn verify(&self, signature: &str, pubkey: &PublicKey) -> Result<(), MokshaCoreError> {
+        let message = Message::from_digest(self.digest()?);
+        let signature = Signature::from_compact(&hex::decode(signature)?)?;
+        Ok(Secp256k1::verification_only().verify_ecdsa(&message, &signature, pubkey)?)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct PostMeltRefundRequest {
+    /// payment hash of the melt quote invoice, confirms that the operator checked the payment
+    pub payment_hash: String,
+    /// blank outputs of the wallet for the refunded amount
+    pub outputs: Vec<BlindedMessage>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct PostMeltRefundResponse {
+    pub signatures: Vec<BlindedSignature>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct GetLiabilitiesSnapshotResponse {
+    pub snapshot: LiabilitiesSnapshot,
+    /// hex encoded ecdsa signature of the snapshot by the mint pubkey from /v1/info
+    pub signature: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct BtcOnchainMintQuote {
     pub quote_id: Uuid,
@@ -269,6 +468,7 @@ impl From<BtcOnchainMintQuote> for PostMintQuoteBtcOnchainResponse {
         Self {
             quote: quote.quote_id.to_string(),
             address: quote.address,
+            amount: quote.amount,
             state: quote.state,
             expiry: quote.expiry,
         }
@@ -278,13 +478,24 @@ impl From<BtcOnchainMintQuote> for PostMintQuoteBtcOnchainResponse {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct BtcOnchainMeltQuote {
     pub quote_id: Uuid,
+    /// total amount of all outputs
     pub amount: u64,
+    /// address of the first output
     pub address: String,
     pub fee_total: u64,
     pub fee_sat_per_vbyte: u32,
     pub expiry: u64,
     pub state: MeltBtcOnchainState,
     pub description: Option<String>,
+    /// all outputs of the transaction
+    pub outputs: Vec<OnchainOutput>,
+}
+
+/// Output of an onchain melt transaction
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct OnchainOutput {
+    pub address: String,
+    pub amount: u64,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
@@ -297,6 +508,7 @@ pub struct PostMintQuoteBtcOnchainRequest {
 pub struct PostMintQuoteBtcOnchainResponse {
     pub quote: String,
     pub address: String,
+    pub amount: u64,
     pub state: MintBtcOnchainState,
     pub expiry: u64,
 }
@@ -352,10 +564,29 @@ pub struct PostMintBtcOnchainResponse {
 
 #[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
 pub struct PostMeltQuoteBtcOnchainRequest {
+    /// amount of a single output, ignored if `outputs` is set
+    #[serde(default)]
     pub amount: u64,
-    /// onchain address
+    /// onchain address of a single output, ignored if `outputs` is set
+    #[serde(default)]
     pub address: String,
     pub unit: CurrencyUnit,
+    /// outputs of a batched transaction
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<OnchainOutput>,
+}
+
+impl PostMeltQuoteBtcOnchainRequest {
+    /// Returns the batched outputs or the single output of `address` and `amount`
+    pub fn into_outputs(self) -> Vec<OnchainOutput> {
+        match self.outputs.is_empty() {
+            true => vec![OnchainOutput {
+                address: self.address,
+                amount: self.amount,
+            }],
+            false => self.outputs,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
@@ -497,11 +728,17 @@ impl Default for Nuts {
         Self {
             nut4: Nut4::default(),
             nut5: Nut5::default(),
-            nut7: Some(Nut7 { supported: false }),
+            nut7: Some(Nut7 {
+                supported: true,
+                max_inputs: None,
+            }),
             nut8: Some(Nut8 { supported: true }),
-            nut9: Some(Nut9 { supported: false }),
-            nut10: Some(Nut10 { supported: false }),
-            nut11: Some(Nut11 { supported: false }),
+            nut9: Some(Nut9 {
+                supported: true,
+                max_outputs: None,
+            }),
+            nut10: Some(Nut10 { supported: true }),
+            nut11: Some(Nut11 { supported: true }),
             nut12: Some(Nut12 { supported: false }),
             nut13: Some(Nut13::default()),
             nut14: Some(Nut14::default()),
@@ -521,44 +758,85 @@ pub struct Nut4 {
     pub disabled: bool,
 }
 
-impl Default for Nut4 {
-    fn default() -> Self {
+impl Nut4 {
+    /// Creates a bolt11 payment method for each unit
+    pub fn new(units: &[CurrencyUnit]) -> Self {
         Self {
-            payment_methods: vec![PaymentMethodConfig {
-                payment_method: PaymentMethod::Bolt11,
-                unit: CurrencyUnit::Sat,
-                min_amount: Some(1),
-                max_amount: Some(10_000_000),
-            }],
+            payment_methods: PaymentMethodConfig::bolt11(units),
             disabled: false,
         }
     }
 }
 
+impl Default for Nut4 {
+    fn default() -> Self {
+        Self::new(&[CurrencyUnit::Sat])
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
 pub struct Nut5 {
     #[serde(rename = "methods")]
     pub payment_methods: Vec<PaymentMethodConfig>,
     pub disabled: bool,
+    /// the mint can probe routes for bolt11 melt quotes
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub probe: bool,
 }
 
-impl Default for Nut5 {
-    fn default() -> Self {
+impl Nut5 {
+    /// Creates a bolt11 payment method for each unit
+    pub fn new(units: &[CurrencyUnit]) -> Self {
         Self {
-            payment_methods: vec![PaymentMethodConfig {
-                payment_method: PaymentMethod::Bolt11,
-                unit: CurrencyUnit::Sat,
-                min_amount: Some(1),
-                max_amount: Some(10_000_000),
-            }],
+            payment_methods: PaymentMethodConfig::bolt11(units),
             disabled: false,
+            probe: false,
         }
     }
 }
 
+impl Default for Nut5 {
+    fn default() -> Self {
+        Self::new(&[CurrencyUnit::Sat])
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default, ToSchema)]
+#[serde(from = "Nut7Repr")]
 pub struct Nut7 {
     pub supported: bool,
+    /// maximum number of proofs per state check request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_inputs: Option<u64>,
+}
+
+/// older mints report nut7 as a bare bool
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Nut7Repr {
+    Supported(bool),
+    Detailed {
+        supported: bool,
+        max_inputs: Option<u64>,
+    },
+}
+
+impl From<Nut7Repr> for Nut7 {
+    fn from(repr: Nut7Repr) -> Self {
+        match repr {
+            Nut7Repr::Supported(supported) => Self {
+                supported,
+                max_inputs: None,
+            },
+            Nut7Repr::Detailed {
+                supported,
+                max_inputs,
+            } => Self {
+                supported,
+                max_inputs,
+            },
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
@@ -573,8 +851,41 @@ impl Default for Nut8 {
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default, ToSchema)]
+#[serde(from = "Nut9Repr")]
 pub struct Nut9 {
     pub supported: bool,
+    /// maximum number of outputs per restore request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_outputs: Option<u64>,
+}
+
+/// older mints report nut9 as a bare bool
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Nut9Repr {
+    Supported(bool),
+    Detailed {
+        supported: bool,
+        max_outputs: Option<u64>,
+    },
+}
+
+impl From<Nut9Repr> for Nut9 {
+    fn from(repr: Nut9Repr) -> Self {
+        match repr {
+            Nut9Repr::Supported(supported) => Self {
+                supported,
+                max_outputs: None,
+            },
+            Nut9Repr::Detailed {
+                supported,
+                max_outputs,
+            } => Self {
+                supported,
+                max_outputs,
+            },
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default, ToSchema)]
@@ -613,7 +924,40 @@ pub struct Nut16 {
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default, ToSchema)]
-pub struct Nut17 {}
+pub struct Nut17 {
+    #[serde(default)]
+    pub supported: Vec<Nut17Method>,
+}
+
+impl Nut17 {
+    /// Supports subscriptions to bolt11 mint quotes for each unit
+    pub fn bolt11_mint_quote(units: &[CurrencyUnit]) -> Self {
+        Self {
+            supported: units
+                .iter()
+                .map(|unit| Nut17Method {
+                    payment_method: PaymentMethod::Bolt11,
+                    unit: unit.clone(),
+                    commands: vec![SubscriptionKind::Bolt11MintQuote],
+                })
+                .collect(),
+        }
+    }
+
+    pub fn supports(&self, unit: &CurrencyUnit, kind: SubscriptionKind) -> bool {
+        self.supported
+            .iter()
+            .any(|method| method.unit == *unit && method.commands.contains(&kind))
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct Nut17Method {
+    #[serde(rename = "method")]
+    pub payment_method: PaymentMethod,
+    pub unit: CurrencyUnit,
+    pub commands: Vec<SubscriptionKind>,
+}
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
 pub struct Nut18 {
@@ -622,6 +966,9 @@ pub struct Nut18 {
     pub payment_methods: Vec<PaymentMethodConfigBtcOnchainMint>,
 }
 
+/// max amount of bolt11 mint and melt quotes if the mint doesn't configure one
+pub const DEFAULT_BOLT11_MAX_AMOUNT: u64 = 10_000_000;
+
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
 pub struct PaymentMethodConfig {
     #[serde(rename = "method")]
@@ -631,6 +978,35 @@ pub struct PaymentMethodConfig {
     pub max_amount: Option<u64>,
 }
 
+impl PaymentMethodConfig {
+    /// Creates a config with the same limits for each unit
+    pub fn for_units(
+        payment_method: PaymentMethod,
+        units: &[CurrencyUnit],
+        min_amount: Option<u64>,
+        max_amount: Option<u64>,
+    ) -> Vec<Self> {
+        units
+            .iter()
+            .map(|unit| Self {
+                payment_method: payment_method.clone(),
+                unit: unit.clone(),
+                min_amount,
+                max_amount,
+            })
+            .collect()
+    }
+
+    fn bolt11(units: &[CurrencyUnit]) -> Vec<Self> {
+        Self::for_units(
+            PaymentMethod::Bolt11,
+            units,
+            Some(1),
+            Some(DEFAULT_BOLT11_MAX_AMOUNT),
+        )
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
 pub struct PaymentMethodConfigBtcOnchainMint {
     #[serde(rename = "method")]
@@ -639,6 +1015,52 @@ pub struct PaymentMethodConfigBtcOnchainMint {
     pub min_amount: u64,
     pub max_amount: u64,
     pub min_confirmations: u8,
+    /// deposits above a tier amount need more confirmations than `min_confirmations`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub confirmation_tiers: Vec<ConfirmationTier>,
+}
+
+impl PaymentMethodConfigBtcOnchainMint {
+    /// Returns the confirmations a deposit of the given amount in sats needs before it can be minted
+    pub fn required_confirmations(&self, amount: u64) -> u8 {
+        ConfirmationTier::required_confirmations(
+            &self.confirmation_tiers,
+            self.min_confirmations,
+            amount,
+        )
+    }
+}
+
+/// Onchain deposits of at least `min_amount` sats need `min_confirmations` confirmations
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct ConfirmationTier {
+    pub min_amount: u64,
+    pub min_confirmations: u8,
+}
+
+impl ConfirmationTier {
+    /// Returns the confirmations of the highest tier the amount reaches, but at least `min_confirmations`
+    pub fn required_confirmations(tiers: &[Self], min_confirmations: u8, amount: u64) -> u8 {
+        tiers
+            .iter()
+            .filter(|tier| amount >= tier.min_amount)
+            .map(|tier| tier.min_confirmations)
+            .fold(min_confirmations, std::cmp::max)
+    }
+}
+
+impl FromStr for ConfirmationTier {
+    type Err = MokshaCoreError;
+
+    /// Parses a tier in the format `<min_amount>:<min_confirmations>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || MokshaCoreError::InvalidConfirmationTier(s.to_owned());
+        let (min_amount, min_confirmations) = s.split_once(':').ok_or_else(invalid)?;
+        Ok(Self {
+            min_amount: min_amount.trim().parse().map_err(|_| invalid())?,
+            min_confirmations: min_confirmations.trim().parse().map_err(|_| invalid())?,
+        })
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
@@ -660,6 +1082,7 @@ impl Default for Nut18 {
                 min_amount: 10_000,
                 max_amount: 1_000_000,
                 min_confirmations: 3,
+                confirmation_tiers: vec![],
             }],
         }
     }
@@ -693,9 +1116,39 @@ mod tests {
     use crate::{
         dhke::public_key_from_hex,
         fixture::read_fixture,
-        primitives::{ContactInfoResponse, KeyResponse, MintInfoResponse, Nuts, PostSwapResponse},
+        primitives::{
+            ConfirmationTier, ContactInfoResponse, KeyResponse, MintInfoResponse, Nut7, Nut9, Nuts,
+            PostSwapResponse,
+        },
     };
 
+    #[test]
+    fn test_required_confirmations() -> anyhow::Result<()> {
+        let tiers = vec![
+            "10000000:6".parse::<ConfirmationTier>()?,
+            "1000000:3".parse::<ConfirmationTier>()?,
+        ];
+        assert_eq!(
+            1,
+            ConfirmationTier::required_confirmations(&tiers, 1, 50_000)
+        );
+        assert_eq!(
+            3,
+            ConfirmationTier::required_confirmations(&tiers, 1, 1_000_000)
+        );
+        assert_eq!(
+            6,
+            ConfirmationTier::required_confirmations(&tiers, 1, 25_000_000)
+        );
+        // a tier never lowers the global minimum
+        assert_eq!(
+            4,
+            ConfirmationTier::required_confirmations(&tiers, 4, 1_000_000)
+        );
+        assert!("6".parse::<ConfirmationTier>().is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_serialize_empty_swap_response() -> anyhow::Result<()> {
         let response = PostSwapResponse::default();
@@ -733,6 +1186,7 @@ mod tests {
             ]),
             nuts: Nuts::default(),
             motd: Some("Message to display to users.".to_string()),
+            time: None,
         };
         let out = serde_json::to_string_pretty(&mint_info)?;
         assert!(!out.is_empty());
@@ -775,4 +1229,59 @@ mod tests {
         assert!(info.nuts.nut12.is_none());
         Ok(())
     }
+
+    #[test]
+    fn test_deserialize_nut7_nut9_bool() -> anyhow::Result<()> {
+        let nuts = serde_json::from_str::<Nuts>(
+            r#"{"4": {"methods": [], "disabled": false}, "5": {"methods": [], "disabled": false}, "7": true, "9": false}"#,
+        )?;
+        assert_eq!(
+            Some(Nut7 {
+                supported: true,
+                max_inputs: None
+            }),
+            nuts.nut7
+        );
+        assert_eq!(
+            Some(Nut9 {
+                supported: false,
+                max_outputs: None
+            }),
+            nuts.nut9
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_nut7_nut9_detailed() -> anyhow::Result<()> {
+        let nut7 = serde_json::from_str::<Nut7>(r#"{"supported": true}"#)?;
+        assert_eq!(
+            Nut7 {
+                supported: true,
+                max_inputs: None
+            },
+            nut7
+        );
+
+        let nut7 = serde_json::from_str::<Nut7>(r#"{"supported": true, "max_inputs": 1000}"#)?;
+        assert_eq!(Some(1000), nut7.max_inputs);
+        assert_eq!(
+            r#"{"supported":true,"max_inputs":1000}"#,
+            serde_json::to_string(&nut7)?
+        );
+
+        let nut9 = serde_json::from_str::<Nut9>(r#"{"supported": true, "max_outputs": 500}"#)?;
+        assert_eq!(
+            Nut9 {
+                supported: true,
+                max_outputs: Some(500)
+            },
+            nut9
+        );
+        assert_eq!(
+            r#"{"supported":false}"#,
+            serde_json::to_string(&Nut9::default())?
+        );
+        Ok(())
+    }
 }
@@ -10,12 +10,17 @@
 //!
 //! Both the `Proof` and `Proofs` structs are serializable and deserializable using serde.
 
-use secp256k1::PublicKey;
+use secp256k1::{PublicKey, SecretKey};
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 use utoipa::ToSchema;
 
-use crate::{error::MokshaCoreError, keyset::KeysetId};
+use crate::{
+    dhke::Dhke,
+    error::MokshaCoreError,
+    keyset::KeysetId,
+    p2pk::{self, P2PKSecret},
+};
 
 #[skip_serializing_none]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
@@ -28,6 +33,8 @@ pub struct Proof {
     #[schema(value_type = String)]
     pub c: PublicKey,
     pub script: Option<P2SHScript>,
+    /// json encoded signatures that unlock a P2PK locked proof, see [`crate::p2pk`]
+    pub witness: Option<String>,
 }
 
 impl Proof {
@@ -38,12 +45,50 @@ impl Proof {
             c,
             keyset_id: id,
             script: None,
+            witness: None,
+        }
+    }
+
+    /// Returns Y = hash_to_curve(secret), which identifies the proof without revealing the secret
+    pub fn y(&self) -> Result<PublicKey, MokshaCoreError> {
+        Dhke::hash_to_curve(self.secret.as_bytes())
+    }
+
+    /// Adds a signature with the given key to the witness that unlocks a P2PK locked proof.
+    /// Multisig proofs are signed once with each key
+    pub fn sign_p2pk(self, key: &SecretKey) -> Result<Self, MokshaCoreError> {
+        Ok(Self {
+            witness: Some(p2pk::sign_secret(
+                &self.secret,
+                key,
+                self.witness.as_deref(),
+            )?),
+            ..self
+        })
+    }
+
+    /// Checks the spending conditions of the secret at the unix time `now`. Proofs with an ordinary
+    /// secret have none.
+    pub fn verify_spending_conditions(&self, now: u64) -> Result<(), MokshaCoreError> {
+        match P2PKSecret::from_secret(&self.secret)? {
+            Some(condition) => {
+                p2pk::verify_witness(&condition, &self.secret, self.witness.as_deref(), now)
+            }
+            None => Ok(()),
         }
     }
 }
 
+/// Pay to script hash lock of the legacy api. Mints reject proofs that carry one.
+#[skip_serializing_none]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
-pub struct P2SHScript;
+pub struct P2SHScript {
+    /// base64 encoded script
+    pub script: String,
+    /// base64 encoded signature that unlocks the script
+    pub signature: String,
+    pub address: Option<String>,
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
 pub struct Proofs(pub(super) Vec<Proof>);
@@ -61,8 +106,16 @@ impl Proofs {
         Self(vec![])
     }
 
+    /// Returns the sum of all amounts, saturating at `u64::MAX`
     pub fn total_amount(&self) -> u64 {
-        self.0.iter().map(|proof| proof.amount).sum()
+        self.checked_total_amount().unwrap_or(u64::MAX)
+    }
+
+    /// Returns the sum of all amounts or `None` if it overflows
+    pub fn checked_total_amount(&self) -> Option<u64> {
+        self.0
+            .iter()
+            .try_fold(0u64, |acc, proof| acc.checked_add(proof.amount))
     }
 
     pub fn proofs(&self) -> Vec<Proof> {
@@ -109,6 +162,77 @@ impl Proofs {
 
         Ok(selected_proofs.into())
     }
+
+    /// Selects proofs that sum up to exactly the amount, so they can be sent without a swap.
+    /// Returns `None` if no such selection is found. Picking the largest proof that still fits
+    /// always finds one for power of two denominations.
+    pub fn proofs_for_exact_amount(&self, amount: u64) -> Option<Self> {
+        let mut all_proofs = self.0.clone();
+        all_proofs.sort_by(|a, b| b.amount.cmp(&a.amount));
+
+        let mut remaining = amount;
+        let selected_proofs = all_proofs
+            .into_iter()
+            .filter(|proof| {
+                let fits = proof.amount <= remaining;
+                if fits {
+                    remaining -= proof.amount;
+                }
+                fits
+            })
+            .collect::<Vec<_>>();
+
+        (amount > 0 && remaining == 0).then(|| selected_proofs.into())
+    }
+
+    /// Selects proofs that cover the amount with as little change as possible. An exact match is
+    /// preferred, otherwise the selection with the smallest overshoot and then the fewest proofs
+    /// wins.
+    pub fn select_proofs(&self, amount: u64) -> Result<Self, MokshaCoreError> {
+        if amount > self.total_amount() {
+            return Err(MokshaCoreError::NotEnoughTokens);
+        }
+        if let Some(exact) = self.proofs_for_exact_amount(amount) {
+            return Ok(exact);
+        }
+
+        let mut all_proofs = self.0.clone();
+        all_proofs.sort_by(|a, b| b.amount.cmp(&a.amount));
+
+        // fill up with the largest proofs that fit, then cover the rest with the smallest
+        // proof that is large enough or keep adding the largest ones left
+        let (mut selected, mut unused): (Vec<Proof>, Vec<Proof>) = (vec![], vec![]);
+        let mut remaining = amount;
+        for proof in all_proofs.iter() {
+            if proof.amount <= remaining {
+                remaining -= proof.amount;
+                selected.push(proof.clone());
+            } else {
+                unused.push(proof.clone());
+            }
+        }
+        while remaining > 0 && !unused.is_empty() {
+            let index = unused
+                .iter()
+                .rposition(|proof| proof.amount >= remaining)
+                .unwrap_or(0);
+            let proof = unused.remove(index);
+            remaining = remaining.saturating_sub(proof.amount);
+            selected.push(proof);
+        }
+
+        // a single proof can still beat the combination above
+        let single = all_proofs
+            .iter()
+            .rev()
+            .find(|proof| proof.amount >= amount)
+            .cloned();
+        let selected: Self = selected.into();
+        Ok(match single {
+            Some(single) if single.amount <= selected.total_amount() => single.into(),
+            _ => selected,
+        })
+    }
 }
 
 impl From<Vec<Proof>> for Proofs {
@@ -160,6 +284,41 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_proofs_for_exact_amount() -> anyhow::Result<()> {
+        let fixture = read_fixture("token_60.cashu")?; // 60 tokens (4,8,16,32)
+        let token: TokenV3 = fixture.try_into()?;
+
+        let result = token.proofs().proofs_for_exact_amount(36);
+        assert_eq!(
+            Some(vec![32, 4]),
+            result.map(|proofs| proofs.proofs().iter().map(|p| p.amount).collect())
+        );
+        assert!(token.proofs().proofs_for_exact_amount(10).is_none());
+        assert!(token.proofs().proofs_for_exact_amount(0).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_checked_total_amount_overflow() -> anyhow::Result<()> {
+        let fixture = read_fixture("token_60.cashu")?;
+        let token: TokenV3 = fixture.try_into()?;
+        let proofs: Proofs = token
+            .proofs()
+            .proofs()
+            .into_iter()
+            .map(|proof| Proof {
+                amount: u64::MAX / 2,
+                ..proof
+            })
+            .collect::<Vec<_>>()
+            .into();
+
+        assert_eq!(None, proofs.checked_total_amount());
+        assert_eq!(u64::MAX, proofs.total_amount());
+        Ok(())
+    }
+
     #[test]
     fn test_proof() -> anyhow::Result<()> {
         let js = json!(
@@ -181,4 +340,26 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_select_proofs() -> anyhow::Result<()> {
+        let fixture = read_fixture("token_60.cashu")?; // 60 tokens (4,8,16,32)
+        let token: TokenV3 = fixture.try_into()?;
+        let amounts = |proofs: Proofs| {
+            let mut amounts = proofs.proofs().iter().map(|p| p.amount).collect::<Vec<_>>();
+            amounts.sort();
+            amounts
+        };
+
+        assert_eq!(vec![4, 16], amounts(token.proofs().select_proofs(20)?));
+        assert_eq!(vec![4, 32], amounts(token.proofs().select_proofs(33)?));
+        assert_eq!(vec![4, 8], amounts(token.proofs().select_proofs(10)?));
+        assert_eq!(vec![32], amounts(token.proofs().select_proofs(30)?));
+        assert_eq!(
+            vec![4, 8, 16, 32],
+            amounts(token.proofs().select_proofs(57)?)
+        );
+        assert!(token.proofs().select_proofs(61).is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,107 @@
+//! This module defines the `SpentDigest`, a bloom filter over the Y values of all spent proofs of a mint.
+//!
+//! A wallet downloads the digest and checks its proofs locally, so it doesn't have to reveal all of them to the mint
+//! with a checkstate request. A bloom filter has no false negatives: a proof that is not in the digest is unspent.
+//! Proofs that are in the digest may be false positives and need to be checked with `/v1/checkstate`.
+//!
+//! The positions of a Y in the filter are derived from sha256(Y) with double hashing, `h1 + i * h2 mod bits`.
+
+use base64::{engine::general_purpose, Engine as _};
+use bitcoin_hashes::{sha256, Hash};
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::MokshaCoreError;
+
+/// false positive rate the digest is sized for
+const FALSE_POSITIVE_RATE: f64 = 0.01;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct SpentDigest {
+    /// number of spent proofs in the digest
+    pub count: u64,
+    /// number of bit positions per proof
+    pub num_hashes: u32,
+    /// base64 encoded bits of the filter
+    pub filter: String,
+}
+
+impl SpentDigest {
+    /// Creates a digest of the Y values of spent proofs
+    pub fn new(ys: &[PublicKey]) -> Self {
+        let count = ys.len() as f64;
+        let num_bits = (-count * FALSE_POSITIVE_RATE.ln() / std::f64::consts::LN_2.powi(2))
+            .ceil()
+            .max(8.0) as u64;
+        let num_hashes = ((num_bits as f64 / count.max(1.0)) * std::f64::consts::LN_2)
+            .round()
+            .max(1.0) as u32;
+
+        let mut bits = vec![0u8; num_bits.div_ceil(8) as usize];
+        let num_bits = bits.len() as u64 * 8;
+        for y in ys {
+            for position in positions(y, num_hashes, num_bits) {
+                bits[(position / 8) as usize] |= 1 << (position % 8);
+            }
+        }
+
+        Self {
+            count: ys.len() as u64,
+            num_hashes,
+            filter: general_purpose::STANDARD.encode(bits),
+        }
+    }
+
+    /// Returns false if the proof with the Y value is not spent. True means it may be spent.
+    pub fn might_contain(&self, y: &PublicKey) -> Result<bool, MokshaCoreError> {
+        let bits = general_purpose::STANDARD.decode(&self.filter)?;
+        if bits.is_empty() {
+            return Ok(false);
+        }
+        Ok(positions(y, self.num_hashes, bits.len() as u64 * 8)
+            .all(|position| bits[(position / 8) as usize] & (1 << (position % 8)) != 0))
+    }
+}
+
+fn positions(y: &PublicKey, num_hashes: u32, num_bits: u64) -> impl Iterator<Item = u64> {
+    let hash = sha256::Hash::hash(&y.serialize()).to_byte_array();
+    let h1 = u64::from_be_bytes(hash[..8].try_into().expect("invalid slice"));
+    let h2 = u64::from_be_bytes(hash[8..16].try_into().expect("invalid slice"));
+    (0..num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dhke::Dhke;
+
+    use super::SpentDigest;
+
+    #[test]
+    fn test_spent_digest() -> anyhow::Result<()> {
+        let ys = (0..1_000)
+            .map(|i| Dhke::hash_to_curve(format!("spent{i}").as_bytes()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let digest = SpentDigest::new(&ys);
+        assert_eq!(1_000, digest.count);
+
+        for y in ys.iter() {
+            assert!(digest.might_contain(y)?);
+        }
+        let false_positives = (0..1_000)
+            .map(|i| Dhke::hash_to_curve(format!("unspent{i}").as_bytes()))
+            .collect::<Result<Vec<_>, _>>()?
+            .iter()
+            .filter(|y| digest.might_contain(y).unwrap_or(true))
+            .count();
+        assert!(false_positives < 50, "{false_positives} false positives");
+        Ok(())
+    }
+
+    #[test]
+    fn test_spent_digest_empty() -> anyhow::Result<()> {
+        let digest = SpentDigest::new(&[]);
+        assert!(!digest.might_contain(&Dhke::hash_to_curve(b"secret")?)?);
+        Ok(())
+    }
+}
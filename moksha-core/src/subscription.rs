@@ -0,0 +1,221 @@
+//! Messages of NUT-17 websocket subscriptions. They follow JSON-RPC 2.0, see
+//! <https://github.com/cashubtc/nuts/blob/main/17.md>
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/// the request has been accepted
+pub const STATUS_OK: &str = "OK";
+
+/// JSON-RPC error codes
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionKind {
+    Bolt11MintQuote,
+    Bolt11MeltQuote,
+    ProofState,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct WsRequest {
+    pub jsonrpc: String,
+    #[serde(flatten)]
+    pub method: WsRequestMethod,
+    pub id: u64,
+}
+
+impl WsRequest {
+    pub fn subscribe(id: u64, kind: SubscriptionKind, sub_id: &str, filters: Vec<String>) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_owned(),
+            method: WsRequestMethod::Subscribe(SubscribeParams {
+                kind,
+                sub_id: sub_id.to_owned(),
+                filters,
+            }),
+            id,
+        }
+    }
+
+    pub fn unsubscribe(id: u64, sub_id: &str) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_owned(),
+            method: WsRequestMethod::Unsubscribe(UnsubscribeParams {
+                sub_id: sub_id.to_owned(),
+            }),
+            id,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum WsRequestMethod {
+    Subscribe(SubscribeParams),
+    Unsubscribe(UnsubscribeParams),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SubscribeParams {
+    pub kind: SubscriptionKind,
+    #[serde(rename = "subId")]
+    pub sub_id: String,
+    /// ids of the objects to watch, e.g. the quote ids for [`SubscriptionKind::Bolt11MintQuote`]
+    pub filters: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct UnsubscribeParams {
+    #[serde(rename = "subId")]
+    pub sub_id: String,
+}
+
+/// Answer of the mint to a [`WsRequest`]. Contains either a result or an error.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct WsResponse {
+    pub jsonrpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<WsResult>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<WsError>,
+    /// id of the request. `None` if the request could not be parsed
+    pub id: Option<u64>,
+}
+
+impl WsResponse {
+    pub fn ok(id: u64, sub_id: &str) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_owned(),
+            result: Some(WsResult {
+                status: STATUS_OK.to_owned(),
+                sub_id: sub_id.to_owned(),
+            }),
+            error: None,
+            id: Some(id),
+        }
+    }
+
+    pub fn error(id: Option<u64>, code: i64, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_owned(),
+            result: None,
+            error: Some(WsError {
+                code,
+                message: message.into(),
+            }),
+            id,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct WsResult {
+    pub status: String,
+    #[serde(rename = "subId")]
+    pub sub_id: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct WsError {
+    pub code: i64,
+    pub message: String,
+}
+
+/// Update of a subscribed object that is pushed by the mint
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct WsNotification<T> {
+    pub jsonrpc: String,
+    /// always "subscribe"
+    pub method: String,
+    pub params: WsNotificationParams<T>,
+}
+
+impl<T> WsNotification<T> {
+    pub fn new(sub_id: &str, payload: T) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_owned(),
+            method: "subscribe".to_owned(),
+            params: WsNotificationParams {
+                sub_id: sub_id.to_owned(),
+                payload,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct WsNotificationParams<T> {
+    #[serde(rename = "subId")]
+    pub sub_id: String,
+    pub payload: T,
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::subscription::{SubscriptionKind, WsNotification, WsRequest, WsResponse};
+
+    #[test]
+    fn test_subscribe_request() -> anyhow::Result<()> {
+        let request = WsRequest::subscribe(
+            0,
+            SubscriptionKind::Bolt11MintQuote,
+            "sub",
+            vec!["quote".to_owned()],
+        );
+        let json = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "subscribe",
+            "params": {
+                "kind": "bolt11_mint_quote",
+                "subId": "sub",
+                "filters": ["quote"]
+            },
+            "id": 0
+        });
+        assert_eq!(json, serde_json::to_value(&request)?);
+        assert_eq!(request, serde_json::from_value(json)?);
+
+        let unsubscribe = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "unsubscribe",
+            "params": { "subId": "sub" },
+            "id": 1
+        });
+        assert_eq!(
+            WsRequest::unsubscribe(1, "sub"),
+            serde_json::from_value(unsubscribe)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_response_and_notification() -> anyhow::Result<()> {
+        assert_eq!(
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "result": { "status": "OK", "subId": "sub" },
+                "id": 0
+            }),
+            serde_json::to_value(WsResponse::ok(0, "sub"))?
+        );
+
+        let notification = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "subscribe",
+            "params": { "subId": "sub", "payload": { "paid": true } }
+        });
+        assert_eq!(
+            WsNotification::new("sub", serde_json::json!({ "paid": true })),
+            serde_json::from_value(notification)?
+        );
+        Ok(())
+    }
+}
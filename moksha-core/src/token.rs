@@ -12,6 +12,8 @@ use url::Url;
 use crate::{error::MokshaCoreError, primitives::CurrencyUnit, proof::Proofs};
 
 const TOKEN_PREFIX_V3: &str = "cashuA";
+/// max number of chars of a memo that get displayed
+pub const MAX_MEMO_LENGTH: usize = 256;
 
 #[skip_serializing_none]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -121,6 +123,20 @@ impl TokenV3 {
         Ok(serde_json::from_slice::<Self>(&json)?)
     }
 
+    /// Returns the memo with control characters removed and truncated to [`MAX_MEMO_LENGTH`]
+    /// chars, so it is safe to print to a terminal
+    pub fn display_memo(&self) -> Option<String> {
+        self.memo
+            .as_ref()
+            .map(|memo| {
+                memo.chars()
+                    .filter(|c| !c.is_control())
+                    .take(MAX_MEMO_LENGTH)
+                    .collect::<String>()
+            })
+            .filter(|memo| !memo.trim().is_empty())
+    }
+
     pub fn mint(&self) -> Option<Url> {
         self.tokens
             .first()
@@ -191,7 +207,7 @@ mod tests {
         fixture::read_fixture,
         primitives::CurrencyUnit,
         proof::Proof,
-        token::{Token, TokenV3},
+        token::{Token, TokenV3, MAX_MEMO_LENGTH},
     };
     use pretty_assertions::assert_eq;
 
@@ -281,6 +297,7 @@ mod tests {
                 ),
                 keyset_id: "someid".to_string(),
                 script: None,
+                witness: None,
             }
             .into(),
         };
@@ -343,4 +360,20 @@ mod tests {
         assert!(tokens.memo.is_none());
         Ok(())
     }
+
+    #[test]
+    fn test_display_memo() -> anyhow::Result<()> {
+        let mut token = TokenV3::empty();
+        assert_eq!(None, token.display_memo());
+
+        token.memo = Some("Thank\u{1b}[31m you.\n".to_string());
+        assert_eq!(Some("Thank[31m you.".to_string()), token.display_memo());
+
+        token.memo = Some("\u{7}\r\n".to_string());
+        assert_eq!(None, token.display_memo());
+
+        token.memo = Some("a".repeat(MAX_MEMO_LENGTH + 10));
+        assert_eq!(Some(MAX_MEMO_LENGTH), token.display_memo().map(|m| m.len()));
+        Ok(())
+    }
 }
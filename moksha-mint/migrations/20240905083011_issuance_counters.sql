@@ -0,0 +1,8 @@
+-- total amounts of ecash issued and redeemed by the mint. Counters start at zero for existing mints.
+CREATE TABLE issuance_counters (
+    id INT PRIMARY KEY CHECK (id = 1),
+    issued BIGINT NOT NULL,
+    redeemed BIGINT NOT NULL
+);
+
+INSERT INTO issuance_counters (id, issued, redeemed) VALUES (1, 0, 0);
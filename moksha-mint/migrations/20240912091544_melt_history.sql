@@ -0,0 +1,8 @@
+-- melted amounts with timestamps for enforcing melt velocity limits
+CREATE TABLE melt_history (
+    id BIGSERIAL PRIMARY KEY,
+    amount BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX melt_history_created_at_idx ON melt_history (created_at);
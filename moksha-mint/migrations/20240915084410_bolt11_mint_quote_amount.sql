@@ -0,0 +1,3 @@
+-- existing quotes have no known amount and are reported as 0
+ALTER TABLE bolt11_mint_quotes ADD COLUMN amount BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE bolt11_mint_quotes ALTER COLUMN amount DROP DEFAULT;
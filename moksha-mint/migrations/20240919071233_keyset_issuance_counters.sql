@@ -0,0 +1,6 @@
+-- amounts of ecash issued and redeemed per keyset. Counters start at zero for existing mints.
+CREATE TABLE keyset_issuance_counters (
+    keyset_id TEXT PRIMARY KEY,
+    issued BIGINT NOT NULL DEFAULT 0,
+    redeemed BIGINT NOT NULL DEFAULT 0
+);
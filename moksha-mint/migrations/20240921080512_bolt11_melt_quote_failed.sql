@@ -0,0 +1,2 @@
+-- melt quotes whose payment failed and whose inputs were refunded by the operator
+ALTER TABLE bolt11_melt_quotes ADD COLUMN failed BOOLEAN NOT NULL DEFAULT FALSE;
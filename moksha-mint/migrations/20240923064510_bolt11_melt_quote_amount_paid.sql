@@ -0,0 +1,3 @@
+-- actual amount and lightning fee of a settled melt. Both are null until the invoice has been paid
+ALTER TABLE bolt11_melt_quotes ADD COLUMN amount_paid BIGINT;
+ALTER TABLE bolt11_melt_quotes ADD COLUMN fee_paid BIGINT;
@@ -0,0 +1,13 @@
+-- outputs of a (batched) onchain melt transaction
+CREATE TABLE IF NOT EXISTS onchain_melt_quote_outputs
+(
+    quote_id uuid NOT NULL REFERENCES onchain_melt_quotes (id) ON DELETE CASCADE,
+    position integer NOT NULL,
+    address text NOT NULL,
+    amount bigint NOT NULL,
+    CONSTRAINT onchain_melt_quote_outputs_pkey PRIMARY KEY (quote_id, position)
+);
+
+-- existing quotes have a single output
+INSERT INTO onchain_melt_quote_outputs (quote_id, position, address, amount)
+SELECT id, 0, address, amount FROM onchain_melt_quotes;
@@ -0,0 +1,7 @@
+-- unix timestamps of when a keyset became active and when it was replaced. valid_to is NULL while the keyset is active
+CREATE TABLE keysets (
+    keyset_id TEXT PRIMARY KEY,
+    unit TEXT NOT NULL,
+    valid_from BIGINT NOT NULL,
+    valid_to BIGINT
+);
@@ -0,0 +1,8 @@
+-- every address the mint handed out for an onchain mint quote
+CREATE TABLE onchain_addresses (
+    address TEXT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+INSERT INTO onchain_addresses (address)
+SELECT DISTINCT address FROM onchain_mint_quotes;
@@ -0,0 +1,5 @@
+-- nonces and idempotency keys that were evicted from the in-memory store before they expired
+CREATE TABLE nonces (
+    key TEXT PRIMARY KEY,
+    expires_at BIGINT NOT NULL
+);
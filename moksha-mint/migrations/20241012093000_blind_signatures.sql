@@ -0,0 +1,7 @@
+-- signatures of all issued outputs, so wallets can restore their proofs from a seed (nut09)
+CREATE TABLE blind_signatures (
+    b_ TEXT PRIMARY KEY,
+    amount BIGINT NOT NULL,
+    keyset_id TEXT NOT NULL,
+    c_ TEXT NOT NULL
+);
@@ -0,0 +1,7 @@
+-- single row that serializes the melt velocity checks. A melt locks it only while it reserves its
+-- amount in the melt history, so no lock is held while the payment is in flight
+CREATE TABLE melt_limit_lock (
+    id INT PRIMARY KEY CHECK (id = 1)
+);
+
+INSERT INTO melt_limit_lock (id) VALUES (1);
@@ -0,0 +1,5 @@
+-- inputs of a melt reference its quote while the payment is in flight. They are released if the
+-- payment fails and kept as used proofs once it settles or the quote is refunded
+ALTER TABLE used_proofs ADD COLUMN melt_quote_id UUID;
+
+CREATE INDEX used_proofs_melt_quote_id ON used_proofs (melt_quote_id) WHERE melt_quote_id IS NOT NULL;
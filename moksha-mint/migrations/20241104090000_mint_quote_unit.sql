@@ -0,0 +1,2 @@
+-- unit of the ecash a bolt11 mint quote issues. Existing quotes were created for sat
+ALTER TABLE bolt11_mint_quotes ADD COLUMN unit TEXT NOT NULL DEFAULT 'sat';
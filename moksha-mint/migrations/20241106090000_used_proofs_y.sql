@@ -0,0 +1,5 @@
+-- Y of each used proof, set on insert. Rows from before are filled in by the mint on startup
+ALTER TABLE used_proofs ADD COLUMN y TEXT;
+CREATE UNIQUE INDEX used_proofs_y ON used_proofs (y);
+-- increases with every insert, so a cached spent digest can tell if proofs were added
+ALTER TABLE used_proofs ADD COLUMN id BIGSERIAL;
@@ -0,0 +1,14 @@
+-- the issuance counters of mints that existed before the counters start at zero. The ecash they
+-- issued before can't be reconstructed, because blind signatures weren't stored then. Such mints
+-- are flagged and skip the max-outstanding cap until the operator configures the amount that was
+-- in circulation (`--initial-outstanding`).
+ALTER TABLE issuance_counters ADD COLUMN issued_before_counters BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE issuance_counters SET issued_before_counters =
+    EXISTS (SELECT 1 FROM used_proofs)
+    OR EXISTS (SELECT 1 FROM pending_invoices)
+    OR EXISTS (SELECT 1 FROM bolt11_mint_quotes)
+    OR EXISTS (SELECT 1 FROM bolt11_melt_quotes)
+    OR EXISTS (SELECT 1 FROM onchain_mint_quotes)
+    OR EXISTS (SELECT 1 FROM onchain_melt_quotes)
+WHERE id = 1;
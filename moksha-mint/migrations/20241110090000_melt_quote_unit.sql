@@ -0,0 +1,2 @@
+-- unit of the inputs and the change of a bolt11 melt quote. Existing quotes were created for sat
+ALTER TABLE bolt11_melt_quotes ADD COLUMN unit TEXT NOT NULL DEFAULT 'sat';
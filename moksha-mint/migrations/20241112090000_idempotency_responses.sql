@@ -0,0 +1,8 @@
+-- responses of requests with an idempotency key, returned again for a retry with the same key
+CREATE TABLE idempotency_responses (
+    key TEXT PRIMARY KEY,
+    request_hash TEXT NOT NULL,
+    status INT NOT NULL,
+    body BYTEA NOT NULL,
+    expires_at BIGINT NOT NULL
+);
@@ -0,0 +1,7 @@
+-- swaps and melts only update the keyset counters, so they don't contend for the single row of
+-- issuance_counters. The row keeps the amounts that were counted before the keyset counters
+-- existed, and the totals are that row plus the sums of the keyset counters.
+UPDATE issuance_counters SET
+    issued = issued - (SELECT COALESCE(SUM(issued), 0) FROM keyset_issuance_counters),
+    redeemed = redeemed - (SELECT COALESCE(SUM(redeemed), 0) FROM keyset_issuance_counters)
+WHERE id = 1;
@@ -28,6 +28,10 @@ pub async fn main() -> anyhow::Result<()> {
     let MintConfig {
         privatekey,
         derivation_path,
+        legacy_derivation_path,
+        units,
+        denomination_limits,
+        network,
         info,
         lightning_fee,
         server,
@@ -35,6 +39,14 @@ pub async fn main() -> anyhow::Result<()> {
         lightning_backend,
         tracing,
         database,
+        max_outstanding,
+        initial_outstanding,
+        min_mint_amount,
+        melt_limits,
+        bolt11_limits,
+        quote,
+        nonce,
+        self_test,
     } = MintConfig::read_config_with_defaults();
 
     init_tracing(tracing.clone())?;
@@ -44,11 +56,23 @@ pub async fn main() -> anyhow::Result<()> {
         .with_server(Some(server))
         .with_private_key(privatekey)
         .with_derivation_path(derivation_path)
+        .with_legacy_derivation_path(legacy_derivation_path)
         .with_db(Some(database))
         .with_lightning(lightning_backend.expect("lightning not set"))
         .with_btc_onchain(btconchain_backend)
         .with_fee(Some(lightning_fee))
         .with_tracing(tracing)
+        .with_max_outstanding(max_outstanding)
+        .with_initial_outstanding(initial_outstanding)
+        .with_min_mint_amount(min_mint_amount)
+        .with_melt_limits(Some(melt_limits))
+        .with_bolt11_limits(Some(bolt11_limits))
+        .with_quote(Some(quote))
+        .with_nonce(Some(nonce))
+        .with_self_test(Some(self_test))
+        .with_units(units)
+        .with_denomination_limits(denomination_limits)
+        .with_network(network)
         .build()
         .await;
 
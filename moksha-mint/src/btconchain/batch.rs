@@ -0,0 +1,208 @@
+//! Coalesces the onchain payments of melt quotes into a single transaction.
+//!
+//! The first payment that arrives opens a batch for its fee rate and waits for the configured
+//! window. All payments with the same fee rate that arrive within the window are sent together, so
+//! every transaction pays exactly the fee rate that was quoted. Every payment of the batch gets the
+//! txid of the shared transaction. A window of zero sends every payment on its own.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use moksha_core::primitives::OnchainOutput;
+use tokio::sync::oneshot;
+use tracing::debug;
+
+use super::{BtcOnchain, SendCoinsResult};
+use crate::error::MokshaMintError;
+
+#[derive(Debug, Default)]
+pub struct SendBatch {
+    window: Duration,
+    /// open batches by fee rate
+    pending: Mutex<HashMap<u32, Vec<PendingSend>>>,
+}
+
+#[derive(Debug)]
+struct PendingSend {
+    outputs: Vec<OnchainOutput>,
+    result: oneshot::Sender<Result<SendCoinsResult, String>>,
+}
+
+impl SendBatch {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Pays the outputs, together with all other payments of the same fee rate that arrive within
+    /// the window
+    pub async fn send_coins(
+        self: &Arc<Self>,
+        onchain: &Arc<dyn BtcOnchain + Send + Sync>,
+        outputs: &[OnchainOutput],
+        sat_per_vbyte: u32,
+    ) -> Result<SendCoinsResult, MokshaMintError> {
+        if self.window.is_zero() {
+            return onchain.send_coins(outputs, sat_per_vbyte).await;
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        let opens_batch = {
+            let mut pending = self.pending.lock().expect("send batch lock poisoned");
+            let batch = pending.entry(sat_per_vbyte).or_default();
+            batch.push(PendingSend {
+                outputs: outputs.to_vec(),
+                result: sender,
+            });
+            batch.len() == 1
+        };
+
+        // the batch is sent by its own task, so a cancelled request doesn't leave the others waiting
+        if opens_batch {
+            let batch = Arc::clone(self);
+            let onchain = Arc::clone(onchain);
+            tokio::spawn(async move {
+                tokio::time::sleep(batch.window).await;
+                batch.flush(onchain.as_ref(), sat_per_vbyte).await;
+            });
+        }
+
+        receiver
+            .await
+            .map_err(|_| MokshaMintError::OnchainBatchFailed("batch was dropped".to_owned()))?
+            .map_err(MokshaMintError::OnchainBatchFailed)
+    }
+
+    async fn flush(&self, onchain: &(dyn BtcOnchain + Send + Sync), sat_per_vbyte: u32) {
+        let batch = self
+            .pending
+            .lock()
+            .expect("send batch lock poisoned")
+            .remove(&sat_per_vbyte)
+            .unwrap_or_default();
+        let outputs = batch
+            .iter()
+            .flat_map(|send| send.outputs.clone())
+            .collect::<Vec<_>>();
+        debug!(
+            "sending {} onchain payments with {} outputs in one transaction",
+            batch.len(),
+            outputs.len()
+        );
+
+        let result = onchain
+            .send_coins(&outputs, sat_per_vbyte)
+            .await
+            .map_err(|err| err.to_string());
+        for send in batch {
+            // fails only if the request was cancelled in the meantime
+            let _ = send.result.send(result.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use moksha_core::primitives::OnchainOutput;
+
+    use super::SendBatch;
+    use crate::btconchain::{BtcOnchain, MockBtcOnchain, SendCoinsResult};
+
+    fn output(address: &str, amount: u64) -> Vec<OnchainOutput> {
+        vec![OnchainOutput {
+            address: address.to_owned(),
+            amount,
+        }]
+    }
+
+    #[tokio::test]
+    async fn test_send_coins_batches_payments() -> anyhow::Result<()> {
+        let mut onchain = MockBtcOnchain::new();
+        onchain
+            .expect_send_coins()
+            .times(1)
+            .withf(|outputs, sat_per_vbyte| outputs.len() == 2 && *sat_per_vbyte == 5)
+            .returning(|_, _| {
+                Ok(SendCoinsResult {
+                    txid: "txid".to_owned(),
+                })
+            });
+        let onchain: Arc<dyn BtcOnchain + Send + Sync> = Arc::new(onchain);
+        let batch = Arc::new(SendBatch::new(Duration::from_millis(50)));
+
+        let (first_outputs, second_outputs) =
+            (output("address1", 1_000), output("address2", 2_000));
+        let (first, second) = tokio::join!(
+            batch.send_coins(&onchain, &first_outputs, 5),
+            batch.send_coins(&onchain, &second_outputs, 5),
+        );
+        assert_eq!("txid", first?.txid);
+        assert_eq!("txid", second?.txid);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_coins_keeps_quoted_fee_rate() -> anyhow::Result<()> {
+        let mut onchain = MockBtcOnchain::new();
+        onchain
+            .expect_send_coins()
+            .times(2)
+            .withf(|outputs, sat_per_vbyte| {
+                outputs.len() == 1
+                    && match outputs[0].address.as_str() {
+                        "address1" => *sat_per_vbyte == 2,
+                        _ => *sat_per_vbyte == 5,
+                    }
+            })
+            .returning(|outputs, _| {
+                Ok(SendCoinsResult {
+                    txid: format!("txid-{}", outputs[0].address),
+                })
+            });
+        let onchain: Arc<dyn BtcOnchain + Send + Sync> = Arc::new(onchain);
+        let batch = Arc::new(SendBatch::new(Duration::from_millis(50)));
+
+        let (first_outputs, second_outputs) =
+            (output("address1", 1_000), output("address2", 2_000));
+        let (first, second) = tokio::join!(
+            batch.send_coins(&onchain, &first_outputs, 2),
+            batch.send_coins(&onchain, &second_outputs, 5),
+        );
+        assert_eq!("txid-address1", first?.txid);
+        assert_eq!("txid-address2", second?.txid);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_coins_without_window() -> anyhow::Result<()> {
+        let mut onchain = MockBtcOnchain::new();
+        onchain
+            .expect_send_coins()
+            .times(2)
+            .withf(|outputs, _| outputs.len() == 1)
+            .returning(|_, _| {
+                Ok(SendCoinsResult {
+                    txid: "txid".to_owned(),
+                })
+            });
+        let onchain: Arc<dyn BtcOnchain + Send + Sync> = Arc::new(onchain);
+        let batch = Arc::new(SendBatch::default());
+
+        let (first_outputs, second_outputs) =
+            (output("address1", 1_000), output("address2", 2_000));
+        let (first, second) = tokio::join!(
+            batch.send_coins(&onchain, &first_outputs, 2),
+            batch.send_coins(&onchain, &second_outputs, 5),
+        );
+        assert!(first.is_ok());
+        assert!(second.is_ok());
+        Ok(())
+    }
+}
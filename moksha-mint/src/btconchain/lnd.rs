@@ -3,10 +3,14 @@ use super::{BtcOnchain, EstimateFeeResult, SendCoinsResult};
 use crate::error::MokshaMintError;
 use async_trait::async_trait;
 use fedimint_tonic_lnd::{
-    lnrpc::{AddressType, EstimateFeeRequest, NewAddressRequest, SendCoinsRequest},
+    lnrpc::{
+        AddressType, EstimateFeeRequest, GetInfoRequest, NewAddressRequest, SendCoinsRequest,
+        SendManyRequest,
+    },
     walletrpc::ListUnspentRequest,
     Client,
 };
+use moksha_core::primitives::OnchainOutput;
 use std::{collections::HashMap, path::PathBuf, sync::Arc};
 use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
 use tracing::instrument;
@@ -106,41 +110,62 @@ impl BtcOnchain for LndBtcOnchain {
     #[instrument(level = "debug", skip(self), err)]
     async fn send_coins(
         &self,
-        address: &str,
-        amount: u64,
+        outputs: &[OnchainOutput],
         sat_per_vbyte: u32,
     ) -> Result<SendCoinsResult, MokshaMintError> {
+        let mut client = self.client_lock().await?;
+        let txid = match outputs {
+            [output] => {
+                client
+                    .send_coins(SendCoinsRequest {
+                        addr: output.address.clone(),
+                        amount: output.amount as i64,
+                        sat_per_vbyte: sat_per_vbyte as u64,
+                        ..Default::default()
+                    })
+                    .await?
+                    .into_inner()
+                    .txid
+            }
+            _ => {
+                client
+                    .send_many(SendManyRequest {
+                        addr_to_amount: addr_to_amount(outputs),
+                        sat_per_vbyte: sat_per_vbyte as u64,
+                        ..Default::default()
+                    })
+                    .await?
+                    .into_inner()
+                    .txid
+            }
+        };
+
+        Ok(SendCoinsResult { txid })
+    }
+
+    #[instrument(level = "debug", skip(self), err)]
+    async fn is_synced(&self) -> Result<bool, MokshaMintError> {
         let response = self
             .client_lock()
             .await?
-            .send_coins(SendCoinsRequest {
-                addr: address.to_owned(),
-                amount: amount as i64,
-                sat_per_vbyte: sat_per_vbyte as u64,
-                ..Default::default()
-            })
+            .get_info(GetInfoRequest {})
             .await?
             .into_inner();
-
-        Ok(SendCoinsResult {
-            txid: response.txid,
-        })
+        Ok(response.synced_to_chain)
     }
 
     #[instrument(level = "debug", skip(self), err)]
     async fn estimate_fee(
         &self,
-        address: &str,
-        amount: u64,
+        outputs: &[OnchainOutput],
+        target_conf: u32,
     ) -> Result<EstimateFeeResult, MokshaMintError> {
         let response = self
             .client_lock()
             .await?
             .estimate_fee(EstimateFeeRequest {
-                addr_to_amount: std::iter::once(&(address.to_owned(), amount as i64))
-                    .cloned()
-                    .collect::<HashMap<_, _>>(),
-                target_conf: 1,
+                addr_to_amount: addr_to_amount(outputs),
+                target_conf: target_conf as i32,
                 ..Default::default()
             })
             .await?
@@ -152,3 +177,13 @@ impl BtcOnchain for LndBtcOnchain {
         })
     }
 }
+
+/// Sums up the amounts per address, batched melts can pay the same address more than once
+fn addr_to_amount(outputs: &[OnchainOutput]) -> HashMap<String, i64> {
+    outputs
+        .iter()
+        .fold(HashMap::new(), |mut addr_to_amount, output| {
+            *addr_to_amount.entry(output.address.clone()).or_default() += output.amount as i64;
+            addr_to_amount
+        })
+}
@@ -1,7 +1,9 @@
 use async_trait::async_trait;
+use moksha_core::primitives::OnchainOutput;
 
 use crate::error::MokshaMintError;
 
+pub mod batch;
 pub mod lnd;
 
 #[cfg(test)]
@@ -11,17 +13,19 @@ use mockall::automock;
 #[async_trait]
 pub trait BtcOnchain: Send + Sync {
     async fn new_address(&self) -> Result<String, MokshaMintError>;
+
+    /// Pays all outputs in a single transaction
     async fn send_coins(
         &self,
-        address: &str,
-        amount: u64,
+        outputs: &[OnchainOutput],
         sat_per_vbyte: u32,
     ) -> Result<SendCoinsResult, MokshaMintError>;
 
+    /// Estimates the fee to pay all outputs with a confirmation within `target_conf` blocks
     async fn estimate_fee(
         &self,
-        address: &str,
-        amount: u64,
+        outputs: &[OnchainOutput],
+        target_conf: u32,
     ) -> Result<EstimateFeeResult, MokshaMintError>;
 
     async fn is_paid(
@@ -32,6 +36,9 @@ pub trait BtcOnchain: Send + Sync {
     ) -> Result<bool, MokshaMintError>;
 
     async fn is_transaction_paid(&self, txid: &str) -> Result<bool, MokshaMintError>;
+
+    /// Returns false while the backend is still syncing to the chain tip
+    async fn is_synced(&self) -> Result<bool, MokshaMintError>;
 }
 
 #[derive(Debug, Clone)]
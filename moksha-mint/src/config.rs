@@ -1,15 +1,21 @@
 use std::{env, net::SocketAddr, path::PathBuf, str::FromStr};
 
 use clap::Parser;
+use lightning_invoice::Currency;
+use moksha_core::keyset::DenominationLimit;
 use moksha_core::primitives::{
-    ContactInfoResponse, CurrencyUnit, Nut18, Nut19, PaymentMethod,
+    ConfirmationTier, ContactInfoResponse, CurrencyUnit, Nut18, Nut19, PaymentMethod,
     PaymentMethodConfigBtcOnchainMelt, PaymentMethodConfigBtcOnchainMint,
+    DEFAULT_BOLT11_MAX_AMOUNT,
 };
 use serde::{Deserialize, Serialize};
+use tracing::log::LevelFilter;
+use url::Url;
 
 use crate::lightning::{
-    alby::AlbyLightningSettings, cln::ClnLightningSettings, lnbits::LnbitsLightningSettings,
-    lnd::LndLightningSettings, strike::StrikeLightningSettings, LightningType,
+    alby::AlbyLightningSettings, btcpay::BTCPaySettings, cln::ClnLightningSettings,
+    lnbits::LnbitsLightningSettings, lnd::LndLightningSettings, lndrest::LndRestSettings,
+    phoenixd::PhoenixdLightningSettings, strike::StrikeLightningSettings, LightningType,
 };
 
 #[derive(Parser, Debug)]
@@ -19,6 +25,15 @@ pub struct Opts {
     pub privatekey: String,
     #[clap(long, env = "MINT_DERIVATION_PATH")]
     pub derivation_path: Option<String>,
+    /// derivation path of the legacy keyset. Defaults to the derivation path of the v1 keysets
+    #[clap(long, env = "MINT_LEGACY_DERIVATION_PATH")]
+    pub legacy_derivation_path: Option<String>,
+    /// currency units the mint issues ecash for. Each unit has its own keyset
+    #[clap(long, value_delimiter = ',', default_value = "sat", env = "MINT_UNITS")]
+    pub units: Vec<CurrencyUnit>,
+    /// denominations a keyset issues as `<unit>:<min_amount>:<max_amount>`, e.g. `usd:1:1048576`
+    #[clap(long, value_delimiter = ',', env = "MINT_DENOMINATION_LIMITS")]
+    pub denomination_limits: Vec<DenominationLimit>,
     #[clap(flatten)]
     pub info: MintInfoConfig,
     #[clap(flatten)]
@@ -31,9 +46,41 @@ pub struct Opts {
     #[clap(long, env = "MINT_LIGHTNING_BACKEND")]
     pub lightning_backend: LightningTypeVariant,
 
+    /// bitcoin network of the lightning backend. Invoices for other networks are rejected
+    #[clap(long, default_value = "bitcoin", env = "MINT_NETWORK")]
+    pub network: Network,
+
     #[clap(long, env = "MINT_BTC_ONCHAIN_BACKEND")]
     pub btconchain_backend: Option<BtcOnchainTypeVariant>,
 
+    /// maximum amount of ecash in circulation in sats
+    #[clap(long, env = "MINT_MAX_OUTSTANDING")]
+    pub max_outstanding: Option<u64>,
+
+    /// ecash in sats that was in circulation before the mint counted issued and redeemed ecash.
+    /// Mints that issued ecash before skip the max-outstanding cap until this is set
+    #[clap(long, env = "MINT_INITIAL_OUTSTANDING")]
+    pub initial_outstanding: Option<u64>,
+
+    /// minimum amount in sats for mint quotes. Smaller quote requests are rejected before an invoice is created
+    #[clap(long, env = "MINT_MIN_MINT_AMOUNT")]
+    pub min_mint_amount: Option<u64>,
+
+    #[clap(flatten)]
+    pub melt_limits: MeltLimitConfig,
+
+    #[clap(flatten)]
+    pub bolt11_limits: Bolt11LimitConfig,
+
+    #[clap(flatten)]
+    pub quote: QuoteConfig,
+
+    #[clap(flatten)]
+    pub nonce: NonceConfig,
+
+    #[clap(flatten)]
+    pub self_test: SelfTestConfig,
+
     #[clap(flatten)]
     pub tracing: Option<TracingConfig>,
 }
@@ -44,7 +91,10 @@ pub enum LightningTypeVariant {
     Alby,
     Strike,
     Lnd,
+    LndRest,
     Cln,
+    Phoenixd,
+    BTCPay,
 }
 
 impl FromStr for LightningTypeVariant {
@@ -56,12 +106,49 @@ impl FromStr for LightningTypeVariant {
             "Alby" => Ok(Self::Alby),
             "Strike" => Ok(Self::Strike),
             "Lnd" => Ok(Self::Lnd),
+            "LndRest" => Ok(Self::LndRest),
             "Cln" => Ok(Self::Cln),
+            "Phoenixd" => Ok(Self::Phoenixd),
+            "BTCPay" => Ok(Self::BTCPay),
             _ => Err("no match"),
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Network {
+    #[default]
+    Bitcoin,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+impl FromStr for Network {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bitcoin" => Ok(Self::Bitcoin),
+            "testnet" => Ok(Self::Testnet),
+            "signet" => Ok(Self::Signet),
+            "regtest" => Ok(Self::Regtest),
+            _ => Err("no match"),
+        }
+    }
+}
+
+impl From<Network> for Currency {
+    fn from(network: Network) -> Self {
+        match network {
+            Network::Bitcoin => Self::Bitcoin,
+            Network::Testnet => Self::BitcoinTestnet,
+            Network::Signet => Self::Signet,
+            Network::Regtest => Self::Regtest,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default, Parser)]
 pub struct TracingConfig {
     #[clap(long, env = "MINT_TRACING_ENDPOINT")]
@@ -72,6 +159,10 @@ pub struct TracingConfig {
 pub struct MintConfig {
     pub privatekey: String,
     pub derivation_path: Option<String>,
+    pub legacy_derivation_path: Option<String>,
+    pub units: Vec<CurrencyUnit>,
+    pub denomination_limits: Vec<DenominationLimit>,
+    pub network: Network,
     pub info: MintInfoConfig,
     pub lightning_fee: LightningFeeConfig,
     pub server: ServerConfig,
@@ -79,6 +170,14 @@ pub struct MintConfig {
     pub lightning_backend: Option<LightningType>,
     pub tracing: Option<TracingConfig>,
     pub database: DatabaseConfig,
+    pub max_outstanding: Option<u64>,
+    pub initial_outstanding: Option<u64>,
+    pub min_mint_amount: Option<u64>,
+    pub melt_limits: MeltLimitConfig,
+    pub bolt11_limits: Bolt11LimitConfig,
+    pub quote: QuoteConfig,
+    pub nonce: NonceConfig,
+    pub self_test: SelfTestConfig,
 }
 
 impl From<(Opts, LightningType, Option<BtcOnchainConfig>)> for MintConfig {
@@ -86,6 +185,10 @@ impl From<(Opts, LightningType, Option<BtcOnchainConfig>)> for MintConfig {
         Self {
             privatekey: opts.privatekey,
             derivation_path: opts.derivation_path,
+            legacy_derivation_path: opts.legacy_derivation_path,
+            units: opts.units,
+            denomination_limits: opts.denomination_limits,
+            network: opts.network,
             info: opts.info,
             lightning_fee: opts.lightning_fee,
             server: opts.server,
@@ -93,6 +196,14 @@ impl From<(Opts, LightningType, Option<BtcOnchainConfig>)> for MintConfig {
             lightning_backend: Some(ln),
             tracing: opts.tracing,
             database: opts.database,
+            max_outstanding: opts.max_outstanding,
+            initial_outstanding: opts.initial_outstanding,
+            min_mint_amount: opts.min_mint_amount,
+            melt_limits: opts.melt_limits,
+            bolt11_limits: opts.bolt11_limits,
+            quote: opts.quote,
+            nonce: opts.nonce,
+            self_test: opts.self_test,
         }
     }
 }
@@ -103,10 +214,15 @@ impl MintConfig {
 
         let lightning = match opts.lightning_backend {
             LightningTypeVariant::Lnd => LightningType::Lnd(LndLightningSettings::parse()),
+            LightningTypeVariant::LndRest => LightningType::LndRest(LndRestSettings::parse()),
             LightningTypeVariant::Lnbits => LightningType::Lnbits(LnbitsLightningSettings::parse()),
             LightningTypeVariant::Strike => LightningType::Strike(StrikeLightningSettings::parse()),
             LightningTypeVariant::Alby => LightningType::Alby(AlbyLightningSettings::parse()),
             LightningTypeVariant::Cln => LightningType::Cln(ClnLightningSettings::parse()),
+            LightningTypeVariant::Phoenixd => {
+                LightningType::Phoenixd(PhoenixdLightningSettings::parse())
+            }
+            LightningTypeVariant::BTCPay => LightningType::BTCPay(BTCPaySettings::parse()),
         };
 
         let btc_onchain: Option<BtcOnchainConfig> = match opts.btconchain_backend {
@@ -129,6 +245,7 @@ impl MintConfig {
     pub const fn new(
         private_key: String,
         derivation_path: Option<String>,
+        legacy_derivation_path: Option<String>,
         info: MintInfoConfig,
         lightning_fee: LightningFeeConfig,
         server: ServerConfig,
@@ -136,10 +253,25 @@ impl MintConfig {
         btconchain_backend: Option<BtcOnchainConfig>,
         lightning_backend: Option<LightningType>,
         tracing: Option<TracingConfig>,
+        max_outstanding: Option<u64>,
+        initial_outstanding: Option<u64>,
+        min_mint_amount: Option<u64>,
+        melt_limits: MeltLimitConfig,
+        bolt11_limits: Bolt11LimitConfig,
+        quote: QuoteConfig,
+        nonce: NonceConfig,
+        self_test: SelfTestConfig,
+        units: Vec<CurrencyUnit>,
+        denomination_limits: Vec<DenominationLimit>,
+        network: Network,
     ) -> Self {
         Self {
             privatekey: private_key,
             derivation_path,
+            legacy_derivation_path,
+            units,
+            denomination_limits,
+            network,
             info,
             lightning_fee,
             server,
@@ -147,6 +279,14 @@ impl MintConfig {
             lightning_backend,
             tracing,
             database,
+            max_outstanding,
+            initial_outstanding,
+            min_mint_amount,
+            melt_limits,
+            bolt11_limits,
+            quote,
+            nonce,
+            self_test,
         }
     }
 }
@@ -176,6 +316,43 @@ pub struct BtcOnchainConfig {
         env = "MINT_BTC_ONCHAIN_BACKEND_MAX_AMOUNT"
     )]
     pub max_amount: u64,
+
+    /// deposits of at least the amount in sats need more confirmations, e.g. `10000000:6,1000000:3`
+    #[clap(
+        long,
+        value_delimiter = ',',
+        env = "MINT_BTC_ONCHAIN_BACKEND_CONFIRMATION_TIERS"
+    )]
+    pub confirmation_tiers: Vec<ConfirmationTier>,
+
+    /// `fresh` derives a new address for every mint quote. `gap-limited:<n>` hands out addresses
+    /// of expired quotes that never received funds again once n addresses are waiting for a deposit
+    #[clap(
+        long,
+        default_value = "fresh",
+        env = "MINT_BTC_ONCHAIN_BACKEND_ADDRESS_POLICY"
+    )]
+    pub address_policy: AddressPolicy,
+
+    /// pays onchain melts with the same fee rate that arrive within the window in milliseconds in
+    /// one transaction. 0 sends every melt on its own
+    #[clap(
+        long,
+        default_value_t = 0,
+        env = "MINT_BTC_ONCHAIN_BACKEND_BATCH_WINDOW_MS"
+    )]
+    pub batch_window_ms: u64,
+}
+
+impl BtcOnchainConfig {
+    /// Returns the confirmations a deposit of the given amount in sats needs before it can be minted
+    pub fn required_confirmations(&self, amount: u64) -> u8 {
+        ConfirmationTier::required_confirmations(
+            &self.confirmation_tiers,
+            self.min_confirmations,
+            amount,
+        )
+    }
 }
 
 impl Default for BtcOnchainConfig {
@@ -185,6 +362,37 @@ impl Default for BtcOnchainConfig {
             min_confirmations: 1,
             min_amount: 10_000,
             max_amount: 1_000_000,
+            confirmation_tiers: vec![],
+            address_policy: AddressPolicy::default(),
+            batch_window_ms: 0,
+        }
+    }
+}
+
+/// How the mint hands out addresses for onchain mint quotes. An address that received funds is
+/// never handed out again.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AddressPolicy {
+    /// derive a new address for every quote
+    #[default]
+    Fresh,
+    /// derive new addresses until the given number of addresses is waiting for a deposit. Then
+    /// addresses of expired quotes that never received funds are handed out again, so a wallet
+    /// restoring the onchain funds with this gap limit finds all deposits
+    GapLimited(u32),
+}
+
+impl FromStr for AddressPolicy {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "fresh" => Ok(Self::Fresh),
+            Some(("gap-limited", gap_limit)) => match gap_limit.parse() {
+                Ok(gap_limit) if gap_limit > 0 => Ok(Self::GapLimited(gap_limit)),
+                _ => Err("gap limit must be a positive number"),
+            },
+            _ => Err("expected fresh or gap-limited:<n>"),
         }
     }
 }
@@ -220,6 +428,7 @@ impl From<BtcOnchainConfig> for Nut18 {
                 min_amount: settings.min_amount,
                 max_amount: settings.max_amount,
                 min_confirmations: settings.min_confirmations,
+                confirmation_tiers: settings.confirmation_tiers,
             }],
         }
     }
@@ -246,6 +455,22 @@ pub struct DatabaseConfig {
 
     #[clap(long, default_value_t = 5, env = "MINT_DB_MAX_CONNECTIONS")]
     pub max_connections: u32,
+
+    /// connection string for a read replica. Read-only quote lookups are served from the replica,
+    /// everything else including the double spend checks uses the primary database.
+    #[clap(long, env = "MINT_DB_READ_REPLICA_URL")]
+    pub read_replica_url: Option<String>,
+
+    /// log every executed sql statement. Only the statement is logged, bound values are omitted.
+    #[clap(long, default_value_t = false, env = "MINT_DB_LOG_STATEMENTS")]
+    pub log_statements: bool,
+
+    #[clap(long, default_value = "debug", env = "MINT_DB_LOG_STATEMENTS_LEVEL")]
+    pub log_statements_level: LevelFilter,
+
+    /// log statements that take longer than this threshold in milliseconds with their duration
+    #[clap(long, env = "MINT_DB_SLOW_STATEMENT_THRESHOLD_MS")]
+    pub slow_statement_threshold_ms: Option<u64>,
 }
 
 impl Default for DatabaseConfig {
@@ -253,10 +478,128 @@ impl Default for DatabaseConfig {
         Self {
             db_url: "".to_owned(),
             max_connections: 5,
+            read_replica_url: None,
+            log_statements: false,
+            log_statements_level: LevelFilter::Debug,
+            slow_statement_threshold_ms: None,
+        }
+    }
+}
+
+/// Limits the amount of ecash that can be melted. All limits default to unlimited.
+#[derive(Debug, Clone, Parser)]
+pub struct MeltLimitConfig {
+    /// maximum amount in sats for a single melt
+    #[clap(long, env = "MINT_MAX_MELT_AMOUNT")]
+    pub max_melt_amount: Option<u64>,
+
+    /// maximum amount in sats that can be melted within the velocity window
+    #[clap(long, env = "MINT_MELT_VELOCITY_LIMIT")]
+    pub melt_velocity_limit: Option<u64>,
+
+    /// length of the velocity window in seconds
+    #[clap(long, default_value_t = 3600, env = "MINT_MELT_VELOCITY_WINDOW_SECS")]
+    pub melt_velocity_window_secs: u64,
+}
+
+impl Default for MeltLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_melt_amount: None,
+            melt_velocity_limit: None,
+            melt_velocity_window_secs: 3600,
+        }
+    }
+}
+
+/// Limits the amount of bolt11 mint and melt quotes. The min amount defaults to none and the max
+/// amount to `DEFAULT_BOLT11_MAX_AMOUNT`, which is also advertised in the mint info.
+#[derive(Debug, Clone, Default, Parser)]
+pub struct Bolt11LimitConfig {
+    /// minimum amount in sats for bolt11 mint and melt quotes
+    #[clap(long, env = "MINT_BOLT11_MIN_AMOUNT")]
+    pub bolt11_min_amount: Option<u64>,
+
+    /// maximum amount in sats for bolt11 mint and melt quotes
+    #[clap(long, env = "MINT_BOLT11_MAX_AMOUNT")]
+    pub bolt11_max_amount: Option<u64>,
+}
+
+impl Bolt11LimitConfig {
+    /// maximum amount in sats for bolt11 quotes, `DEFAULT_BOLT11_MAX_AMOUNT` if none is configured
+    pub fn max_amount(&self) -> u64 {
+        self.bolt11_max_amount.unwrap_or(DEFAULT_BOLT11_MAX_AMOUNT)
+    }
+}
+
+const MAX_QUOTE_EXPIRY_MINUTES: u64 = 60 * 24 * 365;
+
+#[derive(Debug, Clone, Parser)]
+pub struct QuoteConfig {
+    /// minutes a bolt11 mint or melt quote is valid after it has been created, at most one year
+    #[clap(
+        long,
+        default_value_t = 30,
+        env = "MINT_QUOTE_EXPIRY_MINUTES",
+        value_parser = clap::value_parser!(u64).range(1..=MAX_QUOTE_EXPIRY_MINUTES)
+    )]
+    pub expiry_minutes: u64,
+
+    /// seconds after expiry in which a quote is still accepted if its payment is confirmed and
+    /// before expired unpaid quotes are deleted
+    #[clap(long, default_value_t = 60, env = "MINT_QUOTE_EXPIRY_GRACE_SECS")]
+    pub expiry_grace_secs: u64,
+
+    /// seconds between the runs that delete expired unpaid bolt11 quotes. 0 disables the cleanup
+    #[clap(long, default_value_t = 600, env = "MINT_QUOTE_CLEANUP_INTERVAL_SECS")]
+    pub cleanup_interval_secs: u64,
+}
+
+impl Default for QuoteConfig {
+    fn default() -> Self {
+        Self {
+            expiry_minutes: 30,
+            expiry_grace_secs: 60,
+            cleanup_interval_secs: 600,
         }
     }
 }
 
+/// Bounds the in-memory store for nonces and idempotency keys. Keys that are evicted before
+/// they expired are kept in the database until their ttl is over.
+#[derive(Debug, Clone, Parser)]
+pub struct NonceConfig {
+    /// maximum number of keys that are kept in memory
+    #[clap(long, default_value_t = 100_000, env = "MINT_NONCE_CAPACITY")]
+    pub nonce_capacity: usize,
+
+    /// seconds a key and the response of an idempotent request are remembered after it has been used
+    #[clap(long, default_value_t = 86_400, env = "MINT_NONCE_TTL_SECS")]
+    pub nonce_ttl_secs: u64,
+}
+
+impl Default for NonceConfig {
+    fn default() -> Self {
+        Self {
+            nonce_capacity: 100_000,
+            nonce_ttl_secs: 86_400,
+        }
+    }
+}
+
+/// Signs and verifies a blinded message with every key of the configured keysets before the
+/// server starts, to detect a broken key configuration early.
+#[derive(Debug, Clone, Default, Parser)]
+pub struct SelfTestConfig {
+    /// run the self-test at startup
+    #[clap(long, default_value_t = false, env = "MINT_SELF_TEST")]
+    pub self_test: bool,
+
+    /// refuse to start if the self-test fails. Otherwise the failure is only logged
+    #[clap(long, default_value_t = false, env = "MINT_SELF_TEST_REQUIRED")]
+    pub self_test_required: bool,
+}
+
 #[derive(Debug, Clone, Parser)]
 pub struct ServerConfig {
     #[clap(long, default_value = "[::]:3338", env = "MINT_HOST_PORT")]
@@ -265,6 +608,21 @@ pub struct ServerConfig {
     pub serve_wallet_path: Option<PathBuf>,
     #[clap(long, env = "MINT_API_PREFIX")]
     pub api_prefix: Option<String>,
+    /// max-age in seconds for the Cache-Control header of the keys and keysets endpoints
+    #[clap(long, default_value_t = 3600, env = "MINT_KEYS_CACHE_MAX_AGE")]
+    pub keys_cache_max_age: u64,
+    /// bearer token for the admin endpoints. The admin endpoints are disabled if not set
+    #[clap(long, env = "MINT_ADMIN_TOKEN", hide_env_values = true)]
+    pub admin_token: Option<String>,
+    /// url the mint posts events to, e.g. when a quote got paid
+    #[clap(long, env = "MINT_EVENT_WEBHOOK_URL")]
+    pub event_webhook_url: Option<Url>,
+    /// shared secret for the HMAC signature of the webhook events
+    #[clap(long, env = "MINT_EVENT_WEBHOOK_SECRET", hide_env_values = true)]
+    pub event_webhook_secret: Option<String>,
+    /// sign the keysets response with the mint pubkey, so wallets can detect a tampered keyset list
+    #[clap(long, default_value_t = false, env = "MINT_SIGN_KEYSETS")]
+    pub sign_keysets: bool,
 }
 
 impl Default for ServerConfig {
@@ -273,6 +631,11 @@ impl Default for ServerConfig {
             host_port: "[::]:3338".to_string().parse().expect("invalid host port"),
             serve_wallet_path: None,
             api_prefix: None,
+            keys_cache_max_age: 3600,
+            admin_token: None,
+            event_webhook_url: None,
+            event_webhook_secret: None,
+            sign_keysets: false,
         }
     }
 }
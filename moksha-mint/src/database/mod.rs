@@ -1,11 +1,18 @@
 use async_trait::async_trait;
 use moksha_core::{
-    primitives::{Bolt11MeltQuote, Bolt11MintQuote, BtcOnchainMeltQuote, BtcOnchainMintQuote},
+    blind::{BlindedMessage, BlindedSignature},
+    primitives::{
+        Bolt11MeltQuote, Bolt11MintQuote, BtcOnchainMeltQuote, BtcOnchainMintQuote, ProofState,
+    },
     proof::Proofs,
 };
+use secp256k1::PublicKey;
 use uuid::Uuid;
 
-use crate::{error::MokshaMintError, model::Invoice};
+use crate::{
+    error::MokshaMintError,
+    model::{IdempotentResponse, Invoice, IssuanceCounters, KeysetValidity},
+};
 
 pub mod postgres;
 
@@ -13,6 +20,8 @@ pub mod postgres;
 pub trait Database {
     type DB: sqlx::Database;
     async fn begin_tx(&self) -> Result<sqlx::Transaction<Self::DB>, sqlx::Error>;
+    /// starts a read-only transaction on the read replica or on the primary if no replica is configured
+    async fn begin_read_tx(&self) -> Result<sqlx::Transaction<Self::DB>, sqlx::Error>;
     async fn get_used_proofs(
         &self,
         tx: &mut sqlx::Transaction<Self::DB>,
@@ -23,6 +32,71 @@ pub trait Database {
         proofs: &Proofs,
     ) -> Result<(), MokshaMintError>;
 
+    /// returns the Y of every used proof, including the inputs of melts that are in flight
+    async fn get_used_proof_ys(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+    ) -> Result<Vec<PublicKey>, MokshaMintError>;
+
+    /// returns the state of the used proofs with the given Ys. Inputs of melts that are in flight
+    /// are pending, all other used proofs are spent. Ys that aren't used are left out
+    async fn get_proof_states(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        ys: &[PublicKey],
+    ) -> Result<Vec<ProofState>, MokshaMintError>;
+
+    /// returns the number of used proofs and the id of the latest one. The pair changes whenever
+    /// proofs are added or removed
+    async fn get_used_proofs_revision(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+    ) -> Result<(u64, u64), MokshaMintError>;
+
+    /// returns the secrets of used proofs that were stored before their Y was persisted
+    async fn get_used_proof_secrets_without_y(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+    ) -> Result<Vec<String>, MokshaMintError>;
+
+    /// sets the Y of the used proofs with the secrets. `secrets` and `ys` have the same order
+    async fn update_used_proof_ys(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        secrets: &[String],
+        ys: &[PublicKey],
+    ) -> Result<(), MokshaMintError>;
+
+    /// stores the inputs of a melt whose payment is in flight. They count as used proofs until
+    /// they are released or settled
+    async fn add_pending_proofs(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        quote_id: &Uuid,
+        proofs: &Proofs,
+    ) -> Result<(), MokshaMintError>;
+
+    /// returns the inputs of the melt quote whose payment is in flight
+    async fn get_pending_proofs(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        quote_id: &Uuid,
+    ) -> Result<Proofs, MokshaMintError>;
+
+    /// deletes the inputs of a melt whose payment failed, so they can be spent again
+    async fn delete_pending_proofs(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        quote_id: &Uuid,
+    ) -> Result<(), MokshaMintError>;
+
+    /// keeps the inputs of a melt as used proofs after the payment settled or the quote was refunded
+    async fn settle_pending_proofs(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        quote_id: &Uuid,
+    ) -> Result<(), MokshaMintError>;
+
     async fn get_pending_invoice(
         &self,
         tx: &mut sqlx::Transaction<Self::DB>,
@@ -66,6 +140,12 @@ pub trait Database {
         tx: &mut sqlx::Transaction<Self::DB>,
         key: &Uuid,
     ) -> Result<Bolt11MeltQuote, MokshaMintError>;
+    /// loads the melt quote and locks it until the end of the transaction
+    async fn get_bolt11_melt_quote_for_update(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        key: &Uuid,
+    ) -> Result<Bolt11MeltQuote, MokshaMintError>;
     async fn add_bolt11_melt_quote(
         &self,
         tx: &mut sqlx::Transaction<Self::DB>,
@@ -113,6 +193,13 @@ pub trait Database {
         key: &Uuid,
     ) -> Result<BtcOnchainMeltQuote, MokshaMintError>;
 
+    /// locks the onchain melt quote until the end of the transaction
+    async fn lock_onchain_melt_quote(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        key: &Uuid,
+    ) -> Result<(), MokshaMintError>;
+
     async fn add_onchain_melt_quote(
         &self,
         tx: &mut sqlx::Transaction<Self::DB>,
@@ -130,4 +217,176 @@ pub trait Database {
         tx: &mut sqlx::Transaction<Self::DB>,
         quote: &BtcOnchainMeltQuote,
     ) -> Result<(), MokshaMintError>;
+
+    async fn get_issuance_counters(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+    ) -> Result<IssuanceCounters, MokshaMintError>;
+
+    /// returns true if the mint issued or redeemed ecash before the issuance counters existed
+    async fn get_issued_before_counters(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+    ) -> Result<bool, MokshaMintError>;
+
+    /// returns the issuance counters of every keyset that has issued or redeemed ecash
+    async fn get_keyset_issuance_counters(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+    ) -> Result<Vec<(String, IssuanceCounters)>, MokshaMintError>;
+
+    async fn add_issued_amount(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        keyset_id: &str,
+        amount: u64,
+    ) -> Result<(), MokshaMintError>;
+
+    async fn add_redeemed_amount(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        keyset_id: &str,
+        amount: u64,
+    ) -> Result<(), MokshaMintError>;
+
+    /// Adds the amount to the melt history if the total amount melted within the window stays
+    /// within the limit. Returns the id of the entry, or `None` if the limit would be exceeded.
+    async fn reserve_melt_amount(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        amount: u64,
+        limit: Option<u64>,
+        window_secs: u64,
+    ) -> Result<Option<i64>, MokshaMintError>;
+
+    /// Removes a reserved amount from the melt history, e.g. after the payment failed
+    async fn release_melt_amount(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        id: i64,
+    ) -> Result<(), MokshaMintError>;
+
+    /// returns all keysets the mint has ever activated
+    async fn get_keysets(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+    ) -> Result<Vec<KeysetValidity>, MokshaMintError>;
+
+    async fn add_keyset(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        keyset: &KeysetValidity,
+    ) -> Result<(), MokshaMintError>;
+
+    async fn update_keyset_valid_to(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        keyset_id: &str,
+        valid_to: Option<u64>,
+    ) -> Result<(), MokshaMintError>;
+
+    /// records an address handed out for a mint quote. Returns false if it was handed out before
+    async fn add_onchain_address(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        address: &str,
+    ) -> Result<bool, MokshaMintError>;
+
+    /// returns the number of addresses of unpaid mint quotes that have not expired at `now`
+    async fn count_open_onchain_addresses(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        now: u64,
+    ) -> Result<u64, MokshaMintError>;
+
+    /// returns the addresses whose mint quotes all expired unpaid before `now`, oldest first
+    async fn get_expired_onchain_addresses(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        now: u64,
+    ) -> Result<Vec<String>, MokshaMintError>;
+
+    /// returns the mint quotes that expired before `now` without being minted
+    async fn get_expired_bolt11_mint_quotes(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        now: u64,
+    ) -> Result<Vec<Bolt11MintQuote>, MokshaMintError>;
+
+    /// returns the melt quotes that expired before `now` without being paid or refunded. Quotes
+    /// with a payment in flight are skipped and the returned quotes are locked
+    async fn get_expired_bolt11_melt_quotes(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        now: u64,
+    ) -> Result<Vec<Bolt11MeltQuote>, MokshaMintError>;
+
+    /// stores a nonce until `expires_at`. An existing nonce gets the new expiry
+    async fn add_nonce(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        key: &str,
+        expires_at: u64,
+    ) -> Result<(), MokshaMintError>;
+
+    /// returns the expiry of a stored nonce
+    async fn get_nonce_expiry(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        key: &str,
+    ) -> Result<Option<u64>, MokshaMintError>;
+
+    /// deletes a stored nonce
+    async fn delete_nonce(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        key: &str,
+    ) -> Result<(), MokshaMintError>;
+
+    /// deletes the nonces that expired at `now`
+    async fn delete_expired_nonces(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        now: u64,
+    ) -> Result<(), MokshaMintError>;
+
+    /// returns the response stored for an idempotency key if it has not expired at `now`
+    async fn get_idempotent_response(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        key: &str,
+        now: u64,
+    ) -> Result<Option<IdempotentResponse>, MokshaMintError>;
+
+    /// stores the response of a request with an idempotency key until `expires_at`. An expired
+    /// response of the same key is replaced
+    async fn add_idempotent_response(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        key: &str,
+        response: &IdempotentResponse,
+        expires_at: u64,
+    ) -> Result<(), MokshaMintError>;
+
+    /// deletes the responses of idempotency keys that expired at `now`
+    async fn delete_expired_idempotent_responses(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        now: u64,
+    ) -> Result<(), MokshaMintError>;
+
+    /// stores the signatures of issued outputs. `outputs` and `signatures` have the same order
+    async fn add_blind_signatures(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        outputs: &[BlindedMessage],
+        signatures: &[BlindedSignature],
+    ) -> Result<(), MokshaMintError>;
+
+    /// returns the stored signatures of the outputs. Unknown outputs are skipped
+    async fn get_blind_signatures(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        outputs: &[BlindedMessage],
+    ) -> Result<Vec<(BlindedMessage, BlindedSignature)>, MokshaMintError>;
 }
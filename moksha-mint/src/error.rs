@@ -7,8 +7,8 @@ use axum::{
 };
 use fedimint_tonic_lnd::{tonic::Status, ConnectError};
 
-use lightning_invoice::ParseOrSemanticError;
-use moksha_core::primitives::CurrencyUnit;
+use lightning_invoice::{Currency, ParseOrSemanticError};
+use moksha_core::primitives::{CashuErrorResponse, CurrencyUnit};
 use serde_json::json;
 use thiserror::Error;
 use tracing::{event, Level};
@@ -38,9 +38,33 @@ pub enum MokshaMintError {
     #[error("Serde Error {0}")]
     Serialization(#[from] serde_json::Error),
 
+    #[error("Invalid request body: {0}")]
+    InvalidRequestBody(String),
+
+    #[error("Amount {0} is below the minimum mint amount of {1}")]
+    AmountTooLow(u64, u64),
+
+    #[error("Amount out of range: {0}")]
+    AmountOutOfRange(String),
+
     #[error("Invoice amount is too low {0}")]
     InvoiceAmountTooLow(String),
 
+    #[error("Paying amountless invoices is not supported by this backend {0}")]
+    AmountlessInvoiceNotSupported(String),
+
+    #[error("Multi-path payments are not supported by this backend")]
+    MppNotSupported,
+
+    #[error("Invalid invoice signature {0}")]
+    InvalidInvoiceSignature(String),
+
+    #[error("Invoice is for network {0:?}, but the mint runs on {1:?}")]
+    InvoiceNetworkMismatch(Currency, Currency),
+
+    #[error("Invoice expired {0}")]
+    InvoiceExpired(String),
+
     #[error("Invoice not found for hash {0}")]
     InvoiceNotFound(String),
 
@@ -50,15 +74,45 @@ pub enum MokshaMintError {
     #[error("BTC-Onchain not paid yet.")]
     BtcOnchainNotPaidYet,
 
+    #[error("Proofs with a P2SH script of the legacy api are not supported")]
+    P2SHUnsupported,
+
+    #[error("Spending condition not met: {0}")]
+    SpendingConditionNotMet(moksha_core::error::MokshaCoreError),
+
+    #[error("Invalid proof: {0}")]
+    InvalidProof(String),
+
     #[error("Proof already used {0}")]
     ProofAlreadyUsed(String),
 
+    #[error("Nonce already used {0}")]
+    NonceAlreadyUsed(String),
+
+    #[error("Invalid idempotency key")]
+    InvalidIdempotencyKey,
+
+    #[error("Idempotency key was used for a different request")]
+    IdempotencyKeyMismatch,
+
     #[error("{0}")]
     SwapAmountMismatch(String),
 
     #[error("duplicate promises.")]
     SwapHasDuplicatePromises,
 
+    #[error("duplicate inputs.")]
+    DuplicateInputs,
+
+    #[error("Too many proofs {0}. At most {1} proofs can be checked per request")]
+    TooManyProofs(usize, usize),
+
+    #[error("Too many outputs {0}. At most {1} outputs can be restored per request")]
+    TooManyOutputs(usize, usize),
+
+    #[error("Total amount overflows")]
+    AmountOverflow,
+
     #[error("Invalid amount: {0}")]
     InvalidAmount(String),
 
@@ -68,6 +122,21 @@ pub enum MokshaMintError {
     #[error("Invalid quote {0}")]
     InvalidQuote(String),
 
+    #[error("Quote expired {0}")]
+    QuoteExpired(String),
+
+    #[error("Melt quote {0} is already paid")]
+    MeltQuotePaid(String),
+
+    #[error("Melt quote {0} failed and was refunded")]
+    MeltQuoteFailed(String),
+
+    #[error("Melt quote {0} has a payment in flight")]
+    MeltQuotePending(String),
+
+    #[error("Melt quote can not be refunded: {0}")]
+    RefundNotAllowed(String),
+
     #[error("Invalid quote uuid {0}")]
     InvalidUuid(#[from] uuid::Error),
 
@@ -77,6 +146,15 @@ pub enum MokshaMintError {
     #[error("Currency not supported {0}")]
     CurrencyNotSupported(CurrencyUnit),
 
+    #[error("Inputs and outputs must all be in unit {0}")]
+    UnitMismatch(CurrencyUnit),
+
+    #[error("Mint cap reached. Max outstanding amount {0}")]
+    MintCapReached(u64),
+
+    #[error("Melt velocity limit exceeded: {0}")]
+    VelocityLimitExceeded(String),
+
     #[error("Not Enough tokens. Required amount {0}")]
     NotEnoughTokens(u64),
 
@@ -86,6 +164,21 @@ pub enum MokshaMintError {
     #[error("PrivateKey in keyset not found")]
     PrivateKeyNotFound,
 
+    #[error("Denomination {0} is out of the range of keyset {1}")]
+    DenominationOutOfRange(u64, String),
+
+    #[error("Self-test failed for keyset {0}: {1}")]
+    SelfTestFailed(String, String),
+
+    #[error("Onchain backend is not synced to the chain tip yet")]
+    OnchainNotSynced,
+
+    #[error("Batched onchain payment failed: {0}")]
+    OnchainBatchFailed(String),
+
+    #[error("Onchain backend returned no unused address")]
+    NoUnusedOnchainAddress,
+
     #[error("MokshaCoreError: {0}")]
     MokshaCore(#[from] moksha_core::error::MokshaCoreError),
 }
@@ -94,11 +187,19 @@ impl IntoResponse for MokshaMintError {
     fn into_response(self) -> Response {
         event!(Level::ERROR, "error in mint: {:?}", self);
 
+        let code = match self {
+            Self::ProofAlreadyUsed(_) => CashuErrorResponse::PROOF_ALREADY_USED,
+            _ => 0,
+        };
         let body = Json(json!({
-            "code": 0,
+            "code": code,
             "detail": self.to_string(),
         }));
 
-        (StatusCode::BAD_REQUEST, body).into_response()
+        let status = match self {
+            Self::OnchainNotSynced => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        (status, body).into_response()
     }
 }
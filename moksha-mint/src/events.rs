@@ -0,0 +1,205 @@
+use std::time::Duration;
+
+use bitcoin_hashes::{
+    hmac::{Hmac, HmacEngine},
+    sha256, Hash, HashEngine,
+};
+use chrono::Utc;
+use moksha_core::primitives::PaymentMethod;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+use url::Url;
+
+/// header that contains the hex encoded HMAC-SHA256 of the request body
+pub const SIGNATURE_HEADER: &str = "X-Moksha-Signature";
+
+/// events that are not delivered yet. New events are dropped if the queue is full
+const QUEUE_SIZE: usize = 1_000;
+const MAX_ATTEMPTS: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// a webhook that doesn't respond must not stall the delivery of the following events
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum MintEvent {
+    /// a mint quote was paid and the ecash has been issued
+    MintQuotePaid {
+        quote: String,
+        payment_method: PaymentMethod,
+        amount: u64,
+    },
+    /// the mint paid the invoice or broadcasted the transaction of a melt quote
+    MeltCompleted {
+        quote: String,
+        payment_method: PaymentMethod,
+        amount: u64,
+    },
+    /// the onchain transaction of a melt quote reached the configured confirmations
+    OnchainTxConfirmed {
+        quote: String,
+        address: String,
+        amount: u64,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WebhookPayload {
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub event: MintEvent,
+}
+
+/// Posts mint events to a webhook. Events are queued and delivered by a background task,
+/// so emitting an event never blocks the request that caused it.
+#[derive(Debug, Clone)]
+pub struct EventWebhook {
+    sender: mpsc::Sender<MintEvent>,
+}
+
+impl EventWebhook {
+    /// Spawns the delivery task. Must be called from within a tokio runtime.
+    pub fn new(url: Url, secret: Option<String>) -> Self {
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+        tokio::spawn(deliver_events(url, secret, receiver));
+        Self { sender }
+    }
+
+    pub fn emit(&self, event: MintEvent) {
+        if let Err(err) = self.sender.try_send(event) {
+            warn!("dropping webhook event: {}", err);
+        }
+    }
+}
+
+/// Returns the hex encoded HMAC-SHA256 of the body
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut engine = HmacEngine::<sha256::Hash>::new(secret.as_bytes());
+    engine.input(body);
+    Hmac::<sha256::Hash>::from_engine(engine).to_string()
+}
+
+async fn deliver_events(url: Url, secret: Option<String>, mut receiver: mpsc::Receiver<MintEvent>) {
+    let client = match reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(err) => {
+            warn!("failed to create webhook client: {}", err);
+            return;
+        }
+    };
+    while let Some(event) = receiver.recv().await {
+        let payload = WebhookPayload {
+            timestamp: Utc::now().timestamp() as u64,
+            event,
+        };
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(err) => {
+                warn!("failed to serialize webhook event: {}", err);
+                continue;
+            }
+        };
+
+        let mut backoff = INITIAL_BACKOFF;
+        for attempt in 1..=MAX_ATTEMPTS {
+            let mut request = client
+                .post(url.clone())
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.clone());
+            if let Some(ref secret) = secret {
+                request = request.header(SIGNATURE_HEADER, sign(secret, &body));
+            }
+
+            match request.send().await.and_then(|res| res.error_for_status()) {
+                Ok(_) => {
+                    debug!("delivered webhook event {:?}", payload.event);
+                    break;
+                }
+                Err(err) if attempt < MAX_ATTEMPTS => {
+                    debug!("webhook attempt {} failed: {}", attempt, err);
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                Err(err) => {
+                    warn!(
+                        "giving up on webhook event {:?} after {} attempts: {}",
+                        payload.event, MAX_ATTEMPTS, err
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::{extract::State, http::HeaderMap, routing::post, Router};
+    use moksha_core::primitives::PaymentMethod;
+    use tokio::sync::mpsc;
+
+    use super::{sign, EventWebhook, MintEvent, WebhookPayload, SIGNATURE_HEADER};
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            sign("Jefe", b"what do ya want for nothing?")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_emit_event() -> anyhow::Result<()> {
+        let (sender, mut receiver) = mpsc::channel::<(HeaderMap, String)>(1);
+        let app = Router::new()
+            .route(
+                "/webhook",
+                post(
+                    |State(sender): State<mpsc::Sender<(HeaderMap, String)>>,
+                     headers: HeaderMap,
+                     body: String| async move {
+                        sender.send((headers, body)).await.unwrap();
+                    },
+                ),
+            )
+            .with_state(sender);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let webhook = EventWebhook::new(
+            format!("http://{addr}/webhook").parse()?,
+            Some("secret".to_owned()),
+        );
+        let event = MintEvent::MintQuotePaid {
+            quote: "quote-id".to_owned(),
+            payment_method: PaymentMethod::Bolt11,
+            amount: 21,
+        };
+        webhook.emit(event.clone());
+
+        let (headers, body) = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await?
+            .expect("no webhook request received");
+
+        let payload: WebhookPayload = serde_json::from_str(&body)?;
+        assert_eq!(event, payload.event);
+        assert!(body.contains(r#""event":"mint_quote_paid""#));
+        assert_eq!(
+            sign("secret", body.as_bytes()),
+            headers
+                .get(SIGNATURE_HEADER)
+                .expect("signature header not set")
+                .to_str()?
+        );
+        Ok(())
+    }
+}
@@ -2,9 +2,11 @@ pub mod btconchain;
 pub mod config;
 pub mod database;
 pub mod error;
+pub mod events;
 pub mod lightning;
 pub mod mint;
 pub mod model;
+pub mod nonce;
 mod routes;
 pub mod server;
 pub mod url_serialize;
@@ -72,6 +72,7 @@ impl Lightning for AlbyLightning {
     async fn pay_invoice(
         &self,
         payment_request: String,
+        _fee_reserve: u64,
     ) -> Result<PayInvoiceResult, MokshaMintError> {
         self.client
             .pay_invoice(&payment_request)
@@ -190,7 +191,8 @@ impl AlbyClient {
                 .as_str()
                 .expect("payment_hash is empty")
                 .to_owned(),
-            total_fees: 0, // FIXME alby does not return fees at the moment
+            // alby doesn't report the fee of the payment
+            total_fees: None,
         })
     }
 
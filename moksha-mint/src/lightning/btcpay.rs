@@ -0,0 +1,402 @@
+use std::fmt::{self, Formatter};
+
+use async_trait::async_trait;
+
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    error::MokshaMintError,
+    model::{CreateInvoiceResult, PayInvoiceResult},
+};
+
+use super::{error::LightningError, Lightning};
+
+/// expiry of created invoices in seconds
+const INVOICE_EXPIRY_SECONDS: u32 = 3600;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Parser)]
+pub struct BTCPaySettings {
+    /// url of the BTCPay Server, e.g. https://btcpay.example.com
+    #[clap(long, env = "MINT_BTCPAY_BASE_URL")]
+    pub base_url: Option<String>,
+    #[clap(long, env = "MINT_BTCPAY_STORE_ID")]
+    pub store_id: Option<String>,
+    /// Greenfield API key with the permissions to create and pay lightning invoices of the store
+    #[clap(long, env = "MINT_BTCPAY_API_KEY")]
+    pub api_key: Option<String>,
+}
+
+impl BTCPaySettings {
+    pub fn new(base_url: &str, store_id: &str, api_key: &str) -> Self {
+        Self {
+            base_url: Some(base_url.to_owned()),
+            store_id: Some(store_id.to_owned()),
+            api_key: Some(api_key.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for BTCPaySettings {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "base_url: {}, store_id: {}",
+            self.base_url.as_ref().unwrap(),
+            self.store_id.as_ref().unwrap()
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct BTCPayLightning {
+    pub client: BTCPayClient,
+}
+
+impl BTCPayLightning {
+    pub fn new(base_url: String, store_id: String, api_key: String) -> Self {
+        Self {
+            client: BTCPayClient::new(&base_url, &store_id, &api_key)
+                .expect("Can not create BTCPay client"),
+        }
+    }
+}
+
+#[async_trait]
+impl Lightning for BTCPayLightning {
+    async fn is_invoice_paid(&self, invoice: String) -> Result<bool, MokshaMintError> {
+        let decoded_invoice = self.decode_invoice(invoice).await?;
+        Ok(self
+            .client
+            .is_invoice_paid(&decoded_invoice.payment_hash().to_string())
+            .await?)
+    }
+
+    async fn create_invoice(&self, amount: u64) -> Result<CreateInvoiceResult, MokshaMintError> {
+        Ok(self.client.create_invoice(amount).await?)
+    }
+
+    async fn pay_invoice(
+        &self,
+        payment_request: String,
+        fee_reserve: u64,
+    ) -> Result<PayInvoiceResult, MokshaMintError> {
+        let amount_msat = self
+            .decode_invoice(payment_request.clone())
+            .await?
+            .amount_milli_satoshis();
+        self.client
+            .pay_invoice(&payment_request, amount_msat, fee_reserve)
+            .await
+            .map_err(|err| MokshaMintError::PayInvoice(payment_request, err))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LightningInvoiceResponse {
+    status: String,
+    #[serde(rename = "BOLT11")]
+    bolt11: String,
+    payment_hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LightningPaymentResponse {
+    status: String,
+    payment_hash: Option<String>,
+    /// fee in msat
+    fee_amount: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct BTCPayClient {
+    api_key: String,
+    /// url of the lightning node of the store, the endpoints are relative to it
+    lightning_url: Url,
+    reqwest_client: reqwest::Client,
+}
+
+impl BTCPayClient {
+    pub fn new(base_url: &str, store_id: &str, api_key: &str) -> Result<Self, LightningError> {
+        let lightning_url =
+            Url::parse(base_url)?.join(&format!("api/v1/stores/{store_id}/lightning/BTC/"))?;
+        let reqwest_client = reqwest::Client::builder().build()?;
+
+        Ok(Self {
+            api_key: api_key.to_owned(),
+            lightning_url,
+            reqwest_client,
+        })
+    }
+}
+
+impl BTCPayClient {
+    // the Greenfield api expects the api key in the authorization header with the "token" scheme
+    pub async fn make_get(&self, endpoint: &str) -> Result<String, LightningError> {
+        let url = self.lightning_url.join(endpoint)?;
+        let response = self
+            .reqwest_client
+            .get(url)
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("token {}", self.api_key),
+            )
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(LightningError::NotFound);
+        }
+
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(LightningError::Unauthorized);
+        }
+
+        Ok(response.text().await?)
+    }
+
+    pub async fn make_post(
+        &self,
+        endpoint: &str,
+        body: &serde_json::Value,
+    ) -> Result<String, LightningError> {
+        let url = self.lightning_url.join(endpoint)?;
+        let response = self
+            .reqwest_client
+            .post(url)
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("token {}", self.api_key),
+            )
+            .json(body)
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(LightningError::NotFound);
+        }
+
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(LightningError::Unauthorized);
+        }
+
+        Ok(response.text().await?)
+    }
+}
+
+impl BTCPayClient {
+    pub async fn create_invoice(&self, amount: u64) -> Result<CreateInvoiceResult, LightningError> {
+        // amounts are msat encoded as strings
+        let amount_msat = amount
+            .checked_mul(1_000)
+            .ok_or(LightningError::AmountOverflow)?;
+        let body = self
+            .make_post(
+                "invoices",
+                &serde_json::json!({
+                    "amount": amount_msat.to_string(),
+                    "description": "",
+                    "expiry": INVOICE_EXPIRY_SECONDS,
+                }),
+            )
+            .await?;
+        let response: LightningInvoiceResponse = serde_json::from_str(&body)?;
+
+        Ok(CreateInvoiceResult {
+            payment_hash: hex::decode(&response.payment_hash)
+                .map_err(|_| LightningError::InvalidPaymentHash(response.payment_hash))?,
+            payment_request: response.bolt11,
+        })
+    }
+
+    /// Pays the invoice with at most `fee_reserve` (sat) in fees. The limit is sent as flat fee
+    /// and, if the invoice has an amount, as percentage of the amount.
+    pub async fn pay_invoice(
+        &self,
+        bolt11: &str,
+        amount_msat: Option<u64>,
+        fee_reserve: u64,
+    ) -> Result<PayInvoiceResult, LightningError> {
+        let mut request = serde_json::json!({
+            "BOLT11": bolt11,
+            "maxFeeFlat": fee_reserve.to_string(),
+        });
+        if let Some(amount_msat) = amount_msat.filter(|amount_msat| *amount_msat > 0) {
+            let max_fee_percent = (fee_reserve as f64 * 1_000.0 * 100.0) / amount_msat as f64;
+            request["maxFeePercent"] = max_fee_percent.to_string().into();
+        }
+        let body = self.make_post("invoices/pay", &request).await?;
+        match serde_json::from_str::<LightningPaymentResponse>(&body)? {
+            LightningPaymentResponse {
+                status,
+                payment_hash: Some(payment_hash),
+                fee_amount,
+            } if status == "Complete" => Ok(PayInvoiceResult {
+                payment_hash,
+                total_fees: fee_amount
+                    .and_then(|fee| fee.parse::<u64>().ok())
+                    .map(|fee| fee.div_ceil(1_000)),
+            }),
+            // the payment hasn't failed yet and might still succeed
+            LightningPaymentResponse { status, .. } if status == "Pending" => {
+                Err(LightningError::PaymentPending)
+            }
+            _ => Err(LightningError::PaymentFailed),
+        }
+    }
+
+    // BTCPay uses the payment hash as id of the invoices of the lightning node
+    pub async fn is_invoice_paid(&self, payment_hash: &str) -> Result<bool, LightningError> {
+        let body = self.make_get(&format!("invoices/{payment_hash}")).await?;
+
+        Ok(serde_json::from_str::<LightningInvoiceResponse>(&body)?.status == "Paid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        extract::Path,
+        http::{HeaderMap, StatusCode},
+        routing::{get, post},
+        Json, Router,
+    };
+
+    use crate::lightning::{btcpay::BTCPayLightning, error::LightningError, Lightning};
+
+    const PAYMENT_HASH: &str = "ac2d2e2a1c0e7b6a9f9f0b5c1b5a0e4d8e3c2b1a09f8e7d6c5b4a39281706f5e";
+
+    fn authorized(headers: &HeaderMap) -> bool {
+        headers
+            .get("authorization")
+            .is_some_and(|value| value == "token apikey")
+    }
+
+    async fn start_btcpay_mock() -> anyhow::Result<String> {
+        let app = Router::new()
+            .route(
+                "/api/v1/stores/store/lightning/BTC/invoices",
+                post(
+                    |headers: HeaderMap, Json(body): Json<serde_json::Value>| async move {
+                        if !authorized(&headers) {
+                            return Err(StatusCode::UNAUTHORIZED);
+                        }
+                        Ok(Json(serde_json::json!({
+                            "id": PAYMENT_HASH,
+                            "status": "Unpaid",
+                            "BOLT11": format!("lnbc{}invoice", body["amount"].as_str().unwrap()),
+                            "paymentHash": PAYMENT_HASH,
+                            "amount": body["amount"],
+                        })))
+                    },
+                ),
+            )
+            .route(
+                "/api/v1/stores/store/lightning/BTC/invoices/pay",
+                post(|Json(body): Json<serde_json::Value>| async move {
+                    // the fee limit is 2 sat of the 20 sat invoice
+                    let limited = body["maxFeeFlat"] == "2" && body["maxFeePercent"] == "10";
+                    let status = match body["BOLT11"].as_str() {
+                        Some("lnbcpayable") if limited => "Complete",
+                        Some("lnbcpending") => "Pending",
+                        _ => "Failed",
+                    };
+                    Json(serde_json::json!({
+                        "id": PAYMENT_HASH,
+                        "status": status,
+                        "BOLT11": body["BOLT11"],
+                        "paymentHash": PAYMENT_HASH,
+                        "totalAmount": "21000",
+                        "feeAmount": "1500",
+                    }))
+                }),
+            )
+            .route(
+                "/api/v1/stores/store/lightning/BTC/invoices/:id",
+                get(|Path(id): Path<String>| async move {
+                    Json(serde_json::json!({
+                        "id": id,
+                        "status": if id == PAYMENT_HASH { "Paid" } else { "Unpaid" },
+                        "BOLT11": "lnbc21invoice",
+                        "paymentHash": id,
+                    }))
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Ok(format!("http://{addr}"))
+    }
+
+    fn btcpay(url: String, api_key: &str) -> BTCPayLightning {
+        BTCPayLightning::new(url, "store".to_owned(), api_key.to_owned())
+    }
+
+    #[tokio::test]
+    async fn test_create_invoice() -> anyhow::Result<()> {
+        let lightning = btcpay(start_btcpay_mock().await?, "apikey");
+
+        let result = lightning.create_invoice(21).await?;
+        assert_eq!(hex::decode(PAYMENT_HASH)?, result.payment_hash);
+        assert_eq!("lnbc21000invoice", result.payment_request);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_invoice_unauthorized() -> anyhow::Result<()> {
+        let lightning = btcpay(start_btcpay_mock().await?, "wrong");
+
+        assert!(lightning.create_invoice(21).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_invoice_amount_overflow() -> anyhow::Result<()> {
+        let lightning = btcpay(start_btcpay_mock().await?, "apikey");
+
+        assert!(matches!(
+            lightning.client.create_invoice(u64::MAX).await,
+            Err(LightningError::AmountOverflow)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pay_invoice() -> anyhow::Result<()> {
+        let lightning = btcpay(start_btcpay_mock().await?, "apikey");
+
+        let result = lightning
+            .client
+            .pay_invoice("lnbcpayable", Some(20_000), 2)
+            .await?;
+        assert_eq!(PAYMENT_HASH, result.payment_hash);
+        assert_eq!(Some(2), result.total_fees);
+        assert!(matches!(
+            lightning
+                .client
+                .pay_invoice("lnbcfailing", Some(20_000), 2)
+                .await,
+            Err(LightningError::PaymentFailed)
+        ));
+        assert!(matches!(
+            lightning
+                .client
+                .pay_invoice("lnbcpending", Some(20_000), 2)
+                .await,
+            Err(LightningError::PaymentPending)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_is_invoice_paid() -> anyhow::Result<()> {
+        let lightning = btcpay(start_btcpay_mock().await?, "apikey");
+
+        assert!(lightning.client.is_invoice_paid(PAYMENT_HASH).await?);
+        assert!(!lightning.client.is_invoice_paid("unknown").await?);
+        Ok(())
+    }
+}
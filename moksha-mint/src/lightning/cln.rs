@@ -17,6 +17,7 @@ use super::Lightning;
 use secp256k1::rand;
 use std::fs::read;
 use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+use tracing::warn;
 
 #[derive(Deserialize, Serialize, Debug, Clone, Default, Parser)]
 pub struct ClnLightningSettings {
@@ -76,6 +77,43 @@ impl ClnLightning {
         let guard = self.0.lock().await;
         Ok(MutexGuard::map(guard, |client| client))
     }
+
+    async fn pay(
+        &self,
+        payment_request: String,
+        amount_msat: Option<Amount>,
+    ) -> Result<PayInvoiceResult, MokshaMintError> {
+        let payment = self
+            .client_lock()
+            .await
+            .expect("failed to lock client") //FIXME map error
+            .pay(cln_grpc::pb::PayRequest {
+                bolt11: payment_request,
+                amount_msat,
+                label: None,
+                riskfactor: None,
+                maxfeepercent: None,
+                retry_for: None,
+                maxdelay: None,
+                exemptfee: None,
+                localinvreqid: None,
+                exclude: vec![],
+                maxfee: None,
+                description: None,
+            })
+            .await
+            .expect("failed to pay invoice")
+            .into_inner();
+
+        Ok(PayInvoiceResult {
+            payment_hash: hex::encode(payment.payment_hash),
+            // the fee is the difference of the sent and the received amount in msat
+            total_fees: payment
+                .amount_sent_msat
+                .zip(payment.amount_msat)
+                .map(|(sent, amount)| sent.msat.saturating_sub(amount.msat).div_ceil(1_000)),
+        })
+    }
 }
 
 #[async_trait]
@@ -130,6 +168,21 @@ impl Lightning for ClnLightning {
             .expect("failed to create invoice")
             .into_inner();
 
+        // cln-grpc 0.1.8 can't set exposeprivatechannels, so cln decides which route hints are
+        // added. It warns if the invoice can't be paid over the channels it considered.
+        for warning in [
+            &invoice.warning_capacity,
+            &invoice.warning_offline,
+            &invoice.warning_deadends,
+            &invoice.warning_private_unused,
+            &invoice.warning_mpp,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            warn!("cln invoice warning: {}", warning);
+        }
+
         Ok(CreateInvoiceResult {
             payment_hash: invoice.payment_hash,
             payment_request: invoice.bolt11,
@@ -139,33 +192,19 @@ impl Lightning for ClnLightning {
     async fn pay_invoice(
         &self,
         payment_request: String,
+        _fee_reserve: u64,
     ) -> Result<PayInvoiceResult, MokshaMintError> {
-        let payment = self
-            .client_lock()
-            .await
-            .expect("failed to lock client") //FIXME map error
-            .pay(cln_grpc::pb::PayRequest {
-                bolt11: payment_request,
-                amount_msat: None,
-                label: None,
-                riskfactor: None,
-                maxfeepercent: None,
-                retry_for: None,
-                maxdelay: None,
-                exemptfee: None,
-                localinvreqid: None,
-                exclude: vec![],
-                maxfee: None,
-                description: None,
-            })
-            .await
-            .expect("failed to pay invoice")
-            .into_inner();
+        self.pay(payment_request, None).await
+    }
 
-        Ok(PayInvoiceResult {
-            payment_hash: hex::encode(payment.payment_hash),
-            total_fees: payment.amount_sent_msat.unwrap().msat - payment.amount_msat.unwrap().msat, // FIXME check if this is correct
-        })
+    async fn pay_amountless_invoice(
+        &self,
+        payment_request: String,
+        amount_msat: u64,
+        _fee_reserve: u64,
+    ) -> Result<PayInvoiceResult, MokshaMintError> {
+        self.pay(payment_request, Some(Amount { msat: amount_msat }))
+            .await
     }
 }
 
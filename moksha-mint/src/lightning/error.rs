@@ -17,4 +17,16 @@ pub enum LightningError {
 
     #[error("Payment failed")]
     PaymentFailed,
+
+    #[error("Payment is still in flight")]
+    PaymentPending,
+
+    #[error("Invalid fee: {0}")]
+    InvalidFee(String),
+
+    #[error("Amount overflows")]
+    AmountOverflow,
+
+    #[error("Invalid payment hash: {0}")]
+    InvalidPaymentHash(String),
 }
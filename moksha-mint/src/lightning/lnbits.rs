@@ -83,6 +83,7 @@ impl Lightning for LnbitsLightning {
     async fn pay_invoice(
         &self,
         payment_request: String,
+        _fee_reserve: u64,
     ) -> Result<PayInvoiceResult, MokshaMintError> {
         self.client
             .pay_invoice(&payment_request)
@@ -214,7 +215,8 @@ impl LNBitsClient {
             .to_owned();
         Ok(PayInvoiceResult {
             payment_hash,
-            total_fees: 0,
+            // lnbits doesn't report the fee of the payment
+            total_fees: None,
         })
     }
 
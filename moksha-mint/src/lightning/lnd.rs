@@ -7,18 +7,19 @@ use std::{
 
 use crate::{
     error::MokshaMintError,
-    model::{CreateInvoiceResult, PayInvoiceResult},
+    model::{CreateInvoiceResult, PayInvoiceResult, ProbeResult},
     url_serialize::{deserialize_url, serialize_url},
 };
 use async_trait::async_trait;
 use clap::Parser;
 use fedimint_tonic_lnd::Client;
+use lightning_invoice::Bolt11Invoice as LNInvoice;
 use serde::{Deserialize, Serialize};
 use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
 use tracing::{debug, instrument};
 use url::Url;
 
-use super::Lightning;
+use super::{error::LightningError, Lightning};
 
 #[derive(Deserialize, Serialize, Debug, Clone, Default, Parser)]
 pub struct LndLightningSettings {
@@ -85,6 +86,61 @@ impl LndLightning {
         let guard = self.0.lock().await;
         Ok(MutexGuard::map(guard, |client| client.lightning()))
     }
+
+    async fn send_payment(
+        &self,
+        pay_req: fedimint_tonic_lnd::lnrpc::SendRequest,
+    ) -> Result<PayInvoiceResult, MokshaMintError> {
+        let payment_response = self
+            .client_lock()
+            .await?
+            .send_payment_sync(fedimint_tonic_lnd::tonic::Request::new(pay_req))
+            .await?
+            .into_inner();
+
+        let total_fees = payment_response
+            .payment_route
+            .map(|route| route.total_fees_msat as u64 / 1_000);
+
+        debug!("lnd total_fees: {:?}", total_fees);
+
+        Ok(PayInvoiceResult {
+            payment_hash: hex::encode(payment_response.payment_hash),
+            total_fees,
+        })
+    }
+}
+
+fn query_routes_request(
+    invoice: &LNInvoice,
+    amount_msat: u64,
+) -> fedimint_tonic_lnd::lnrpc::QueryRoutesRequest {
+    let route_hints = invoice
+        .route_hints()
+        .into_iter()
+        .map(|hint| fedimint_tonic_lnd::lnrpc::RouteHint {
+            hop_hints: hint
+                .0
+                .into_iter()
+                .map(|hop| fedimint_tonic_lnd::lnrpc::HopHint {
+                    node_id: hop.src_node_id.to_string(),
+                    chan_id: hop.short_channel_id,
+                    fee_base_msat: hop.fees.base_msat,
+                    fee_proportional_millionths: hop.fees.proportional_millionths,
+                    cltv_expiry_delta: hop.cltv_expiry_delta as u32,
+                })
+                .collect(),
+        })
+        .collect();
+
+    fedimint_tonic_lnd::lnrpc::QueryRoutesRequest {
+        pub_key: invoice.get_payee_pub_key().to_string(),
+        amt_msat: amount_msat as i64,
+        final_cltv_delta: invoice.min_final_cltv_expiry_delta() as i32,
+        route_hints,
+        use_mission_control: true,
+        ..Default::default()
+    }
 }
 
 #[async_trait]
@@ -133,27 +189,119 @@ impl Lightning for LndLightning {
     async fn pay_invoice(
         &self,
         payment_request: String,
+        _fee_reserve: u64,
     ) -> Result<PayInvoiceResult, MokshaMintError> {
-        let pay_req = fedimint_tonic_lnd::lnrpc::SendRequest {
+        self.send_payment(fedimint_tonic_lnd::lnrpc::SendRequest {
             payment_request,
             ..Default::default()
-        };
-        let payment_response = self
-            .client_lock()
+        })
+        .await
+    }
+
+    #[instrument(skip(self), err)]
+    async fn pay_amountless_invoice(
+        &self,
+        payment_request: String,
+        amount_msat: u64,
+        _fee_reserve: u64,
+    ) -> Result<PayInvoiceResult, MokshaMintError> {
+        self.send_payment(fedimint_tonic_lnd::lnrpc::SendRequest {
+            payment_request,
+            amt_msat: amount_msat as i64,
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Sends the part over a single route. The mpp record tells the payee the total amount, so it
+    /// waits for the other parts before it settles the invoice.
+    #[instrument(skip(self), err)]
+    async fn pay_partial_invoice(
+        &self,
+        payment_request: String,
+        amount_msat: u64,
+    ) -> Result<PayInvoiceResult, MokshaMintError> {
+        let invoice = self.decode_invoice(payment_request.clone()).await?;
+        let total_amt_msat =
+            invoice
+                .amount_milli_satoshis()
+                .ok_or(MokshaMintError::InvalidAmount(
+                    "multi-path payments require an invoice with amount".to_owned(),
+                ))?;
+
+        let mut client = self.client_lock().await?;
+        let mut route = client
+            .query_routes(fedimint_tonic_lnd::tonic::Request::new(
+                query_routes_request(&invoice, amount_msat),
+            ))
             .await?
-            .send_payment_sync(fedimint_tonic_lnd::tonic::Request::new(pay_req))
+            .into_inner()
+            .routes
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                MokshaMintError::PayInvoice(payment_request.clone(), LightningError::PaymentFailed)
+            })?;
+        if let Some(last_hop) = route.hops.last_mut() {
+            last_hop.mpp_record = Some(fedimint_tonic_lnd::lnrpc::MppRecord {
+                payment_addr: invoice.payment_secret().0.to_vec(),
+                total_amt_msat: total_amt_msat as i64,
+            });
+        }
+        let total_fees = Some(route.total_fees_msat as u64 / 1_000);
+        let payment_hash: &[u8] = invoice.payment_hash().as_ref();
+
+        let response = client
+            .send_to_route_sync(fedimint_tonic_lnd::tonic::Request::new(
+                fedimint_tonic_lnd::lnrpc::SendToRouteRequest {
+                    payment_hash: payment_hash.to_vec(),
+                    route: Some(route),
+                    ..Default::default()
+                },
+            ))
             .await?
             .into_inner();
-
-        let total_fees = payment_response
-            .payment_route
-            .map_or(0, |route| route.total_fees_msat / 1_000) as u64;
-
-        debug!("lnd total_fees: {}", total_fees);
+        if !response.payment_error.is_empty() {
+            debug!("lnd partial payment failed: {}", response.payment_error);
+            return Err(MokshaMintError::PayInvoice(
+                payment_request,
+                LightningError::PaymentFailed,
+            ));
+        }
 
         Ok(PayInvoiceResult {
-            payment_hash: hex::encode(payment_response.payment_hash),
+            payment_hash: hex::encode(response.payment_hash),
             total_fees,
         })
     }
+
+    #[instrument(skip(self), err)]
+    async fn probe_route(
+        &self,
+        payment_request: String,
+        amount_msat: u64,
+    ) -> Result<Option<ProbeResult>, MokshaMintError> {
+        let invoice = self.decode_invoice(payment_request).await?;
+        let request = query_routes_request(&invoice, amount_msat);
+
+        let response = self
+            .client_lock()
+            .await?
+            .query_routes(fedimint_tonic_lnd::tonic::Request::new(request))
+            .await;
+
+        match response {
+            Ok(response) => Ok(Some(response.into_inner().routes.first().map_or(
+                ProbeResult::NoRoute,
+                |route| ProbeResult::Route {
+                    fee_msat: route.total_fees_msat as u64,
+                },
+            ))),
+            // lnd returns an error instead of an empty list if no route exists
+            Err(status) if status.message().contains("unable to find a path") => {
+                Ok(Some(ProbeResult::NoRoute))
+            }
+            Err(status) => Err(status.into()),
+        }
+    }
 }
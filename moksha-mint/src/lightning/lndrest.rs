@@ -0,0 +1,406 @@
+use std::fmt::{self, Formatter};
+
+use async_trait::async_trait;
+
+use base64::{engine::general_purpose, Engine as _};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    error::MokshaMintError,
+    model::{CreateInvoiceResult, PayInvoiceResult},
+};
+
+use super::{error::LightningError, Lightning};
+
+// timeout for a payment over /v2/router/send before lnd gives up
+const PAYMENT_TIMEOUT_SECONDS: u32 = 60;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Parser)]
+pub struct LndRestSettings {
+    #[clap(long, env = "MINT_LND_REST_URL")]
+    pub url: Option<String>,
+
+    /// hex encoded admin macaroon
+    #[clap(long, env = "MINT_LND_REST_MACAROON")]
+    pub macaroon: Option<String>,
+
+    /// PEM encoded tls cert. Can be omitted if lnd is running behind a trusted certificate
+    #[clap(long, env = "MINT_LND_REST_TLS_CERT")]
+    pub tls_cert: Option<String>,
+}
+
+impl LndRestSettings {
+    pub fn new(url: &str, macaroon: &str, tls_cert: Option<&str>) -> Self {
+        Self {
+            url: Some(url.to_owned()),
+            macaroon: Some(macaroon.to_owned()),
+            tls_cert: tls_cert.map(ToOwned::to_owned),
+        }
+    }
+}
+
+impl fmt::Display for LndRestSettings {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "url: {}", self.url.as_ref().unwrap())
+    }
+}
+
+#[derive(Clone)]
+pub struct LndRestLightning {
+    pub client: LndRestClient,
+}
+
+impl LndRestLightning {
+    pub fn new(url: String, macaroon: String, tls_cert: Option<String>) -> Self {
+        Self {
+            client: LndRestClient::new(&url, &macaroon, tls_cert.as_deref())
+                .expect("Can not create Lnd REST client"),
+        }
+    }
+}
+
+#[async_trait]
+impl Lightning for LndRestLightning {
+    async fn is_invoice_paid(&self, invoice: String) -> Result<bool, MokshaMintError> {
+        let decoded_invoice = self.decode_invoice(invoice).await?;
+        Ok(self
+            .client
+            .is_invoice_paid(&decoded_invoice.payment_hash().to_string())
+            .await?)
+    }
+
+    async fn create_invoice(&self, amount: u64) -> Result<CreateInvoiceResult, MokshaMintError> {
+        Ok(self.client.create_invoice(amount).await?)
+    }
+
+    async fn pay_invoice(
+        &self,
+        payment_request: String,
+        fee_reserve: u64,
+    ) -> Result<PayInvoiceResult, MokshaMintError> {
+        self.client
+            .pay_invoice(&payment_request, None, fee_reserve)
+            .await
+            .map_err(|err| MokshaMintError::PayInvoice(payment_request, err))
+    }
+
+    async fn pay_amountless_invoice(
+        &self,
+        payment_request: String,
+        amount_msat: u64,
+        fee_reserve: u64,
+    ) -> Result<PayInvoiceResult, MokshaMintError> {
+        self.client
+            .pay_invoice(&payment_request, Some(amount_msat), fee_reserve)
+            .await
+            .map_err(|err| MokshaMintError::PayInvoice(payment_request, err))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CreateInvoiceRequest {
+    value: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateInvoiceResponse {
+    /// base64 encoded payment hash
+    r_hash: String,
+    payment_request: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct InvoiceResponse {
+    state: String,
+}
+
+#[derive(Debug, Serialize)]
+struct SendPaymentRequest<'a> {
+    payment_request: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    amt_msat: Option<u64>,
+    /// the payment fails if the routing fee would exceed it
+    fee_limit_sat: u64,
+    timeout_seconds: u32,
+    no_inflight_updates: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SendPaymentResponse {
+    result: Option<Payment>,
+}
+
+// lnd encodes 64 bit integers as strings in its REST API
+#[derive(Debug, Deserialize)]
+struct Payment {
+    payment_hash: String,
+    status: String,
+    fee_sat: String,
+}
+
+#[derive(Clone)]
+pub struct LndRestClient {
+    macaroon: String,
+    lnd_url: Url,
+    reqwest_client: reqwest::Client,
+}
+
+impl LndRestClient {
+    pub fn new(
+        lnd_url: &str,
+        macaroon: &str,
+        tls_cert: Option<&str>,
+    ) -> Result<Self, LightningError> {
+        let lnd_url = Url::parse(lnd_url)?;
+        let mut builder = reqwest::Client::builder();
+        if let Some(tls_cert) = tls_cert {
+            builder =
+                builder.add_root_certificate(reqwest::Certificate::from_pem(tls_cert.as_bytes())?);
+        }
+
+        Ok(Self {
+            macaroon: macaroon.to_owned(),
+            lnd_url,
+            reqwest_client: builder.build()?,
+        })
+    }
+}
+
+impl LndRestClient {
+    pub async fn make_get(&self, endpoint: &str) -> Result<String, LightningError> {
+        let url = self.lnd_url.join(endpoint)?;
+        let response = self
+            .reqwest_client
+            .get(url)
+            .header("Grpc-Metadata-macaroon", &self.macaroon)
+            .send()
+            .await?;
+
+        Self::check_status(response).await
+    }
+
+    pub async fn make_post(
+        &self,
+        endpoint: &str,
+        body: &impl Serialize,
+    ) -> Result<String, LightningError> {
+        let url = self.lnd_url.join(endpoint)?;
+        let response = self
+            .reqwest_client
+            .post(url)
+            .header("Grpc-Metadata-macaroon", &self.macaroon)
+            .json(body)
+            .send()
+            .await?;
+
+        Self::check_status(response).await
+    }
+
+    async fn check_status(response: reqwest::Response) -> Result<String, LightningError> {
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(LightningError::NotFound);
+        }
+
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(LightningError::Unauthorized);
+        }
+
+        Ok(response.text().await?)
+    }
+}
+
+impl LndRestClient {
+    pub async fn create_invoice(&self, amount: u64) -> Result<CreateInvoiceResult, LightningError> {
+        let body = self
+            .make_post("v1/invoices", &CreateInvoiceRequest { value: amount })
+            .await?;
+        let response: CreateInvoiceResponse = serde_json::from_str(&body)?;
+
+        Ok(CreateInvoiceResult {
+            payment_hash: general_purpose::STANDARD
+                .decode(&response.r_hash)
+                .map_err(|_| LightningError::InvalidPaymentHash(response.r_hash))?,
+            payment_request: response.payment_request,
+        })
+    }
+
+    /// Pays the invoice with at most `fee_limit_sat` in routing fees
+    pub async fn pay_invoice(
+        &self,
+        bolt11: &str,
+        amount_msat: Option<u64>,
+        fee_limit_sat: u64,
+    ) -> Result<PayInvoiceResult, LightningError> {
+        let body = self
+            .make_post(
+                "v2/router/send",
+                &SendPaymentRequest {
+                    payment_request: bolt11,
+                    amt_msat: amount_msat,
+                    fee_limit_sat,
+                    timeout_seconds: PAYMENT_TIMEOUT_SECONDS,
+                    no_inflight_updates: true,
+                },
+            )
+            .await?;
+
+        // the response is streamed as newline delimited json, the last update holds the final state
+        let last_update = body
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .last()
+            .ok_or(LightningError::PaymentFailed)?;
+
+        match serde_json::from_str::<SendPaymentResponse>(last_update)?.result {
+            Some(payment) if payment.status == "SUCCEEDED" => Ok(PayInvoiceResult {
+                total_fees: Some(
+                    payment
+                        .fee_sat
+                        .parse()
+                        .map_err(|_| LightningError::InvalidFee(payment.fee_sat))?,
+                ),
+                payment_hash: payment.payment_hash,
+            }),
+            _ => Err(LightningError::PaymentFailed),
+        }
+    }
+
+    pub async fn is_invoice_paid(&self, payment_hash: &str) -> Result<bool, LightningError> {
+        let body = self.make_get(&format!("v1/invoice/{payment_hash}")).await?;
+
+        Ok(serde_json::from_str::<InvoiceResponse>(&body)?.state == "SETTLED")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        extract::Path,
+        http::{HeaderMap, StatusCode},
+        routing::{get, post},
+        Json, Router,
+    };
+    use base64::{engine::general_purpose, Engine as _};
+
+    use crate::lightning::{error::LightningError, lndrest::LndRestLightning, Lightning};
+
+    const PAYMENT_HASH: &str = "ac2d2e2a1c0e7b6a9f9f0b5c1b5a0e4d8e3c2b1a09f8e7d6c5b4a39281706f5e";
+    const MACAROON: &str = "0201036c6e64";
+
+    fn authorized(headers: &HeaderMap) -> bool {
+        headers
+            .get("grpc-metadata-macaroon")
+            .is_some_and(|value| value == MACAROON)
+    }
+
+    async fn start_lnd_mock() -> anyhow::Result<String> {
+        let app = Router::new()
+            .route(
+                "/v1/invoices",
+                post(
+                    |headers: HeaderMap, Json(body): Json<serde_json::Value>| async move {
+                        if !authorized(&headers) {
+                            return Err(StatusCode::UNAUTHORIZED);
+                        }
+                        let hash = hex::decode(PAYMENT_HASH).unwrap();
+                        Ok(Json(serde_json::json!({
+                            "r_hash": general_purpose::STANDARD.encode(hash),
+                            "payment_request": format!("lnbc{}invoice", body["value"]),
+                            "add_index": "1",
+                        })))
+                    },
+                ),
+            )
+            .route(
+                "/v1/invoice/:hash",
+                get(|Path(hash): Path<String>| async move {
+                    let state = if hash == PAYMENT_HASH {
+                        "SETTLED"
+                    } else {
+                        "OPEN"
+                    };
+                    Json(serde_json::json!({ "state": state }))
+                }),
+            )
+            .route(
+                "/v2/router/send",
+                post(|Json(body): Json<serde_json::Value>| async move {
+                    // payments without a fee limit of 10 sat fail
+                    let (status, fee_sat) = match body["payment_request"].as_str() {
+                        _ if body["fee_limit_sat"] != 10 => ("FAILED", "0"),
+                        Some("lnbcpayable") => ("SUCCEEDED", "2"),
+                        Some("lnbcinvalidfee") => ("SUCCEEDED", "two"),
+                        _ => ("FAILED", "0"),
+                    };
+                    let update = serde_json::json!({
+                        "result": {
+                            "payment_hash": PAYMENT_HASH,
+                            "status": status,
+                            "fee_sat": fee_sat,
+                        }
+                    });
+                    format!("{update}\n")
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Ok(format!("http://{addr}"))
+    }
+
+    #[tokio::test]
+    async fn test_create_invoice() -> anyhow::Result<()> {
+        let url = start_lnd_mock().await?;
+        let lightning = LndRestLightning::new(url, MACAROON.to_owned(), None);
+
+        let result = lightning.create_invoice(21).await?;
+        assert_eq!(hex::decode(PAYMENT_HASH)?, result.payment_hash);
+        assert_eq!("lnbc21invoice", result.payment_request);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_invoice_unauthorized() -> anyhow::Result<()> {
+        let url = start_lnd_mock().await?;
+        let lightning = LndRestLightning::new(url, "wrong".to_owned(), None);
+
+        assert!(lightning.create_invoice(21).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_is_invoice_paid() -> anyhow::Result<()> {
+        let url = start_lnd_mock().await?;
+        let lightning = LndRestLightning::new(url, MACAROON.to_owned(), None);
+
+        assert!(lightning.client.is_invoice_paid(PAYMENT_HASH).await?);
+        assert!(!lightning.client.is_invoice_paid("unknown").await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pay_invoice() -> anyhow::Result<()> {
+        let url = start_lnd_mock().await?;
+        let lightning = LndRestLightning::new(url, MACAROON.to_owned(), None);
+
+        let result = lightning.pay_invoice("lnbcpayable".to_owned(), 10).await?;
+        assert_eq!(PAYMENT_HASH, result.payment_hash);
+        assert_eq!(Some(2), result.total_fees);
+
+        assert!(lightning
+            .pay_invoice("lnbcfailing".to_owned(), 10)
+            .await
+            .is_err());
+        assert!(lightning
+            .pay_invoice("lnbcpayable".to_owned(), 5)
+            .await
+            .is_err());
+        assert!(matches!(
+            lightning.client.pay_invoice("lnbcinvalidfee", None, 10).await,
+            Err(LightningError::InvalidFee(fee)) if fee == "two"
+        ));
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
     error::MokshaMintError,
-    model::{CreateInvoiceResult, PayInvoiceResult},
+    model::{CreateInvoiceResult, PayInvoiceResult, ProbeResult},
 };
 use async_trait::async_trait;
 use lightning_invoice::Bolt11Invoice as LNInvoice;
@@ -8,10 +8,13 @@ use serde::{Deserialize, Serialize};
 use std::fmt::{self, Formatter};
 
 pub mod alby;
+pub mod btcpay;
 pub mod cln;
 pub mod error;
 pub mod lnbits;
 pub mod lnd;
+pub mod lndrest;
+pub mod phoenixd;
 pub mod strike;
 
 #[cfg(test)]
@@ -19,8 +22,10 @@ use mockall::automock;
 use std::str::FromStr;
 
 use self::lnd::LndLightningSettings;
+use self::lndrest::LndRestSettings;
 use self::{
-    alby::AlbyLightningSettings, cln::ClnLightningSettings, lnbits::LnbitsLightningSettings,
+    alby::AlbyLightningSettings, btcpay::BTCPaySettings, cln::ClnLightningSettings,
+    lnbits::LnbitsLightningSettings, phoenixd::PhoenixdLightningSettings,
     strike::StrikeLightningSettings,
 };
 
@@ -31,7 +36,10 @@ pub enum LightningType {
     Alby(AlbyLightningSettings),
     Strike(StrikeLightningSettings),
     Lnd(LndLightningSettings),
+    LndRest(LndRestSettings),
     Cln(ClnLightningSettings),
+    Phoenixd(PhoenixdLightningSettings),
+    BTCPay(BTCPaySettings),
 }
 
 impl fmt::Display for LightningType {
@@ -41,21 +49,69 @@ impl fmt::Display for LightningType {
             Self::Alby(settings) => write!(f, "Alby: {}", settings),
             Self::Strike(settings) => write!(f, "Strike: {}", settings),
             Self::Lnd(settings) => write!(f, "Lnd: {}", settings),
+            Self::LndRest(settings) => write!(f, "LndRest: {}", settings),
             Self::Cln(settings) => write!(f, "Cln: {}", settings),
+            Self::Phoenixd(settings) => write!(f, "Phoenixd: {}", settings),
+            Self::BTCPay(settings) => write!(f, "BTCPay: {}", settings),
         }
     }
 }
 
+impl LightningType {
+    /// Returns true if the backend implements [`Lightning::probe_route`]
+    pub const fn supports_probe(&self) -> bool {
+        matches!(self, Self::Lnd(_))
+    }
+
+    /// Returns true if the backend implements [`Lightning::pay_partial_invoice`]
+    pub const fn supports_mpp(&self) -> bool {
+        matches!(self, Self::Lnd(_))
+    }
+}
+
 #[cfg_attr(test, automock)]
 #[async_trait]
 pub trait Lightning: Send + Sync {
     async fn is_invoice_paid(&self, invoice: String) -> Result<bool, MokshaMintError>;
     async fn create_invoice(&self, amount: u64) -> Result<CreateInvoiceResult, MokshaMintError>;
+    /// Pays the invoice. Backends that can limit the routing fee don't pay more than
+    /// `fee_reserve` (sat) in fees.
     async fn pay_invoice(
         &self,
         payment_request: String,
+        fee_reserve: u64,
     ) -> Result<PayInvoiceResult, MokshaMintError>;
 
+    /// Pays an invoice that doesn't specify an amount. Backends that can't pay amountless invoices keep the default.
+    async fn pay_amountless_invoice(
+        &self,
+        payment_request: String,
+        _amount_msat: u64,
+        _fee_reserve: u64,
+    ) -> Result<PayInvoiceResult, MokshaMintError> {
+        Err(MokshaMintError::AmountlessInvoiceNotSupported(
+            payment_request,
+        ))
+    }
+
+    /// Pays a part of a multi-path payment (nut15). Backends that can't send partial payments keep the default.
+    async fn pay_partial_invoice(
+        &self,
+        _payment_request: String,
+        _amount_msat: u64,
+    ) -> Result<PayInvoiceResult, MokshaMintError> {
+        Err(MokshaMintError::MppNotSupported)
+    }
+
+    /// Probes a route for the payment without paying it. Returns None for backends that can't probe.
+    async fn probe_route(
+        &self,
+        _payment_request: String,
+        _amount_msat: u64,
+    ) -> Result<Option<ProbeResult>, MokshaMintError> {
+        Ok(None)
+    }
+
     async fn decode_invoice(&self, payment_request: String) -> Result<LNInvoice, MokshaMintError> {
         LNInvoice::from_str(&payment_request)
             .map_err(|err| MokshaMintError::DecodeInvoice(payment_request, err))
@@ -0,0 +1,293 @@
+use std::fmt::{self, Formatter};
+
+use async_trait::async_trait;
+
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    error::MokshaMintError,
+    model::{CreateInvoiceResult, PayInvoiceResult},
+};
+
+use super::{error::LightningError, Lightning};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Parser)]
+pub struct PhoenixdLightningSettings {
+    #[clap(long, env = "MINT_PHOENIXD_URL")]
+    pub url: Option<String>,
+    #[clap(long, env = "MINT_PHOENIXD_PASSWORD")]
+    pub password: Option<String>,
+}
+
+impl PhoenixdLightningSettings {
+    pub fn new(url: &str, password: &str) -> Self {
+        Self {
+            url: Some(url.to_owned()),
+            password: Some(password.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for PhoenixdLightningSettings {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "url: {}", self.url.as_ref().unwrap())
+    }
+}
+
+#[derive(Clone)]
+pub struct PhoenixdLightning {
+    pub client: PhoenixdClient,
+}
+
+impl PhoenixdLightning {
+    pub fn new(url: String, password: String) -> Self {
+        Self {
+            client: PhoenixdClient::new(&url, &password).expect("Can not create Phoenixd client"),
+        }
+    }
+}
+
+#[async_trait]
+impl Lightning for PhoenixdLightning {
+    async fn is_invoice_paid(&self, invoice: String) -> Result<bool, MokshaMintError> {
+        let decoded_invoice = self.decode_invoice(invoice).await?;
+        Ok(self
+            .client
+            .is_invoice_paid(&decoded_invoice.payment_hash().to_string())
+            .await?)
+    }
+
+    async fn create_invoice(&self, amount: u64) -> Result<CreateInvoiceResult, MokshaMintError> {
+        Ok(self.client.create_invoice(amount).await?)
+    }
+
+    async fn pay_invoice(
+        &self,
+        payment_request: String,
+        _fee_reserve: u64,
+    ) -> Result<PayInvoiceResult, MokshaMintError> {
+        self.client
+            .pay_invoice(&payment_request)
+            .await
+            .map_err(|err| MokshaMintError::PayInvoice(payment_request, err))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateInvoiceResponse {
+    payment_hash: String,
+    serialized: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PayInvoiceResponse {
+    payment_hash: Option<String>,
+    routing_fee_sat: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IncomingPaymentResponse {
+    is_paid: bool,
+}
+
+#[derive(Clone)]
+pub struct PhoenixdClient {
+    password: String,
+    phoenixd_url: Url,
+    reqwest_client: reqwest::Client,
+}
+
+impl PhoenixdClient {
+    pub fn new(phoenixd_url: &str, password: &str) -> Result<Self, LightningError> {
+        let phoenixd_url = Url::parse(phoenixd_url)?;
+        let reqwest_client = reqwest::Client::builder().build()?;
+
+        Ok(Self {
+            password: password.to_owned(),
+            phoenixd_url,
+            reqwest_client,
+        })
+    }
+}
+
+impl PhoenixdClient {
+    // phoenixd uses http basic auth with an empty username
+    pub async fn make_get(&self, endpoint: &str) -> Result<String, LightningError> {
+        let url = self.phoenixd_url.join(endpoint)?;
+        let response = self
+            .reqwest_client
+            .get(url)
+            .basic_auth("", Some(&self.password))
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(LightningError::NotFound);
+        }
+
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(LightningError::Unauthorized);
+        }
+
+        Ok(response.text().await?)
+    }
+
+    // phoenixd expects form encoded request bodies
+    pub async fn make_post(
+        &self,
+        endpoint: &str,
+        form: &[(&str, &str)],
+    ) -> Result<String, LightningError> {
+        let url = self.phoenixd_url.join(endpoint)?;
+        let response = self
+            .reqwest_client
+            .post(url)
+            .basic_auth("", Some(&self.password))
+            .form(form)
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(LightningError::NotFound);
+        }
+
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(LightningError::Unauthorized);
+        }
+
+        Ok(response.text().await?)
+    }
+}
+
+impl PhoenixdClient {
+    pub async fn create_invoice(&self, amount: u64) -> Result<CreateInvoiceResult, LightningError> {
+        let body = self
+            .make_post(
+                "createinvoice",
+                &[("amountSat", &amount.to_string()), ("description", "")],
+            )
+            .await?;
+        let response: CreateInvoiceResponse = serde_json::from_str(&body)?;
+
+        Ok(CreateInvoiceResult {
+            payment_hash: hex::decode(&response.payment_hash)
+                .map_err(|_| LightningError::InvalidPaymentHash(response.payment_hash))?,
+            payment_request: response.serialized,
+        })
+    }
+
+    pub async fn pay_invoice(&self, bolt11: &str) -> Result<PayInvoiceResult, LightningError> {
+        let body = self.make_post("payinvoice", &[("invoice", bolt11)]).await?;
+        // failed payments are returned without a payment hash
+        match serde_json::from_str::<PayInvoiceResponse>(&body)? {
+            PayInvoiceResponse {
+                payment_hash: Some(payment_hash),
+                routing_fee_sat,
+            } => Ok(PayInvoiceResult {
+                payment_hash,
+                total_fees: routing_fee_sat,
+            }),
+            _ => Err(LightningError::PaymentFailed),
+        }
+    }
+
+    pub async fn is_invoice_paid(&self, payment_hash: &str) -> Result<bool, LightningError> {
+        let body = self
+            .make_get(&format!("payments/incoming/{payment_hash}"))
+            .await?;
+
+        Ok(serde_json::from_str::<IncomingPaymentResponse>(&body)?.is_paid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        extract::Path,
+        http::{HeaderMap, StatusCode},
+        routing::{get, post},
+        Form, Json, Router,
+    };
+    use std::collections::HashMap;
+
+    use crate::lightning::{phoenixd::PhoenixdLightning, Lightning};
+
+    const PAYMENT_HASH: &str = "ac2d2e2a1c0e7b6a9f9f0b5c1b5a0e4d8e3c2b1a09f8e7d6c5b4a39281706f5e";
+
+    // basic auth header for an empty username and the password "password"
+    const AUTHORIZATION: &str = "Basic OnBhc3N3b3Jk";
+
+    fn authorized(headers: &HeaderMap) -> bool {
+        headers
+            .get("authorization")
+            .is_some_and(|value| value == AUTHORIZATION)
+    }
+
+    async fn start_phoenixd_mock() -> anyhow::Result<String> {
+        let app = Router::new()
+            .route(
+                "/createinvoice",
+                post(
+                    |headers: HeaderMap, Form(form): Form<HashMap<String, String>>| async move {
+                        if !authorized(&headers) {
+                            return Err(StatusCode::UNAUTHORIZED);
+                        }
+                        Ok(Json(serde_json::json!({
+                            "amountSat": form["amountSat"].parse::<u64>().unwrap(),
+                            "paymentHash": PAYMENT_HASH,
+                            "serialized": format!("lnbc{}invoice", form["amountSat"]),
+                        })))
+                    },
+                ),
+            )
+            .route(
+                "/payments/incoming/:hash",
+                get(|Path(hash): Path<String>| async move {
+                    Json(serde_json::json!({
+                        "paymentHash": hash,
+                        "isPaid": hash == PAYMENT_HASH,
+                        "receivedSat": 0,
+                    }))
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Ok(format!("http://{addr}"))
+    }
+
+    #[tokio::test]
+    async fn test_create_invoice() -> anyhow::Result<()> {
+        let url = start_phoenixd_mock().await?;
+        let lightning = PhoenixdLightning::new(url, "password".to_owned());
+
+        let result = lightning.create_invoice(21).await?;
+        assert_eq!(hex::decode(PAYMENT_HASH)?, result.payment_hash);
+        assert_eq!("lnbc21invoice", result.payment_request);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_invoice_unauthorized() -> anyhow::Result<()> {
+        let url = start_phoenixd_mock().await?;
+        let lightning = PhoenixdLightning::new(url, "wrong".to_owned());
+
+        assert!(lightning.create_invoice(21).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_is_invoice_paid() -> anyhow::Result<()> {
+        let url = start_phoenixd_mock().await?;
+        let lightning = PhoenixdLightning::new(url, "password".to_owned());
+
+        assert!(lightning.client.is_invoice_paid(PAYMENT_HASH).await?);
+        assert!(!lightning.client.is_invoice_paid("unknown").await?);
+        Ok(())
+    }
+}
@@ -93,6 +93,7 @@ impl Lightning for StrikeLightning {
     async fn pay_invoice(
         &self,
         payment_request: String,
+        _fee_reserve: u64,
     ) -> Result<PayInvoiceResult, MokshaMintError> {
         // strike doesn't return the payment_hash so we have to read the invoice into a Bolt11 and extract it
         let invoice = self.decode_invoice(payment_request.clone()).await?;
@@ -118,7 +119,8 @@ impl Lightning for StrikeLightning {
 
         Ok(PayInvoiceResult {
             payment_hash: hex::encode(payment_hash),
-            total_fees: 0, // FIXME return fees for strike
+            // strike doesn't report the fee of the payment
+            total_fees: None,
         })
     }
 }
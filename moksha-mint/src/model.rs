@@ -1,3 +1,7 @@
+use moksha_core::{
+    blind::BlindedSignature,
+    primitives::{CurrencyUnit, PostMeltBolt11Response},
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -15,6 +19,40 @@ impl Invoice {
     }
 }
 
+/// Total amounts of ecash issued and redeemed by the mint
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IssuanceCounters {
+    pub issued: u64,
+    pub redeemed: u64,
+}
+
+impl IssuanceCounters {
+    /// amount of ecash currently in circulation
+    pub const fn outstanding(&self) -> u64 {
+        self.issued.saturating_sub(self.redeemed)
+    }
+}
+
+/// Response of a request with an idempotency key, returned again for a retry with the same key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotentResponse {
+    /// hash of the method, path and body of the request
+    pub request_hash: String,
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+/// Activation period of a keyset the mint has issued ecash with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeysetValidity {
+    pub keyset_id: String,
+    pub unit: CurrencyUnit,
+    /// unix timestamp of when the keyset became active
+    pub valid_from: u64,
+    /// unix timestamp of when the keyset was replaced. `None` while it is active
+    pub valid_to: Option<u64>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateInvoiceResult {
     pub payment_hash: Vec<u8>,
@@ -24,8 +62,36 @@ pub struct CreateInvoiceResult {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PayInvoiceResult {
     pub payment_hash: String,
-    /// total fees in sat
-    pub total_fees: u64,
+    /// total fees in sat, `None` if the backend doesn't report them
+    pub total_fees: Option<u64>,
+}
+
+/// Outcome of a bolt11 melt
+#[derive(Debug, Clone)]
+pub struct MeltBolt11Result {
+    pub paid: bool,
+    pub payment_hash: String,
+    pub change: Vec<BlindedSignature>,
+    /// amount in sats the payee received
+    pub amount_paid: u64,
+    /// lightning fee in sats reported by the backend, `None` if it doesn't report fees
+    pub fee_paid: Option<u64>,
+}
+
+impl From<MeltBolt11Result> for PostMeltBolt11Response {
+    fn from(result: MeltBolt11Result) -> Self {
+        let (amount_paid, fee_paid) = match result.paid {
+            true => (Some(result.amount_paid), result.fee_paid),
+            false => (None, None),
+        };
+        Self {
+            paid: result.paid,
+            payment_preimage: Some(result.payment_hash),
+            change: result.change,
+            amount_paid,
+            fee_paid,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,3 +103,10 @@ pub struct CreateInvoiceParams {
     pub webhook: Option<String>,
     pub internal: Option<bool>,
 }
+
+/// Outcome of a routing probe for an invoice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeResult {
+    Route { fee_msat: u64 },
+    NoRoute,
+}
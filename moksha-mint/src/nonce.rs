@@ -0,0 +1,228 @@
+//! Bounded store for nonces and idempotency keys.
+//!
+//! Keys are remembered for a fixed ttl. At most `capacity` keys are kept in memory. If the store
+//! is full, expired keys are dropped first and then the least recently used key is evicted. A key
+//! that has to be evicted before it expired is returned to the caller, which has to persist it
+//! until it expires and then remove it with [`NonceStore::evict`]. The key stays in memory until
+//! then, so a retry within the ttl is never mistaken for a new request.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+#[derive(Debug)]
+pub struct NonceStore {
+    capacity: usize,
+    ttl_secs: u64,
+    inner: Mutex<Entries>,
+}
+
+/// A key that was evicted from memory before it expired
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvictedNonce {
+    pub key: String,
+    pub expires_at: u64,
+}
+
+/// Result of [`NonceStore::insert`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NonceInsert {
+    /// the key is stored and has not expired yet
+    AlreadyUsed,
+    /// the key has been stored. Contains the key that has to be persisted and evicted to make
+    /// room, because it has not expired yet
+    Inserted(Option<EvictedNonce>),
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// key -> (expires_at, tick of the last use)
+    keys: HashMap<String, (u64, u64)>,
+    /// tick of the last use -> key. The first entry is the least recently used key
+    lru: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl Entries {
+    fn touch(&mut self, key: &str) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some((_, last_use)) = self.keys.get_mut(key) {
+            self.lru.remove(last_use);
+            *last_use = tick;
+            self.lru.insert(tick, key.to_owned());
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Option<u64> {
+        let (expires_at, last_use) = self.keys.remove(key)?;
+        self.lru.remove(&last_use);
+        Some(expires_at)
+    }
+
+    fn remove_expired(&mut self, now: u64) {
+        let expired = self
+            .keys
+            .iter()
+            .filter(|(_, (expires_at, _))| *expires_at <= now)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in expired {
+            self.remove(&key);
+        }
+    }
+}
+
+impl NonceStore {
+    pub fn new(capacity: usize, ttl_secs: u64) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ttl_secs,
+            inner: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// Returns true if the key is stored and not expired at `now`. Marks the key as used.
+    pub fn contains(&self, key: &str, now: u64) -> bool {
+        let mut entries = self.inner.lock().expect("nonce store poisoned");
+        match entries.keys.get(key) {
+            Some((expires_at, _)) if *expires_at > now => {
+                entries.touch(key);
+                true
+            }
+            Some(_) => {
+                entries.remove(key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Stores the key until `now` + ttl unless it is already stored and not expired
+    pub fn insert(&self, key: &str, now: u64) -> NonceInsert {
+        let mut entries = self.inner.lock().expect("nonce store poisoned");
+        match entries.keys.get(key) {
+            Some((expires_at, _)) if *expires_at > now => {
+                entries.touch(key);
+                return NonceInsert::AlreadyUsed;
+            }
+            Some(_) => {
+                entries.remove(key);
+            }
+            None => {}
+        }
+
+        let mut evicted = None;
+        if entries.keys.len() >= self.capacity {
+            entries.remove_expired(now);
+        }
+        if entries.keys.len() >= self.capacity {
+            if let Some((_, lru_key)) = entries.lru.first_key_value() {
+                let (expires_at, _) = entries.keys.get(lru_key).expect("lru out of sync");
+                evicted = Some(EvictedNonce {
+                    key: lru_key.clone(),
+                    expires_at: *expires_at,
+                });
+            }
+        }
+
+        entries.tick += 1;
+        let tick = entries.tick;
+        entries
+            .keys
+            .insert(key.to_owned(), (now + self.ttl_secs, tick));
+        entries.lru.insert(tick, key.to_owned());
+        NonceInsert::Inserted(evicted)
+    }
+
+    /// Removes a key returned by [`NonceStore::insert`] after it has been persisted. A key that
+    /// has been stored again in the meantime is kept.
+    pub fn evict(&self, evicted: &EvictedNonce) {
+        let mut entries = self.inner.lock().expect("nonce store poisoned");
+        if entries
+            .keys
+            .get(&evicted.key)
+            .is_some_and(|(expires_at, _)| *expires_at == evicted.expires_at)
+        {
+            entries.remove(&evicted.key);
+        }
+    }
+
+    /// Removes a key, so it can be used again
+    pub fn release(&self, key: &str) {
+        self.inner.lock().expect("nonce store poisoned").remove(key);
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().expect("nonce store poisoned").keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EvictedNonce, NonceInsert, NonceStore};
+
+    #[test]
+    fn test_entries_expire_after_ttl() {
+        let store = NonceStore::new(10, 60);
+        assert_eq!(NonceInsert::Inserted(None), store.insert("nonce", 100));
+        assert!(store.contains("nonce", 159));
+        assert_eq!(NonceInsert::AlreadyUsed, store.insert("nonce", 159));
+
+        assert!(!store.contains("nonce", 160));
+        assert!(store.is_empty());
+        assert_eq!(NonceInsert::Inserted(None), store.insert("nonce", 160));
+    }
+
+    #[test]
+    fn test_capacity_eviction_is_lru() {
+        let store = NonceStore::new(2, 60);
+        store.insert("first", 100);
+        store.insert("second", 101);
+        // using the first key makes the second one the least recently used
+        assert!(store.contains("first", 102));
+
+        let evicted = EvictedNonce {
+            key: "second".to_owned(),
+            expires_at: 161,
+        };
+        assert_eq!(
+            NonceInsert::Inserted(Some(evicted.clone())),
+            store.insert("third", 103)
+        );
+        // the key is kept until the caller has persisted it
+        assert!(store.contains("second", 104));
+        store.evict(&evicted);
+        assert!(store.contains("first", 104));
+        assert!(!store.contains("second", 104));
+        assert!(store.contains("third", 104));
+        assert_eq!(2, store.len());
+    }
+
+    #[test]
+    fn test_released_key_can_be_used_again() {
+        let store = NonceStore::new(10, 60);
+        store.insert("nonce", 100);
+        store.release("nonce");
+        assert!(store.is_empty());
+        assert_eq!(NonceInsert::Inserted(None), store.insert("nonce", 101));
+    }
+
+    #[test]
+    fn test_expired_entries_are_evicted_first() {
+        let store = NonceStore::new(2, 60);
+        store.insert("first", 100);
+        store.insert("second", 150);
+        assert!(store.contains("first", 155));
+
+        // the first key expired, so the full store makes room without evicting a live key
+        assert_eq!(NonceInsert::Inserted(None), store.insert("third", 165));
+        assert!(store.contains("second", 166));
+        assert!(store.contains("third", 166));
+    }
+}
@@ -0,0 +1,91 @@
+use axum::{
+    extract::{Path, Request, State},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use bitcoin_hashes::{sha256, Hash};
+use moksha_core::primitives::{
+    GetLiabilitiesSnapshotResponse, PostMeltRefundRequest, PostMeltRefundResponse,
+};
+use std::str::FromStr;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{error::MokshaMintError, mint::Mint, routes::Json};
+
+/// Rejects requests that don't carry the configured admin token as bearer token
+pub async fn require_admin_token(
+    State(mint): State<Mint>,
+    headers: HeaderMap,
+    req: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match (token, mint.config.server.admin_token.as_deref()) {
+        (Some(token), Some(admin_token)) if tokens_match(token, admin_token) => {
+            Ok(next.run(req).await)
+        }
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+/// Compares the tokens in constant time. Both are hashed first, so the time doesn't depend on
+/// their lengths either.
+fn tokens_match(token: &str, admin_token: &str) -> bool {
+    let token = sha256::Hash::hash(token.as_bytes());
+    let admin_token = sha256::Hash::hash(admin_token.as_bytes());
+    token
+        .as_byte_array()
+        .iter()
+        .zip(admin_token.as_byte_array())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+#[utoipa::path(
+        get,
+        path = "/v1/admin/liabilities/snapshot",
+        responses(
+            (status = 200, description = "signed snapshot of the outstanding ecash", body = [GetLiabilitiesSnapshotResponse]),
+            (status = 401, description = "missing or invalid admin token")
+        ),
+    )]
+#[instrument(name = "get_liabilities_snapshot", skip(mint), err)]
+pub async fn get_liabilities_snapshot(
+    State(mint): State<Mint>,
+) -> Result<Json<GetLiabilitiesSnapshotResponse>, MokshaMintError> {
+    Ok(Json(mint.liabilities_snapshot().await?))
+}
+
+#[utoipa::path(
+        post,
+        path = "/v1/admin/melt/{quote_id}/refund",
+        request_body = PostMeltRefundRequest,
+        responses(
+            (status = 200, description = "refund a failed melt", body = [PostMeltRefundResponse]),
+            (status = 401, description = "missing or invalid admin token")
+        ),
+        params(
+            ("quote_id" = String, Path, description = "melt quote id"),
+        )
+    )]
+#[instrument(name = "post_melt_refund", skip(mint, request), err)]
+pub async fn post_melt_refund(
+    Path(quote_id): Path<String>,
+    State(mint): State<Mint>,
+    Json(request): Json<PostMeltRefundRequest>,
+) -> Result<Json<PostMeltRefundResponse>, MokshaMintError> {
+    let signatures = mint
+        .refund_melt_quote(
+            &Uuid::from_str(&quote_id)?,
+            &request.payment_hash,
+            &request.outputs,
+        )
+        .await?;
+    Ok(Json(PostMeltRefundResponse { signatures }))
+}
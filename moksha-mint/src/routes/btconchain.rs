@@ -1,6 +1,7 @@
 use axum::{
-    extract::{Path, State},
-    Json,
+    extract::{Path, Request, State},
+    middleware::Next,
+    response::Response,
 };
 use moksha_core::primitives::{
     BtcOnchainMeltQuote, BtcOnchainMintQuote, CurrencyUnit, MeltBtcOnchainState,
@@ -12,9 +13,23 @@ use tracing::{info, instrument};
 use uuid::Uuid;
 
 use crate::database::Database;
-use crate::{error::MokshaMintError, mint::Mint};
+use crate::{error::MokshaMintError, events::MintEvent, mint::Mint, routes::Json};
 use chrono::{Duration, Utc};
-use std::str::FromStr;
+use std::{collections::HashSet, str::FromStr};
+
+/// confirmation targets in blocks a melt quote is offered for, from fast to slow
+const MELT_CONFIRMATION_TARGETS: [u32; 3] = [1, 3, 6];
+
+/// Rejects onchain requests while the onchain backend is syncing, because it may report
+/// wrong payment states and fees until it reached the chain tip
+pub async fn require_onchain_synced(
+    State(mint): State<Mint>,
+    req: Request,
+    next: Next,
+) -> Result<Response, MokshaMintError> {
+    mint.check_onchain_synced().await?;
+    Ok(next.run(req).await)
+}
 
 #[utoipa::path(
         post,
@@ -29,12 +44,14 @@ pub async fn post_mint_quote_btconchain(
     State(mint): State<Mint>,
     Json(request): Json<PostMintQuoteBtcOnchainRequest>,
 ) -> Result<Json<PostMintQuoteBtcOnchainResponse>, MokshaMintError> {
-    let onchain_config = mint.config.btconchain_backend.unwrap_or_default();
+    let onchain_config = mint.config.btconchain_backend.clone().unwrap_or_default();
 
     if request.unit != CurrencyUnit::Sat {
         return Err(MokshaMintError::CurrencyNotSupported(request.unit));
     }
 
+    mint.check_min_mint_amount(request.amount)?;
+
     if request.amount < onchain_config.min_amount {
         return Err(MokshaMintError::InvalidAmount(format!(
             "amount is too low. Min amount is {}",
@@ -50,12 +67,8 @@ pub async fn post_mint_quote_btconchain(
     }
 
     let quote_id = Uuid::new_v4();
-    let address = mint
-        .onchain
-        .as_ref()
-        .expect("onchain backend not configured")
-        .new_address()
-        .await?;
+    let mut tx = mint.db.begin_tx().await?;
+    let address = mint.new_onchain_address(&mut tx).await?;
 
     let quote = BtcOnchainMintQuote {
         quote_id,
@@ -66,7 +79,6 @@ pub async fn post_mint_quote_btconchain(
         state: MintBtcOnchainState::Unpaid,
     };
 
-    let mut tx = mint.db.begin_tx().await?;
     mint.db.add_onchain_mint_quote(&mut tx, &quote).await?;
     tx.commit().await?;
     Ok(Json(quote.into()))
@@ -89,25 +101,14 @@ pub async fn get_mint_quote_btconchain(
 ) -> Result<Json<PostMintQuoteBtcOnchainResponse>, MokshaMintError> {
     info!("get_quote onchain: {}", quote_id);
 
-    let mut tx = mint.db.begin_tx().await?;
+    let mut tx = mint.db.begin_read_tx().await?;
     let quote = mint
         .db
         .get_onchain_mint_quote(&mut tx, &Uuid::from_str(quote_id.as_str())?)
         .await?;
     tx.commit().await?;
 
-    let min_confs = mint
-        .config
-        .btconchain_backend
-        .unwrap_or_default()
-        .min_confirmations;
-
-    let paid = mint
-        .onchain
-        .as_ref()
-        .expect("onchain backend not configured")
-        .is_paid(&quote.address, quote.amount, min_confs)
-        .await?;
+    let paid = mint.is_onchain_mint_quote_paid(&quote).await?;
 
     // FIXME compute correct state
     let state = match paid {
@@ -132,6 +133,14 @@ pub async fn post_mint_btconchain(
     Json(request): Json<PostMintBtcOnchainRequest>,
 ) -> Result<Json<PostMintBtcOnchainResponse>, MokshaMintError> {
     let mut tx = mint.db.begin_tx().await?;
+    let old_quote = &mint
+        .db
+        .get_onchain_mint_quote(&mut tx, &Uuid::from_str(request.quote.as_str())?)
+        .await?;
+    if !mint.is_onchain_mint_quote_paid(old_quote).await? {
+        return Err(MokshaMintError::BtcOnchainNotPaidYet);
+    }
+
     let signatures = mint
         .mint_tokens(
             &mut tx,
@@ -143,11 +152,6 @@ pub async fn post_mint_btconchain(
         )
         .await?;
 
-    let old_quote = &mint
-        .db
-        .get_onchain_mint_quote(&mut tx, &Uuid::from_str(request.quote.as_str())?)
-        .await?;
-
     mint.db
         .update_onchain_mint_quote(
             &mut tx,
@@ -158,6 +162,12 @@ pub async fn post_mint_btconchain(
         )
         .await?;
     tx.commit().await?;
+
+    mint.emit_event(MintEvent::MintQuotePaid {
+        quote: request.quote,
+        payment_method: PaymentMethod::BtcOnchain,
+        amount: old_quote.amount,
+    });
     Ok(Json(PostMintBtcOnchainResponse { signatures }))
 }
 
@@ -174,11 +184,12 @@ pub async fn post_melt_quote_btconchain(
     State(mint): State<Mint>,
     Json(melt_request): Json<PostMeltQuoteBtcOnchainRequest>,
 ) -> Result<Json<Vec<PostMeltQuoteBtcOnchainResponse>>, MokshaMintError> {
-    let PostMeltQuoteBtcOnchainRequest {
-        address,
-        amount,
-        unit,
-    } = melt_request;
+    let unit = melt_request.unit.clone();
+    let outputs = melt_request.into_outputs();
+    let amount = outputs
+        .iter()
+        .try_fold(0u64, |total, output| total.checked_add(output.amount))
+        .ok_or(MokshaMintError::AmountOverflow)?;
 
     let onchain_config = mint.config.btconchain_backend.unwrap_or_default();
 
@@ -186,7 +197,20 @@ pub async fn post_melt_quote_btconchain(
         return Err(MokshaMintError::CurrencyNotSupported(unit));
     }
 
-    if amount < onchain_config.min_amount {
+    let addresses = outputs
+        .iter()
+        .map(|output| output.address.as_str())
+        .collect::<HashSet<_>>();
+    if addresses.len() != outputs.len() {
+        return Err(MokshaMintError::InvalidAmount(
+            "every output needs a different address".to_owned(),
+        ));
+    }
+
+    if outputs
+        .iter()
+        .any(|output| output.amount < onchain_config.min_amount)
+    {
         return Err(MokshaMintError::InvalidAmount(format!(
             "amount is too low. Min amount is {}",
             onchain_config.min_amount
@@ -200,31 +224,50 @@ pub async fn post_melt_quote_btconchain(
         )));
     }
 
-    let fee_response = mint
+    let onchain = mint
         .onchain
         .as_ref()
-        .expect("onchain backend not configured")
-        .estimate_fee(&address, amount)
-        .await?;
-
-    info!("post_melt_quote_onchain fee_reserve: {:#?}", &fee_response);
-
-    let quote = BtcOnchainMeltQuote {
-        quote_id: Uuid::new_v4(),
-        address,
-        amount,
-        fee_total: fee_response.fee_in_sat,
-        fee_sat_per_vbyte: fee_response.sat_per_vbyte,
-        expiry: quote_onchain_expiry(),
-        state: MeltBtcOnchainState::Unpaid,
-        description: Some(format!("{} sat per vbyte", fee_response.sat_per_vbyte)),
-    };
+        .expect("onchain backend not configured");
+
+    // one quote per confirmation target. Targets that don't lower the fee are no real tradeoff
+    let mut quotes: Vec<BtcOnchainMeltQuote> = Vec::new();
+    for target_conf in MELT_CONFIRMATION_TARGETS {
+        let fee_response = onchain.estimate_fee(&outputs, target_conf).await?;
+        info!(
+            "post_melt_quote_onchain fee_reserve for {} blocks: {:#?}",
+            target_conf, &fee_response
+        );
+
+        if quotes
+            .last()
+            .is_some_and(|quote| quote.fee_total <= fee_response.fee_in_sat)
+        {
+            continue;
+        }
+
+        quotes.push(BtcOnchainMeltQuote {
+            quote_id: Uuid::new_v4(),
+            address: outputs[0].address.clone(),
+            amount,
+            fee_total: fee_response.fee_in_sat,
+            fee_sat_per_vbyte: fee_response.sat_per_vbyte,
+            expiry: quote_onchain_expiry(),
+            state: MeltBtcOnchainState::Unpaid,
+            description: Some(format!(
+                "confirmation within {} blocks, {} sat per vbyte",
+                target_conf, fee_response.sat_per_vbyte
+            )),
+            outputs: outputs.clone(),
+        });
+    }
 
     let mut tx = mint.db.begin_tx().await?;
-    mint.db.add_onchain_melt_quote(&mut tx, &quote).await?;
+    for quote in quotes.iter() {
+        mint.db.add_onchain_melt_quote(&mut tx, quote).await?;
+    }
     tx.commit().await?;
 
-    Ok(Json(vec![quote.into()]))
+    Ok(Json(quotes.into_iter().map(Into::into).collect()))
 }
 
 #[utoipa::path(
@@ -266,6 +309,15 @@ pub async fn get_melt_quote_btconchain(
                 },
             )
             .await?;
+        tx.commit().await?;
+
+        if quote.state != MeltBtcOnchainState::Paid {
+            mint.emit_event(MintEvent::OnchainTxConfirmed {
+                quote: quote.quote_id.to_string(),
+                address: quote.address.clone(),
+                amount: quote.amount,
+            });
+        }
     }
 
     Ok(Json(BtcOnchainMeltQuote { state, ..quote }.into()))
@@ -304,12 +356,25 @@ pub async fn post_melt_btconchain(
             &mut tx,
             &BtcOnchainMeltQuote {
                 state: state.clone(),
-                ..quote
+                ..quote.clone()
             },
         )
         .await?;
     tx.commit().await?;
 
+    mint.emit_event(MintEvent::MeltCompleted {
+        quote: quote.quote_id.to_string(),
+        payment_method: PaymentMethod::BtcOnchain,
+        amount: quote.amount,
+    });
+    if paid {
+        mint.emit_event(MintEvent::OnchainTxConfirmed {
+            quote: quote.quote_id.to_string(),
+            address: quote.address,
+            amount: quote.amount,
+        });
+    }
+
     Ok(Json(PostMeltBtcOnchainResponse {
         state,
         txid: Some(txid),
@@ -327,11 +392,19 @@ async fn is_onchain_paid(
         .unwrap_or_default()
         .min_confirmations;
 
-    mint.onchain
+    let onchain = mint
+        .onchain
         .as_ref()
-        .expect("onchain backend not configured")
-        .is_paid(&quote.address, quote.amount, min_confs)
-        .await
+        .expect("onchain backend not configured");
+    for output in quote.outputs.iter() {
+        if !onchain
+            .is_paid(&output.address, output.amount, min_confs)
+            .await?
+        {
+            return Ok(false);
+        }
+    }
+    Ok(true)
 }
 
 fn quote_onchain_expiry() -> u64 {
@@ -1,29 +1,124 @@
 use axum::{
-    extract::{Path, State},
-    Json,
+    body::{to_bytes, Body},
+    extract::{Path, Request, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
 };
+use bitcoin_hashes::{sha256, Hash};
 use moksha_core::{
-    keyset::Keysets,
+    keyset::{derive_secret_key, Keyset, Keysets, MintKeyset},
     primitives::{
-        Bolt11MeltQuote, Bolt11MintQuote, CurrencyUnit, KeyResponse, KeysResponse,
-        MintInfoResponse, Nuts, PaymentMethod, PostMeltBolt11Request, PostMeltBolt11Response,
-        PostMeltQuoteBolt11Request, PostMeltQuoteBolt11Response, PostMintBolt11Request,
-        PostMintBolt11Response, PostMintQuoteBolt11Request, PostMintQuoteBolt11Response,
-        PostSwapRequest, PostSwapResponse,
+        Bolt11MeltQuote, Bolt11MintQuote, CurrencyUnit, KeyResponse, KeysResponse, MeltProbeState,
+        MintInfoResponse, Nut15, Nut17, Nut4, Nut5, Nut7, Nut9, Nuts, PaymentMethod,
+        PaymentMethodConfig, PostCheckStateRequest, PostCheckStateResponse, PostMeltBolt11Request,
+        PostMeltBolt11Response, PostMeltProbeBolt11Response, PostMeltQuoteBolt11Request,
+        PostMeltQuoteBolt11Response, PostMintBolt11Request, PostMintBolt11Response,
+        PostMintQuoteBolt11Request, PostMintQuoteBolt11Response, PostRestoreRequest,
+        PostRestoreResponse, PostSwapRequest, PostSwapResponse,
     },
+    spent_digest::SpentDigest,
 };
-use tracing::{debug, instrument};
+use serde::Serialize;
+use tracing::{debug, instrument, warn};
 use uuid::Uuid;
 
 use crate::database::Database;
 use crate::{
-    config::{BtcOnchainConfig, MintConfig},
+    config::{BtcOnchainConfig, MintConfig, QuoteConfig},
     error::MokshaMintError,
-    mint::Mint,
+    events::MintEvent,
+    lightning::LightningType,
+    mint::{Mint, MAX_CHECKSTATE_INPUTS, MAX_RESTORE_OUTPUTS},
+    model::{IdempotentResponse, ProbeResult},
+    routes::Json,
 };
-use chrono::{Duration, Utc};
+use chrono::Utc;
 use std::str::FromStr;
 
+/// Header with a key chosen by the wallet that makes retries of a POST request safe
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// longest idempotency key that is accepted
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 128;
+
+/// longest request body that is buffered for an idempotency key, the default body limit of axum
+const MAX_IDEMPOTENT_BODY_LEN: usize = 2 * 1024 * 1024;
+
+/// Makes retries of mint, melt and swap requests safe. The response of a successful request with
+/// an idempotency key is stored for the nonce ttl and returned again for a retry with the same key,
+/// so e.g. a retried quote request doesn't create a second quote. A retry that arrives while the
+/// first request is still running is rejected and a key that is reused for a different request
+/// fails. The key is released if the request fails, so the wallet can retry it with the same key.
+/// Requests without the header are not checked.
+pub async fn replay_idempotent_request(
+    State(mint): State<Mint>,
+    headers: HeaderMap,
+    req: Request,
+    next: Next,
+) -> Result<Response, MokshaMintError> {
+    let Some(key) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(next.run(req).await);
+    };
+    let key = key
+        .to_str()
+        .ok()
+        .filter(|key| !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LEN)
+        .ok_or(MokshaMintError::InvalidIdempotencyKey)?;
+    let key = format!("idempotency:{key}");
+
+    let (parts, body) = req.into_parts();
+    let body = to_bytes(body, MAX_IDEMPOTENT_BODY_LEN)
+        .await
+        .map_err(|err| MokshaMintError::InvalidRequestBody(err.to_string()))?;
+    let request_hash = sha256::Hash::hash(
+        &[
+            parts.method.as_str().as_bytes(),
+            parts.uri.path().as_bytes(),
+            &body[..],
+        ]
+        .concat(),
+    )
+    .to_string();
+
+    if let Some(stored) = mint.get_idempotent_response(&key).await? {
+        if stored.request_hash != request_hash {
+            return Err(MokshaMintError::IdempotencyKeyMismatch);
+        }
+        let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+        return Ok((status, [(CONTENT_TYPE, "application/json")], stored.body).into_response());
+    }
+
+    // rejects a retry while the first request is running
+    mint.use_nonce(&key).await?;
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if !response.status().is_success() {
+        mint.release_nonce(&key).await?;
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, usize::MAX)
+        .await
+        .map_err(|err| MokshaMintError::InvalidRequestBody(err.to_string()))?;
+    let stored = IdempotentResponse {
+        request_hash,
+        status: parts.status.as_u16(),
+        body: body.to_vec(),
+    };
+    // the request succeeded, so its response is returned even if it can't be stored
+    if let Err(err) = mint.store_idempotent_response(&key, &stored).await {
+        warn!(
+            "failed to store the response of idempotency key {}: {}",
+            key, err
+        );
+    }
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
 #[utoipa::path(
         post,
         path = "/v1/swap",
@@ -37,8 +132,12 @@ pub async fn post_swap(
     State(mint): State<Mint>,
     Json(swap_request): Json<PostSwapRequest>,
 ) -> Result<Json<PostSwapResponse>, MokshaMintError> {
+    let keyset = match swap_request.outputs.first() {
+        Some(output) => mint.keyset_by_id(&output.id)?,
+        None => &mint.keyset,
+    };
     let response = mint
-        .swap(&swap_request.inputs, &swap_request.outputs, &mint.keyset)
+        .swap(&swap_request.inputs, &swap_request.outputs, keyset)
         .await?;
 
     Ok(Json(PostSwapResponse {
@@ -46,6 +145,58 @@ pub async fn post_swap(
     }))
 }
 
+#[utoipa::path(
+        post,
+        path = "/v1/checkstate",
+        request_body = PostCheckStateRequest,
+        responses(
+            (status = 200, description = "post checkstate", body = [PostCheckStateResponse])
+        ),
+    )]
+#[instrument(name = "post_checkstate", skip_all, err)]
+pub async fn post_checkstate(
+    State(mint): State<Mint>,
+    Json(request): Json<PostCheckStateRequest>,
+) -> Result<Json<PostCheckStateResponse>, MokshaMintError> {
+    Ok(Json(PostCheckStateResponse {
+        states: mint.check_state(&request.ys).await?,
+    }))
+}
+
+#[utoipa::path(
+        get,
+        path = "/v1/spent-digest",
+        responses(
+            (status = 200, description = "get spent digest", body = [SpentDigest])
+        ),
+    )]
+#[instrument(name = "get_spent_digest", skip_all, err)]
+pub async fn get_spent_digest(
+    State(mint): State<Mint>,
+) -> Result<Json<SpentDigest>, MokshaMintError> {
+    Ok(Json(mint.spent_digest().await?))
+}
+
+#[utoipa::path(
+        post,
+        path = "/v1/restore",
+        request_body = PostRestoreRequest,
+        responses(
+            (status = 200, description = "post restore", body = [PostRestoreResponse])
+        ),
+    )]
+#[instrument(name = "post_restore", skip_all, err)]
+pub async fn post_restore(
+    State(mint): State<Mint>,
+    Json(request): Json<PostRestoreRequest>,
+) -> Result<Json<PostRestoreResponse>, MokshaMintError> {
+    let (outputs, signatures) = mint.restore(&request.outputs).await?.into_iter().unzip();
+    Ok(Json(PostRestoreResponse {
+        outputs,
+        signatures,
+    }))
+}
+
 #[utoipa::path(
         get,
         path = "/v1/keys",
@@ -54,14 +205,17 @@ pub async fn post_swap(
         )
     )]
 #[instrument(skip(mint), err)]
-pub async fn get_keys(State(mint): State<Mint>) -> Result<Json<KeysResponse>, MokshaMintError> {
-    Ok(Json(KeysResponse {
-        keysets: vec![KeyResponse {
-            id: mint.keyset.keyset_id.clone(),
-            unit: CurrencyUnit::Sat,
-            keys: mint.keyset.public_keys,
-        }],
-    }))
+pub async fn get_keys(
+    headers: HeaderMap,
+    State(mint): State<Mint>,
+) -> Result<Response, MokshaMintError> {
+    cached_response(
+        &headers,
+        mint.config.server.keys_cache_max_age,
+        &KeysResponse {
+            keysets: mint.keysets.iter().map(key_response).collect(),
+        },
+    )
 }
 
 #[utoipa::path(
@@ -76,20 +230,31 @@ pub async fn get_keys(State(mint): State<Mint>) -> Result<Json<KeysResponse>, Mo
     )]
 #[instrument(skip(mint), err)]
 pub async fn get_keys_by_id(
+    headers: HeaderMap,
     Path(id): Path<String>,
     State(mint): State<Mint>,
-) -> Result<Json<KeysResponse>, MokshaMintError> {
-    if id != mint.keyset.keyset_id {
-        return Err(MokshaMintError::KeysetNotFound(id));
-    }
+) -> Result<Response, MokshaMintError> {
+    // proofs of the legacy keyset can still be redeemed, so its keys are served as well
+    let keyset = match mint.keyset_by_id(&id) {
+        Err(_) if mint.keyset_legacy.keyset_id == id => &mint.keyset_legacy,
+        keyset => keyset?,
+    };
 
-    Ok(Json(KeysResponse {
-        keysets: vec![KeyResponse {
-            id: mint.keyset.keyset_id.clone(),
-            unit: CurrencyUnit::Sat,
-            keys: mint.keyset.public_keys,
-        }],
-    }))
+    cached_response(
+        &headers,
+        mint.config.server.keys_cache_max_age,
+        &KeysResponse {
+            keysets: vec![key_response(keyset)],
+        },
+    )
+}
+
+fn key_response(keyset: &MintKeyset) -> KeyResponse {
+    KeyResponse {
+        id: keyset.keyset_id.clone(),
+        unit: keyset.unit.clone(),
+        keys: keyset.public_keys.clone(),
+    }
 }
 
 #[utoipa::path(
@@ -100,12 +265,85 @@ pub async fn get_keys_by_id(
         ),
     )]
 #[instrument(skip(mint), err)]
-pub async fn get_keysets(State(mint): State<Mint>) -> Result<Json<Keysets>, MokshaMintError> {
-    Ok(Json(Keysets::new(
-        mint.keyset.keyset_id,
-        CurrencyUnit::Sat,
-        true,
-    )))
+pub async fn get_keysets(
+    headers: HeaderMap,
+    State(mint): State<Mint>,
+) -> Result<Response, MokshaMintError> {
+    let mut tx = mint.db.begin_read_tx().await?;
+    let validity = mint.db.get_keysets(&mut tx).await?;
+    tx.commit().await?;
+
+    let active = mint.keysets.iter().map(|keyset| {
+        let stored = validity.iter().find(|k| k.keyset_id == keyset.keyset_id);
+        Keyset {
+            id: keyset.keyset_id.clone(),
+            unit: keyset.unit.clone(),
+            active: true,
+            input_fee_ppk: 0,
+            min_amount: keyset.min_amount,
+            max_amount: keyset.max_amount,
+            valid_from: stored.map(|k| k.valid_from),
+            valid_to: None,
+        }
+    });
+    // replaced keysets are listed so wallets know when they were deactivated
+    let inactive = validity
+        .iter()
+        .filter(|k| mint.keyset_by_id(&k.keyset_id).is_err())
+        .map(|k| Keyset {
+            id: k.keyset_id.clone(),
+            unit: k.unit.clone(),
+            active: false,
+            input_fee_ppk: 0,
+            min_amount: None,
+            max_amount: None,
+            valid_from: Some(k.valid_from),
+            valid_to: k.valid_to,
+        });
+    let keysets = Keysets {
+        keysets: active.chain(inactive).collect(),
+        signature: None,
+    };
+    let keysets = match mint.config.server.sign_keysets {
+        true => keysets.sign(&derive_secret_key(&mint.config.privatekey)?),
+        false => keysets,
+    };
+
+    cached_response(&headers, mint.config.server.keys_cache_max_age, &keysets)
+}
+
+/// Answers with the serialized body and an ETag over it, or with 304 if the client already has
+/// the same body
+fn cached_response(
+    headers: &HeaderMap,
+    max_age: u64,
+    body: &impl Serialize,
+) -> Result<Response, MokshaMintError> {
+    let body = serde_json::to_vec(body)?;
+    let etag = format!("\"{}\"", sha256::Hash::hash(&body));
+    let not_modified = headers
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .any(|tag| tag.trim() == etag || tag.trim() == "*")
+        });
+
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        ([(CONTENT_TYPE, "application/json")], body).into_response()
+    };
+
+    let response_headers = response.headers_mut();
+    if let Ok(etag) = HeaderValue::from_str(&etag) {
+        response_headers.insert(ETAG, etag);
+    }
+    if let Ok(cache_control) = HeaderValue::from_str(&format!("public, max-age={max_age}")) {
+        response_headers.insert(CACHE_CONTROL, cache_control);
+    }
+    Ok(response)
 }
 
 #[utoipa::path(
@@ -121,15 +359,19 @@ pub async fn post_mint_quote_bolt11(
     State(mint): State<Mint>,
     Json(request): Json<PostMintQuoteBolt11Request>,
 ) -> Result<Json<PostMintQuoteBolt11Response>, MokshaMintError> {
-    // FIXME check currency unit
+    mint.bolt11_keyset(&request.unit)?;
+    mint.check_min_mint_amount(request.amount)?;
+    mint.check_bolt11_amount(request.amount)?;
     let key = Uuid::new_v4();
     let (pr, _hash) = mint.create_invoice(key.to_string(), request.amount).await?;
 
     let quote = Bolt11MintQuote {
         quote_id: key,
         payment_request: pr.clone(),
-        expiry: quote_expiry(), // FIXME use timestamp type in DB
+        amount: request.amount,
+        expiry: quote_expiry(&mint.config.quote), // FIXME use timestamp type in DB
         paid: false,
+        unit: request.unit,
     };
 
     let mut tx = mint.db.begin_tx().await?;
@@ -155,22 +397,24 @@ pub async fn post_mint_bolt11(
     Json(request): Json<PostMintBolt11Request>,
 ) -> Result<Json<PostMintBolt11Response>, MokshaMintError> {
     let mut tx = mint.db.begin_tx().await?;
+    let old_quote = &mint
+        .db
+        .get_bolt11_mint_quote(&mut tx, &Uuid::from_str(request.quote.as_str())?)
+        .await?;
+    mint.check_mint_quote_expiry(old_quote).await?;
+    let keyset = mint.bolt11_keyset(&old_quote.unit)?;
+
     let signatures = mint
         .mint_tokens(
             &mut tx,
             PaymentMethod::Bolt11,
             request.quote.clone(),
             &request.outputs,
-            &mint.keyset,
+            keyset,
             false,
         )
         .await?;
 
-    let old_quote = &mint
-        .db
-        .get_bolt11_mint_quote(&mut tx, &Uuid::from_str(request.quote.as_str())?)
-        .await?;
-
     mint.db
         .update_bolt11_mint_quote(
             &mut tx,
@@ -181,6 +425,12 @@ pub async fn post_mint_bolt11(
         )
         .await?;
     tx.commit().await?;
+
+    mint.emit_event(MintEvent::MintQuotePaid {
+        quote: request.quote,
+        payment_method: PaymentMethod::Bolt11,
+        amount: old_quote.amount,
+    });
     Ok(Json(PostMintBolt11Response { signatures }))
 }
 
@@ -197,13 +447,17 @@ pub async fn post_melt_quote_bolt11(
     State(mint): State<Mint>,
     Json(melt_request): Json<PostMeltQuoteBolt11Request>,
 ) -> Result<Json<PostMeltQuoteBolt11Response>, MokshaMintError> {
+    mint.bolt11_keyset(&melt_request.unit)?;
     let invoice = mint
         .lightning
         .decode_invoice(melt_request.request.clone())
         .await?;
-    let amount = invoice.amount_milli_satoshis().ok_or_else(|| {
-        crate::error::MokshaMintError::InvalidAmount("invalid invoice".to_owned())
-    })?;
+    mint.validate_invoice(&invoice)?;
+    let amount = match melt_request.mpp_amount() {
+        Some(mpp_amount) => mint.mpp_amount_msat(&invoice, mpp_amount)?,
+        None => mint.melt_amount_msat(&invoice, melt_request.amount)?,
+    };
+    mint.check_bolt11_amount(amount / 1_000)?;
     let fee_reserve = mint.fee_reserve(amount) / 1_000; // FIXME check if this is correct
     debug!("fee_reserve: {}", fee_reserve);
 
@@ -213,9 +467,13 @@ pub async fn post_melt_quote_bolt11(
         quote_id: key,
         amount: amount_sat,
         fee_reserve,
-        expiry: quote_expiry(),
+        expiry: quote_expiry(&mint.config.quote),
         payment_request: melt_request.request.clone(),
         paid: false,
+        failed: false,
+        amount_paid: None,
+        fee_paid: None,
+        unit: melt_request.unit,
     };
     let mut tx = mint.db.begin_tx().await?;
     mint.db.add_bolt11_melt_quote(&mut tx, &quote).await?;
@@ -224,10 +482,49 @@ pub async fn post_melt_quote_bolt11(
     Ok(Json(quote.into()))
 }
 
-fn quote_expiry() -> u64 {
-    // FIXME add config option for expiry
-    let now = Utc::now() + Duration::try_minutes(30).expect("invalid duration");
-    now.timestamp() as u64
+#[utoipa::path(
+        post,
+        path = "/v1/melt/quote/bolt11/probe",
+        request_body = PostMeltQuoteBolt11Request,
+        responses(
+            (status = 200, description = "probe route for melt quote", body = [PostMeltProbeBolt11Response])
+        ),
+    )]
+#[instrument(name = "post_melt_probe_bolt11", skip(mint), err)]
+pub async fn post_melt_probe_bolt11(
+    State(mint): State<Mint>,
+    Json(melt_request): Json<PostMeltQuoteBolt11Request>,
+) -> Result<Json<PostMeltProbeBolt11Response>, MokshaMintError> {
+    let invoice = mint
+        .lightning
+        .decode_invoice(melt_request.request.clone())
+        .await?;
+    mint.validate_invoice(&invoice)?;
+    let amount = mint.melt_amount_msat(&invoice, melt_request.amount)?;
+
+    let response = match mint
+        .lightning
+        .probe_route(melt_request.request, amount)
+        .await?
+    {
+        Some(ProbeResult::Route { fee_msat }) => PostMeltProbeBolt11Response {
+            state: MeltProbeState::Routable,
+            fee: Some(fee_msat.div_ceil(1_000)),
+        },
+        Some(ProbeResult::NoRoute) => PostMeltProbeBolt11Response {
+            state: MeltProbeState::Unroutable,
+            fee: None,
+        },
+        None => PostMeltProbeBolt11Response {
+            state: MeltProbeState::Unknown,
+            fee: None,
+        },
+    };
+    Ok(Json(response))
+}
+
+fn quote_expiry(quote_config: &QuoteConfig) -> u64 {
+    (Utc::now().timestamp() as u64).saturating_add(quote_config.expiry_minutes.saturating_mul(60))
 }
 
 #[utoipa::path(
@@ -248,29 +545,24 @@ pub async fn post_melt_bolt11(
         .db
         .get_bolt11_melt_quote(&mut tx, &Uuid::from_str(melt_request.quote.as_str())?)
         .await?;
+    tx.commit().await?;
 
     debug!("post_melt_bolt11 fee_reserve: {:#?}", &quote);
+    mint.check_melt_quote_expiry(&quote)?;
 
-    let (paid, payment_preimage, change) = mint
-        .melt_bolt11(
-            &mut tx,
-            quote.payment_request.to_owned(),
-            quote.fee_reserve,
-            &melt_request.inputs,
-            melt_request.outputs,
-            &mint.keyset,
-        )
+    let result = mint
+        .melt_bolt11(&quote, &melt_request.inputs, melt_request.outputs)
         .await?;
-    mint.db
-        .update_bolt11_melt_quote(&mut tx, &Bolt11MeltQuote { paid, ..quote })
-        .await?;
-    tx.commit().await?;
 
-    Ok(Json(PostMeltBolt11Response {
-        paid,
-        payment_preimage: Some(payment_preimage),
-        change,
-    }))
+    if result.paid {
+        mint.emit_event(MintEvent::MeltCompleted {
+            quote: quote.quote_id.to_string(),
+            payment_method: PaymentMethod::Bolt11,
+            amount: result.amount_paid,
+        });
+    }
+
+    Ok(Json(result.into()))
 }
 
 #[utoipa::path(
@@ -290,19 +582,10 @@ pub async fn get_mint_quote_bolt11(
 ) -> Result<Json<PostMintQuoteBolt11Response>, MokshaMintError> {
     debug!("get_quote: {}", quote_id);
 
-    let mut tx = mint.db.begin_tx().await?;
     let quote = mint
-        .db
-        .get_bolt11_mint_quote(&mut tx, &Uuid::from_str(quote_id.as_str())?)
-        .await?;
-    tx.commit().await?;
-
-    let paid = mint
-        .lightning
-        .is_invoice_paid(quote.payment_request.clone())
+        .get_bolt11_mint_quote(&Uuid::from_str(quote_id.as_str())?)
         .await?;
-
-    Ok(Json(Bolt11MintQuote { paid, ..quote }.into()))
+    Ok(Json(quote.into()))
 }
 
 #[utoipa::path(
@@ -321,7 +604,7 @@ pub async fn get_melt_quote_bolt11(
     State(mint): State<Mint>,
 ) -> Result<Json<PostMeltQuoteBolt11Response>, MokshaMintError> {
     debug!("get_melt_quote: {}", quote_id);
-    let mut tx = mint.db.begin_tx().await?;
+    let mut tx = mint.db.begin_read_tx().await?;
     let quote = mint
         .db
         .get_bolt11_melt_quote(&mut tx, &Uuid::from_str(quote_id.as_str())?)
@@ -345,7 +628,7 @@ pub async fn get_info(State(mint): State<Mint>) -> Result<Json<MintInfoResponse>
     let contact = Some(mint_info.into());
 
     let mint_info = MintInfoResponse {
-        nuts: get_nuts(&mint.config),
+        nuts: get_nuts(&mint.config, &mint.units(), &mint.lightning_type),
         name: mint.config.info.name,
         pubkey: mint.keyset.mint_pubkey,
         version: match mint.config.info.version {
@@ -356,14 +639,64 @@ pub async fn get_info(State(mint): State<Mint>) -> Result<Json<MintInfoResponse>
         description_long: mint.config.info.description_long,
         contact,
         motd: mint.config.info.motd,
+        time: Some(Utc::now().timestamp() as u64),
     };
     Ok(Json(mint_info))
 }
 
-fn get_nuts(cfg: &MintConfig) -> Nuts {
+fn get_nuts(cfg: &MintConfig, units: &[CurrencyUnit], lightning_type: &LightningType) -> Nuts {
     let default_config = BtcOnchainConfig::default();
     let config = cfg.btconchain_backend.as_ref().unwrap_or(&default_config);
+    let limits = &cfg.bolt11_limits;
+    let mint_min_amount = cfg
+        .min_mint_amount
+        .into_iter()
+        .chain(limits.bolt11_min_amount)
+        .max()
+        .unwrap_or(1);
+    let melt_max_amount = cfg
+        .melt_limits
+        .max_melt_amount
+        .map_or(limits.max_amount(), |max| max.min(limits.max_amount()));
+    // bolt11 quotes are only supported in sat, see `Mint::bolt11_keyset`
+    let bolt11_units = units
+        .iter()
+        .filter(|unit| **unit == CurrencyUnit::Sat)
+        .cloned()
+        .collect::<Vec<_>>();
+    // bolt12 is not advertised, because the mint has no bolt12 routes
     Nuts {
+        nut4: Nut4 {
+            payment_methods: PaymentMethodConfig::for_units(
+                PaymentMethod::Bolt11,
+                &bolt11_units,
+                Some(mint_min_amount),
+                Some(limits.max_amount()),
+            ),
+            ..Nut4::new(&bolt11_units)
+        },
+        nut5: Nut5 {
+            payment_methods: PaymentMethodConfig::for_units(
+                PaymentMethod::Bolt11,
+                &bolt11_units,
+                Some(limits.bolt11_min_amount.unwrap_or(1)),
+                Some(melt_max_amount),
+            ),
+            probe: lightning_type.supports_probe(),
+            ..Nut5::new(&bolt11_units)
+        },
+        nut7: Some(Nut7 {
+            supported: true,
+            max_inputs: Some(MAX_CHECKSTATE_INPUTS as u64),
+        }),
+        nut9: Some(Nut9 {
+            supported: true,
+            max_outputs: Some(MAX_RESTORE_OUTPUTS as u64),
+        }),
+        nut15: Some(Nut15 {
+            supported: lightning_type.supports_mpp(),
+        }),
+        nut17: Some(Nut17::bolt11_mint_quote(units)),
         nut18: Some(config.to_owned().into()),
         nut19: Some(config.to_owned().into()),
         ..Nuts::default()
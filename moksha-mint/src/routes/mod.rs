@@ -1,2 +1,7 @@
+pub mod admin;
 pub mod btconchain;
 pub mod default;
+mod json;
+pub mod ws;
+
+pub use json::Json;
@@ -0,0 +1,225 @@
+//! NUT-17 websocket endpoint. Wallets subscribe to quotes and the mint pushes their state, so
+//! they don't have to poll the quote endpoints.
+
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::Response,
+};
+use moksha_core::{
+    primitives::PostMintQuoteBolt11Response,
+    subscription::{
+        SubscribeParams, SubscriptionKind, WsNotification, WsRequest, WsRequestMethod, WsResponse,
+        INTERNAL_ERROR, INVALID_PARAMS, PARSE_ERROR,
+    },
+};
+use serde::Serialize;
+use tokio::{sync::mpsc, task::JoinHandle};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::mint::Mint;
+
+/// interval in which the lightning backend is asked if the invoice of a watched quote is paid
+const QUOTE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// max number of quotes a single connection can watch
+const MAX_WATCHED_QUOTES: usize = 100;
+
+/// notifications that are not sent yet. Watchers wait if the queue is full
+const QUEUE_SIZE: usize = 100;
+
+/// number of failed checks in a row after which a watcher gives up
+const MAX_CHECK_ERRORS: u32 = 5;
+
+/// messages of the watchers to the connection
+enum Outgoing {
+    Message(String),
+    /// sends the message and closes the connection, so the wallet can fall back to polling
+    Close(String),
+}
+
+pub async fn get_ws(ws: WebSocketUpgrade, State(mint): State<Mint>) -> Response {
+    ws.on_upgrade(move |socket| handle_socket(socket, mint))
+}
+
+async fn handle_socket(mut socket: WebSocket, mint: Mint) {
+    let (sender, mut receiver) = mpsc::channel::<Outgoing>(QUEUE_SIZE);
+    // subId -> watcher tasks
+    let mut subscriptions = HashMap::<String, Vec<JoinHandle<()>>>::new();
+
+    loop {
+        tokio::select! {
+            message = socket.recv() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => break,
+                    // pings are answered by axum
+                    Some(Ok(_)) => continue,
+                    Some(Err(err)) => {
+                        debug!("websocket error: {}", err);
+                        break;
+                    }
+                };
+                // the response is sent before any notification of the new subscription
+                let response = handle_request(&text, &mint, &sender, &mut subscriptions);
+                if socket.send(Message::Text(to_message(&response))).await.is_err() {
+                    break;
+                }
+            }
+            Some(outgoing) = receiver.recv() => {
+                let (text, close) = match outgoing {
+                    Outgoing::Message(text) => (text, false),
+                    Outgoing::Close(text) => (text, true),
+                };
+                if socket.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+                if close {
+                    let _ = socket.send(Message::Close(None)).await;
+                    break;
+                }
+            }
+        }
+    }
+
+    for watcher in subscriptions.into_values().flatten() {
+        watcher.abort();
+    }
+}
+
+fn handle_request(
+    text: &str,
+    mint: &Mint,
+    sender: &mpsc::Sender<Outgoing>,
+    subscriptions: &mut HashMap<String, Vec<JoinHandle<()>>>,
+) -> WsResponse {
+    let request = match serde_json::from_str::<WsRequest>(text) {
+        Ok(request) => request,
+        Err(err) => return WsResponse::error(None, PARSE_ERROR, err.to_string()),
+    };
+
+    match request.method {
+        WsRequestMethod::Subscribe(params) => {
+            match subscribe(params, mint, sender, subscriptions) {
+                Ok(sub_id) => WsResponse::ok(request.id, &sub_id),
+                Err(message) => WsResponse::error(Some(request.id), INVALID_PARAMS, message),
+            }
+        }
+        WsRequestMethod::Unsubscribe(params) => match subscriptions.remove(&params.sub_id) {
+            Some(watchers) => {
+                watchers.iter().for_each(JoinHandle::abort);
+                WsResponse::ok(request.id, &params.sub_id)
+            }
+            None => WsResponse::error(
+                Some(request.id),
+                INVALID_PARAMS,
+                format!("unknown subId {}", params.sub_id),
+            ),
+        },
+    }
+}
+
+/// Spawns a watcher for each quote of the filters. Returns the subId
+fn subscribe(
+    params: SubscribeParams,
+    mint: &Mint,
+    sender: &mpsc::Sender<Outgoing>,
+    subscriptions: &mut HashMap<String, Vec<JoinHandle<()>>>,
+) -> Result<String, String> {
+    if params.kind != SubscriptionKind::Bolt11MintQuote {
+        return Err(format!("unsupported subscription kind {:?}", params.kind));
+    }
+    if subscriptions.contains_key(&params.sub_id) {
+        return Err(format!("subId {} is already used", params.sub_id));
+    }
+    let watched = subscriptions.values().map(Vec::len).sum::<usize>();
+    if watched + params.filters.len() > MAX_WATCHED_QUOTES {
+        return Err(format!(
+            "a connection can watch at most {MAX_WATCHED_QUOTES} quotes"
+        ));
+    }
+
+    let quote_ids = params
+        .filters
+        .iter()
+        .map(|filter| Uuid::from_str(filter).map_err(|_| format!("invalid quote id {filter}")))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let watchers = quote_ids
+        .into_iter()
+        .map(|quote_id| {
+            tokio::spawn(watch_mint_quote(
+                mint.clone(),
+                quote_id,
+                params.sub_id.clone(),
+                sender.clone(),
+            ))
+        })
+        .collect();
+    subscriptions.insert(params.sub_id.clone(), watchers);
+    Ok(params.sub_id)
+}
+
+/// Sends the current state of the quote and then every change until the quote is paid or expired.
+/// Failed checks are retried. If the quote can't be checked [`MAX_CHECK_ERRORS`] times in a row,
+/// an error is sent and the connection is closed.
+async fn watch_mint_quote(
+    mint: Mint,
+    quote_id: Uuid,
+    sub_id: String,
+    sender: mpsc::Sender<Outgoing>,
+) {
+    let mut last_paid = None;
+    let mut errors = 0;
+    loop {
+        let quote = match mint.get_bolt11_mint_quote(&quote_id).await {
+            Ok(quote) => {
+                errors = 0;
+                quote
+            }
+            Err(err) if errors + 1 < MAX_CHECK_ERRORS => {
+                errors += 1;
+                debug!("checking mint quote {} failed: {}", quote_id, err);
+                tokio::time::sleep(QUOTE_CHECK_INTERVAL).await;
+                continue;
+            }
+            Err(err) => {
+                warn!("stop watching mint quote {}: {}", quote_id, err);
+                let error = WsResponse::error(
+                    None,
+                    INTERNAL_ERROR,
+                    format!("checking quote {quote_id} of subId {sub_id} failed: {err}"),
+                );
+                let _ = sender.send(Outgoing::Close(to_message(&error))).await;
+                return;
+            }
+        };
+
+        if last_paid != Some(quote.paid) {
+            last_paid = Some(quote.paid);
+            let notification =
+                WsNotification::new(&sub_id, PostMintQuoteBolt11Response::from(quote.clone()));
+            if sender
+                .send(Outgoing::Message(to_message(&notification)))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+
+        if quote.paid || mint.is_mint_quote_expired(&quote) {
+            return;
+        }
+        tokio::time::sleep(QUOTE_CHECK_INTERVAL).await;
+    }
+}
+
+fn to_message(message: &impl Serialize) -> String {
+    serde_json::to_string(message).expect("websocket messages are always serializable")
+}
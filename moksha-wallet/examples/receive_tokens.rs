@@ -23,7 +23,7 @@ async fn main() -> anyhow::Result<()> {
     // FIXME add better filtering by CurrencyUnit
 
     let tokens = TokenV3::from_str("cashuAeyJ0b2tlbiI6IFt7InByb29mcyI6IFt7ImlkIjogIjAwOTkxZjRmMjc3MzMzOGMiLCAiYW1vdW50IjogMiwgInNlY3JldCI6ICI5ZmFjZWE0Y2QzN2I3ZWRlOGE4NmQzYWY1ZWIxZTczNzIxMDNmZDE2YTQ1M2E5NDQ5YjE0MDFkZDhhMzAzMWJiIiwgIkMiOiAiMDM2ZTVhOWJhOWE1ZjYxZmQ5MTk3YzM2OTgzZjc1YzAzYTUyYzc0YTJmZmM2NTBmNzg5MjJlMDcyZWY1MTI0YjZlIn1dLCAibWludCI6ICJodHRwczovL21pbnQubXV0aW55bmV0Lm1va3NoYS5jYXNoOjMzMzgifV19")?;
-    wallet.receive_tokens(wallet_keyset, &tokens).await?;
+    wallet.receive_tokens(wallet_keyset, &tokens, false).await?;
     let balance = wallet.get_balance().await?;
     println!("New balance: {} sats", balance);
     Ok(())
@@ -0,0 +1,5 @@
+-- wallet settings as key value pairs, e.g. the max send amount
+CREATE TABLE settings (
+    key TEXT PRIMARY KEY NOT NULL,
+    value TEXT NOT NULL
+);
@@ -0,0 +1,2 @@
+-- index of the deterministic secret of a proof. NULL for proofs stored before this migration
+ALTER TABLE proofs ADD COLUMN derivation_index INTEGER;
@@ -0,0 +1,2 @@
+-- optional tag of a proof for coin control, e.g. savings
+ALTER TABLE proofs ADD COLUMN label TEXT;
@@ -0,0 +1,9 @@
+-- user facing history of completed mint, melt, send and receive operations
+CREATE TABLE transactions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    operation TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    mint_url TEXT NOT NULL,
+    memo TEXT,
+    created_at INTEGER NOT NULL DEFAULT (CAST(strftime('%s', 'now') AS INTEGER))
+);
@@ -0,0 +1,27 @@
+-- named accounts with their own seed, proofs, keysets and transactions. Existing data belongs to the default account
+CREATE TABLE seeds (
+    account TEXT PRIMARY KEY NOT NULL,
+    seed_words TEXT NOT NULL
+);
+INSERT INTO seeds (account, seed_words) SELECT 'default', seed_words FROM seed;
+DROP TABLE seed;
+
+ALTER TABLE proofs ADD COLUMN account TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE transactions ADD COLUMN account TEXT NOT NULL DEFAULT 'default';
+
+-- the same keyset can be used by several accounts, each with its own last_index
+CREATE TABLE keysets_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    mint_url TEXT NOT NULL,
+    keyset_id TEXT NOT NULL,
+    currency_unit TEXT NOT NULL,
+    active BOOL NOT NULL DEFAULT TRUE,
+    last_index INTEGER NOT NULL,
+    public_keys TEXT NOT NULL CHECK (json_valid(public_keys)),
+    account TEXT NOT NULL DEFAULT 'default',
+    UNIQUE (keyset_id, mint_url, account)
+);
+INSERT INTO keysets_new (id, mint_url, keyset_id, currency_unit, active, last_index, public_keys)
+    SELECT id, mint_url, keyset_id, currency_unit, active, last_index, public_keys FROM keysets;
+DROP TABLE keysets;
+ALTER TABLE keysets_new RENAME TO keysets;
@@ -0,0 +1,14 @@
+-- proofs that were sent to the mint in a melt whose result has not been stored yet. They are
+-- restored by the wallet if the mint didn't spend them, e.g. because the wallet crashed
+CREATE TABLE pending_melts (
+    keyset_id TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    C TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    derivation_index INTEGER,
+    mint_url TEXT NOT NULL,
+    quote TEXT NOT NULL,
+    account TEXT NOT NULL DEFAULT 'default',
+    created_at INTEGER NOT NULL DEFAULT (CAST(strftime('%s', 'now') AS INTEGER)),
+    UNIQUE (secret)
+);
@@ -0,0 +1,2 @@
+-- unit of the transaction amount. Existing transactions were made in sat
+ALTER TABLE transactions ADD COLUMN unit TEXT NOT NULL DEFAULT 'sat';
@@ -4,27 +4,36 @@ use moksha_core::{
     blind::BlindedMessage,
     keyset::Keysets,
     primitives::{
-        CurrencyUnit, KeysResponse, MintInfoResponse, PostMeltBolt11Request,
-        PostMeltBolt11Response, PostMeltBtcOnchainRequest, PostMeltBtcOnchainResponse,
-        PostMeltQuoteBolt11Request, PostMeltQuoteBolt11Response, PostMeltQuoteBtcOnchainRequest,
+        CurrencyUnit, KeysResponse, MintInfoResponse, OnchainOutput, PostCheckStateRequest,
+        PostCheckStateResponse, PostMeltBolt11Request, PostMeltBolt11Response,
+        PostMeltBtcOnchainRequest, PostMeltBtcOnchainResponse, PostMeltQuoteBolt11Request,
+        PostMeltQuoteBolt11Response, PostMeltQuoteBtcOnchainRequest,
         PostMeltQuoteBtcOnchainResponse, PostMintBolt11Request, PostMintBolt11Response,
         PostMintBtcOnchainRequest, PostMintBtcOnchainResponse, PostMintQuoteBolt11Request,
         PostMintQuoteBolt11Response, PostMintQuoteBtcOnchainRequest,
-        PostMintQuoteBtcOnchainResponse, PostSwapRequest, PostSwapResponse,
+        PostMintQuoteBtcOnchainResponse, PostRestoreRequest, PostRestoreResponse, PostSwapRequest,
+        PostSwapResponse,
     },
     proof::Proofs,
+    spent_digest::SpentDigest,
 };
 
+use secp256k1::PublicKey;
 use url::Url;
 
-use crate::{error::MokshaWalletError, http::CrossPlatformHttpClient};
+use crate::{
+    error::MokshaWalletError,
+    http::CrossPlatformHttpClient,
+    lnurl::{parse_lnurl_response, LnurlPayInvoice, LnurlPayRequest, LnurlWithdrawRequest},
+};
+use serde_json::Value;
 
 use super::CashuClient;
 
 #[async_trait(?Send)]
 impl CashuClient for CrossPlatformHttpClient {
     async fn get_keys(&self, mint_url: &Url) -> Result<KeysResponse, MokshaWalletError> {
-        self.do_get(&mint_url.join("v1/keys")?).await
+        self.do_get_cached(&mint_url.join("v1/keys")?).await
     }
 
     async fn get_keys_by_id(
@@ -32,12 +41,12 @@ impl CashuClient for CrossPlatformHttpClient {
         mint_url: &Url,
         keyset_id: String,
     ) -> Result<KeysResponse, MokshaWalletError> {
-        self.do_get(&mint_url.join(&format!("v1/keys/{}", keyset_id))?)
+        self.do_get_cached(&mint_url.join(&format!("v1/keys/{}", keyset_id))?)
             .await
     }
 
     async fn get_keysets(&self, mint_url: &Url) -> Result<Keysets, MokshaWalletError> {
-        self.do_get(&mint_url.join("v1/keysets")?).await
+        self.do_get_cached(&mint_url.join("v1/keysets")?).await
     }
 
     async fn post_swap(
@@ -51,6 +60,30 @@ impl CashuClient for CrossPlatformHttpClient {
         self.do_post(&mint_url.join("v1/swap")?, &body).await
     }
 
+    async fn get_spent_digest(&self, mint_url: &Url) -> Result<SpentDigest, MokshaWalletError> {
+        self.do_get(&mint_url.join("v1/spent-digest")?).await
+    }
+
+    async fn post_restore(
+        &self,
+        mint_url: &Url,
+        outputs: Vec<BlindedMessage>,
+    ) -> Result<PostRestoreResponse, MokshaWalletError> {
+        let body = PostRestoreRequest { outputs };
+
+        self.do_post(&mint_url.join("v1/restore")?, &body).await
+    }
+
+    async fn post_checkstate(
+        &self,
+        mint_url: &Url,
+        ys: Vec<PublicKey>,
+    ) -> Result<PostCheckStateResponse, MokshaWalletError> {
+        let body = PostCheckStateRequest { ys };
+
+        self.do_post(&mint_url.join("v1/checkstate")?, &body).await
+    }
+
     async fn post_melt_bolt11(
         &self,
         mint_url: &Url,
@@ -72,10 +105,13 @@ impl CashuClient for CrossPlatformHttpClient {
         mint_url: &Url,
         payment_request: String,
         unit: CurrencyUnit,
+        amount: Option<u64>,
     ) -> Result<PostMeltQuoteBolt11Response, MokshaWalletError> {
         let body = PostMeltQuoteBolt11Request {
             request: payment_request,
             unit,
+            amount,
+            options: None,
         };
 
         self.do_post(&mint_url.join("v1/melt/quote/bolt11")?, &body)
@@ -124,6 +160,20 @@ impl CashuClient for CrossPlatformHttpClient {
             .await
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn subscribe_mint_quote(
+        &self,
+        mint_url: &Url,
+        quote: String,
+    ) -> Result<tokio::sync::mpsc::Receiver<PostMintQuoteBolt11Response>, MokshaWalletError> {
+        self.subscribe(
+            mint_url,
+            moksha_core::subscription::SubscriptionKind::Bolt11MintQuote,
+            vec![quote],
+        )
+        .await
+    }
+
     async fn post_mint_onchain(
         &self,
         mint_url: &Url,
@@ -162,12 +212,79 @@ impl CashuClient for CrossPlatformHttpClient {
         self.do_get(&mint_url.join("v1/info")?).await
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn get_tls_fingerprint(
+        &self,
+        mint_url: &Url,
+    ) -> Result<Option<String>, MokshaWalletError> {
+        self.get_tls_fingerprint(&mint_url.join("v1/info")?).await
+    }
+
     async fn is_v1_supported(&self, mint_url: &Url) -> Result<bool, MokshaWalletError> {
         self.get_status(&mint_url.join("v1/info")?)
             .await
             .map(|s| s == 200)
     }
 
+    async fn get_mint_list(&self, list_url: &Url) -> Result<Vec<String>, MokshaWalletError> {
+        let mints: Vec<Value> = self.do_get(list_url).await?;
+        Ok(mints
+            .into_iter()
+            .map(|mint| match mint {
+                Value::String(mint) => mint,
+                other => other.to_string(),
+            })
+            .collect())
+    }
+
+    async fn get_lnurl_withdraw(
+        &self,
+        url: &Url,
+    ) -> Result<LnurlWithdrawRequest, MokshaWalletError> {
+        let request: LnurlWithdrawRequest = parse_lnurl_response(self.do_get(url).await?)?;
+        if request.tag != "withdrawRequest" {
+            return Err(MokshaWalletError::Lnurl(format!(
+                "expected a withdrawRequest, got {}",
+                request.tag
+            )));
+        }
+        Ok(request)
+    }
+
+    async fn submit_lnurl_withdraw(
+        &self,
+        request: &LnurlWithdrawRequest,
+        payment_request: &str,
+    ) -> Result<(), MokshaWalletError> {
+        // the callback makes the service pay the invoice, so it must not be retried
+        let response: Value = self
+            .do_get_once(&request.callback_url(payment_request)?)
+            .await?;
+        parse_lnurl_response::<Value>(response)?;
+        Ok(())
+    }
+
+    async fn get_lnurl_pay(&self, url: &Url) -> Result<LnurlPayRequest, MokshaWalletError> {
+        let request: LnurlPayRequest = parse_lnurl_response(self.do_get(url).await?)?;
+        if request.tag != "payRequest" {
+            return Err(MokshaWalletError::Lnurl(format!(
+                "expected a payRequest, got {}",
+                request.tag
+            )));
+        }
+        Ok(request)
+    }
+
+    async fn get_lnurl_pay_invoice(
+        &self,
+        request: &LnurlPayRequest,
+        amount_msat: u64,
+    ) -> Result<String, MokshaWalletError> {
+        let invoice: LnurlPayInvoice =
+            parse_lnurl_response(self.do_get(&request.callback_url(amount_msat)?).await?)?;
+        Ok(invoice.pr)
+    }
+
     async fn post_melt_onchain(
         &self,
         mint_url: &Url,
@@ -190,6 +307,23 @@ impl CashuClient for CrossPlatformHttpClient {
             address,
             amount,
             unit,
+            outputs: vec![],
+        };
+        self.do_post(&mint_url.join("v1/melt/quote/btconchain")?, &body)
+            .await
+    }
+
+    async fn post_melt_quote_onchain_batch(
+        &self,
+        mint_url: &Url,
+        outputs: Vec<OnchainOutput>,
+        unit: CurrencyUnit,
+    ) -> Result<Vec<PostMeltQuoteBtcOnchainResponse>, MokshaWalletError> {
+        let body = PostMeltQuoteBtcOnchainRequest {
+            address: String::new(),
+            amount: 0,
+            unit,
+            outputs,
         };
         self.do_post(&mint_url.join("v1/melt/quote/btconchain")?, &body)
             .await
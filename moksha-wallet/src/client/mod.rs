@@ -3,17 +3,23 @@ use moksha_core::{
     blind::BlindedMessage,
     keyset::Keysets,
     primitives::{
-        CurrencyUnit, KeysResponse, MintInfoResponse, PostMeltBolt11Response,
-        PostMeltBtcOnchainResponse, PostMeltQuoteBolt11Response, PostMeltQuoteBtcOnchainResponse,
-        PostMintBolt11Response, PostMintBtcOnchainResponse, PostMintQuoteBolt11Response,
-        PostMintQuoteBtcOnchainResponse, PostSwapResponse,
+        CurrencyUnit, KeysResponse, MintInfoResponse, OnchainOutput, PostCheckStateResponse,
+        PostMeltBolt11Response, PostMeltBtcOnchainResponse, PostMeltQuoteBolt11Response,
+        PostMeltQuoteBtcOnchainResponse, PostMintBolt11Response, PostMintBtcOnchainResponse,
+        PostMintQuoteBolt11Response, PostMintQuoteBtcOnchainResponse, PostRestoreResponse,
+        PostSwapResponse,
     },
     proof::Proofs,
+    spent_digest::SpentDigest,
 };
+use secp256k1::PublicKey;
 
 use url::Url;
 
-use crate::error::MokshaWalletError;
+use crate::{
+    error::MokshaWalletError,
+    lnurl::{LnurlPayRequest, LnurlWithdrawRequest},
+};
 
 pub mod crossplatform;
 
@@ -48,11 +54,29 @@ pub trait CashuClient {
         outputs: Vec<BlindedMessage>,
     ) -> Result<PostMeltBolt11Response, MokshaWalletError>;
 
+    /// Returns the spend state of the proofs with the given Ys
+    async fn post_checkstate(
+        &self,
+        mint_url: &Url,
+        ys: Vec<PublicKey>,
+    ) -> Result<PostCheckStateResponse, MokshaWalletError>;
+
+    /// Returns a bloom filter of all proofs the mint has seen spent
+    async fn get_spent_digest(&self, mint_url: &Url) -> Result<SpentDigest, MokshaWalletError>;
+
+    /// Returns the signatures of the outputs the mint has signed before
+    async fn post_restore(
+        &self,
+        mint_url: &Url,
+        outputs: Vec<BlindedMessage>,
+    ) -> Result<PostRestoreResponse, MokshaWalletError>;
+
     async fn post_melt_quote_bolt11(
         &self,
         mint_url: &Url,
         payment_request: String,
         unit: CurrencyUnit,
+        amount: Option<u64>,
     ) -> Result<PostMeltQuoteBolt11Response, MokshaWalletError>;
 
     async fn get_melt_quote_bolt11(
@@ -81,10 +105,53 @@ pub trait CashuClient {
         quote: String,
     ) -> Result<PostMintQuoteBolt11Response, MokshaWalletError>;
 
+    /// Subscribes to a bolt11 mint quote (NUT-17). The receiver yields the quote whenever its
+    /// state changes and is closed when the connection to the mint ends.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn subscribe_mint_quote(
+        &self,
+        mint_url: &Url,
+        quote: String,
+    ) -> Result<tokio::sync::mpsc::Receiver<PostMintQuoteBolt11Response>, MokshaWalletError>;
+
     async fn get_info(&self, mint_url: &Url) -> Result<MintInfoResponse, MokshaWalletError>;
 
+    /// Returns the fingerprint of the TLS certificate of the mint, `None` if it doesn't use TLS
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn get_tls_fingerprint(
+        &self,
+        mint_url: &Url,
+    ) -> Result<Option<String>, MokshaWalletError>;
+
     async fn is_v1_supported(&self, mint_url: &Url) -> Result<bool, MokshaWalletError>;
 
+    /// Fetches a json array of mint urls, e.g. from a mint discovery list. The entries are returned
+    /// as they are, so the caller can report entries that are no valid urls
+    async fn get_mint_list(&self, list_url: &Url) -> Result<Vec<String>, MokshaWalletError>;
+
+    /// Fetches the parameters of a LNURL-withdraw request
+    async fn get_lnurl_withdraw(
+        &self,
+        url: &Url,
+    ) -> Result<LnurlWithdrawRequest, MokshaWalletError>;
+
+    /// Asks the LNURL-withdraw service to pay the invoice
+    async fn submit_lnurl_withdraw(
+        &self,
+        request: &LnurlWithdrawRequest,
+        payment_request: &str,
+    ) -> Result<(), MokshaWalletError>;
+
+    /// Fetches the parameters of a LNURL-pay request
+    async fn get_lnurl_pay(&self, url: &Url) -> Result<LnurlPayRequest, MokshaWalletError>;
+
+    /// Asks the LNURL-pay service for an invoice of the amount in msat
+    async fn get_lnurl_pay_invoice(
+        &self,
+        request: &LnurlPayRequest,
+        amount_msat: u64,
+    ) -> Result<String, MokshaWalletError>;
+
     async fn post_mint_onchain(
         &self,
         mint_url: &Url,
@@ -120,6 +187,14 @@ pub trait CashuClient {
         unit: CurrencyUnit,
     ) -> Result<Vec<PostMeltQuoteBtcOnchainResponse>, MokshaWalletError>;
 
+    /// Requests a melt quote for a single transaction that pays all outputs
+    async fn post_melt_quote_onchain_batch(
+        &self,
+        mint_url: &Url,
+        outputs: Vec<OnchainOutput>,
+        unit: CurrencyUnit,
+    ) -> Result<Vec<PostMeltQuoteBtcOnchainResponse>, MokshaWalletError>;
+
     async fn get_melt_quote_onchain(
         &self,
         mint_url: &Url,
@@ -1,6 +1,7 @@
 use std::string::FromUtf8Error;
 
 use lightning_invoice::ParseOrSemanticError;
+use moksha_core::primitives::PaymentMethod;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -19,9 +20,17 @@ pub enum MokshaWalletError {
     #[cfg(not(target_arch = "wasm32"))]
     #[error("InvalidHeaderValueError - {0}")]
     InvalidHeaderValue(#[from] reqwest::header::InvalidHeaderValue),
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("WebSocketError - {0}")]
+    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+
     #[error("{0}")]
     MintError(String),
 
+    #[error("{0}")]
+    ProofAlreadyUsed(String),
+
     #[error("{1}")]
     InvoiceNotPaidYet(u64, String),
 
@@ -39,6 +48,10 @@ pub enum MokshaWalletError {
     #[error("Migrate Error {0}")]
     Migrate(#[from] sqlx::migrate::MigrateError),
 
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("IoError - {0}")]
+    Io(#[from] std::io::Error),
+
     #[cfg(not(target_arch = "wasm32"))]
     #[error("Sqlite Error {0}")]
     Sqlite(#[from] sqlx::sqlite::SqliteError),
@@ -52,6 +65,17 @@ pub enum MokshaWalletError {
     #[error("Not enough tokens")]
     NotEnoughTokens,
 
+    #[error("Amount {0} exceeds the configured max send amount of {1}")]
+    MaxSendAmountExceeded(u64, u64),
+
+    #[error(
+        "Claiming the token costs {0} in fees, which exceeds the allowed share of its value {1}"
+    )]
+    FeeExceedsValue(u64, u64),
+
+    #[error("Fee reserve {0} exceeds the allowed share of the payment amount {1}")]
+    FeeReserveTooHigh(u64, u64),
+
     #[error("Failed to decode payment request {0} - Error {1}")]
     DecodeInvoice(String, ParseOrSemanticError),
 
@@ -64,6 +88,9 @@ pub enum MokshaWalletError {
     #[error("Unsupported version: Only mints with /v1 api are supported")]
     UnsupportedApiVersion,
 
+    #[error("Payment method {0} is not supported by the wallet")]
+    UnsupportedPaymentMethod(PaymentMethod),
+
     #[error("Bip32Error {0}")]
     Bip32(#[from] bip32::Error),
 
@@ -87,4 +114,59 @@ pub enum MokshaWalletError {
 
     #[error("Pubkey not found")]
     PubkeyNotFound,
+
+    #[error("Keyset {0} is inactive. Add the mint again to refresh its keysets")]
+    KeysetInactive(String),
+
+    #[error("Keyset {0} is not known by the mint")]
+    UnknownKeyset(String),
+
+    #[error("Mint {0} is not known by the wallet. Add the mint to receive its tokens")]
+    UnknownMint(String),
+
+    #[error("The wallet has no active keyset of mint {0} for unit {1}. Add the mint again to refresh its keysets")]
+    NoActiveKeyset(String, String),
+
+    #[error("Keyset id {0} does not match the id {1} derived from its keys")]
+    KeysetIdMismatch(String, String),
+
+    #[error("The mint serves different keys for the known keyset {0}")]
+    KeysetKeysChanged(String),
+
+    #[error("The mint has no keyset for unit {0}")]
+    UnitNotSupported(String),
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Wallet database schema version {0} is newer than the supported version {1}. Please upgrade moksha-wallet")]
+    DatabaseTooNew(i64, i64),
+
+    #[error("Invalid change from mint: {0}")]
+    InvalidChange(String),
+
+    #[error("The tokens have no locktime with a refund key of this wallet")]
+    NotReclaimable,
+
+    #[error("The tokens can't be reclaimed before their locktime {0}")]
+    LocktimeNotReached(u64),
+
+    #[error("Invoice description hash does not match the LNURL metadata")]
+    LnurlMetadataMismatch,
+
+    #[error("Invalid LNURL {0}")]
+    InvalidLnurl(String),
+
+    #[error("LNURL service returned an error: {0}")]
+    Lnurl(String),
+
+    #[error("Amount {0} sat is outside of the LNURL-withdraw limits of {1} to {2} sat")]
+    LnurlWithdrawLimits(u64, u64, u64),
+
+    #[error("Invalid lightning address {0}")]
+    InvalidLightningAddress(String),
+
+    #[error("Lightning address {0} can not be resolved: {1}")]
+    LightningAddressNotFound(String, String),
+
+    #[error("Amount {0} sat is outside of the LNURL-pay limits of {1} to {2} sat")]
+    LnurlPayLimits(u64, u64, u64),
 }
@@ -4,10 +4,53 @@ pub mod reqwest;
 #[cfg(target_arch = "wasm32")]
 pub mod wasm;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ws;
+
+#[cfg(not(target_arch = "wasm32"))]
+type ETagCache =
+    std::sync::Arc<std::sync::Mutex<std::collections::HashMap<url::Url, (String, String)>>>;
+
+/// Connection settings of the http client. Clones of a client share its connection pool, so
+/// repeated requests to a mint reuse the connection instead of doing a new TLS handshake.
+/// The browser manages the connections of the wasm client, so the settings are ignored there.
+#[derive(Debug, Clone)]
+pub struct HttpClientConfig {
+    /// idle connections are closed after this duration. `None` keeps them open
+    pub pool_idle_timeout: Option<std::time::Duration>,
+    pub pool_max_idle_per_host: usize,
+    /// interval of HTTP/2 keep-alive pings. `None` disables them
+    pub http2_keep_alive_interval: Option<std::time::Duration>,
+    /// number of retries of GET requests after network errors and 5xx responses. POST requests
+    /// are never retried, because mint, melt and swap are not idempotent
+    pub max_retries: u32,
+    /// delay before the first retry, doubled for each further retry up to 30 seconds
+    pub retry_base_delay: std::time::Duration,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            pool_idle_timeout: Some(std::time::Duration::from_secs(90)),
+            pool_max_idle_per_host: usize::MAX,
+            http2_keep_alive_interval: None,
+            max_retries: 2,
+            retry_base_delay: std::time::Duration::from_millis(200),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CrossPlatformHttpClient {
     #[cfg(not(target_arch = "wasm32"))]
     client: ::reqwest::Client,
+    /// etag and response body of cached GET requests
+    #[cfg(not(target_arch = "wasm32"))]
+    cache: ETagCache,
+    #[cfg(not(target_arch = "wasm32"))]
+    max_retries: u32,
+    #[cfg(not(target_arch = "wasm32"))]
+    retry_base_delay: std::time::Duration,
 }
 
 impl Default for CrossPlatformHttpClient {
@@ -1,16 +1,67 @@
-use super::CrossPlatformHttpClient;
+use super::{CrossPlatformHttpClient, HttpClientConfig};
 use crate::error::MokshaWalletError;
+use bitcoin_hashes::{sha256, Hash};
+use moksha_core::primitives::CashuErrorResponse;
 use reqwest::{
-    header::{HeaderValue, CONTENT_TYPE},
-    Response, StatusCode,
+    header::{HeaderValue, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+    tls::TlsInfo,
+    RequestBuilder, Response, StatusCode,
 };
 use serde_json::Value;
+use std::time::Duration;
 use url::Url;
 
+/// upper bound of the backoff between two retries
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Doubles the backoff delay without exceeding `MAX_RETRY_DELAY`
+fn next_retry_delay(delay: Duration) -> Duration {
+    delay.saturating_mul(2).min(MAX_RETRY_DELAY)
+}
+
 impl CrossPlatformHttpClient {
     pub fn new() -> Self {
-        Self {
-            client: reqwest::Client::new(),
+        Self::with_config(&HttpClientConfig::default()).expect("Can not create http client")
+    }
+
+    pub fn with_config(config: &HttpClientConfig) -> Result<Self, MokshaWalletError> {
+        let mut builder = reqwest::Client::builder()
+            .tls_info(true)
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .pool_max_idle_per_host(config.pool_max_idle_per_host);
+        if let Some(interval) = config.http2_keep_alive_interval {
+            builder = builder
+                .http2_keep_alive_interval(interval)
+                .http2_keep_alive_while_idle(true);
+        }
+        Ok(Self {
+            client: builder.build()?,
+            cache: Default::default(),
+            max_retries: config.max_retries,
+            retry_base_delay: config.retry_base_delay,
+        })
+    }
+
+    /// Sends an idempotent request and retries it with exponential backoff after network errors
+    /// and 5xx responses. The response of the last attempt is returned.
+    async fn send_with_retry(
+        &self,
+        request: impl Fn() -> RequestBuilder,
+    ) -> Result<Response, MokshaWalletError> {
+        let mut delay = self.retry_base_delay.min(MAX_RETRY_DELAY);
+        let mut retries = 0;
+        loop {
+            let result = request().send().await;
+            let retryable = match &result {
+                Ok(resp) => resp.status().is_server_error(),
+                Err(err) => err.is_connect() || err.is_timeout(),
+            };
+            if !retryable || retries >= self.max_retries {
+                return Ok(result?);
+            }
+            tokio::time::sleep(delay).await;
+            delay = next_retry_delay(delay);
+            retries += 1;
         }
     }
 
@@ -33,10 +84,13 @@ impl CrossPlatformHttpClient {
                         //     .unwrap();
 
                         // FIXME: use the error code to return a proper error
-                        match detail {
-                            "Lightning invoice not paid yet." => {
+                        match (data["code"].as_u64(), detail) {
+                            (_, "Lightning invoice not paid yet.") => {
                                 Err(MokshaWalletError::InvoiceNotPaidYet(0, detail.to_owned()))
                             }
+                            (Some(CashuErrorResponse::PROOF_ALREADY_USED), _) => {
+                                Err(MokshaWalletError::ProofAlreadyUsed(detail.to_owned()))
+                            }
                             _ => Err(MokshaWalletError::MintError(detail.to_owned())),
                         }
                     }
@@ -50,10 +104,13 @@ impl CrossPlatformHttpClient {
                 let detail = data["detail"].as_str().expect("detail not found");
 
                 // FIXME: use the error code to return a proper error
-                match detail {
-                    "Lightning invoice not paid yet." => {
+                match (data["code"].as_u64(), detail) {
+                    (_, "Lightning invoice not paid yet.") => {
                         Err(MokshaWalletError::InvoiceNotPaidYet(0, detail.to_owned()))
                     }
+                    (Some(CashuErrorResponse::PROOF_ALREADY_USED), _) => {
+                        Err(MokshaWalletError::ProofAlreadyUsed(detail.to_owned()))
+                    }
                     _ => Err(MokshaWalletError::MintError(detail.to_owned())),
                 }
             }
@@ -63,11 +120,71 @@ impl CrossPlatformHttpClient {
     pub async fn do_get<T: serde::de::DeserializeOwned>(
         &self,
         url: &Url,
+    ) -> Result<T, MokshaWalletError> {
+        let resp = self
+            .send_with_retry(|| self.client.get(url.clone()))
+            .await?;
+        Self::extract_response_data::<T>(resp).await
+    }
+
+    /// Sends a GET request without retries, for callbacks that trigger an action on the server
+    pub async fn do_get_once<T: serde::de::DeserializeOwned>(
+        &self,
+        url: &Url,
     ) -> Result<T, MokshaWalletError> {
         let resp = self.client.get(url.clone()).send().await?;
         Self::extract_response_data::<T>(resp).await
     }
 
+    /// Sends a GET request with If-None-Match and reuses the cached body if the mint returns 304
+    pub async fn do_get_cached<T: serde::de::DeserializeOwned>(
+        &self,
+        url: &Url,
+    ) -> Result<T, MokshaWalletError> {
+        let cached = self
+            .cache
+            .lock()
+            .expect("etag cache poisoned")
+            .get(url)
+            .cloned();
+
+        let resp = self
+            .send_with_retry(|| {
+                let request = self.client.get(url.clone());
+                match &cached {
+                    Some((etag, _)) => request.header(IF_NONE_MATCH, etag),
+                    None => request,
+                }
+            })
+            .await?;
+
+        match (resp.status(), cached) {
+            (StatusCode::NOT_MODIFIED, Some((_, body))) => Ok(serde_json::from_str::<T>(&body)?),
+            // the request had no If-None-Match, so there is nothing to reuse
+            (StatusCode::NOT_MODIFIED, None) => Err(MokshaWalletError::UnexpectedResponse(
+                "304 without cached body".to_owned(),
+            )),
+            (StatusCode::OK, _) => {
+                let etag = resp
+                    .headers()
+                    .get(ETAG)
+                    .and_then(|value| value.to_str().ok())
+                    .map(ToOwned::to_owned);
+                let body = resp.text().await?;
+                let data = serde_json::from_str::<T>(&body)
+                    .map_err(|_| MokshaWalletError::UnexpectedResponse(body.clone()))?;
+                if let Some(etag) = etag {
+                    self.cache
+                        .lock()
+                        .expect("etag cache poisoned")
+                        .insert(url.clone(), (etag, body));
+                }
+                Ok(data)
+            }
+            _ => Self::extract_response_data::<T>(resp).await,
+        }
+    }
+
     pub async fn do_post<T: serde::de::DeserializeOwned, B: serde::Serialize>(
         &self,
         url: &Url,
@@ -87,4 +204,181 @@ impl CrossPlatformHttpClient {
         let resp = self.client.get(url.to_owned()).send().await?;
         Ok(resp.status().as_u16())
     }
+
+    /// Returns the hex encoded sha256 of the TLS certificate the server presents, or `None` for
+    /// plain http connections
+    pub async fn get_tls_fingerprint(
+        &self,
+        url: &Url,
+    ) -> Result<Option<String>, MokshaWalletError> {
+        let resp = self.client.get(url.to_owned()).send().await?;
+        Ok(resp
+            .extensions()
+            .get::<TlsInfo>()
+            .and_then(TlsInfo::peer_certificate)
+            .map(|der| sha256::Hash::hash(der).to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        net::SocketAddr,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    use axum::{
+        extract::{ConnectInfo, State},
+        http::StatusCode,
+        routing::get,
+        Json, Router,
+    };
+    use url::Url;
+
+    use super::{next_retry_delay, MAX_RETRY_DELAY};
+    use crate::error::MokshaWalletError;
+    use crate::http::{CrossPlatformHttpClient, HttpClientConfig};
+
+    /// Serves /v1/info, which fails with the given status until it has been hit `failures` times
+    async fn flaky_server(
+        failures: usize,
+        status: StatusCode,
+    ) -> anyhow::Result<(Url, Arc<AtomicUsize>)> {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let handler = move |State(hits): State<Arc<AtomicUsize>>| async move {
+            if hits.fetch_add(1, Ordering::SeqCst) < failures {
+                (status, Json(serde_json::json!({"detail": "unavailable"})))
+            } else {
+                (StatusCode::OK, Json(serde_json::json!({})))
+            }
+        };
+        let app = Router::new()
+            .route("/v1/info", get(handler).post(handler))
+            .with_state(hits.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = Url::parse(&format!("http://{}/v1/info", listener.local_addr()?))?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Ok((url, hits))
+    }
+
+    fn retrying_client() -> anyhow::Result<CrossPlatformHttpClient> {
+        Ok(CrossPlatformHttpClient::with_config(&HttpClientConfig {
+            max_retries: 2,
+            retry_base_delay: Duration::from_millis(1),
+            ..Default::default()
+        })?)
+    }
+
+    #[tokio::test]
+    async fn test_get_retries_server_errors() -> anyhow::Result<()> {
+        let (url, hits) = flaky_server(2, StatusCode::BAD_GATEWAY).await?;
+        retrying_client()?.do_get::<serde_json::Value>(&url).await?;
+        assert_eq!(3, hits.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_gives_up_after_max_retries() -> anyhow::Result<()> {
+        let (url, hits) = flaky_server(3, StatusCode::BAD_GATEWAY).await?;
+        let result = retrying_client()?.do_get::<serde_json::Value>(&url).await;
+        assert!(result.is_err());
+        assert_eq!(3, hits.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_delay_is_clamped() {
+        assert_eq!(
+            Duration::from_millis(400),
+            next_retry_delay(Duration::from_millis(200))
+        );
+        assert_eq!(MAX_RETRY_DELAY, next_retry_delay(Duration::from_secs(20)));
+        assert_eq!(MAX_RETRY_DELAY, next_retry_delay(MAX_RETRY_DELAY));
+        // would overflow without saturating
+        assert_eq!(MAX_RETRY_DELAY, next_retry_delay(Duration::MAX));
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_of_client_errors_and_posts() -> anyhow::Result<()> {
+        let (url, hits) = flaky_server(1, StatusCode::BAD_REQUEST).await?;
+        assert!(retrying_client()?
+            .do_get::<serde_json::Value>(&url)
+            .await
+            .is_err());
+        assert_eq!(1, hits.load(Ordering::SeqCst));
+
+        let (url, hits) = flaky_server(1, StatusCode::BAD_GATEWAY).await?;
+        assert!(retrying_client()?
+            .do_post::<serde_json::Value, _>(&url, &serde_json::json!({}))
+            .await
+            .is_err());
+        assert_eq!(1, hits.load(Ordering::SeqCst));
+
+        let (url, hits) = flaky_server(1, StatusCode::BAD_GATEWAY).await?;
+        assert!(retrying_client()?
+            .do_get_once::<serde_json::Value>(&url)
+            .await
+            .is_err());
+        assert_eq!(1, hits.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cached_get_not_modified_without_cached_body() -> anyhow::Result<()> {
+        let app = Router::new().route("/v1/keys", get(|| async { StatusCode::NOT_MODIFIED }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = Url::parse(&format!("http://{}/v1/keys", listener.local_addr()?))?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let result = CrossPlatformHttpClient::new()
+            .do_get_cached::<serde_json::Value>(&url)
+            .await;
+        assert!(matches!(
+            result,
+            Err(MokshaWalletError::UnexpectedResponse(msg)) if msg == "304 without cached body"
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_clones_reuse_connection() -> anyhow::Result<()> {
+        let peers = Arc::new(Mutex::new(Vec::<SocketAddr>::new()));
+        let app = Router::new()
+            .route(
+                "/v1/info",
+                get(
+                    |ConnectInfo(peer): ConnectInfo<SocketAddr>,
+                     State(peers): State<Arc<Mutex<Vec<SocketAddr>>>>| async move {
+                        peers.lock().unwrap().push(peer);
+                        Json(serde_json::json!({}))
+                    },
+                ),
+            )
+            .with_state(peers.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = Url::parse(&format!("http://{}/v1/info", listener.local_addr()?))?;
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+        });
+
+        let client = CrossPlatformHttpClient::new();
+        for _ in 0..3 {
+            // the wallet clones the client, e.g. into the builder
+            client.clone().do_get::<serde_json::Value>(&url).await?;
+        }
+
+        let peers = peers.lock().unwrap();
+        assert_eq!(3, peers.len());
+        assert_eq!(1, peers.iter().collect::<HashSet<_>>().len());
+        Ok(())
+    }
 }
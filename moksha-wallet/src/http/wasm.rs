@@ -3,7 +3,7 @@ use moksha_core::primitives::CashuErrorResponse;
 use crate::error::MokshaWalletError;
 use url::Url;
 
-use super::CrossPlatformHttpClient;
+use super::{CrossPlatformHttpClient, HttpClientConfig};
 use gloo_net::http::{Request, Response};
 
 impl CrossPlatformHttpClient {
@@ -11,6 +11,10 @@ impl CrossPlatformHttpClient {
         Self {}
     }
 
+    pub fn with_config(_config: &HttpClientConfig) -> Result<Self, MokshaWalletError> {
+        Ok(Self::new())
+    }
+
     pub async fn do_get<T: serde::de::DeserializeOwned>(
         &self,
         url: &Url,
@@ -19,6 +23,22 @@ impl CrossPlatformHttpClient {
         Self::extract_response_data::<T>(resp).await
     }
 
+    /// Requests are never retried on wasm
+    pub async fn do_get_once<T: serde::de::DeserializeOwned>(
+        &self,
+        url: &Url,
+    ) -> Result<T, MokshaWalletError> {
+        self.do_get(url).await
+    }
+
+    /// The browser already handles ETag revalidation for GET requests
+    pub async fn do_get_cached<T: serde::de::DeserializeOwned>(
+        &self,
+        url: &Url,
+    ) -> Result<T, MokshaWalletError> {
+        self.do_get(url).await
+    }
+
     pub async fn do_post<T: serde::de::DeserializeOwned, B: serde::Serialize>(
         &self,
         url: &Url,
@@ -56,6 +76,9 @@ impl CrossPlatformHttpClient {
                             "Lightning invoice not paid yet." => {
                                 Err(MokshaWalletError::InvoiceNotPaidYet(data.code, data.detail))
                             }
+                            _ if data.code == CashuErrorResponse::PROOF_ALREADY_USED => {
+                                Err(MokshaWalletError::ProofAlreadyUsed(data.detail))
+                            }
                             _ => Err(MokshaWalletError::MintError(data.detail)),
                         }
                     }
@@ -72,6 +95,9 @@ impl CrossPlatformHttpClient {
                     "Lightning invoice not paid yet." => {
                         Err(MokshaWalletError::InvoiceNotPaidYet(data.code, data.detail))
                     }
+                    _ if data.code == CashuErrorResponse::PROOF_ALREADY_USED => {
+                        Err(MokshaWalletError::ProofAlreadyUsed(data.detail))
+                    }
                     _ => Err(MokshaWalletError::MintError(data.detail)),
                 }
             }
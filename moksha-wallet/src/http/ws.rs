@@ -0,0 +1,130 @@
+use futures_util::{SinkExt, StreamExt};
+use moksha_core::subscription::{SubscriptionKind, WsNotification, WsRequest, WsResponse};
+use serde::de::DeserializeOwned;
+use tokio::sync::mpsc;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use url::Url;
+
+use super::CrossPlatformHttpClient;
+use crate::error::MokshaWalletError;
+
+/// updates that have not been received by the caller yet
+const QUEUE_SIZE: usize = 16;
+
+impl CrossPlatformHttpClient {
+    /// Opens a websocket to the mint and subscribes to updates of the given objects (NUT-17).
+    /// The receiver yields the payload of each update. Dropping it closes the connection.
+    pub async fn subscribe<T: DeserializeOwned + Send + 'static>(
+        &self,
+        mint_url: &Url,
+        kind: SubscriptionKind,
+        filters: Vec<String>,
+    ) -> Result<mpsc::Receiver<T>, MokshaWalletError> {
+        // http -> ws, https -> wss
+        let url = Url::parse(&mint_url.join("v1/ws")?.as_str().replacen("http", "ws", 1))?;
+        let (mut socket, _) = connect_async(url.as_str()).await?;
+
+        let sub_id = hex::encode(rand::random::<[u8; 16]>());
+        let request = WsRequest::subscribe(0, kind, &sub_id, filters);
+        socket
+            .send(Message::Text(serde_json::to_string(&request)?))
+            .await?;
+
+        // the mint answers the request before it sends any update
+        let response = loop {
+            match socket.next().await {
+                Some(Ok(Message::Text(text))) => break serde_json::from_str::<WsResponse>(&text)?,
+                Some(Ok(_)) => continue,
+                Some(Err(err)) => return Err(err.into()),
+                None => {
+                    return Err(MokshaWalletError::UnexpectedResponse(
+                        "websocket closed before the subscription was confirmed".to_owned(),
+                    ))
+                }
+            }
+        };
+        if let Some(error) = response.error {
+            return Err(MokshaWalletError::MintError(error.message));
+        }
+
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+        tokio::spawn(async move {
+            loop {
+                let message = tokio::select! {
+                    message = socket.next() => message,
+                    _ = sender.closed() => break,
+                };
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(_)) => continue,
+                    Some(Err(_)) | None => break,
+                };
+                let Ok(notification) = serde_json::from_str::<WsNotification<T>>(&text) else {
+                    continue;
+                };
+                if notification.params.sub_id == sub_id
+                    && sender.send(notification.params.payload).await.is_err()
+                {
+                    break;
+                }
+            }
+            let _ = socket.close(None).await;
+        });
+        Ok(receiver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        extract::ws::{Message, WebSocketUpgrade},
+        routing::get,
+        Router,
+    };
+    use moksha_core::subscription::{
+        SubscriptionKind, WsNotification, WsRequest, WsRequestMethod, WsResponse,
+    };
+    use url::Url;
+
+    use crate::http::CrossPlatformHttpClient;
+
+    #[tokio::test]
+    async fn test_subscribe() -> anyhow::Result<()> {
+        let app = Router::new().route(
+            "/v1/ws",
+            get(|ws: WebSocketUpgrade| async {
+                ws.on_upgrade(|mut socket| async move {
+                    let Some(Ok(Message::Text(text))) = socket.recv().await else {
+                        return;
+                    };
+                    let request = serde_json::from_str::<WsRequest>(&text).unwrap();
+                    let WsRequestMethod::Subscribe(params) = request.method else {
+                        return;
+                    };
+                    let messages = [
+                        serde_json::to_string(&WsResponse::ok(request.id, &params.sub_id)),
+                        // updates of other subscriptions are skipped
+                        serde_json::to_string(&WsNotification::new("other", "skipped")),
+                        serde_json::to_string(&WsNotification::new(&params.sub_id, "update")),
+                    ];
+                    for message in messages {
+                        socket.send(Message::Text(message.unwrap())).await.unwrap();
+                    }
+                })
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let mint_url = Url::parse(&format!("http://{}", listener.local_addr()?))?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut updates = CrossPlatformHttpClient::new()
+            .subscribe::<String>(
+                &mint_url,
+                SubscriptionKind::Bolt11MintQuote,
+                vec!["quote".to_owned()],
+            )
+            .await?;
+        assert_eq!(Some("update".to_owned()), updates.recv().await);
+        Ok(())
+    }
+}
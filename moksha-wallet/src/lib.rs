@@ -2,6 +2,7 @@ pub mod client;
 pub mod config_path;
 pub mod error;
 pub mod http;
+pub mod lnurl;
 pub mod localstore;
 pub mod secret;
 pub mod wallet;
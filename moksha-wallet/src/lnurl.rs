@@ -0,0 +1,257 @@
+//! Helpers for paying LNURL-pay requests (LUD-06) and LNURL-withdraw requests (LUD-03).
+
+use bech32::FromBase32;
+use bitcoin_hashes::{sha256, Hash};
+use lightning_invoice::{Bolt11Invoice as LNInvoice, Bolt11InvoiceDescription};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+use url::Url;
+
+use crate::error::MokshaWalletError;
+
+/// Parameters of a LNURL-withdraw request (LUD-03). Amounts are in msat.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LnurlWithdrawRequest {
+    pub tag: String,
+    pub callback: String,
+    pub k1: String,
+    pub min_withdrawable: u64,
+    pub max_withdrawable: u64,
+    #[serde(default)]
+    pub default_description: String,
+}
+
+impl LnurlWithdrawRequest {
+    /// Checks that the amount in sats is within the limits of the withdraw request
+    pub fn check_amount(&self, amount: u64) -> Result<(), MokshaWalletError> {
+        let min = self.min_withdrawable.div_ceil(1_000);
+        let max = self.max_withdrawable / 1_000;
+        if amount < min || amount > max {
+            return Err(MokshaWalletError::LnurlWithdrawLimits(amount, min, max));
+        }
+        Ok(())
+    }
+
+    /// Url that asks the withdraw service to pay the invoice
+    pub fn callback_url(&self, payment_request: &str) -> Result<Url, MokshaWalletError> {
+        let mut callback = Url::parse(&self.callback)?;
+        callback
+            .query_pairs_mut()
+            .append_pair("k1", &self.k1)
+            .append_pair("pr", payment_request);
+        Ok(callback)
+    }
+}
+
+/// Parameters of a LNURL-pay request (LUD-06). Amounts are in msat.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LnurlPayRequest {
+    pub tag: String,
+    pub callback: String,
+    pub min_sendable: u64,
+    pub max_sendable: u64,
+    pub metadata: String,
+}
+
+impl LnurlPayRequest {
+    /// Checks that the amount in sats is within the limits of the pay request
+    pub fn check_amount(&self, amount: u64) -> Result<(), MokshaWalletError> {
+        let min = self.min_sendable.div_ceil(1_000);
+        let max = self.max_sendable / 1_000;
+        if amount < min || amount > max {
+            return Err(MokshaWalletError::LnurlPayLimits(amount, min, max));
+        }
+        Ok(())
+    }
+
+    /// Url that asks the pay service for an invoice of the amount in msat
+    pub fn callback_url(&self, amount_msat: u64) -> Result<Url, MokshaWalletError> {
+        let mut callback = Url::parse(&self.callback)?;
+        callback
+            .query_pairs_mut()
+            .append_pair("amount", &amount_msat.to_string());
+        Ok(callback)
+    }
+}
+
+/// Invoice returned by the callback of a LNURL-pay request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LnurlPayInvoice {
+    pub pr: String,
+}
+
+/// Returns the url of the LNURL-pay request behind a lightning address `user@domain` (LUD-16)
+pub fn lightning_address_url(address: &str) -> Result<Url, MokshaWalletError> {
+    let address = address.trim();
+    let invalid = || MokshaWalletError::InvalidLightningAddress(address.to_owned());
+    let (user, domain) = address.split_once('@').ok_or_else(invalid)?;
+    if user.is_empty() || domain.is_empty() || domain.contains(['/', '@']) {
+        return Err(invalid());
+    }
+
+    let scheme = match domain.ends_with(".onion") {
+        true => "http",
+        false => "https",
+    };
+    Url::parse(&format!("{scheme}://{domain}/.well-known/lnurlp/{user}")).map_err(|_| invalid())
+}
+
+/// Decodes a bech32 encoded LNURL (LUD-01). Plain urls and the `lnurlw://` scheme (LUD-17) are accepted as well.
+pub fn decode_lnurl(lnurl: &str) -> Result<Url, MokshaWalletError> {
+    let lnurl = lnurl.trim();
+    let lnurl = lnurl
+        .strip_prefix("lightning:")
+        .or_else(|| lnurl.strip_prefix("LIGHTNING:"))
+        .unwrap_or(lnurl);
+
+    if let Some(rest) = lnurl.strip_prefix("lnurlw://") {
+        let scheme = match rest.ends_with(".onion") || rest.contains(".onion/") {
+            true => "http",
+            false => "https",
+        };
+        return Ok(Url::parse(&format!("{scheme}://{rest}"))?);
+    }
+    if lnurl.starts_with("https://") || lnurl.starts_with("http://") {
+        return Ok(Url::parse(lnurl)?);
+    }
+
+    let invalid = || MokshaWalletError::InvalidLnurl(lnurl.to_owned());
+    let (hrp, data, _) = bech32::decode(lnurl).map_err(|_| invalid())?;
+    if hrp != "lnurl" {
+        return Err(invalid());
+    }
+    let bytes = Vec::<u8>::from_base32(&data).map_err(|_| invalid())?;
+    Ok(Url::parse(&String::from_utf8(bytes)?)?)
+}
+
+/// Parses a LNURL response and turns `{"status": "ERROR", "reason": ...}` into an error
+pub fn parse_lnurl_response<T: DeserializeOwned>(value: Value) -> Result<T, MokshaWalletError> {
+    if value["status"].as_str() == Some("ERROR") {
+        let reason = value["reason"].as_str().unwrap_or("unknown error");
+        return Err(MokshaWalletError::Lnurl(reason.to_owned()));
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Checks that an invoice returned by a LNURL-pay server commits to the metadata of the pay request.
+///
+/// LUD-06 requires the `description_hash` of the invoice to be the sha256 of the metadata string,
+/// so a malicious server can't swap in an invoice for something else.
+pub fn verify_invoice_metadata(
+    payment_request: &str,
+    metadata: &str,
+) -> Result<(), MokshaWalletError> {
+    let invoice = LNInvoice::from_str(payment_request)
+        .map_err(|err| MokshaWalletError::DecodeInvoice(payment_request.to_owned(), err))?;
+
+    let metadata_hash = sha256::Hash::hash(metadata.as_bytes());
+    match invoice.description() {
+        Bolt11InvoiceDescription::Hash(hash)
+            if AsRef::<[u8]>::as_ref(&hash.0) == metadata_hash.as_byte_array() =>
+        {
+            Ok(())
+        }
+        _ => Err(MokshaWalletError::LnurlMetadataMismatch),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{LnurlPayRequest, LnurlWithdrawRequest};
+    use crate::error::MokshaWalletError;
+
+    // 21 sats, description hash of [["text/plain","moksha"]]
+    const INVOICE: &str = "lnbcrt210n1pj48ugqhp5lqel9mmhhue5et7zywv4r2le0yagqf2hvjj302eww9ljw0ptsvuqpp5wkrhhdqa8ya4lwz9tnnqanvdmgqp6p33vjttzn0607y4v4hwef9qsp5qurswpc8qurswpc8qurswpc8qurswpc8qurswpc8qurswpc8qurs9qrsgqcqzysdrrq42aah6uqqhhn6llfvg0tnkrpv654fp75rknxaql3kd7hhlxxgna3zen7lz7ujzjqke0txculd42476efkq3fz85m6rcz7qjjqfsphwgaxz";
+
+    #[test]
+    fn test_verify_invoice_metadata() -> anyhow::Result<()> {
+        super::verify_invoice_metadata(INVOICE, r#"[["text/plain","moksha"]]"#)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_invoice_metadata_mismatch() {
+        let result = super::verify_invoice_metadata(INVOICE, r#"[["text/plain","other"]]"#);
+        assert!(matches!(
+            result,
+            Err(MokshaWalletError::LnurlMetadataMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_decode_lnurl() -> anyhow::Result<()> {
+        // example from LUD-01
+        let url = super::decode_lnurl("LNURL1DP68GURN8GHJ7UM9WFMXJCM99E3K7MF0V9CXJ0M385EKVCENXC6R2C35XVUKXEFCV5MKVV34X5EKZD3EV56NYD3HXQURZEPEXEJXXEPNXSCRVWFNV9NXZCN9XQ6XYEFHVGCXXCMYXYMNSERXFQ5FNS")?;
+        assert_eq!(
+            "https://service.com/api?q=3fc3645b439ce8e7f2553a69e5267081d96dcd340693afabe04be7b0ccd178df",
+            url.as_str()
+        );
+        assert_eq!(
+            "https://service.com/withdraw",
+            super::decode_lnurl("lnurlw://service.com/withdraw")?.as_str()
+        );
+        assert!(super::decode_lnurl("lnurl1invalid").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_lnurl_error_response() {
+        let result = super::parse_lnurl_response::<LnurlWithdrawRequest>(
+            json!({"status": "ERROR", "reason": "withdraw link already used"}),
+        );
+        assert!(
+            matches!(result, Err(MokshaWalletError::Lnurl(reason)) if reason == "withdraw link already used")
+        );
+    }
+
+    #[test]
+    fn test_lightning_address_url() -> anyhow::Result<()> {
+        assert_eq!(
+            "https://service.com/.well-known/lnurlp/satoshi",
+            super::lightning_address_url("satoshi@service.com")?.as_str()
+        );
+        assert_eq!(
+            "http://service.onion/.well-known/lnurlp/satoshi",
+            super::lightning_address_url("satoshi@service.onion")?.as_str()
+        );
+        for address in [
+            "satoshi",
+            "@service.com",
+            "satoshi@",
+            "satoshi@service.com/pay",
+        ] {
+            assert!(matches!(
+                super::lightning_address_url(address),
+                Err(MokshaWalletError::InvalidLightningAddress(_))
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_lnurl_pay_request() -> anyhow::Result<()> {
+        let request = super::parse_lnurl_response::<LnurlPayRequest>(json!({
+            "tag": "payRequest",
+            "callback": "https://service.com/lnurlp/satoshi/callback",
+            "minSendable": 1_000,
+            "maxSendable": 100_000,
+            "metadata": r#"[["text/plain","moksha"]]"#,
+        }))?;
+
+        assert!(request.check_amount(21).is_ok());
+        assert!(matches!(
+            request.check_amount(101),
+            Err(MokshaWalletError::LnurlPayLimits(101, 1, 100))
+        ));
+        assert_eq!(
+            "https://service.com/lnurlp/satoshi/callback?amount=21000",
+            request.callback_url(21_000)?.as_str()
+        );
+        Ok(())
+    }
+}
@@ -1,8 +1,13 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, str::FromStr};
 
 use async_trait::async_trait;
-use moksha_core::{keyset::KeysetId, primitives::CurrencyUnit, proof::Proofs};
+use moksha_core::{
+    keyset::KeysetId,
+    primitives::CurrencyUnit,
+    proof::{Proof, Proofs},
+};
 use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
 use url::Url;
 
 use crate::error::MokshaWalletError;
@@ -13,6 +18,9 @@ pub mod sqlite;
 #[cfg(target_arch = "wasm32")]
 pub mod rexie;
 
+/// account the seed, proofs and keysets of a localstore belong to if no other is chosen
+pub const DEFAULT_ACCOUNT: &str = "default";
+
 #[derive(Debug, Clone)]
 pub struct WalletKeyset {
     /// primary key
@@ -58,6 +66,198 @@ impl WalletKeyset {
     }
 }
 
+/// state changing wallet calls that are recorded in the operation log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Mint,
+    Melt,
+    Swap,
+    Send,
+    Receive,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mint => write!(f, "mint"),
+            Self::Melt => write!(f, "melt"),
+            Self::Swap => write!(f, "swap"),
+            Self::Send => write!(f, "send"),
+            Self::Receive => write!(f, "receive"),
+        }
+    }
+}
+
+impl FromStr for Operation {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mint" => Ok(Self::Mint),
+            "melt" => Ok(Self::Melt),
+            "swap" => Ok(Self::Swap),
+            "send" => Ok(Self::Send),
+            "receive" => Ok(Self::Receive),
+            _ => Err("unknown operation"),
+        }
+    }
+}
+
+/// Entry of the operation log, a low level audit trail for debugging. Unlike the transaction
+/// history it records failed calls as well.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperationLogEntry {
+    /// primary key
+    pub id: Option<u64>,
+    pub operation: Operation,
+    /// json encoded arguments of the call, e.g. amounts and the Y of the spent proofs
+    pub inputs: String,
+    /// json encoded result of the call
+    pub outputs: String,
+    /// the error if the call failed
+    pub error: Option<String>,
+    /// unix timestamp, set by the localstore
+    pub created_at: Option<i64>,
+}
+
+/// Completed operation in the transaction history that is shown to the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletTransaction {
+    /// primary key
+    pub id: Option<u64>,
+    pub operation: Operation,
+    /// amount in the unit of the keyset. Melts include the paid fees
+    pub amount: u64,
+    pub unit: CurrencyUnit,
+    pub mint_url: Url,
+    pub memo: Option<String>,
+    /// unix timestamp, set by the localstore
+    pub created_at: Option<i64>,
+}
+
+impl WalletTransaction {
+    pub fn new(
+        operation: Operation,
+        amount: u64,
+        wallet_keyset: &WalletKeyset,
+        memo: Option<String>,
+    ) -> Self {
+        Self {
+            id: None,
+            operation,
+            amount,
+            unit: wallet_keyset.currency_unit.clone(),
+            mint_url: wallet_keyset.mint_url.to_owned(),
+            memo,
+            created_at: None,
+        }
+    }
+}
+
+/// Proof as it is kept in the localstore, together with data that is never sent to the mint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredProof {
+    #[serde(flatten)]
+    pub proof: Proof,
+    /// index the secret was derived from, `None` for received and locked proofs
+    pub derivation_index: Option<u32>,
+    /// tag for coin control, e.g. `savings`
+    pub label: Option<String>,
+}
+
+impl From<Proof> for StoredProof {
+    fn from(proof: Proof) -> Self {
+        Self {
+            proof,
+            derivation_index: None,
+            label: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StoredProofs(pub Vec<StoredProof>);
+
+impl StoredProofs {
+    pub fn new(proofs: Vec<StoredProof>) -> Self {
+        Self(proofs)
+    }
+
+    /// assigns consecutive derivation indexes, starting at `start_index`
+    pub fn with_derivation_indexes(proofs: Proofs, start_index: u32) -> Self {
+        proofs
+            .proofs()
+            .into_iter()
+            .zip(start_index..)
+            .map(|(proof, index)| StoredProof {
+                proof,
+                derivation_index: Some(index),
+                label: None,
+            })
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    /// returns the proofs without the wallet data
+    pub fn proofs(&self) -> Proofs {
+        self.0
+            .iter()
+            .map(|stored| stored.proof.clone())
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    pub fn total_amount(&self) -> u64 {
+        self.0.iter().map(|stored| stored.proof.amount).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// returns the proofs with the given label
+    pub fn with_label(&self, label: &str) -> Self {
+        self.0
+            .iter()
+            .filter(|stored| stored.label.as_deref() == Some(label))
+            .cloned()
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    /// sets the label of all proofs, `None` removes it
+    pub fn labeled(self, label: Option<&str>) -> Self {
+        self.0
+            .into_iter()
+            .map(|stored| StoredProof {
+                label: label.map(str::to_owned),
+                ..stored
+            })
+            .collect::<Vec<_>>()
+            .into()
+    }
+}
+
+impl From<Vec<StoredProof>> for StoredProofs {
+    fn from(proofs: Vec<StoredProof>) -> Self {
+        Self(proofs)
+    }
+}
+
+impl From<Proofs> for StoredProofs {
+    fn from(proofs: Proofs) -> Self {
+        Self(proofs.proofs().into_iter().map(Into::into).collect())
+    }
+}
+
+/// Proofs that were sent to the mint for a melt whose result has not been stored yet
+#[derive(Debug, Clone)]
+pub struct PendingMelt {
+    pub mint_url: Url,
+    pub quote: String,
+    pub proofs: StoredProofs,
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 #[async_trait(?Send)]
 pub trait LocalStore {
@@ -68,15 +268,36 @@ pub trait LocalStore {
         tx: &mut sqlx::Transaction<Self::DB>,
         proofs: &Proofs,
     ) -> Result<(), MokshaWalletError>;
+    async fn add_stored_proofs(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        proofs: &StoredProofs,
+    ) -> Result<(), MokshaWalletError>;
+    /// adds proofs that have no derivation index, e.g. received ones
     async fn add_proofs(
         &self,
         tx: &mut sqlx::Transaction<Self::DB>,
         proofs: &Proofs,
+    ) -> Result<(), MokshaWalletError> {
+        self.add_stored_proofs(tx, &proofs.clone().into()).await
+    }
+    /// sets the label of the stored proofs. `None` removes it
+    async fn update_proof_labels(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        proofs: &Proofs,
+        label: Option<&str>,
     ) -> Result<(), MokshaWalletError>;
+    async fn get_stored_proofs(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+    ) -> Result<StoredProofs, MokshaWalletError>;
     async fn get_proofs(
         &self,
         tx: &mut sqlx::Transaction<Self::DB>,
-    ) -> Result<Proofs, MokshaWalletError>;
+    ) -> Result<Proofs, MokshaWalletError> {
+        Ok(self.get_stored_proofs(tx).await?.proofs())
+    }
 
     async fn get_keysets(
         &self,
@@ -88,11 +309,20 @@ pub trait LocalStore {
         keyset: &WalletKeyset,
     ) -> Result<(), MokshaWalletError>;
 
+    /// raises the last index of the keyset, it is never lowered
     async fn update_keyset_last_index(
         &self,
         tx: &mut sqlx::Transaction<Self::DB>,
         keyset: &WalletKeyset,
     ) -> Result<(), MokshaWalletError>;
+    /// increments the last index of the keyset by `count` in a single statement and returns the
+    /// first of the reserved indexes, so concurrent callers never get the same ones
+    async fn reserve_keyset_indexes(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        keyset: &WalletKeyset,
+        count: u32,
+    ) -> Result<u64, MokshaWalletError>;
 
     async fn add_seed(
         &self,
@@ -100,10 +330,98 @@ pub trait LocalStore {
         seed_words: &str,
     ) -> Result<(), MokshaWalletError>;
 
+    /// returns the seed of the account the store was opened for
     async fn get_seed(
         &self,
         tx: &mut sqlx::Transaction<Self::DB>,
     ) -> Result<Option<String>, MokshaWalletError>;
+
+    async fn get_seed_for_account(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        account: &str,
+    ) -> Result<Option<String>, MokshaWalletError>;
+
+    /// returns the names of all accounts that have a seed
+    async fn list_accounts(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+    ) -> Result<Vec<String>, MokshaWalletError>;
+
+    async fn get_setting(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        key: &str,
+    ) -> Result<Option<String>, MokshaWalletError>;
+
+    async fn upsert_setting(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        key: &str,
+        value: &str,
+    ) -> Result<(), MokshaWalletError>;
+
+    async fn delete_setting(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        key: &str,
+    ) -> Result<(), MokshaWalletError>;
+
+    /// appends the entry, removes the oldest entries if the log has more than `max_entries`
+    /// and returns the id of the new entry
+    async fn add_log_entry(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        entry: &OperationLogEntry,
+        max_entries: u64,
+    ) -> Result<u64, MokshaWalletError>;
+
+    /// sets the result of an entry that was added before the call finished
+    async fn update_log_entry(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        id: u64,
+        outputs: &str,
+        error: Option<&str>,
+    ) -> Result<(), MokshaWalletError>;
+
+    /// returns the latest entries, newest first
+    async fn get_log_entries(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        limit: u64,
+    ) -> Result<Vec<OperationLogEntry>, MokshaWalletError>;
+
+    async fn add_transaction(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        transaction: &WalletTransaction,
+    ) -> Result<(), MokshaWalletError>;
+
+    /// returns the latest transactions, newest first
+    async fn list_transactions(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        limit: u64,
+    ) -> Result<Vec<WalletTransaction>, MokshaWalletError>;
+
+    async fn add_pending_melt(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        pending_melt: &PendingMelt,
+    ) -> Result<(), MokshaWalletError>;
+
+    /// returns the pending melts, grouped by mint and quote
+    async fn get_pending_melts(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+    ) -> Result<Vec<PendingMelt>, MokshaWalletError>;
+
+    async fn delete_pending_melt_proofs(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        proofs: &Proofs,
+    ) -> Result<(), MokshaWalletError>;
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -128,12 +446,31 @@ pub trait LocalStore {
         tx: &mut RexieTransaction,
         proofs: &Proofs,
     ) -> Result<(), MokshaWalletError>;
+    async fn add_stored_proofs(
+        &self,
+        tx: &mut RexieTransaction,
+        proofs: &StoredProofs,
+    ) -> Result<(), MokshaWalletError>;
     async fn add_proofs(
         &self,
         tx: &mut RexieTransaction,
         proofs: &Proofs,
+    ) -> Result<(), MokshaWalletError> {
+        self.add_stored_proofs(tx, &proofs.clone().into()).await
+    }
+    async fn update_proof_labels(
+        &self,
+        tx: &mut RexieTransaction,
+        proofs: &Proofs,
+        label: Option<&str>,
     ) -> Result<(), MokshaWalletError>;
-    async fn get_proofs(&self, tx: &mut RexieTransaction) -> Result<Proofs, MokshaWalletError>;
+    async fn get_stored_proofs(
+        &self,
+        tx: &mut RexieTransaction,
+    ) -> Result<StoredProofs, MokshaWalletError>;
+    async fn get_proofs(&self, tx: &mut RexieTransaction) -> Result<Proofs, MokshaWalletError> {
+        Ok(self.get_stored_proofs(tx).await?.proofs())
+    }
 
     async fn get_keysets(
         &self,
@@ -152,6 +489,13 @@ pub trait LocalStore {
         keyset: &WalletKeyset,
     ) -> Result<(), MokshaWalletError>;
 
+    async fn reserve_keyset_indexes(
+        &self,
+        _tx: &mut RexieTransaction,
+        keyset: &WalletKeyset,
+        count: u32,
+    ) -> Result<u64, MokshaWalletError>;
+
     async fn add_seed(
         &self,
         _tx: &mut RexieTransaction,
@@ -162,6 +506,86 @@ pub trait LocalStore {
         &self,
         _tx: &mut RexieTransaction,
     ) -> Result<Option<String>, MokshaWalletError>;
+
+    async fn get_seed_for_account(
+        &self,
+        _tx: &mut RexieTransaction,
+        account: &str,
+    ) -> Result<Option<String>, MokshaWalletError>;
+
+    async fn list_accounts(
+        &self,
+        _tx: &mut RexieTransaction,
+    ) -> Result<Vec<String>, MokshaWalletError>;
+
+    async fn get_setting(
+        &self,
+        _tx: &mut RexieTransaction,
+        key: &str,
+    ) -> Result<Option<String>, MokshaWalletError>;
+
+    async fn upsert_setting(
+        &self,
+        _tx: &mut RexieTransaction,
+        key: &str,
+        value: &str,
+    ) -> Result<(), MokshaWalletError>;
+
+    async fn delete_setting(
+        &self,
+        _tx: &mut RexieTransaction,
+        key: &str,
+    ) -> Result<(), MokshaWalletError>;
+
+    async fn add_log_entry(
+        &self,
+        _tx: &mut RexieTransaction,
+        entry: &OperationLogEntry,
+        max_entries: u64,
+    ) -> Result<u64, MokshaWalletError>;
+
+    async fn update_log_entry(
+        &self,
+        _tx: &mut RexieTransaction,
+        id: u64,
+        outputs: &str,
+        error: Option<&str>,
+    ) -> Result<(), MokshaWalletError>;
+
+    async fn get_log_entries(
+        &self,
+        _tx: &mut RexieTransaction,
+        limit: u64,
+    ) -> Result<Vec<OperationLogEntry>, MokshaWalletError>;
+
+    async fn add_transaction(
+        &self,
+        _tx: &mut RexieTransaction,
+        transaction: &WalletTransaction,
+    ) -> Result<(), MokshaWalletError>;
+
+    async fn list_transactions(
+        &self,
+        _tx: &mut RexieTransaction,
+        limit: u64,
+    ) -> Result<Vec<WalletTransaction>, MokshaWalletError>;
+
+    async fn add_pending_melt(
+        &self,
+        _tx: &mut RexieTransaction,
+        pending_melt: &PendingMelt,
+    ) -> Result<(), MokshaWalletError>;
+
+    async fn get_pending_melts(
+        &self,
+        _tx: &mut RexieTransaction,
+    ) -> Result<Vec<PendingMelt>, MokshaWalletError>;
+
+    async fn delete_pending_melt_proofs(
+        &self,
+        _tx: &mut RexieTransaction,
+        proofs: &Proofs,
+    ) -> Result<(), MokshaWalletError>;
 }
 
 #[cfg(test)]
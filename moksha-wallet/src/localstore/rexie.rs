@@ -1,14 +1,28 @@
-use super::{LocalStore, RexieTransaction, WalletKeyset};
+use super::{
+    LocalStore, OperationLogEntry, PendingMelt, RexieTransaction, StoredProof, StoredProofs,
+    WalletKeyset, WalletTransaction, DEFAULT_ACCOUNT,
+};
 use crate::error::MokshaWalletError;
 use async_trait::async_trait;
-use moksha_core::proof::{Proof, Proofs};
+use moksha_core::{
+    keyset::KeysetId,
+    primitives::CurrencyUnit,
+    proof::{Proof, Proofs},
+};
 use rexie::*;
+use url::Url;
 use wasm_bindgen::JsValue;
 
 #[derive(Clone, Default)]
 pub struct RexieLocalStore;
 
 const STORE_NAME: &str = "proofs";
+const TRANSACTIONS_STORE_NAME: &str = "transactions";
+const SETTINGS_STORE_NAME: &str = "settings";
+const OPERATION_LOG_STORE_NAME: &str = "operation_log";
+const PENDING_MELTS_STORE_NAME: &str = "pending_melts";
+const KEYSETS_STORE_NAME: &str = "keysets";
+const SEEDS_STORE_NAME: &str = "seeds";
 
 impl RexieLocalStore {
     pub async fn new() -> Self {
@@ -19,13 +33,57 @@ impl RexieLocalStore {
 impl RexieLocalStore {
     async fn get_rexie() -> Rexie {
         Rexie::builder("moksha")
-            .version(1)
+            .version(7)
             .add_object_store(ObjectStore::new(STORE_NAME))
+            .add_object_store(ObjectStore::new(TRANSACTIONS_STORE_NAME).auto_increment(true))
+            .add_object_store(ObjectStore::new(SETTINGS_STORE_NAME))
+            .add_object_store(ObjectStore::new(OPERATION_LOG_STORE_NAME).auto_increment(true))
+            .add_object_store(ObjectStore::new(PENDING_MELTS_STORE_NAME))
+            .add_object_store(ObjectStore::new(KEYSETS_STORE_NAME).auto_increment(true))
+            .add_object_store(ObjectStore::new(SEEDS_STORE_NAME))
             .build()
             .await
             .unwrap()
     }
 
+    fn keyset_to_js(keyset: &WalletKeyset) -> JsValue {
+        let json = serde_json::json!({
+            "keyset_id": keyset.keyset_id.to_string(),
+            "mint_url": keyset.mint_url.as_str(),
+            "currency_unit": keyset.currency_unit.to_string(),
+            "last_index": keyset.last_index,
+            "public_keys": keyset.public_keys,
+            "active": keyset.active,
+        })
+        .to_string();
+        serde_wasm_bindgen::to_value(&json).unwrap()
+    }
+
+    fn keyset_from_js(key: &JsValue, value: JsValue) -> WalletKeyset {
+        let json: String = serde_wasm_bindgen::from_value(value).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        WalletKeyset {
+            id: key.as_f64().map(|id| id as u64),
+            keyset_id: value["keyset_id"]
+                .as_str()
+                .and_then(|keyset_id| KeysetId::new(keyset_id).ok())
+                .expect("invalid keyset_id in localstore"),
+            mint_url: value["mint_url"]
+                .as_str()
+                .and_then(|url| Url::parse(url).ok())
+                .expect("invalid URL in localstore"),
+            currency_unit: value["currency_unit"]
+                .as_str()
+                .expect("invalid currency_unit in localstore")
+                .to_owned()
+                .into(),
+            last_index: value["last_index"].as_u64().unwrap_or_default(),
+            public_keys: serde_json::from_value(value["public_keys"].clone())
+                .expect("invalid json in localstore"),
+            active: value["active"].as_bool().unwrap_or_default(),
+        }
+    }
+
     fn get_key(proof: &Proof) -> JsValue {
         let key = serde_json::json!({
             "key": proof.secret,
@@ -38,14 +96,14 @@ impl RexieLocalStore {
 #[async_trait(?Send)]
 impl LocalStore for RexieLocalStore {
     // FIXME implement tx-handling for Rexie
-    async fn add_proofs(
+    async fn add_stored_proofs(
         &self,
         _tx: &mut RexieTransaction,
-        proofs: &Proofs,
+        proofs: &StoredProofs,
     ) -> std::result::Result<(), MokshaWalletError> {
         let db = Self::get_rexie().await;
 
-        for proof in proofs.proofs() {
+        for proof in &proofs.0 {
             let transaction = db
                 .transaction(&[STORE_NAME], rexie::TransactionMode::ReadWrite)
                 .expect("db error");
@@ -54,7 +112,7 @@ impl LocalStore for RexieLocalStore {
             let js_value = serde_wasm_bindgen::to_value(&json).unwrap();
 
             store
-                .add(&js_value, Some(&Self::get_key(&proof)))
+                .add(&js_value, Some(&Self::get_key(&proof.proof)))
                 .await
                 .expect("db store error");
             transaction.done().await.expect("db error");
@@ -63,10 +121,41 @@ impl LocalStore for RexieLocalStore {
         Ok(())
     }
 
-    async fn get_proofs(
+    async fn update_proof_labels(
         &self,
         _tx: &mut RexieTransaction,
-    ) -> std::result::Result<Proofs, MokshaWalletError> {
+        proofs: &Proofs,
+        label: Option<&str>,
+    ) -> std::result::Result<(), MokshaWalletError> {
+        let db = Self::get_rexie().await;
+        let transaction = db
+            .transaction(&[STORE_NAME], rexie::TransactionMode::ReadWrite)
+            .expect("db error");
+        let store = transaction.store(STORE_NAME).expect("db error");
+
+        for proof in proofs.proofs() {
+            let key = Self::get_key(&proof);
+            // proofs that are not stored are skipped, like the UPDATE of the sqlite store
+            let Some(stored) = store.get(&key).await.expect("db error").as_string() else {
+                continue;
+            };
+            let stored = StoredProof {
+                label: label.map(str::to_owned),
+                ..serde_json::from_str::<StoredProof>(&stored).unwrap()
+            };
+            let json = serde_json::to_string(&stored).unwrap();
+            let js_value = serde_wasm_bindgen::to_value(&json).unwrap();
+            store.put(&js_value, Some(&key)).await.expect("db error");
+        }
+        transaction.done().await.expect("db error");
+
+        Ok(())
+    }
+
+    async fn get_stored_proofs(
+        &self,
+        _tx: &mut RexieTransaction,
+    ) -> std::result::Result<StoredProofs, MokshaWalletError> {
         let db = Self::get_rexie().await;
         let transaction = db
             .transaction(&[STORE_NAME], rexie::TransactionMode::ReadOnly)
@@ -78,12 +167,13 @@ impl LocalStore for RexieLocalStore {
                 let mut proofs = vec![];
                 for (_, proof) in all {
                     let proof: String = serde_wasm_bindgen::from_value(proof).unwrap();
-                    let proof = serde_json::from_str::<Proof>(&proof).unwrap();
+                    // proofs stored before the wallet data was added parse without it
+                    let proof = serde_json::from_str::<StoredProof>(&proof).unwrap();
                     proofs.push(proof);
                 }
-                Ok(Proofs::new(proofs))
+                Ok(StoredProofs::new(proofs))
             }
-            Err(_) => Ok(Proofs::new(vec![])),
+            Err(_) => Ok(StoredProofs::default()),
         }
     }
 
@@ -114,37 +204,547 @@ impl LocalStore for RexieLocalStore {
         &self,
         _tx: &mut RexieTransaction,
     ) -> std::result::Result<Vec<WalletKeyset>, MokshaWalletError> {
-        todo!()
+        let db = Self::get_rexie().await;
+        let rexie_tx = db
+            .transaction(&[KEYSETS_STORE_NAME], rexie::TransactionMode::ReadOnly)
+            .expect("db error");
+        let store = rexie_tx.store(KEYSETS_STORE_NAME).expect("db error");
+        let all = store
+            .get_all(None, None, None, None)
+            .await
+            .expect("db error");
+
+        Ok(all
+            .into_iter()
+            .map(|(key, value)| Self::keyset_from_js(&key, value))
+            .collect())
     }
 
     async fn upsert_keyset(
         &self,
         _tx: &mut RexieTransaction,
-        _keyset: &WalletKeyset,
+        keyset: &WalletKeyset,
     ) -> std::result::Result<(), MokshaWalletError> {
-        todo!()
+        let db = Self::get_rexie().await;
+        let rexie_tx = db
+            .transaction(&[KEYSETS_STORE_NAME], rexie::TransactionMode::ReadWrite)
+            .expect("db error");
+        let store = rexie_tx.store(KEYSETS_STORE_NAME).expect("db error");
+        let stored = store
+            .get_all(None, None, None, None)
+            .await
+            .expect("db error")
+            .into_iter()
+            .map(|(key, value)| (key.clone(), Self::keyset_from_js(&key, value)))
+            .find(|(_, stored)| {
+                stored.keyset_id == keyset.keyset_id && stored.mint_url == keyset.mint_url
+            });
+
+        match stored {
+            // the last index is kept, like the ON CONFLICT clause of the sqlite store
+            Some((key, stored)) => {
+                let updated = WalletKeyset {
+                    currency_unit: keyset.currency_unit.clone(),
+                    public_keys: keyset.public_keys.clone(),
+                    active: keyset.active,
+                    ..stored
+                };
+                store
+                    .put(&Self::keyset_to_js(&updated), Some(&key))
+                    .await
+                    .expect("db store error");
+            }
+            // the key is generated by the auto incremented store
+            None => {
+                store
+                    .add(&Self::keyset_to_js(keyset), None)
+                    .await
+                    .expect("db store error");
+            }
+        }
+        rexie_tx.done().await.expect("db error");
+        Ok(())
     }
 
     async fn update_keyset_last_index(
         &self,
         _tx: &mut RexieTransaction,
-        _keyset: &WalletKeyset,
+        keyset: &WalletKeyset,
     ) -> std::result::Result<(), MokshaWalletError> {
-        todo!()
+        let id = keyset.id.ok_or(MokshaWalletError::IdNotSet)?;
+        let db = Self::get_rexie().await;
+        let rexie_tx = db
+            .transaction(&[KEYSETS_STORE_NAME], rexie::TransactionMode::ReadWrite)
+            .expect("db error");
+        let store = rexie_tx.store(KEYSETS_STORE_NAME).expect("db error");
+        let key = JsValue::from_f64(id as f64);
+
+        // keysets that are not stored are skipped, like the UPDATE of the sqlite store
+        let value = store.get(&key).await.expect("db error");
+        if !value.is_undefined() {
+            let stored = Self::keyset_from_js(&key, value);
+            let updated = WalletKeyset {
+                last_index: stored.last_index.max(keyset.last_index),
+                ..stored
+            };
+            store
+                .put(&Self::keyset_to_js(&updated), Some(&key))
+                .await
+                .expect("db error");
+        }
+        rexie_tx.done().await.expect("db error");
+        Ok(())
+    }
+
+    async fn reserve_keyset_indexes(
+        &self,
+        _tx: &mut RexieTransaction,
+        keyset: &WalletKeyset,
+        count: u32,
+    ) -> std::result::Result<u64, MokshaWalletError> {
+        let id = keyset.id.ok_or(MokshaWalletError::IdNotSet)?;
+        let db = Self::get_rexie().await;
+        // the read and the write are in one readwrite transaction, so concurrent reservations
+        // get distinct indexes
+        let rexie_tx = db
+            .transaction(&[KEYSETS_STORE_NAME], rexie::TransactionMode::ReadWrite)
+            .expect("db error");
+        let store = rexie_tx.store(KEYSETS_STORE_NAME).expect("db error");
+        let key = JsValue::from_f64(id as f64);
+
+        let value = store.get(&key).await.expect("db error");
+        if value.is_undefined() {
+            return Err(MokshaWalletError::IdNotSet);
+        }
+        let stored = Self::keyset_from_js(&key, value);
+        let first_index = stored.last_index + 1;
+        let updated = WalletKeyset {
+            last_index: stored.last_index + u64::from(count),
+            ..stored
+        };
+        store
+            .put(&Self::keyset_to_js(&updated), Some(&key))
+            .await
+            .expect("db error");
+        rexie_tx.done().await.expect("db error");
+        Ok(first_index)
     }
 
+    // the store has a single account, its seed is stored under the default account
     async fn add_seed(
         &self,
         _tx: &mut RexieTransaction,
-        _seed_words: &str,
+        seed_words: &str,
     ) -> std::result::Result<(), MokshaWalletError> {
-        todo!()
+        let db = Self::get_rexie().await;
+        let rexie_tx = db
+            .transaction(&[SEEDS_STORE_NAME], rexie::TransactionMode::ReadWrite)
+            .expect("db error");
+        let store = rexie_tx.store(SEEDS_STORE_NAME).expect("db error");
+        store
+            .add(
+                &JsValue::from_str(seed_words),
+                Some(&JsValue::from_str(DEFAULT_ACCOUNT)),
+            )
+            .await
+            .expect("db store error");
+        rexie_tx.done().await.expect("db error");
+        Ok(())
     }
 
     async fn get_seed(
+        &self,
+        tx: &mut RexieTransaction,
+    ) -> std::result::Result<Option<String>, MokshaWalletError> {
+        self.get_seed_for_account(tx, DEFAULT_ACCOUNT).await
+    }
+
+    async fn get_seed_for_account(
         &self,
         _tx: &mut RexieTransaction,
+        account: &str,
     ) -> std::result::Result<Option<String>, MokshaWalletError> {
-        todo!()
+        let db = Self::get_rexie().await;
+        let rexie_tx = db
+            .transaction(&[SEEDS_STORE_NAME], rexie::TransactionMode::ReadOnly)
+            .expect("db error");
+        let store = rexie_tx.store(SEEDS_STORE_NAME).expect("db error");
+        let value = store
+            .get(&JsValue::from_str(account))
+            .await
+            .expect("db error");
+
+        // a missing key returns undefined
+        Ok(value.as_string())
+    }
+
+    async fn list_accounts(
+        &self,
+        _tx: &mut RexieTransaction,
+    ) -> std::result::Result<Vec<String>, MokshaWalletError> {
+        let db = Self::get_rexie().await;
+        let rexie_tx = db
+            .transaction(&[SEEDS_STORE_NAME], rexie::TransactionMode::ReadOnly)
+            .expect("db error");
+        let store = rexie_tx.store(SEEDS_STORE_NAME).expect("db error");
+
+        // keys are returned in ascending order, like the ORDER BY of the sqlite store
+        Ok(store
+            .get_all(None, None, None, None)
+            .await
+            .expect("db error")
+            .into_iter()
+            .filter_map(|(key, _)| key.as_string())
+            .collect())
+    }
+
+    async fn get_setting(
+        &self,
+        _tx: &mut RexieTransaction,
+        key: &str,
+    ) -> std::result::Result<Option<String>, MokshaWalletError> {
+        let db = Self::get_rexie().await;
+        let rexie_tx = db
+            .transaction(&[SETTINGS_STORE_NAME], rexie::TransactionMode::ReadOnly)
+            .expect("db error");
+        let store = rexie_tx.store(SETTINGS_STORE_NAME).expect("db error");
+        let value = store.get(&JsValue::from_str(key)).await.expect("db error");
+
+        // a missing key returns undefined
+        Ok(value.as_string())
+    }
+
+    async fn upsert_setting(
+        &self,
+        _tx: &mut RexieTransaction,
+        key: &str,
+        value: &str,
+    ) -> std::result::Result<(), MokshaWalletError> {
+        let db = Self::get_rexie().await;
+        let rexie_tx = db
+            .transaction(&[SETTINGS_STORE_NAME], rexie::TransactionMode::ReadWrite)
+            .expect("db error");
+        let store = rexie_tx.store(SETTINGS_STORE_NAME).expect("db error");
+        store
+            .put(&JsValue::from_str(value), Some(&JsValue::from_str(key)))
+            .await
+            .expect("db store error");
+        rexie_tx.done().await.expect("db error");
+        Ok(())
+    }
+
+    async fn delete_setting(
+        &self,
+        _tx: &mut RexieTransaction,
+        key: &str,
+    ) -> std::result::Result<(), MokshaWalletError> {
+        let db = Self::get_rexie().await;
+        let rexie_tx = db
+            .transaction(&[SETTINGS_STORE_NAME], rexie::TransactionMode::ReadWrite)
+            .expect("db error");
+        let store = rexie_tx.store(SETTINGS_STORE_NAME).expect("db error");
+        store
+            .delete(&JsValue::from_str(key))
+            .await
+            .expect("db error");
+        rexie_tx.done().await.expect("db error");
+        Ok(())
+    }
+
+    async fn add_log_entry(
+        &self,
+        _tx: &mut RexieTransaction,
+        entry: &OperationLogEntry,
+        max_entries: u64,
+    ) -> std::result::Result<u64, MokshaWalletError> {
+        let db = Self::get_rexie().await;
+        let rexie_tx = db
+            .transaction(
+                &[OPERATION_LOG_STORE_NAME],
+                rexie::TransactionMode::ReadWrite,
+            )
+            .expect("db error");
+        let store = rexie_tx.store(OPERATION_LOG_STORE_NAME).expect("db error");
+        let json = serde_json::json!({
+            "operation": entry.operation.to_string(),
+            "inputs": entry.inputs,
+            "outputs": entry.outputs,
+            "error": entry.error,
+        })
+        .to_string();
+        let js_value = serde_wasm_bindgen::to_value(&json).unwrap();
+
+        // the key is generated by the auto incremented store
+        let id = store.add(&js_value, None).await.expect("db store error");
+
+        // keys are ascending, so the oldest entries come first
+        let count = u64::from(store.count(None).await.expect("db error"));
+        if count > max_entries {
+            let oldest = store
+                .get_all(None, Some((count - max_entries) as u32), None, None)
+                .await
+                .expect("db error");
+            for (key, _) in oldest {
+                store.delete(&key).await.expect("db error");
+            }
+        }
+        rexie_tx.done().await.expect("db error");
+        Ok(id.as_f64().expect("invalid key in localstore") as u64)
+    }
+
+    async fn update_log_entry(
+        &self,
+        _tx: &mut RexieTransaction,
+        id: u64,
+        outputs: &str,
+        error: Option<&str>,
+    ) -> std::result::Result<(), MokshaWalletError> {
+        let db = Self::get_rexie().await;
+        let rexie_tx = db
+            .transaction(
+                &[OPERATION_LOG_STORE_NAME],
+                rexie::TransactionMode::ReadWrite,
+            )
+            .expect("db error");
+        let store = rexie_tx.store(OPERATION_LOG_STORE_NAME).expect("db error");
+        let key = JsValue::from_f64(id as f64);
+
+        // entries that were already removed from the log are skipped, like the UPDATE of the
+        // sqlite store
+        if let Some(json) = store.get(&key).await.expect("db error").as_string() {
+            let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+            value["outputs"] = serde_json::json!(outputs);
+            value["error"] = serde_json::json!(error);
+            let js_value = serde_wasm_bindgen::to_value(&value.to_string()).unwrap();
+            store.put(&js_value, Some(&key)).await.expect("db error");
+        }
+        rexie_tx.done().await.expect("db error");
+        Ok(())
+    }
+
+    async fn get_log_entries(
+        &self,
+        _tx: &mut RexieTransaction,
+        limit: u64,
+    ) -> std::result::Result<Vec<OperationLogEntry>, MokshaWalletError> {
+        let db = Self::get_rexie().await;
+        let rexie_tx = db
+            .transaction(
+                &[OPERATION_LOG_STORE_NAME],
+                rexie::TransactionMode::ReadOnly,
+            )
+            .expect("db error");
+        let store = rexie_tx.store(OPERATION_LOG_STORE_NAME).expect("db error");
+        let all = store
+            .get_all(None, Some(limit as u32), None, Some(Direction::Prev))
+            .await
+            .expect("db error");
+
+        Ok(all
+            .into_iter()
+            .map(|(key, value)| {
+                let json: String = serde_wasm_bindgen::from_value(value).unwrap();
+                let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+                OperationLogEntry {
+                    id: key.as_f64().map(|id| id as u64),
+                    operation: value["operation"]
+                        .as_str()
+                        .and_then(|operation| operation.parse().ok())
+                        .expect("invalid operation in localstore"),
+                    inputs: value["inputs"].as_str().unwrap_or_default().to_owned(),
+                    outputs: value["outputs"].as_str().unwrap_or_default().to_owned(),
+                    error: value["error"].as_str().map(str::to_owned),
+                    // IndexedDB has no default values, the timestamp is not tracked
+                    created_at: None,
+                }
+            })
+            .collect())
+    }
+
+    async fn add_transaction(
+        &self,
+        _tx: &mut RexieTransaction,
+        transaction: &WalletTransaction,
+    ) -> std::result::Result<(), MokshaWalletError> {
+        let db = Self::get_rexie().await;
+        let rexie_tx = db
+            .transaction(
+                &[TRANSACTIONS_STORE_NAME],
+                rexie::TransactionMode::ReadWrite,
+            )
+            .expect("db error");
+        let store = rexie_tx.store(TRANSACTIONS_STORE_NAME).expect("db error");
+        let json = serde_json::json!({
+            "operation": transaction.operation.to_string(),
+            "amount": transaction.amount,
+            "unit": transaction.unit.to_string(),
+            "mint_url": transaction.mint_url.as_str(),
+            "memo": transaction.memo,
+        })
+        .to_string();
+        let js_value = serde_wasm_bindgen::to_value(&json).unwrap();
+
+        // the key is generated by the auto incremented store
+        store.add(&js_value, None).await.expect("db store error");
+        rexie_tx.done().await.expect("db error");
+        Ok(())
+    }
+
+    async fn list_transactions(
+        &self,
+        _tx: &mut RexieTransaction,
+        limit: u64,
+    ) -> std::result::Result<Vec<WalletTransaction>, MokshaWalletError> {
+        let db = Self::get_rexie().await;
+        let rexie_tx = db
+            .transaction(&[TRANSACTIONS_STORE_NAME], rexie::TransactionMode::ReadOnly)
+            .expect("db error");
+        let store = rexie_tx.store(TRANSACTIONS_STORE_NAME).expect("db error");
+        let all = store
+            .get_all(None, Some(limit as u32), None, Some(Direction::Prev))
+            .await
+            .expect("db error");
+
+        Ok(all
+            .into_iter()
+            .map(|(key, value)| {
+                let json: String = serde_wasm_bindgen::from_value(value).unwrap();
+                let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+                WalletTransaction {
+                    id: key.as_f64().map(|id| id as u64),
+                    operation: value["operation"]
+                        .as_str()
+                        .and_then(|operation| operation.parse().ok())
+                        .expect("invalid operation in localstore"),
+                    amount: value["amount"].as_u64().unwrap_or_default(),
+                    unit: value["unit"]
+                        .as_str()
+                        .map(|unit| unit.to_owned().into())
+                        // transactions stored before the unit was tracked were made in sat
+                        .unwrap_or(CurrencyUnit::Sat),
+                    mint_url: value["mint_url"]
+                        .as_str()
+                        .and_then(|url| Url::parse(url).ok())
+                        .expect("invalid URL in localstore"),
+                    memo: value["memo"].as_str().map(str::to_owned),
+                    // IndexedDB has no default values, the timestamp is not tracked
+                    created_at: None,
+                }
+            })
+            .collect())
+    }
+
+    async fn add_pending_melt(
+        &self,
+        _tx: &mut RexieTransaction,
+        pending_melt: &PendingMelt,
+    ) -> std::result::Result<(), MokshaWalletError> {
+        let db = Self::get_rexie().await;
+        let rexie_tx = db
+            .transaction(
+                &[PENDING_MELTS_STORE_NAME],
+                rexie::TransactionMode::ReadWrite,
+            )
+            .expect("db error");
+        let store = rexie_tx.store(PENDING_MELTS_STORE_NAME).expect("db error");
+
+        // one entry per proof, so proofs can be deleted one by one like in the sqlite store
+        for proof in &pending_melt.proofs.0 {
+            let json = serde_json::json!({
+                "mint_url": pending_melt.mint_url.as_str(),
+                "quote": pending_melt.quote,
+                "proof": proof,
+            })
+            .to_string();
+            let js_value = serde_wasm_bindgen::to_value(&json).unwrap();
+            store
+                .put(&js_value, Some(&Self::get_key(&proof.proof)))
+                .await
+                .expect("db store error");
+        }
+        rexie_tx.done().await.expect("db error");
+        Ok(())
+    }
+
+    async fn get_pending_melts(
+        &self,
+        _tx: &mut RexieTransaction,
+    ) -> std::result::Result<Vec<PendingMelt>, MokshaWalletError> {
+        let db = Self::get_rexie().await;
+        let rexie_tx = db
+            .transaction(
+                &[PENDING_MELTS_STORE_NAME],
+                rexie::TransactionMode::ReadOnly,
+            )
+            .expect("db error");
+        let store = rexie_tx.store(PENDING_MELTS_STORE_NAME).expect("db error");
+        let all = store
+            .get_all(None, None, None, None)
+            .await
+            .expect("db error");
+
+        let mut rows = all
+            .into_iter()
+            .map(|(_, value)| {
+                let json: String = serde_wasm_bindgen::from_value(value).unwrap();
+                let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+                let mint_url = value["mint_url"]
+                    .as_str()
+                    .and_then(|url| Url::parse(url).ok())
+                    .expect("invalid URL in localstore");
+                let quote = value["quote"]
+                    .as_str()
+                    .expect("invalid quote in localstore")
+                    .to_owned();
+                let proof = serde_json::from_value::<StoredProof>(value["proof"].clone())
+                    .expect("invalid proof in localstore");
+                (mint_url, quote, proof)
+            })
+            .collect::<Vec<_>>();
+        // grouped like the ORDER BY of the sqlite store
+        rows.sort_by(|(url_a, quote_a, _), (url_b, quote_b, _)| {
+            (url_a.as_str(), quote_a).cmp(&(url_b.as_str(), quote_b))
+        });
+
+        let mut pending_melts: Vec<(Url, String, Vec<StoredProof>)> = vec![];
+        for (mint_url, quote, proof) in rows {
+            match pending_melts.last_mut() {
+                Some((url, last_quote, proofs)) if *url == mint_url && *last_quote == quote => {
+                    proofs.push(proof)
+                }
+                _ => pending_melts.push((mint_url, quote, vec![proof])),
+            }
+        }
+        Ok(pending_melts
+            .into_iter()
+            .map(|(mint_url, quote, proofs)| PendingMelt {
+                mint_url,
+                quote,
+                proofs: proofs.into(),
+            })
+            .collect())
+    }
+
+    async fn delete_pending_melt_proofs(
+        &self,
+        _tx: &mut RexieTransaction,
+        proofs: &Proofs,
+    ) -> std::result::Result<(), MokshaWalletError> {
+        let db = Self::get_rexie().await;
+        let rexie_tx = db
+            .transaction(
+                &[PENDING_MELTS_STORE_NAME],
+                rexie::TransactionMode::ReadWrite,
+            )
+            .expect("db error");
+        let store = rexie_tx.store(PENDING_MELTS_STORE_NAME).expect("db error");
+        for proof in proofs.proofs() {
+            store
+                .delete(&Self::get_key(&proof))
+                .await
+                .expect("db error");
+        }
+        rexie_tx.done().await.expect("db error");
+        Ok(())
     }
 }
@@ -7,13 +7,19 @@ use secp256k1::PublicKey;
 use url::Url;
 
 use crate::error::MokshaWalletError;
-use crate::localstore::{LocalStore, WalletKeyset};
+use crate::localstore::{
+    LocalStore, OperationLogEntry, PendingMelt, StoredProof, StoredProofs, WalletKeyset,
+    WalletTransaction, DEFAULT_ACCOUNT,
+};
 
-use sqlx::sqlite::SqliteError;
+use sqlx::sqlite::SqliteConnectOptions;
+use std::str::FromStr;
 
 #[derive(Clone, Debug)]
 pub struct SqliteLocalStore {
     pool: sqlx::SqlitePool,
+    /// proofs, keysets, transactions and the seed are stored per account
+    account: String,
 }
 
 #[async_trait(?Send)]
@@ -40,54 +46,88 @@ impl LocalStore for SqliteLocalStore {
             .collect();
 
         let sql = format!(
-            "DELETE FROM proofs WHERE secret IN ({})",
+            "DELETE FROM proofs WHERE account = ?{} AND secret IN ({})",
+            proof_secrets.len() + 1,
             placeholders.join(",")
         );
         let mut query = sqlx::query(&sql);
         for secret in &proof_secrets {
             query = query.bind(secret);
         }
+        query = query.bind(&self.account);
         query.execute(&mut **tx).await?;
 
         Ok(())
     }
 
-    async fn add_proofs(
+    async fn add_stored_proofs(
         &self,
         tx: &mut sqlx::Transaction<Self::DB>,
-        proofs: &Proofs,
+        proofs: &StoredProofs,
     ) -> Result<(), MokshaWalletError> {
-        for proof in proofs.proofs() {
+        for StoredProof {
+            proof,
+            derivation_index,
+            label,
+        } in &proofs.0
+        {
             let c = proof.c.to_string();
             let amount = proof.amount as i64;
             sqlx::query!(
-                "INSERT INTO proofs (keyset_id, amount, C, secret, time_created) VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP);",
-            proof.keyset_id,amount, c, proof.secret )
+                "INSERT INTO proofs (keyset_id, amount, C, secret, derivation_index, label, account, time_created) VALUES ($1, $2, $3, $4, $5, $6, $7, CURRENT_TIMESTAMP);",
+            proof.keyset_id,amount, c, proof.secret, derivation_index, label, self.account )
             .execute(&mut **tx)
             .await?;
         }
         Ok(())
     }
 
-    async fn get_proofs(
+    async fn update_proof_labels(
         &self,
         tx: &mut sqlx::Transaction<Self::DB>,
-    ) -> Result<Proofs, MokshaWalletError> {
-        let rows = sqlx::query!("SELECT keyset_id, amount, C, secret FROM proofs;")
-            .fetch_all(&mut **tx)
+        proofs: &Proofs,
+        label: Option<&str>,
+    ) -> Result<(), MokshaWalletError> {
+        for proof in proofs.proofs() {
+            sqlx::query!(
+                "UPDATE proofs SET label = $1 WHERE secret = $2 AND account = $3;",
+                label,
+                proof.secret,
+                self.account
+            )
+            .execute(&mut **tx)
             .await?;
+        }
+        Ok(())
+    }
+
+    async fn get_stored_proofs(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+    ) -> Result<StoredProofs, MokshaWalletError> {
+        let rows = sqlx::query!(
+            "SELECT keyset_id, amount, C, secret, derivation_index, label FROM proofs WHERE account = $1;",
+            self.account
+        )
+        .fetch_all(&mut **tx)
+        .await?;
 
         // FIXME read time_created
         Ok(rows
             .into_iter()
-            .map(|row| Proof {
-                keyset_id: row.keyset_id,
-                amount: row.amount as u64,
-                c: row.C.parse().expect("Invalid Pubkey"),
-                secret: row.secret,
-                script: None,
+            .map(|row| StoredProof {
+                proof: Proof {
+                    keyset_id: row.keyset_id,
+                    amount: row.amount as u64,
+                    c: row.C.parse().expect("Invalid Pubkey"),
+                    secret: row.secret,
+                    script: None,
+                    witness: None,
+                },
+                derivation_index: row.derivation_index.map(|index| index as u32),
+                label: row.label,
             })
-            .collect::<Vec<Proof>>()
+            .collect::<Vec<_>>()
             .into())
     }
 
@@ -102,9 +142,9 @@ impl LocalStore for SqliteLocalStore {
         let last_index = keyset.last_index as i64;
         let public_keys = serde_json::to_string(&keyset.public_keys)?;
         sqlx::query!(
-            r#"INSERT INTO keysets (keyset_id, mint_url, currency_unit, last_index, public_keys, active) VALUES ($1, $2, $3, $4, $5, $6)
-            ON CONFLICT(keyset_id, mint_url) DO UPDATE SET currency_unit = $3, public_keys = $5, active = $6;
-            "#,keyset_id, mint_url, currency_unit, last_index, public_keys, keyset.active)
+            r#"INSERT INTO keysets (keyset_id, mint_url, currency_unit, last_index, public_keys, active, account) VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT(keyset_id, mint_url, account) DO UPDATE SET currency_unit = $3, public_keys = $5, active = $6;
+            "#,keyset_id, mint_url, currency_unit, last_index, public_keys, keyset.active, self.account)
         .execute(&mut **tx)
         .await?;
         Ok(())
@@ -114,7 +154,7 @@ impl LocalStore for SqliteLocalStore {
         &self,
         tx: &mut sqlx::Transaction<Self::DB>,
     ) -> Result<Vec<WalletKeyset>, MokshaWalletError> {
-        let rows = sqlx::query!("SELECT id, mint_url, keyset_id, currency_unit, active, last_index, public_keys FROM keysets;")
+        let rows = sqlx::query!("SELECT id, mint_url, keyset_id, currency_unit, active, last_index, public_keys FROM keysets WHERE account = $1;", self.account)
             .fetch_all(&mut **tx)
             .await?;
 
@@ -123,8 +163,7 @@ impl LocalStore for SqliteLocalStore {
             .map(|row| {
                 let id: i64 = row.id;
                 let mint_url: Url = Url::parse(&row.mint_url).expect("invalid URL in localstore");
-                let keyset_id: KeysetId =
-                    KeysetId::new(&row.keyset_id).expect("invalid keyset_id in localstore");
+                let keyset_id: KeysetId = KeysetId::new(&row.keyset_id)?;
                 let currency_unit: String = row.currency_unit.clone();
                 let active: bool = row.active;
                 let last_index: i64 = row.last_index;
@@ -141,7 +180,7 @@ impl LocalStore for SqliteLocalStore {
                     public_keys,
                 })
             })
-            .collect::<Result<Vec<WalletKeyset>, SqliteError>>()?)
+            .collect::<Result<Vec<WalletKeyset>, MokshaWalletError>>()?)
     }
 
     async fn update_keyset_last_index(
@@ -156,7 +195,7 @@ impl LocalStore for SqliteLocalStore {
         let last_index = keyset.last_index as i64;
 
         sqlx::query!(
-            "UPDATE keysets SET last_index = $1 WHERE id = $2;",
+            "UPDATE keysets SET last_index = MAX(last_index, $1) WHERE id = $2;",
             last_index,
             id
         )
@@ -165,14 +204,40 @@ impl LocalStore for SqliteLocalStore {
         Ok(())
     }
 
+    async fn reserve_keyset_indexes(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        keyset: &WalletKeyset,
+        count: u32,
+    ) -> Result<u64, MokshaWalletError> {
+        let id = match keyset.id {
+            None => return Err(MokshaWalletError::IdNotSet),
+            Some(id) => id as i64,
+        };
+        let count = count as i64;
+
+        let last_index = sqlx::query_scalar!(
+            "UPDATE keysets SET last_index = last_index + $1 WHERE id = $2 RETURNING last_index;",
+            count,
+            id
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+        Ok((last_index - count + 1) as u64)
+    }
+
     async fn add_seed(
         &self,
         tx: &mut sqlx::Transaction<Self::DB>,
         seed_words: &str,
     ) -> Result<(), MokshaWalletError> {
-        sqlx::query!("INSERT INTO seed (seed_words) VALUES ($1);", seed_words)
-            .execute(&mut **tx)
-            .await?;
+        sqlx::query!(
+            "INSERT INTO seeds (account, seed_words) VALUES ($1, $2);",
+            self.account,
+            seed_words
+        )
+        .execute(&mut **tx)
+        .await?;
         Ok(())
     }
 
@@ -180,14 +245,280 @@ impl LocalStore for SqliteLocalStore {
         &self,
         tx: &mut sqlx::Transaction<Self::DB>,
     ) -> Result<Option<String>, MokshaWalletError> {
-        let row = sqlx::query!("SELECT seed_words FROM seed;")
+        self.get_seed_for_account(tx, &self.account).await
+    }
+
+    async fn get_seed_for_account(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        account: &str,
+    ) -> Result<Option<String>, MokshaWalletError> {
+        let row = sqlx::query!("SELECT seed_words FROM seeds WHERE account = $1;", account)
+            .fetch_optional(&mut **tx)
+            .await?;
+        Ok(row.map(|row| row.seed_words))
+    }
+
+    async fn list_accounts(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+    ) -> Result<Vec<String>, MokshaWalletError> {
+        let rows = sqlx::query!("SELECT account FROM seeds ORDER BY account;")
             .fetch_all(&mut **tx)
             .await?;
-        match row.len() {
-            0 => Ok(None),
-            1 => Ok(Some(row[0].seed_words.clone())),
-            _ => Err(MokshaWalletError::MultipleSeeds),
+        Ok(rows.into_iter().map(|row| row.account).collect())
+    }
+
+    async fn get_setting(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        key: &str,
+    ) -> Result<Option<String>, MokshaWalletError> {
+        let row = sqlx::query!("SELECT value FROM settings WHERE key = $1;", key)
+            .fetch_optional(&mut **tx)
+            .await?;
+        Ok(row.map(|row| row.value))
+    }
+
+    async fn upsert_setting(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        key: &str,
+        value: &str,
+    ) -> Result<(), MokshaWalletError> {
+        sqlx::query!(
+            r#"INSERT INTO settings (key, value) VALUES ($1, $2)
+               ON CONFLICT(key) DO UPDATE SET value = excluded.value;"#,
+            key,
+            value
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_setting(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        key: &str,
+    ) -> Result<(), MokshaWalletError> {
+        sqlx::query!("DELETE FROM settings WHERE key = $1;", key)
+            .execute(&mut **tx)
+            .await?;
+        Ok(())
+    }
+
+    async fn add_log_entry(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        entry: &OperationLogEntry,
+        max_entries: u64,
+    ) -> Result<u64, MokshaWalletError> {
+        let operation = entry.operation.to_string();
+        let max_entries = max_entries as i64;
+        let id = sqlx::query!(
+            "INSERT INTO operation_log (operation, inputs, outputs, error) VALUES ($1, $2, $3, $4);",
+            operation,
+            entry.inputs,
+            entry.outputs,
+            entry.error
+        )
+        .execute(&mut **tx)
+        .await?
+        .last_insert_rowid();
+        sqlx::query!(
+            "DELETE FROM operation_log WHERE id <= (SELECT MAX(id) FROM operation_log) - $1;",
+            max_entries
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(id as u64)
+    }
+
+    async fn update_log_entry(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        id: u64,
+        outputs: &str,
+        error: Option<&str>,
+    ) -> Result<(), MokshaWalletError> {
+        let id = id as i64;
+        sqlx::query!(
+            "UPDATE operation_log SET outputs = $1, error = $2 WHERE id = $3;",
+            outputs,
+            error,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_log_entries(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        limit: u64,
+    ) -> Result<Vec<OperationLogEntry>, MokshaWalletError> {
+        let limit = limit as i64;
+        let rows = sqlx::query!(
+            "SELECT id, operation, inputs, outputs, error, created_at FROM operation_log ORDER BY id DESC LIMIT $1;",
+            limit
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| OperationLogEntry {
+                id: Some(row.id as u64),
+                operation: row
+                    .operation
+                    .parse()
+                    .expect("invalid operation in localstore"),
+                inputs: row.inputs,
+                outputs: row.outputs,
+                error: row.error,
+                created_at: Some(row.created_at),
+            })
+            .collect())
+    }
+
+    async fn add_pending_melt(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        pending_melt: &PendingMelt,
+    ) -> Result<(), MokshaWalletError> {
+        let mint_url = pending_melt.mint_url.to_string();
+        for StoredProof {
+            proof,
+            derivation_index,
+            ..
+        } in &pending_melt.proofs.0
+        {
+            let c = proof.c.to_string();
+            let amount = proof.amount as i64;
+            sqlx::query!(
+                "INSERT INTO pending_melts (keyset_id, amount, C, secret, derivation_index, mint_url, quote, account) VALUES ($1, $2, $3, $4, $5, $6, $7, $8);",
+                proof.keyset_id, amount, c, proof.secret, derivation_index, mint_url, pending_melt.quote, self.account
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn get_pending_melts(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+    ) -> Result<Vec<PendingMelt>, MokshaWalletError> {
+        let rows = sqlx::query!(
+            "SELECT keyset_id, amount, C, secret, derivation_index, mint_url, quote FROM pending_melts WHERE account = $1 ORDER BY mint_url, quote;",
+            self.account
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+
+        let mut pending_melts: Vec<(Url, String, Vec<StoredProof>)> = vec![];
+        for row in rows {
+            let mint_url = Url::parse(&row.mint_url).expect("invalid URL in localstore");
+            let proof = StoredProof {
+                proof: Proof {
+                    keyset_id: row.keyset_id,
+                    amount: row.amount as u64,
+                    c: row.C.parse().expect("Invalid Pubkey"),
+                    secret: row.secret,
+                    script: None,
+                    witness: None,
+                },
+                derivation_index: row.derivation_index.map(|index| index as u32),
+                label: None,
+            };
+            match pending_melts.last_mut() {
+                Some((url, quote, proofs)) if *url == mint_url && *quote == row.quote => {
+                    proofs.push(proof)
+                }
+                _ => pending_melts.push((mint_url, row.quote, vec![proof])),
+            }
+        }
+        Ok(pending_melts
+            .into_iter()
+            .map(|(mint_url, quote, proofs)| PendingMelt {
+                mint_url,
+                quote,
+                proofs: proofs.into(),
+            })
+            .collect())
+    }
+
+    async fn delete_pending_melt_proofs(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        proofs: &Proofs,
+    ) -> Result<(), MokshaWalletError> {
+        for proof in proofs.proofs() {
+            sqlx::query!(
+                "DELETE FROM pending_melts WHERE secret = $1 AND account = $2;",
+                proof.secret,
+                self.account
+            )
+            .execute(&mut **tx)
+            .await?;
         }
+        Ok(())
+    }
+
+    async fn add_transaction(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        transaction: &WalletTransaction,
+    ) -> Result<(), MokshaWalletError> {
+        let operation = transaction.operation.to_string();
+        let amount = transaction.amount as i64;
+        let unit = transaction.unit.to_string();
+        let mint_url = transaction.mint_url.to_string();
+        sqlx::query!(
+            "INSERT INTO transactions (operation, amount, unit, mint_url, memo, account) VALUES ($1, $2, $3, $4, $5, $6);",
+            operation,
+            amount,
+            unit,
+            mint_url,
+            transaction.memo,
+            self.account
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_transactions(
+        &self,
+        tx: &mut sqlx::Transaction<Self::DB>,
+        limit: u64,
+    ) -> Result<Vec<WalletTransaction>, MokshaWalletError> {
+        let limit = limit as i64;
+        let rows = sqlx::query!(
+            "SELECT id, operation, amount, unit, mint_url, memo, created_at FROM transactions WHERE account = $1 ORDER BY id DESC LIMIT $2;",
+            self.account,
+            limit
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| WalletTransaction {
+                id: Some(row.id as u64),
+                operation: row
+                    .operation
+                    .parse()
+                    .expect("invalid operation in localstore"),
+                amount: row.amount as u64,
+                unit: row.unit.into(),
+                mint_url: Url::parse(&row.mint_url).expect("invalid URL in localstore"),
+                memo: row.memo,
+                created_at: Some(row.created_at),
+            })
+            .collect())
     }
 }
 
@@ -201,26 +532,183 @@ impl SqliteLocalStore {
     }
 
     async fn with_connection_string(connection_string: &str) -> Result<Self, MokshaWalletError> {
+        Self::with_connect_options(SqliteConnectOptions::from_str(connection_string)?).await
+    }
+
+    async fn with_connect_options(
+        options: SqliteConnectOptions,
+    ) -> Result<Self, MokshaWalletError> {
         // creates db-file if not already exists
         let pool = sqlx::sqlite::SqlitePoolOptions::new()
             .acquire_timeout(std::time::Duration::from_secs(5))
             .idle_timeout(std::time::Duration::from_secs(5))
-            .connect(connection_string)
+            .connect_with(options)
             .await?;
 
         sqlx::query("PRAGMA journal_mode=WAL")
             .execute(&pool)
             .await?;
-        sqlx::migrate!("./migrations").run(&pool).await?;
-        Ok(Self { pool })
+
+        // the schema version is the number of applied migrations, stored in the user_version pragma
+        let migrator = sqlx::migrate!("./migrations");
+        let known_version = migrator.migrations.len() as i64;
+        let db_version: i64 = sqlx::query_scalar("PRAGMA user_version")
+            .fetch_one(&pool)
+            .await?;
+        if db_version > known_version {
+            pool.close().await;
+            return Err(MokshaWalletError::DatabaseTooNew(db_version, known_version));
+        }
+
+        migrator.run(&pool).await?;
+        sqlx::query(&format!("PRAGMA user_version = {known_version}"))
+            .execute(&pool)
+            .await?;
+        Ok(Self {
+            pool,
+            account: DEFAULT_ACCOUNT.to_owned(),
+        })
+    }
+
+    /// Returns a store for the named account that shares the database connections
+    pub fn with_account(self, account: &str) -> Self {
+        Self {
+            account: account.to_owned(),
+            ..self
+        }
+    }
+
+    pub fn account(&self) -> &str {
+        &self.account
     }
 }
 
+#[cfg(feature = "sqlcipher")]
+impl SqliteLocalStore {
+    /// Opens a database that is encrypted with SQLCipher
+    pub async fn with_encrypted_path(
+        absolute_path: String,
+        passphrase: &str,
+    ) -> Result<Self, MokshaWalletError> {
+        let options =
+            SqliteConnectOptions::from_str(&format!("sqlite://{absolute_path}?mode=rwc"))?
+                .pragma("key", quote_passphrase(passphrase));
+        Self::with_connect_options(options).await
+    }
+
+    /// Sets a new passphrase for the database. An unencrypted database gets encrypted in place.
+    pub async fn change_passphrase(
+        absolute_path: &str,
+        old_passphrase: Option<&str>,
+        new_passphrase: &str,
+    ) -> Result<(), MokshaWalletError> {
+        use sqlx::{Connection, SqliteConnection};
+
+        let mut options = SqliteConnectOptions::from_str(&format!("sqlite://{absolute_path}"))?;
+        if let Some(old_passphrase) = old_passphrase {
+            options = options.pragma("key", quote_passphrase(old_passphrase));
+        }
+        let mut conn = SqliteConnection::connect_with(&options).await?;
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .execute(&mut conn)
+            .await?;
+
+        match old_passphrase {
+            Some(_) => {
+                sqlx::query(&format!(
+                    "PRAGMA rekey = {}",
+                    quote_passphrase(new_passphrase)
+                ))
+                .execute(&mut conn)
+                .await?;
+                conn.close().await?;
+            }
+            None => {
+                let encrypted_path = format!("{absolute_path}.encrypted");
+                // the connection is not allowed to create files, so ATTACH needs an existing one
+                std::fs::File::create(&encrypted_path)?;
+                sqlx::query("ATTACH DATABASE ? AS encrypted KEY ?")
+                    .bind(&encrypted_path)
+                    .bind(new_passphrase)
+                    .execute(&mut conn)
+                    .await?;
+                sqlx::query("SELECT sqlcipher_export('encrypted')")
+                    .execute(&mut conn)
+                    .await?;
+                sqlx::query("DETACH DATABASE encrypted")
+                    .execute(&mut conn)
+                    .await?;
+                sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+                    .execute(&mut conn)
+                    .await?;
+                conn.close().await?;
+                // the WAL and shared memory files contain plaintext pages and would be applied
+                // to the encrypted database
+                for suffix in ["-wal", "-shm"] {
+                    match std::fs::remove_file(format!("{absolute_path}{suffix}")) {
+                        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                        _ => {}
+                    }
+                }
+                std::fs::rename(encrypted_path, absolute_path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlcipher")]
+fn quote_passphrase(passphrase: &str) -> String {
+    format!("'{}'", passphrase.replace('\'', "''"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::SqliteLocalStore;
-    use crate::localstore::LocalStore;
-    use moksha_core::{fixture::read_fixture, token::TokenV3};
+    use crate::{
+        error::MokshaWalletError,
+        localstore::{LocalStore, Operation, OperationLogEntry, WalletKeyset, WalletTransaction},
+    };
+    use moksha_core::{
+        fixture::read_fixture, keyset::KeysetId, primitives::CurrencyUnit, token::TokenV3,
+    };
+    use url::Url;
+
+    #[tokio::test]
+    async fn test_open_database_too_new() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("wallet.db").to_str().unwrap().to_owned();
+
+        let db = SqliteLocalStore::with_path(path.clone()).await?;
+        sqlx::query("PRAGMA user_version = 9999")
+            .execute(&db.pool)
+            .await?;
+        db.pool.close().await;
+
+        let result = SqliteLocalStore::with_path(path).await;
+        assert!(matches!(
+            result,
+            Err(MokshaWalletError::DatabaseTooNew(9999, _))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_keysets_invalid_keyset_id() -> anyhow::Result<()> {
+        let db = SqliteLocalStore::with_in_memory().await?;
+        sqlx::query(
+            "INSERT INTO keysets (keyset_id, mint_url, currency_unit, last_index, public_keys, active, account) VALUES ('I2yN+iRYfkzT', 'http://127.0.0.1:3338/', 'sat', 0, '{}', true, $1)",
+        )
+        .bind(&db.account)
+        .execute(&db.pool)
+        .await?;
+
+        let mut tx = db.begin_tx().await?;
+        let result = db.get_keysets(&mut tx).await;
+        assert!(matches!(result, Err(MokshaWalletError::MokshaCore(_))));
+        tx.commit().await?;
+        Ok(())
+    }
 
     #[tokio::test]
     async fn test_add_proofs() -> anyhow::Result<()> {
@@ -301,4 +789,124 @@ mod tests {
         tx.commit().await?;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_log_entries_rotate() -> anyhow::Result<()> {
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = localstore.begin_tx().await?;
+        for amount in 1..=5 {
+            let entry = OperationLogEntry {
+                id: None,
+                operation: Operation::Send,
+                inputs: format!(r#"{{"amount":{amount}}}"#),
+                outputs: "null".to_owned(),
+                error: None,
+                created_at: None,
+            };
+            localstore.add_log_entry(&mut tx, &entry, 3).await?;
+        }
+
+        let entries = localstore.get_log_entries(&mut tx, 10).await?;
+        assert_eq!(
+            vec![r#"{"amount":5}"#, r#"{"amount":4}"#, r#"{"amount":3}"#],
+            entries
+                .iter()
+                .map(|e| e.inputs.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert!(entries.iter().all(|e| e.created_at.is_some()));
+        tx.commit().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_transactions() -> anyhow::Result<()> {
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mint_url = Url::parse("http://127.0.0.1:3338")?;
+        let keyset = |unit: CurrencyUnit| -> anyhow::Result<WalletKeyset> {
+            Ok(WalletKeyset::new(
+                &KeysetId::new("00ffd48b8f5ecf80")?,
+                &mint_url,
+                &unit,
+                0,
+                Default::default(),
+                true,
+            ))
+        };
+        let mut tx = localstore.begin_tx().await?;
+        localstore
+            .add_transaction(
+                &mut tx,
+                &WalletTransaction::new(Operation::Mint, 100, &keyset(CurrencyUnit::Sat)?, None),
+            )
+            .await?;
+        localstore
+            .add_transaction(
+                &mut tx,
+                &WalletTransaction::new(
+                    Operation::Receive,
+                    21,
+                    &keyset(CurrencyUnit::Usd)?,
+                    Some("coffee".to_owned()),
+                ),
+            )
+            .await?;
+
+        let transactions = localstore.list_transactions(&mut tx, 10).await?;
+        tx.commit().await?;
+        assert_eq!(
+            vec![(Operation::Receive, 21), (Operation::Mint, 100)],
+            transactions
+                .iter()
+                .map(|t| (t.operation, t.amount))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some("coffee".to_owned()), transactions[0].memo);
+        assert_eq!(CurrencyUnit::Usd, transactions[0].unit);
+        assert_eq!(CurrencyUnit::Sat, transactions[1].unit);
+        assert_eq!(mint_url, transactions[1].mint_url);
+        assert!(transactions.iter().all(|t| t.created_at.is_some()));
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[tokio::test]
+    async fn test_change_passphrase() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wallet.db").to_string_lossy().to_string();
+
+        let db = SqliteLocalStore::with_path(path.clone()).await?;
+        let mut tx = db.begin_tx().await?;
+        db.upsert_setting(&mut tx, "foo", "bar").await?;
+        tx.commit().await?;
+        db.pool.close().await;
+
+        SqliteLocalStore::change_passphrase(&path, None, "secret").await?;
+        assert!(!std::path::Path::new(&format!("{path}-wal")).exists());
+        assert!(!std::path::Path::new(&format!("{path}-shm")).exists());
+        assert!(SqliteLocalStore::with_path(path.clone()).await.is_err());
+        let db = SqliteLocalStore::with_encrypted_path(path.clone(), "secret").await?;
+        let mut tx = db.begin_tx().await?;
+        assert_eq!(
+            Some("bar".to_owned()),
+            db.get_setting(&mut tx, "foo").await?
+        );
+        tx.commit().await?;
+        db.pool.close().await;
+
+        SqliteLocalStore::change_passphrase(&path, Some("secret"), "n'ew").await?;
+        assert!(
+            SqliteLocalStore::with_encrypted_path(path.clone(), "secret")
+                .await
+                .is_err()
+        );
+        let db = SqliteLocalStore::with_encrypted_path(path, "n'ew").await?;
+        let mut tx = db.begin_tx().await?;
+        assert_eq!(
+            Some("bar".to_owned()),
+            db.get_setting(&mut tx, "foo").await?
+        );
+        tx.commit().await?;
+        Ok(())
+    }
 }
@@ -88,6 +88,14 @@ impl DeterministicSecret {
         let key = self.derive_private_key(keyset_id, counter, DerivationType::Blinding)?;
         Ok(SecretKey::from_slice(&key)?.into())
     }
+
+    /// Derives the P2PK signing key with the index at `m/129372'/1'/{index}'`, next to the
+    /// secrets of the proofs at `m/129372'/0'`
+    pub fn derive_p2pk_key(&self, index: u32) -> Result<SecretKey, MokshaWalletError> {
+        let derivation_path = bip32::DerivationPath::from_str(&format!("m/129372'/1'/{index}'"))?;
+        let key = XPrv::derive_from_path(&self.seed, &derivation_path)?;
+        Ok(SecretKey::from_slice(&key.private_key().to_bytes())?)
+    }
 }
 
 pub fn convert_hex_to_int(keyset_id_hex: &str) -> Result<u32, MokshaWalletError> {
@@ -185,4 +193,20 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_p2pk_key_derivation() -> anyhow::Result<()> {
+        let phrase =
+            "half depart obvious quality work element tank gorilla view sugar picture humble";
+        let deterministic_secret = DeterministicSecret::from_seed_words(phrase)?;
+
+        let key = deterministic_secret.derive_p2pk_key(0)?;
+        assert_eq!(key, deterministic_secret.derive_p2pk_key(0)?);
+        assert_ne!(key, deterministic_secret.derive_p2pk_key(1)?);
+        assert_ne!(
+            key,
+            DeterministicSecret::from_random_seed()?.derive_p2pk_key(0)?
+        );
+        Ok(())
+    }
 }
@@ -2,25 +2,32 @@ use moksha_core::{
     amount::Amount,
     blind::{BlindedMessage, BlindedSignature, BlindingFactor, TotalAmount},
     dhke::Dhke,
-    keyset::KeysetId,
+    error::MokshaCoreError,
+    keyset::{derive_keyset_id, KeysetId, Keysets},
+    p2pk::P2PKSecret,
     primitives::{
-        CurrencyUnit, MeltBtcOnchainState, MintBtcOnchainState, MintInfoResponse, PaymentMethod,
-        PostMeltBolt11Response, PostMeltBtcOnchainResponse, PostMeltQuoteBolt11Response,
-        PostMeltQuoteBtcOnchainResponse, PostMintQuoteBolt11Response,
-        PostMintQuoteBtcOnchainResponse,
+        CurrencyUnit, KeysResponse, MeltBtcOnchainState, MintBtcOnchainState, MintInfoResponse,
+        OnchainOutput, PaymentMethod, PostMeltBolt11Response, PostMeltBtcOnchainResponse,
+        PostMeltQuoteBolt11Response, PostMeltQuoteBtcOnchainResponse, PostMintQuoteBolt11Response,
+        PostMintQuoteBtcOnchainResponse, SpendState,
     },
     proof::{Proof, Proofs},
     token::TokenV3,
 };
 
-use secp256k1::PublicKey;
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use tracing::warn;
 use url::Url;
 
 use crate::{
     client::CashuClient,
     error::MokshaWalletError,
     http::CrossPlatformHttpClient,
-    localstore::{LocalStore, WalletKeyset},
+    lnurl::{decode_lnurl, lightning_address_url, verify_invoice_metadata},
+    localstore::{
+        LocalStore, Operation, OperationLogEntry, PendingMelt, StoredProof, StoredProofs,
+        WalletKeyset, WalletKeysetFilter, WalletTransaction,
+    },
     secret::DeterministicSecret,
 };
 use lightning_invoice::Bolt11Invoice as LNInvoice;
@@ -30,6 +37,105 @@ use std::{
     vec,
 };
 
+const MAX_SEND_AMOUNT_SETTING: &str = "max_send_amount";
+const MAX_RECEIVE_FEE_PERCENT_SETTING: &str = "max_receive_fee_percent";
+const MAX_FEE_RESERVE_RATIO_SETTING: &str = "max_fee_reserve_ratio";
+/// prefix of the setting that stores the pinned pubkey of a mint, followed by the mint url
+const MINT_PUBKEY_SETTING_PREFIX: &str = "mint_pubkey:";
+/// prefix of the setting that stores the last seen TLS certificate fingerprint of a mint,
+/// followed by the mint url
+#[cfg(not(target_arch = "wasm32"))]
+const MINT_TLS_FINGERPRINT_SETTING_PREFIX: &str = "mint_tls_fingerprint:";
+/// prefix of the setting that stores the derivation index of the current P2PK signing key,
+/// followed by the pubkey of the first key, so every seed has its own index
+const P2PK_KEY_INDEX_SETTING_PREFIX: &str = "p2pk_key_index:";
+
+/// Receiving fails without `force` if the claim fee exceeds the token value
+const DEFAULT_MAX_RECEIVE_FEE_PERCENT: u64 = 100;
+const DEFAULT_MAX_FEE_RESERVE_RATIO: f64 = 1.0;
+
+/// the oldest entries of the operation log are removed once it exceeds this size
+const MAX_LOG_ENTRIES: u64 = 10_000;
+/// number of recent operation log entries that reconcile checks for interrupted swaps
+const RECONCILE_LOG_ENTRIES: u64 = 100;
+/// maximum number of proofs per checkstate request
+const CHECKSTATE_BATCH_SIZE: usize = 100;
+/// number of derivation indexes that are restored per request. Restoring stops at the first
+/// batch without any signature
+const RESTORE_BATCH_SIZE: u32 = 100;
+
+/// Outcome of [`Wallet::add_mints_from_list`]
+#[derive(Debug, Default)]
+pub struct MintListImport {
+    pub added: Vec<Url>,
+    /// mints that are already known by the wallet
+    pub skipped: Vec<Url>,
+    /// mints that are unreachable or don't support the v1 api
+    pub failed: Vec<(Url, MokshaWalletError)>,
+    /// entries of the list that are no valid urls
+    pub invalid: Vec<String>,
+}
+
+/// TLS certificate fingerprint of a mint, see [`Wallet::check_tls_fingerprint`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsFingerprint {
+    /// fingerprint of the current connection, `None` if the mint doesn't use TLS
+    pub current: Option<String>,
+    /// trusted fingerprint if the current certificate differs from it
+    pub previous: Option<String>,
+}
+
+impl TlsFingerprint {
+    pub fn changed(&self) -> bool {
+        self.previous.is_some()
+    }
+}
+
+/// Outcome of [`Wallet::audit_balance`]
+#[derive(Debug)]
+pub struct BalanceAudit {
+    /// sum of all stored proofs of the mint
+    pub local_balance: u64,
+    /// sum of the stored proofs the mint reports as unspent
+    pub verified_balance: u64,
+    /// stored proofs the mint already considers spent
+    pub spent: Proofs,
+    /// stored proofs that are inputs of a melt the mint has not finished yet
+    pub pending: Proofs,
+    /// stored proofs the mint reported no state for
+    pub unknown: Proofs,
+    /// true if the spent proofs were removed from the wallet
+    pub pruned: bool,
+}
+
+impl BalanceAudit {
+    pub fn is_consistent(&self) -> bool {
+        self.spent.is_empty() && self.pending.is_empty() && self.unknown.is_empty()
+    }
+}
+
+/// State of a lightning payment into the mint, see [`Wallet::get_bolt11_receive_state`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bolt11ReceiveState {
+    /// the invoice has not been paid yet
+    Waiting,
+    /// the invoice is paid and the tokens can be minted
+    Paid,
+    /// the invoice expired before it was paid
+    Expired,
+}
+
+impl Bolt11ReceiveState {
+    /// Returns the state of the quote at `now`. A paid quote is never expired
+    pub fn from_quote(quote: &PostMintQuoteBolt11Response, now: u64) -> Self {
+        match quote.paid {
+            true => Self::Paid,
+            false if quote.expiry.is_some_and(|expiry| expiry < now) => Self::Expired,
+            false => Self::Waiting,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Wallet<L, C>
 where
@@ -126,14 +232,123 @@ where
         WalletBuilder::default()
     }
 
+    /// Returns the client the wallet uses for all requests to mints
+    pub const fn client(&self) -> &C {
+        &self.client
+    }
+
+    /// Creates a mint quote for the amount in the given unit. The unit has to match the unit of
+    /// the keyset that is used to mint the tokens
     pub async fn create_quote_bolt11(
         &self,
         mint_url: &Url,
         amount: u64,
+        unit: CurrencyUnit,
+    ) -> Result<PostMintQuoteBolt11Response, MokshaWalletError> {
+        self.client
+            .post_mint_quote_bolt11(mint_url, amount, unit)
+            .await
+    }
+
+    /// Returns the state of the invoice of a bolt11 mint quote at `now`
+    pub async fn get_bolt11_receive_state(
+        &self,
+        mint_url: &Url,
+        quote: &str,
+        now: u64,
+    ) -> Result<Bolt11ReceiveState, MokshaWalletError> {
+        let quote = self
+            .client
+            .get_mint_quote_bolt11(mint_url, quote.to_owned())
+            .await?;
+        Ok(Bolt11ReceiveState::from_quote(&quote, now))
+    }
+
+    /// Subscribes to the state of a bolt11 mint quote over a websocket. Returns `None` if the
+    /// mint doesn't support subscriptions (NUT-17), so the caller can poll the quote instead.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn subscribe_mint_quote(
+        &self,
+        mint_url: &Url,
+        quote: &str,
+        unit: &CurrencyUnit,
+    ) -> Result<Option<tokio::sync::mpsc::Receiver<PostMintQuoteBolt11Response>>, MokshaWalletError>
+    {
+        let supported = self
+            .client
+            .get_info(mint_url)
+            .await?
+            .nuts
+            .nut17
+            .is_some_and(|nut17| {
+                nut17.supports(
+                    unit,
+                    moksha_core::subscription::SubscriptionKind::Bolt11MintQuote,
+                )
+            });
+        if !supported {
+            return Ok(None);
+        }
+        Ok(Some(
+            self.client
+                .subscribe_mint_quote(mint_url, quote.to_owned())
+                .await?,
+        ))
+    }
+
+    /// Creates a mint quote and lets a LNURL-withdraw service pay its invoice. The tokens can be
+    /// minted with the returned quote once the invoice is paid.
+    pub async fn mint_from_lnurl_withdraw(
+        &self,
+        mint_url: &Url,
+        lnurl: &str,
+        amount: u64,
     ) -> Result<PostMintQuoteBolt11Response, MokshaWalletError> {
+        let request = self
+            .client
+            .get_lnurl_withdraw(&decode_lnurl(lnurl)?)
+            .await?;
+        request.check_amount(amount)?;
+
+        let quote = self
+            .create_quote_bolt11(mint_url, amount, CurrencyUnit::Sat)
+            .await?;
         self.client
-            .post_mint_quote_bolt11(mint_url, amount, CurrencyUnit::Sat)
+            .submit_lnurl_withdraw(&request, &quote.payment_request)
+            .await?;
+        Ok(quote)
+    }
+
+    /// Resolves a lightning address `user@domain` (LUD-16) to an invoice for the amount in sats.
+    /// The invoice has to commit to the metadata of the pay request and to the amount.
+    pub async fn resolve_lightning_address(
+        &self,
+        address: &str,
+        amount: u64,
+    ) -> Result<String, MokshaWalletError> {
+        let request = self
+            .client
+            .get_lnurl_pay(&lightning_address_url(address)?)
             .await
+            .map_err(|err| {
+                MokshaWalletError::LightningAddressNotFound(address.to_owned(), err.to_string())
+            })?;
+        request.check_amount(amount)?;
+
+        let payment_request = self
+            .client
+            .get_lnurl_pay_invoice(&request, amount * 1_000)
+            .await?;
+        verify_invoice_metadata(&payment_request, &request.metadata)?;
+
+        let invoice = LNInvoice::from_str(&payment_request)
+            .map_err(|err| MokshaWalletError::DecodeInvoice(payment_request.clone(), err))?;
+        if invoice.amount_milli_satoshis() != Some(amount * 1_000) {
+            return Err(MokshaWalletError::Lnurl(format!(
+                "invoice amount doesn't match the requested amount of {amount} sat"
+            )));
+        }
+        Ok(payment_request)
     }
 
     pub async fn create_quote_onchain(
@@ -169,6 +384,11 @@ where
                     MintBtcOnchainState::Paid | MintBtcOnchainState::Issued
                 )
             }
+            PaymentMethod::Bolt12 => {
+                return Err(MokshaWalletError::UnsupportedPaymentMethod(
+                    payment_method.clone(),
+                ))
+            }
         })
     }
 
@@ -193,26 +413,279 @@ where
         Ok(keysets)
     }
 
+    pub async fn has_seed(&self) -> Result<bool, MokshaWalletError> {
+        let mut tx = self.localstore.begin_tx().await?;
+        let seed = self.localstore.get_seed(&mut tx).await?;
+        tx.commit().await?;
+        Ok(seed.is_some())
+    }
+
+    /// Returns the names of all accounts in the localstore, the wallet only sees its own
+    pub async fn list_accounts(&self) -> Result<Vec<String>, MokshaWalletError> {
+        let mut tx = self.localstore.begin_tx().await?;
+        let accounts = self.localstore.list_accounts(&mut tx).await?;
+        tx.commit().await?;
+        Ok(accounts)
+    }
+
+    pub async fn get_max_send_amount(&self) -> Result<Option<u64>, MokshaWalletError> {
+        let mut tx = self.localstore.begin_tx().await?;
+        let value = self
+            .localstore
+            .get_setting(&mut tx, MAX_SEND_AMOUNT_SETTING)
+            .await?;
+        tx.commit().await?;
+        Ok(value.and_then(|v| v.parse().ok()))
+    }
+
+    /// Sets the max amount a single send or payment may spend without forcing it. `None` removes the limit.
+    pub async fn set_max_send_amount(&self, amount: Option<u64>) -> Result<(), MokshaWalletError> {
+        let mut tx = self.localstore.begin_tx().await?;
+        match amount {
+            Some(amount) => {
+                self.localstore
+                    .upsert_setting(&mut tx, MAX_SEND_AMOUNT_SETTING, &amount.to_string())
+                    .await?
+            }
+            None => {
+                self.localstore
+                    .delete_setting(&mut tx, MAX_SEND_AMOUNT_SETTING)
+                    .await?
+            }
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn check_max_send_amount(
+        &self,
+        amount: u64,
+        force: bool,
+    ) -> Result<(), MokshaWalletError> {
+        if force {
+            return Ok(());
+        }
+        match self.get_max_send_amount().await? {
+            Some(max) if amount > max => Err(MokshaWalletError::MaxSendAmountExceeded(amount, max)),
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn get_max_receive_fee_percent(&self) -> Result<u64, MokshaWalletError> {
+        let mut tx = self.localstore.begin_tx().await?;
+        let value = self
+            .localstore
+            .get_setting(&mut tx, MAX_RECEIVE_FEE_PERCENT_SETTING)
+            .await?;
+        tx.commit().await?;
+        Ok(value
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_RECEIVE_FEE_PERCENT))
+    }
+
+    /// Sets the max claim fee in percent of the token value that is accepted when receiving
+    /// without forcing it. `None` restores the default of 100%.
+    pub async fn set_max_receive_fee_percent(
+        &self,
+        percent: Option<u64>,
+    ) -> Result<(), MokshaWalletError> {
+        let mut tx = self.localstore.begin_tx().await?;
+        match percent {
+            Some(percent) => {
+                self.localstore
+                    .upsert_setting(
+                        &mut tx,
+                        MAX_RECEIVE_FEE_PERCENT_SETTING,
+                        &percent.to_string(),
+                    )
+                    .await?
+            }
+            None => {
+                self.localstore
+                    .delete_setting(&mut tx, MAX_RECEIVE_FEE_PERCENT_SETTING)
+                    .await?
+            }
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Fails with [`MokshaWalletError::FeeExceedsValue`] if claiming the token costs more than
+    /// the configured percentage of its value
+    pub async fn check_receive_fee(
+        &self,
+        mint_url: &Url,
+        token: &TokenV3,
+    ) -> Result<(), MokshaWalletError> {
+        let amount = token.total_amount();
+        let fee = self.claim_fee_estimate(mint_url, token).await?;
+        let max_percent = self.get_max_receive_fee_percent().await?;
+        // u128 can't overflow for any u64 fee, amount or percentage
+        if u128::from(fee) * 100 > u128::from(amount) * u128::from(max_percent) {
+            return Err(MokshaWalletError::FeeExceedsValue(fee, amount));
+        }
+        Ok(())
+    }
+
+    pub async fn get_max_fee_reserve_ratio(&self) -> Result<f64, MokshaWalletError> {
+        let mut tx = self.localstore.begin_tx().await?;
+        let value = self
+            .localstore
+            .get_setting(&mut tx, MAX_FEE_RESERVE_RATIO_SETTING)
+            .await?;
+        tx.commit().await?;
+        Ok(value
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_FEE_RESERVE_RATIO))
+    }
+
+    /// Sets the max fee reserve of a lightning payment as a ratio of the paid amount that is
+    /// accepted without forcing it. `None` restores the default of 1.0.
+    pub async fn set_max_fee_reserve_ratio(
+        &self,
+        ratio: Option<f64>,
+    ) -> Result<(), MokshaWalletError> {
+        let mut tx = self.localstore.begin_tx().await?;
+        match ratio {
+            Some(ratio) => {
+                self.localstore
+                    .upsert_setting(&mut tx, MAX_FEE_RESERVE_RATIO_SETTING, &ratio.to_string())
+                    .await?
+            }
+            None => {
+                self.localstore
+                    .delete_setting(&mut tx, MAX_FEE_RESERVE_RATIO_SETTING)
+                    .await?
+            }
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Fails with [`MokshaWalletError::FeeReserveTooHigh`] if the fee reserve of a lightning
+    /// payment exceeds the configured ratio of its amount
+    pub async fn check_fee_reserve(
+        &self,
+        amount: u64,
+        fee_reserve: u64,
+        force: bool,
+    ) -> Result<(), MokshaWalletError> {
+        if force {
+            return Ok(());
+        }
+        let max_ratio = self.get_max_fee_reserve_ratio().await?;
+        if fee_reserve as f64 > amount as f64 * max_ratio {
+            return Err(MokshaWalletError::FeeReserveTooHigh(fee_reserve, amount));
+        }
+        Ok(())
+    }
+
+    /// Records the TLS certificate fingerprint of the mint on the first connection and reports if
+    /// the certificate differs from the recorded one, e.g. after a renewal or because of a MITM.
+    /// A changed fingerprint is reported until it is accepted with
+    /// [`Wallet::accept_tls_fingerprint`], connections are never refused.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn check_tls_fingerprint(
+        &self,
+        mint_url: &Url,
+    ) -> Result<TlsFingerprint, MokshaWalletError> {
+        let Some(current) = self.client.get_tls_fingerprint(mint_url).await? else {
+            return Ok(TlsFingerprint {
+                current: None,
+                previous: None,
+            });
+        };
+
+        let setting = format!("{MINT_TLS_FINGERPRINT_SETTING_PREFIX}{mint_url}");
+        let mut tx = self.localstore.begin_tx().await?;
+        let stored = self.localstore.get_setting(&mut tx, &setting).await?;
+        if stored.is_none() {
+            self.localstore
+                .upsert_setting(&mut tx, &setting, &current)
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(TlsFingerprint {
+            previous: stored.filter(|stored| *stored != current),
+            current: Some(current),
+        })
+    }
+
+    /// Records the fingerprint as the trusted TLS certificate of the mint after the user
+    /// acknowledged the change
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn accept_tls_fingerprint(
+        &self,
+        mint_url: &Url,
+        fingerprint: &str,
+    ) -> Result<(), MokshaWalletError> {
+        let setting = format!("{MINT_TLS_FINGERPRINT_SETTING_PREFIX}{mint_url}");
+        let mut tx = self.localstore.begin_tx().await?;
+        self.localstore
+            .upsert_setting(&mut tx, &setting, fingerprint)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
     pub async fn get_mint_urls(&self) -> Result<Vec<Url>, MokshaWalletError> {
         let keysets = self.get_wallet_keysets().await?;
         let mints: HashSet<Url> = keysets.into_iter().map(|k| k.mint_url).collect();
         Ok(mints.into_iter().collect())
     }
 
-    /// Stores the mints keys in the localstore
+    /// Stores the mints keys in the localstore. Fails if the mint serves different keys for a
+    /// keyset the wallet already knows, because proofs of that keyset would no longer verify.
     pub async fn add_mint_keysets(
         &self,
         mint_url: &Url,
+    ) -> Result<Vec<WalletKeyset>, MokshaWalletError> {
+        self.add_mint_keysets_for_unit(mint_url, None).await
+    }
+
+    /// Checks if the mint offers a keyset for the unit, without adding the mint
+    pub async fn mint_supports_unit(
+        &self,
+        mint_url: &Url,
+        unit: &CurrencyUnit,
+    ) -> Result<bool, MokshaWalletError> {
+        let mint_keysets = self.client.get_keysets(mint_url).await?;
+        Ok(mint_keysets
+            .keysets
+            .iter()
+            .any(|keyset| keyset.unit == *unit))
+    }
+
+    /// Adds the keysets of the mint. If a unit is given only the keysets of that unit are added
+    /// and [`MokshaWalletError::UnitNotSupported`] is returned if the mint has none.
+    pub async fn add_mint_keysets_for_unit(
+        &self,
+        mint_url: &Url,
+        unit: Option<&CurrencyUnit>,
     ) -> Result<Vec<WalletKeyset>, MokshaWalletError> {
         if !self.client.is_v1_supported(mint_url).await? {
             return Err(MokshaWalletError::UnsupportedApiVersion);
         }
 
         let mint_keysets = self.client.get_keysets(mint_url).await?;
+        self.verify_keysets(mint_url, &mint_keysets).await?;
+
+        let keysets = mint_keysets
+            .keysets
+            .iter()
+            .filter(|keyset| unit.map_or(true, |unit| keyset.unit == *unit))
+            .collect::<Vec<_>>();
+        if let Some(unit) = unit {
+            if keysets.is_empty() {
+                return Err(MokshaWalletError::UnitNotSupported(unit.to_string()));
+            }
+        }
 
         let mut tx = self.localstore.begin_tx().await?;
+        let stored_keysets = self.localstore.get_keysets(&mut tx).await?;
         let mut result = vec![];
-        for keyset in mint_keysets.keysets.iter() {
+        for keyset in keysets {
             let keysets = self
                 .client
                 .get_keys_by_id(mint_url, keyset.id.clone())
@@ -241,6 +714,25 @@ where
                 }
             };
 
+            let keys_changed = stored_keysets.iter().any(|stored| {
+                stored.keyset_id == keyset_id
+                    && stored.mint_url == *mint_url
+                    && stored.public_keys != public_keys
+            });
+            // the id is derived from the keys, so changed keys can never be trusted for a known id
+            if keys_changed {
+                return Err(MokshaWalletError::KeysetKeysChanged(keyset.id.clone()));
+            }
+
+            // keys that don't match the id are never stored, proofs would end up under a wrong keyset
+            let derived_id = derive_keyset_id(&public_keys);
+            if derived_id != keyset.id {
+                return Err(MokshaWalletError::KeysetIdMismatch(
+                    keyset.id.clone(),
+                    derived_id,
+                ));
+            }
+
             let wallet_keyset = WalletKeyset::new(
                 &keyset_id,
                 mint_url,
@@ -259,812 +751,4932 @@ where
         Ok(result)
     }
 
-    pub async fn get_balance(&self) -> Result<u64, MokshaWalletError> {
+    /// Verifies the signature of a signed keyset list. The mint pubkey from `/v1/info` is pinned
+    /// the first time a signed list is seen (trust on first use). After that every keyset list
+    /// of the mint must be signed by the pinned pubkey.
+    async fn verify_keysets(
+        &self,
+        mint_url: &Url,
+        keysets: &Keysets,
+    ) -> Result<(), MokshaWalletError> {
+        let setting = format!("{MINT_PUBKEY_SETTING_PREFIX}{mint_url}");
         let mut tx = self.localstore.begin_tx().await?;
-        let total_amount = self.localstore.get_proofs(&mut tx).await?.total_amount();
+        let pinned = self.localstore.get_setting(&mut tx, &setting).await?;
         tx.commit().await?;
-        Ok(total_amount)
+
+        let mint_pubkey = match (&pinned, &keysets.signature) {
+            (None, None) => return Ok(()),
+            (Some(pubkey), _) => PublicKey::from_str(pubkey)?,
+            (None, Some(_)) => self.client.get_info(mint_url).await?.pubkey,
+        };
+        keysets.verify_signature(&mint_pubkey)?;
+
+        if pinned.is_none() {
+            let mut tx = self.localstore.begin_tx().await?;
+            self.localstore
+                .upsert_setting(&mut tx, &setting, &mint_pubkey.to_string())
+                .await?;
+            tx.commit().await?;
+        }
+        Ok(())
     }
 
-    pub async fn send_tokens(
+    /// Stores the keysets of a mint from a `/v1/keys` response without contacting the mint, e.g.
+    /// for airgapped wallets. The keyset ids must match the ids derived from the keys.
+    pub async fn add_mint_keysets_offline(
         &self,
-        wallet_keyset: &WalletKeyset,
-        amount: u64,
-    ) -> Result<TokenV3, MokshaWalletError> {
-        let balance = self.get_balance().await?;
-        if amount > balance {
-            return Err(MokshaWalletError::NotEnoughTokens);
+        mint_url: &Url,
+        keys: &KeysResponse,
+    ) -> Result<Vec<WalletKeyset>, MokshaWalletError> {
+        let mut wallet_keysets = vec![];
+        for keyset in keys.keysets.iter() {
+            let derived_id = derive_keyset_id(&keyset.keys);
+            if derived_id != keyset.id {
+                return Err(MokshaWalletError::KeysetIdMismatch(
+                    keyset.id.clone(),
+                    derived_id,
+                ));
+            }
+            wallet_keysets.push(WalletKeyset::new(
+                &KeysetId::new(&keyset.id)?,
+                mint_url,
+                &keyset.unit,
+                0,
+                keyset.keys.clone(),
+                true,
+            ));
         }
 
         let mut tx = self.localstore.begin_tx().await?;
-        let all_proofs = self
-            .localstore
-            .get_proofs(&mut tx)
-            .await?
-            .proofs_by_keyset(&wallet_keyset.keyset_id);
+        for wallet_keyset in wallet_keysets.iter() {
+            self.localstore
+                .upsert_keyset(&mut tx, wallet_keyset)
+                .await?;
+        }
         tx.commit().await?;
+        Ok(wallet_keysets)
+    }
 
-        let selected_proofs = all_proofs.proofs_for_amount(amount)?;
-        let selected_tokens = (wallet_keyset.mint_url.to_owned(), selected_proofs.clone()).into();
-
-        let (remaining_tokens, result) = self
-            .swap_tokens(wallet_keyset, &selected_tokens, amount.into())
-            .await?;
-
-        let mut tx = self.localstore.begin_tx().await?;
-        self.localstore
-            .delete_proofs(&mut tx, &selected_proofs)
-            .await?;
+    /// Fetches a list of mint urls and adds the keysets of every reachable mint that is not
+    /// known by the wallet yet. Entries that are no valid urls are skipped
+    pub async fn add_mints_from_list(
+        &self,
+        list_url: &Url,
+    ) -> Result<MintListImport, MokshaWalletError> {
+        let mut known = self.get_mint_urls().await?;
+        let mut result = MintListImport::default();
+
+        for entry in self.client.get_mint_list(list_url).await? {
+            let Ok(mint_url) = Url::parse(&entry) else {
+                result.invalid.push(entry);
+                continue;
+            };
+            if known.contains(&mint_url) {
+                result.skipped.push(mint_url);
+                continue;
+            }
 
-        self.localstore
-            .add_proofs(&mut tx, &remaining_tokens.proofs())
-            .await?;
-        tx.commit().await?;
+            match self.add_mint_keysets(&mint_url).await {
+                Ok(_) => {
+                    known.push(mint_url.clone());
+                    result.added.push(mint_url);
+                }
+                Err(e) => result.failed.push((mint_url, e)),
+            }
+        }
         Ok(result)
     }
 
-    pub async fn receive_tokens(
+    /// Returns the ids of the keysets of the mint that were replaced before `now` (unix timestamp)
+    /// and that the wallet still holds proofs of
+    pub async fn get_expired_keysets(
         &self,
-        wallet_keyset: &WalletKeyset,
-        tokens: &TokenV3,
-    ) -> Result<(), MokshaWalletError> {
-        let total_amount = tokens.total_amount();
-        let (_, redeemed_tokens) = self
-            .swap_tokens(wallet_keyset, tokens, total_amount.into())
-            .await?;
+        mint_url: &Url,
+        now: u64,
+    ) -> Result<Vec<String>, MokshaWalletError> {
+        let keysets = self.client.get_keysets(mint_url).await?.keysets;
         let mut tx = self.localstore.begin_tx().await?;
-        self.localstore
-            .add_proofs(&mut tx, &redeemed_tokens.proofs())
-            .await?;
+        let proofs = self.localstore.get_proofs(&mut tx).await?;
         tx.commit().await?;
-        Ok(())
+
+        Ok(keysets
+            .into_iter()
+            .filter(|keyset| keyset.valid_to.is_some_and(|valid_to| valid_to <= now))
+            .filter(|keyset| {
+                proofs
+                    .proofs()
+                    .iter()
+                    .any(|proof| proof.keyset_id == keyset.id)
+            })
+            .map(|keyset| keyset.id)
+            .collect())
     }
 
-    pub async fn get_mint_quote(
+    /// Returns the fee in sats the recipient pays to the mint to swap the proofs of the token.
+    /// The fee is the sum of the input fees of all proofs rounded up to whole sats.
+    pub async fn claim_fee_estimate(
         &self,
         mint_url: &Url,
-        amount: Amount,
-        currency: CurrencyUnit,
-    ) -> Result<PostMintQuoteBolt11Response, MokshaWalletError> {
-        self.client
-            .post_mint_quote_bolt11(mint_url, amount.0, currency)
-            .await
+        token: &TokenV3,
+    ) -> Result<u64, MokshaWalletError> {
+        let keysets = self.client.get_keysets(mint_url).await?.keysets;
+        let mut fee_ppk = 0;
+        for proof in token.proofs().proofs() {
+            let keyset = keysets
+                .iter()
+                .find(|keyset| keyset.id == proof.keyset_id)
+                .ok_or_else(|| MokshaWalletError::UnknownKeyset(proof.keyset_id.clone()))?;
+            fee_ppk += keyset.input_fee_ppk;
+        }
+        Ok(fee_ppk.div_ceil(1_000))
     }
 
-    pub async fn get_melt_quote_bolt11(
-        &self,
-        mint_url: &Url,
-        invoice: String,
-        currency: CurrencyUnit,
-    ) -> Result<PostMeltQuoteBolt11Response, MokshaWalletError> {
-        self.client
-            .post_melt_quote_bolt11(mint_url, invoice.clone(), currency)
-            .await
+    /// Checks if the token can be redeemed at its mint. The keysets of all proofs must be known
+    /// by the mint and each proof amount must have a matching key in its keyset.
+    pub async fn can_redeem(&self, token: &TokenV3) -> Result<bool, MokshaWalletError> {
+        for token in token.tokens.iter() {
+            let mint_url = match &token.mint {
+                Some(url) => url,
+                None => return Ok(false),
+            };
+
+            let mint_keysets = self.client.get_keysets(mint_url).await?;
+            let mut keys = HashMap::new();
+            for proof in token.proofs.proofs() {
+                if !mint_keysets.keysets.iter().any(|k| k.id == proof.keyset_id) {
+                    return Ok(false);
+                }
+
+                if !keys.contains_key(&proof.keyset_id) {
+                    let response = self
+                        .client
+                        .get_keys_by_id(mint_url, proof.keyset_id.clone())
+                        .await?;
+                    let public_keys = response
+                        .keysets
+                        .into_iter()
+                        .find(|k| k.id == proof.keyset_id)
+                        .map(|k| k.keys)
+                        .unwrap_or_default();
+                    keys.insert(proof.keyset_id.clone(), public_keys);
+                }
+
+                if !keys[&proof.keyset_id].contains_key(&proof.amount) {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
     }
 
-    pub async fn pay_invoice(
-        &self,
-        wallet_keyset: &WalletKeyset,
-        melt_quote: &PostMeltQuoteBolt11Response,
-        invoice: String,
-    ) -> Result<(PostMeltBolt11Response, u64), MokshaWalletError> {
+    /// Checks the token against the stored keysets without contacting the mint. The keysets of
+    /// all proofs must be known for the mint of the token and have a key for each proof amount.
+    pub async fn can_redeem_offline(&self, token: &TokenV3) -> Result<bool, MokshaWalletError> {
+        let wallet_keysets = self.get_wallet_keysets().await?;
+        for token in token.tokens.iter() {
+            let mint_url = match &token.mint {
+                Some(url) => url,
+                None => return Ok(false),
+            };
+
+            for proof in token.proofs.proofs() {
+                let known = wallet_keysets.iter().any(|keyset| {
+                    keyset.mint_url == *mint_url
+                        && keyset.keyset_id.to_string() == proof.keyset_id
+                        && keyset.public_keys.contains_key(&proof.amount)
+                });
+                if !known {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns the unit of the token. A token without unit has the unit of the wallet keyset its
+    /// proofs belong to and falls back to sat if the keyset is unknown.
+    pub async fn get_token_unit(&self, token: &TokenV3) -> Result<CurrencyUnit, MokshaWalletError> {
+        if let Some(unit) = &token.currency_unit {
+            return Ok(unit.clone());
+        }
+        let keyset_ids = token
+            .proofs()
+            .proofs()
+            .into_iter()
+            .map(|proof| proof.keyset_id)
+            .collect::<Vec<_>>();
+        Ok(self
+            .get_wallet_keysets()
+            .await?
+            .into_iter()
+            .find(|keyset| keyset_ids.contains(&keyset.keyset_id.to_string()))
+            .map(|keyset| keyset.currency_unit)
+            .unwrap_or(CurrencyUnit::Sat))
+    }
+
+    pub async fn get_balance(&self) -> Result<u64, MokshaWalletError> {
         let mut tx = self.localstore.begin_tx().await?;
-        let all_proofs = self.localstore.get_proofs(&mut tx).await?;
+        let total_amount = self.localstore.get_proofs(&mut tx).await?.total_amount();
         tx.commit().await?;
+        Ok(total_amount)
+    }
 
-        let ln_amount = Self::get_invoice_amount(&invoice)? + melt_quote.fee_reserve;
+    /// Returns the total of the stored proofs per mint and keyset. Mints with keysets in several
+    /// units get one entry per keyset, so amounts of different units are never added up.
+    pub async fn get_balances(&self) -> Result<HashMap<(Url, KeysetId), u64>, MokshaWalletError> {
+        let proofs = self.get_proofs().await?;
+        Ok(self
+            .get_wallet_keysets()
+            .await?
+            .into_iter()
+            .map(|keyset| {
+                let amount = proofs.proofs_by_keyset(&keyset.keyset_id).total_amount();
+                ((keyset.mint_url, keyset.keyset_id), amount)
+            })
+            .collect())
+    }
 
-        if ln_amount > all_proofs.total_amount() {
-            return Err(MokshaWalletError::NotEnoughTokens);
-        }
-        let selected_proofs = all_proofs.proofs_for_amount(ln_amount)?;
+    /// Returns the total of the stored proofs of the mint in the given unit
+    pub async fn get_balance_for_mint(
+        &self,
+        mint_url: &Url,
+        currency_unit: &CurrencyUnit,
+    ) -> Result<u64, MokshaWalletError> {
+        let proofs = self.get_proofs().await?;
+        Ok(self
+            .get_wallet_keysets()
+            .await?
+            .iter()
+            .filter(|keyset| &keyset.mint_url == mint_url && &keyset.currency_unit == currency_unit)
+            .map(|keyset| proofs.proofs_by_keyset(&keyset.keyset_id).total_amount())
+            .sum())
+    }
 
-        let total_proofs = {
-            let selected_tokens =
-                (wallet_keyset.mint_url.to_owned(), selected_proofs.clone()).into();
-            let swap_result = self
-                .swap_tokens(wallet_keyset, &selected_tokens, ln_amount.into())
-                .await?;
+    /// Checks the state of all stored proofs of the mint (nut07) and compares the local balance
+    /// with the amount the mint considers unspent. With `prune` spent proofs are removed from the
+    /// wallet, pending ones are kept because the melt may still fail.
+    pub async fn audit_balance(
+        &self,
+        mint_url: &Url,
+        prune: bool,
+    ) -> Result<BalanceAudit, MokshaWalletError> {
+        let keyset_ids = self
+            .get_wallet_keysets()
+            .await?
+            .into_iter()
+            .filter(|keyset| &keyset.mint_url == mint_url)
+            .map(|keyset| keyset.keyset_id.to_string())
+            .collect::<Vec<_>>();
+        let proofs = self
+            .get_proofs()
+            .await?
+            .proofs()
+            .into_iter()
+            .filter(|proof| keyset_ids.contains(&proof.keyset_id))
+            .collect::<Vec<_>>();
+
+        let (mut spent, mut pending, mut unknown) = (vec![], vec![], vec![]);
+        let mut verified_balance = 0;
+        for chunk in proofs.chunks(CHECKSTATE_BATCH_SIZE) {
+            let ys = chunk.iter().map(Proof::y).collect::<Result<Vec<_>, _>>()?;
+            let states = self
+                .client
+                .post_checkstate(mint_url, ys.clone())
+                .await?
+                .states;
+            for (proof, y) in chunk.iter().zip(ys) {
+                match states.iter().find(|state| state.y == y) {
+                    Some(state) => match state.state {
+                        SpendState::Unspent => verified_balance += proof.amount,
+                        SpendState::Pending => pending.push(proof.clone()),
+                        SpendState::Spent => spent.push(proof.clone()),
+                    },
+                    None => unknown.push(proof.clone()),
+                }
+            }
+        }
 
+        let spent = Proofs::new(spent);
+        let pruned = prune && !spent.is_empty();
+        if pruned {
             let mut tx = self.localstore.begin_tx().await?;
-            self.localstore
-                .delete_proofs(&mut tx, &selected_proofs)
-                .await?;
-            self.localstore
-                .add_proofs(&mut tx, &swap_result.0.proofs())
-                .await?;
+            self.localstore.delete_proofs(&mut tx, &spent).await?;
             tx.commit().await?;
+        }
 
-            swap_result.1.proofs()
-        };
-
-        let fee_blind = self
-            .create_blank(melt_quote.fee_reserve.into(), &wallet_keyset.keyset_id)
-            .await?;
-
-        let msgs = fee_blind
-            .iter()
-            .map(|(msg, _, _)| msg.clone())
-            .collect::<Vec<BlindedMessage>>();
+        Ok(BalanceAudit {
+            local_balance: proofs.iter().map(|proof| proof.amount).sum(),
+            verified_balance,
+            spent,
+            pending: Proofs::new(pending),
+            unknown: Proofs::new(unknown),
+            pruned,
+        })
+    }
 
-        let secrets = fee_blind
-            .iter()
-            .map(|(_, _, secret)| secret.clone())
-            .collect::<Vec<String>>();
+    /// Returns the stored proofs of the mint that are spent. The proofs are checked against the
+    /// spent digest of the mint first, so only the possible matches are sent to checkstate.
+    pub async fn sync_spent_digest(&self, mint_url: &Url) -> Result<Proofs, MokshaWalletError> {
+        let keyset_ids = self
+            .get_wallet_keysets()
+            .await?
+            .into_iter()
+            .filter(|keyset| &keyset.mint_url == mint_url)
+            .map(|keyset| keyset.keyset_id.to_string())
+            .collect::<Vec<_>>();
+        let digest = self.client.get_spent_digest(mint_url).await?;
+        let mut candidates = vec![];
+        for proof in self.get_proofs().await?.proofs() {
+            if keyset_ids.contains(&proof.keyset_id) && digest.might_contain(&proof.y()?)? {
+                candidates.push(proof);
+            }
+        }
 
-        let outputs = fee_blind
-            .iter()
-            .map(|(msg, blinding_factor, _)| (msg.clone(), blinding_factor.clone()))
-            .collect::<Vec<(BlindedMessage, BlindingFactor)>>();
+        let mut spent = vec![];
+        for chunk in candidates.chunks(CHECKSTATE_BATCH_SIZE) {
+            let ys = chunk.iter().map(Proof::y).collect::<Result<Vec<_>, _>>()?;
+            let states = self
+                .client
+                .post_checkstate(mint_url, ys.clone())
+                .await?
+                .states;
+            for (proof, y) in chunk.iter().zip(ys) {
+                if states
+                    .iter()
+                    .any(|state| state.y == y && state.state == SpendState::Spent)
+                {
+                    spent.push(proof.clone());
+                }
+            }
+        }
+        Ok(Proofs::new(spent))
+    }
 
+    /// Returns the whole balance as one token per mint and unit. The proofs stay in the wallet
+    /// until they are removed with [`Wallet::withdraw_tokens`].
+    pub async fn export_tokens(&self) -> Result<Vec<TokenV3>, MokshaWalletError> {
         let mut tx = self.localstore.begin_tx().await?;
-        match self
-            .melt_token(
-                &wallet_keyset.mint_url,
-                melt_quote.to_owned().quote,
-                &total_proofs,
-                msgs,
-            )
-            .await
-        {
-            Ok(response) => {
-                if !response.paid {
-                    self.localstore.add_proofs(&mut tx, &total_proofs).await?;
-                }
-                let change_proofs = self.create_proofs_from_blinded_signatures(
-                    &wallet_keyset.keyset_id,
-                    &wallet_keyset.public_keys,
-                    response.clone().change,
-                    secrets,
-                    outputs,
-                )?;
-                self.localstore.add_proofs(&mut tx, &change_proofs).await?;
-                tx.commit().await?;
+        let all_proofs = self.localstore.get_proofs(&mut tx).await?;
+        let keysets = self.localstore.get_keysets(&mut tx).await?;
+        tx.commit().await?;
 
-                Ok((response, change_proofs.total_amount()))
+        let mut grouped: Vec<(Url, CurrencyUnit, Vec<Proof>)> = vec![];
+        for keyset in keysets {
+            let proofs = all_proofs.proofs_by_keyset(&keyset.keyset_id).proofs();
+            if proofs.is_empty() {
+                continue;
             }
-            Err(e) => {
-                self.localstore.add_proofs(&mut tx, &total_proofs).await?;
-                tx.commit().await?;
-                Err(e)
+            match grouped.iter_mut().find(|(mint_url, unit, _)| {
+                mint_url == &keyset.mint_url && unit == &keyset.currency_unit
+            }) {
+                Some((_, _, existing)) => existing.extend(proofs),
+                None => grouped.push((keyset.mint_url, keyset.currency_unit, proofs)),
             }
         }
+
+        Ok(grouped
+            .into_iter()
+            .map(|(mint_url, unit, proofs)| (mint_url, unit, Proofs::new(proofs)).into())
+            .collect())
     }
 
-    pub async fn get_melt_quote_btconchain(
+    /// Deletes the proofs of exported tokens from the wallet
+    pub async fn withdraw_tokens(&self, tokens: &[TokenV3]) -> Result<(), MokshaWalletError> {
+        let proofs = tokens
+            .iter()
+            .flat_map(|token| token.proofs().proofs())
+            .collect();
+        let mut tx = self.localstore.begin_tx().await?;
+        self.localstore
+            .delete_proofs(&mut tx, &Proofs::new(proofs))
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn send_tokens(
         &self,
-        mint_url: &Url,
-        address: String,
+        wallet_keyset: &WalletKeyset,
         amount: u64,
-    ) -> Result<Vec<PostMeltQuoteBtcOnchainResponse>, MokshaWalletError> {
-        self.client
-            .post_melt_quote_onchain(mint_url, address, amount, CurrencyUnit::Sat)
+        force: bool,
+    ) -> Result<TokenV3, MokshaWalletError> {
+        let proofs = self
+            .get_proofs()
+            .await?
+            .proofs_by_keyset(&wallet_keyset.keyset_id);
+        self.send_from_proofs(wallet_keyset, proofs, amount, force, None, None)
             .await
     }
 
-    pub async fn pay_onchain(
+    /// Sends tokens that are locked to the pubkeys (NUT-11). They can only be spent with the
+    /// signatures of `n_sigs` of the matching secret keys. With a `locktime` the P2PK key of this
+    /// wallet can reclaim the tokens with [`Wallet::reclaim_tokens`] after that unix time. The
+    /// locked secrets are random, so they can't be restored from the seed.
+    pub async fn send_tokens_locked(
         &self,
         wallet_keyset: &WalletKeyset,
-        melt_quote: &PostMeltQuoteBtcOnchainResponse,
-    ) -> Result<PostMeltBtcOnchainResponse, MokshaWalletError> {
+        amount: u64,
+        pubkeys: &[PublicKey],
+        n_sigs: usize,
+        locktime: Option<u64>,
+        force: bool,
+    ) -> Result<TokenV3, MokshaWalletError> {
+        let mut lock = P2PKSecret::new_multisig(pubkeys, n_sigs)?;
+        if let Some(locktime) = locktime {
+            lock = lock.with_locktime(locktime, &[self.p2pk_pubkey().await?]);
+        }
+        let proofs = self
+            .get_proofs()
+            .await?
+            .proofs_by_keyset(&wallet_keyset.keyset_id);
+        self.send_from_proofs(wallet_keyset, proofs, amount, force, None, Some(&lock))
+            .await
+    }
+
+    /// Receives tokens that were sent with [`Wallet::send_tokens_locked`] and a locktime, e.g.
+    /// because the recipient never claimed them. The proofs are signed with the P2PK keys of this
+    /// wallet that are refund pubkeys of the secret. Fails if the locktime is after `now`, because
+    /// the mint would reject the proofs.
+    pub async fn reclaim_tokens(
+        &self,
+        wallet_keyset: &WalletKeyset,
+        tokens: &TokenV3,
+        now: u64,
+    ) -> Result<TokenV3, MokshaWalletError> {
+        let (index, _) = self.p2pk_key_index().await?;
+        let secp = Secp256k1::new();
+        let keys = (0..=index)
+            .map(|index| self.secret.derive_p2pk_key(index))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut signed = vec![];
+        for proof in tokens.proofs().proofs() {
+            let refund = match P2PKSecret::from_secret(&proof.secret)? {
+                Some(P2PKSecret {
+                    locktime: Some(locktime),
+                    ..
+                }) if now < locktime => {
+                    return Err(MokshaWalletError::LocktimeNotReached(locktime))
+                }
+                Some(p2pk) if p2pk.locktime.is_some() => p2pk.refund,
+                _ => return Err(MokshaWalletError::NotReclaimable),
+            };
+            signed.push(
+                match keys
+                    .iter()
+                    .find(|key| refund.contains(&key.public_key(&secp)))
+                {
+                    Some(key) => proof.sign_p2pk(key)?,
+                    // without refund pubkeys anyone can spend the proof after the locktime
+                    None if refund.is_empty() => proof,
+                    None => return Err(MokshaWalletError::NotReclaimable),
+                },
+            );
+        }
+
+        let tokens: TokenV3 = (
+            wallet_keyset.mint_url.to_owned(),
+            wallet_keyset.currency_unit.clone(),
+            Proofs::new(signed),
+        )
+            .into();
+        self.receive_tokens(wallet_keyset, &tokens, true).await
+    }
+
+    /// Returns the derivation index of the current P2PK signing key and the name of the setting
+    /// it is stored in
+    async fn p2pk_key_index(&self) -> Result<(u32, String), MokshaWalletError> {
+        let first_key = self.secret.derive_p2pk_key(0)?;
+        let setting = format!(
+            "{P2PK_KEY_INDEX_SETTING_PREFIX}{}",
+            first_key.public_key(&Secp256k1::new())
+        );
         let mut tx = self.localstore.begin_tx().await?;
-        let all_proofs = self.localstore.get_proofs(&mut tx).await?;
+        let value = self.localstore.get_setting(&mut tx, &setting).await?;
         tx.commit().await?;
+        Ok((
+            value.and_then(|v| v.parse().ok()).unwrap_or_default(),
+            setting,
+        ))
+    }
 
-        let ln_amount = melt_quote.amount + melt_quote.fee;
-
-        if ln_amount > all_proofs.total_amount() {
-            return Err(MokshaWalletError::NotEnoughTokens);
-        }
-        let selected_proofs = all_proofs.proofs_for_amount(ln_amount)?;
+    /// Returns the pubkey of the current P2PK signing key, which is derived from the seed
+    pub async fn p2pk_pubkey(&self) -> Result<PublicKey, MokshaWalletError> {
+        let (index, _) = self.p2pk_key_index().await?;
+        Ok(self
+            .secret
+            .derive_p2pk_key(index)?
+            .public_key(&Secp256k1::new()))
+    }
 
+    /// Moves to the next P2PK signing key, e.g. after the current key was exposed. Stored proofs
+    /// that the previous keys of the wallet can unlock are swapped for proofs locked to the new
+    /// key. Proofs that also need signatures of other pubkeys are kept. Returns the new pubkey.
+    pub async fn rotate_p2pk_key(&self) -> Result<PublicKey, MokshaWalletError> {
+        let (index, setting) = self.p2pk_key_index().await?;
+        let secp = Secp256k1::new();
+        let old_keys = (0..=index)
+            .map(|index| self.secret.derive_p2pk_key(index))
+            .collect::<Result<Vec<_>, _>>()?;
+        let new_pubkey = self.secret.derive_p2pk_key(index + 1)?.public_key(&secp);
+
+        // proofs that are not migrated because a swap fails are migrated by the next rotation
         let mut tx = self.localstore.begin_tx().await?;
-        let total_proofs = {
-            let selected_tokens =
-                (wallet_keyset.mint_url.to_owned(), selected_proofs.clone()).into();
-            let swap_result = self
-                .swap_tokens(wallet_keyset, &selected_tokens, ln_amount.into())
-                .await?;
-            self.localstore
-                .delete_proofs(&mut tx, &selected_proofs)
-                .await?;
-            self.localstore
-                .add_proofs(&mut tx, &swap_result.0.proofs())
-                .await?;
+        self.localstore
+            .upsert_setting(&mut tx, &setting, &(index + 1).to_string())
+            .await?;
+        tx.commit().await?;
 
-            swap_result.1.proofs()
-        };
+        let proofs = self.get_proofs().await?;
+        for keyset in self.get_wallet_keysets().await? {
+            let mut locked = vec![];
+            for proof in proofs.proofs_by_keyset(&keyset.keyset_id).proofs() {
+                let Some(p2pk) = P2PKSecret::from_secret(&proof.secret).ok().flatten() else {
+                    continue;
+                };
+                let signers = p2pk.signers();
+                let keys = old_keys
+                    .iter()
+                    .filter(|key| signers.contains(&key.public_key(&secp)))
+                    .take(p2pk.n_sigs)
+                    .collect::<Vec<_>>();
+                if keys.len() < p2pk.n_sigs {
+                    continue;
+                }
+                locked.push(
+                    keys.into_iter()
+                        .try_fold(proof, |proof, key| proof.sign_p2pk(key))?,
+                );
+            }
+            if locked.is_empty() {
+                continue;
+            }
 
-        let melt_response = self
-            .client
-            .post_melt_onchain(
-                &wallet_keyset.mint_url,
-                total_proofs.clone(),
-                melt_quote.quote.clone(),
+            let locked = Proofs::new(locked);
+            let tokens: TokenV3 = (
+                keyset.mint_url.to_owned(),
+                keyset.currency_unit.clone(),
+                locked.clone(),
             )
-            .await?;
+                .into();
+            let (_, migrated) = self
+                .swap_tokens_to(
+                    &keyset,
+                    &tokens,
+                    locked.total_amount().into(),
+                    Some(&P2PKSecret::new(new_pubkey)),
+                )
+                .await?;
 
-        if melt_response.state == MeltBtcOnchainState::Paid {
+            let mut tx = self.localstore.begin_tx().await?;
+            self.localstore.delete_proofs(&mut tx, &locked).await?;
             self.localstore
-                .delete_proofs(&mut tx, &total_proofs)
+                .add_stored_proofs(&mut tx, &migrated)
                 .await?;
+            tx.commit().await?;
         }
-        tx.commit().await?;
-        Ok(melt_response)
+        Ok(new_pubkey)
     }
 
-    async fn create_secrets(
+    /// Sends tokens only from proofs with the given label. The change keeps the label.
+    pub async fn send_tokens_from_label(
         &self,
-        keyset_id: &KeysetId,
-        amount: u32,
-    ) -> Result<Vec<(String, BlindingFactor)>, MokshaWalletError> {
+        wallet_keyset: &WalletKeyset,
+        label: &str,
+        amount: u64,
+        force: bool,
+    ) -> Result<TokenV3, MokshaWalletError> {
         let mut tx = self.localstore.begin_tx().await?;
-        let all_keysets = self.localstore.get_keysets(&mut tx).await?;
-        let keyset = all_keysets
-            .iter()
-            .find(|k| k.keyset_id == *keyset_id)
-            .expect("keyset not found create-secrets");
-
-        let start_index = (keyset.last_index + 1) as u32;
-        let secret_range = self.secret.derive_range(keyset_id, start_index, amount)?;
-
-        self.localstore
-            .update_keyset_last_index(
-                &mut tx,
-                &WalletKeyset {
-                    last_index: (start_index + amount - 1) as u64,
-                    ..keyset.clone()
-                },
-            )
-            .await?;
+        let proofs = self
+            .localstore
+            .get_stored_proofs(&mut tx)
+            .await?
+            .with_label(label)
+            .proofs()
+            .proofs_by_keyset(&wallet_keyset.keyset_id);
         tx.commit().await?;
-        Ok(secret_range)
+        self.send_from_proofs(wallet_keyset, proofs, amount, force, Some(label), None)
+            .await
     }
 
-    pub async fn swap_tokens(
+    async fn send_from_proofs(
         &self,
         wallet_keyset: &WalletKeyset,
-        tokens: &TokenV3,
-        splt_amount: Amount,
-    ) -> Result<(TokenV3, TokenV3), MokshaWalletError> {
-        let total_token_amount = tokens.total_amount();
-        let first_amount: Amount = (total_token_amount - splt_amount.0).into();
-        let first_secrets = self
-            .create_secrets(&wallet_keyset.keyset_id, first_amount.split().len() as u32)
-            .await?;
-        let first_outputs = self.create_blinded_messages(
-            &wallet_keyset.keyset_id,
-            first_amount,
-            first_secrets.clone(),
-        )?;
+        proofs: Proofs,
+        amount: u64,
+        force: bool,
+        change_label: Option<&str>,
+        lock_to: Option<&P2PKSecret>,
+    ) -> Result<TokenV3, MokshaWalletError> {
+        self.check_max_send_amount(amount, force).await?;
+        if amount > proofs.total_amount() {
+            return Err(MokshaWalletError::NotEnoughTokens);
+        }
 
-        // ############################################################################
+        // proofs that match the amount exactly are sent as they are, without a swap. Locked
+        // tokens always need a swap to get proofs with the locked secrets.
+        let exact_proofs = proofs
+            .proofs_for_exact_amount(amount)
+            .filter(|_| lock_to.is_none());
+        let selected_proofs = match exact_proofs {
+            Some(ref exact_proofs) => exact_proofs.clone(),
+            None => proofs.select_proofs(amount)?,
+        };
+        let selected_tokens: TokenV3 =
+            (wallet_keyset.mint_url.to_owned(), selected_proofs.clone()).into();
+
+        let result = async {
+            let (remaining_proofs, result) = match exact_proofs {
+                Some(_) => (StoredProofs::default(), selected_tokens),
+                None => {
+                    let (remaining_proofs, result) = self
+                        .swap_tokens_to(wallet_keyset, &selected_tokens, amount.into(), lock_to)
+                        .await?;
+                    (remaining_proofs, to_token(wallet_keyset, &result))
+                }
+            };
 
-        let second_amount = splt_amount.clone();
-        let second_secrets = self
-            .create_secrets(&wallet_keyset.keyset_id, second_amount.split().len() as u32)
-            .await?;
-        let second_outputs = self.create_blinded_messages(
-            &wallet_keyset.keyset_id,
-            second_amount,
-            second_secrets.clone(),
-        )?;
+            let remaining_proofs = remaining_proofs.labeled(change_label);
 
-        let mut total_outputs = vec![];
-        total_outputs.extend(get_blinded_msg(first_outputs.clone()));
-        total_outputs.extend(get_blinded_msg(second_outputs.clone()));
+            let mut tx = self.localstore.begin_tx().await?;
+            self.localstore
+                .delete_proofs(&mut tx, &selected_proofs)
+                .await?;
 
-        if tokens.total_amount() != total_outputs.total_amount() {
-            return Err(MokshaWalletError::InvalidProofs);
+            self.localstore
+                .add_stored_proofs(&mut tx, &remaining_proofs)
+                .await?;
+            self.localstore
+                .add_transaction(
+                    &mut tx,
+                    &WalletTransaction::new(Operation::Send, amount, wallet_keyset, None),
+                )
+                .await?;
+            tx.commit().await?;
+            Ok(result)
         }
+        .await;
+
+        self.log_operation(
+            Operation::Send,
+            serde_json::json!({
+                "mint_url": wallet_keyset.mint_url.as_str(),
+                "amount": amount,
+                "proofs": proof_ids(&selected_proofs),
+            }),
+            &result,
+            |token| serde_json::json!({ "proofs": proof_ids(&token.proofs()) }),
+        )
+        .await;
+        result
+    }
 
-        let split_result = self
-            .client
-            .post_swap(&wallet_keyset.mint_url, tokens.proofs(), total_outputs)
+    /// Sets the label of stored proofs for coin control. `None` removes the label.
+    pub async fn set_proof_labels(
+        &self,
+        proofs: &Proofs,
+        label: Option<&str>,
+    ) -> Result<(), MokshaWalletError> {
+        let mut tx = self.localstore.begin_tx().await?;
+        self.localstore
+            .update_proof_labels(&mut tx, proofs, label)
             .await?;
+        tx.commit().await?;
+        Ok(())
+    }
 
-        if split_result.signatures.is_empty() {
-            return Ok((TokenV3::empty(), TokenV3::empty()));
+    /// Swaps the proofs of the token for new ones and returns the received tokens
+    pub async fn receive_tokens(
+        &self,
+        wallet_keyset: &WalletKeyset,
+        tokens: &TokenV3,
+        force: bool,
+    ) -> Result<TokenV3, MokshaWalletError> {
+        if !force {
+            self.check_receive_fee(&wallet_keyset.mint_url, tokens)
+                .await?;
         }
-
-        let len_first = first_secrets.len();
-        let secrets = [first_secrets, second_secrets].concat();
-        let outputs = [first_outputs, second_outputs].concat();
-
-        let secrets = secrets.into_iter().map(|(s, _)| s).collect::<Vec<String>>();
-
-        let proofs = self
-            .create_proofs_from_blinded_signatures(
-                &wallet_keyset.keyset_id,
-                &wallet_keyset.public_keys,
-                split_result.signatures,
-                secrets,
-                outputs,
-            )?
-            .proofs();
-
-        let first_tokens: TokenV3 = (
-            wallet_keyset.mint_url.to_owned(),
-            wallet_keyset.currency_unit.clone(),
-            proofs[0..len_first].to_vec().into(),
-        )
-            .into();
-        let second_tokens: TokenV3 = (
-            wallet_keyset.mint_url.to_owned(),
-            wallet_keyset.currency_unit.clone(),
-            proofs[len_first..proofs.len()].to_vec().into(),
+        let total_amount = tokens.total_amount();
+        let result = async {
+            let (_, redeemed_proofs) = self
+                .swap_tokens_to(wallet_keyset, tokens, total_amount.into(), None)
+                .await?;
+            let redeemed_tokens = to_token(wallet_keyset, &redeemed_proofs);
+            let mut tx = self.localstore.begin_tx().await?;
+            self.localstore
+                .add_stored_proofs(&mut tx, &redeemed_proofs)
+                .await?;
+            self.localstore
+                .add_transaction(
+                    &mut tx,
+                    &WalletTransaction::new(
+                        Operation::Receive,
+                        redeemed_tokens.total_amount(),
+                        wallet_keyset,
+                        tokens.memo.clone(),
+                    ),
+                )
+                .await?;
+            tx.commit().await?;
+            Ok(redeemed_tokens)
+        }
+        .await;
+
+        self.log_operation(
+            Operation::Receive,
+            serde_json::json!({
+                "mint_url": wallet_keyset.mint_url.as_str(),
+                "amount": total_amount,
+                "proofs": proof_ids(&tokens.proofs()),
+            }),
+            &result,
+            |token| serde_json::json!({ "proofs": proof_ids(&token.proofs()) }),
         )
-            .into();
+        .await;
+        result
+    }
 
-        if tokens.total_amount() != first_tokens.total_amount() + second_tokens.total_amount() {
-            println!(
-                "Error in swap: input {:?} != output {:?} + {:?}",
-                tokens.total_amount(),
-                first_tokens.total_amount(),
-                second_tokens.total_amount()
-            );
+    /// Receives the token without adding its mint. Tokens of mints the wallet doesn't know yet are
+    /// rejected with [`MokshaWalletError::UnknownMint`], so the caller decides if the mint is added.
+    /// Known mints without an active keyset for the unit of the token are rejected with
+    /// [`MokshaWalletError::NoActiveKeyset`].
+    pub async fn receive_tokens_from_known_mint(
+        &self,
+        tokens: &TokenV3,
+        force: bool,
+    ) -> Result<TokenV3, MokshaWalletError> {
+        let mint_url = tokens.mint().ok_or(MokshaCoreError::InvalidToken)?;
+        let wallet_keysets = self.get_wallet_keysets().await?;
+        if !wallet_keysets
+            .iter()
+            .any(|keyset| keyset.mint_url == mint_url)
+        {
+            return Err(MokshaWalletError::UnknownMint(mint_url.to_string()));
         }
-
-        Ok((first_tokens, second_tokens))
+        let currency = self.get_token_unit(tokens).await?;
+        let wallet_keyset = wallet_keysets
+            .get_active(&mint_url, &currency)
+            .ok_or_else(|| {
+                MokshaWalletError::NoActiveKeyset(mint_url.to_string(), currency.to_string())
+            })?;
+        self.receive_tokens(wallet_keyset, tokens, force).await
     }
 
-    pub async fn get_mint_info(
+    pub async fn get_mint_quote(
         &self,
         mint_url: &Url,
-    ) -> Result<MintInfoResponse, MokshaWalletError> {
-        self.client.get_info(mint_url).await
+        amount: Amount,
+        currency: CurrencyUnit,
+    ) -> Result<PostMintQuoteBolt11Response, MokshaWalletError> {
+        self.client
+            .post_mint_quote_bolt11(mint_url, amount.0, currency)
+            .await
     }
 
-    async fn melt_token(
+    pub async fn get_melt_quote_bolt11(
         &self,
         mint_url: &Url,
-        quote_id: String,
-        proofs: &Proofs,
-        fee_blinded_messages: Vec<BlindedMessage>,
-    ) -> Result<PostMeltBolt11Response, MokshaWalletError> {
-        let melt_response = self
-            .client
-            .post_melt_bolt11(mint_url, proofs.clone(), quote_id, fee_blinded_messages)
-            .await?;
-
-        if melt_response.paid {
-            let mut tx = self.localstore.begin_tx().await?;
-            self.localstore.delete_proofs(&mut tx, proofs).await?;
-            tx.commit().await?;
-        }
-        Ok(melt_response)
-    }
-
-    fn decode_invoice(payment_request: &str) -> Result<LNInvoice, MokshaWalletError> {
-        LNInvoice::from_str(payment_request)
-            .map_err(|err| MokshaWalletError::DecodeInvoice(payment_request.to_owned(), err))
+        invoice: String,
+        currency: CurrencyUnit,
+        amount: Option<u64>,
+    ) -> Result<PostMeltQuoteBolt11Response, MokshaWalletError> {
+        self.client
+            .post_melt_quote_bolt11(mint_url, invoice.clone(), currency, amount)
+            .await
     }
 
-    fn get_invoice_amount(payment_request: &str) -> Result<u64, MokshaWalletError> {
-        let invoice = Self::decode_invoice(payment_request)?;
-        Ok(invoice
-            .amount_milli_satoshis()
-            .ok_or_else(|| MokshaWalletError::InvalidInvoice(payment_request.to_owned()))?
-            / 1000)
+    pub async fn pay_invoice(
+        &self,
+        wallet_keyset: &WalletKeyset,
+        melt_quote: &PostMeltQuoteBolt11Response,
+        invoice: String,
+        force: bool,
+    ) -> Result<(PostMeltBolt11Response, u64), MokshaWalletError> {
+        let inputs = serde_json::json!({
+            "mint_url": wallet_keyset.mint_url.as_str(),
+            "quote": melt_quote.quote,
+            "invoice": invoice,
+            "amount": melt_quote.amount,
+            "fee_reserve": melt_quote.fee_reserve,
+        });
+        let result = self
+            .melt_bolt11(wallet_keyset, melt_quote, invoice, force)
+            .await;
+        self.log_operation(
+            Operation::Melt,
+            inputs,
+            &result,
+            |(response, change)| serde_json::json!({ "paid": response.paid, "change": change }),
+        )
+        .await;
+        result
     }
 
-    pub async fn mint_tokens(
+    async fn melt_bolt11(
         &self,
         wallet_keyset: &WalletKeyset,
-        payment_method: &PaymentMethod,
-        amount: Amount,
-        quote_id: String,
-    ) -> Result<TokenV3, MokshaWalletError> {
-        let split_amount = amount.split();
-
-        let secret_range = self
-            .create_secrets(&wallet_keyset.keyset_id, split_amount.len() as u32)
+        melt_quote: &PostMeltQuoteBolt11Response,
+        invoice: String,
+        force: bool,
+    ) -> Result<(PostMeltBolt11Response, u64), MokshaWalletError> {
+        // amountless invoices are paid with the amount from the melt quote
+        let invoice_amount = Self::get_invoice_amount(&invoice)?.unwrap_or(melt_quote.amount);
+        self.check_fee_reserve(invoice_amount, melt_quote.fee_reserve, force)
             .await?;
+        let ln_amount = invoice_amount + melt_quote.fee_reserve;
+        self.check_max_send_amount(ln_amount, force).await?;
 
-        let blinded_messages = split_amount
-            .into_iter()
-            .zip(secret_range)
-            .map(|(amount, (secret, blinding_factor))| {
-                let b_ = self.dhke.step1_alice(&secret, &blinding_factor)?;
-                Ok((
-                    BlindedMessage {
-                        amount,
-                        b_,
-                        id: wallet_keyset.keyset_id.to_string(), // FIXME use keyset_id
+        let mut tx = self.localstore.begin_tx().await?;
+        let all_proofs = self.localstore.get_proofs(&mut tx).await?;
+        tx.commit().await?;
+
+        if ln_amount > all_proofs.total_amount() {
+            return Err(MokshaWalletError::NotEnoughTokens);
+        }
+        let selected_proofs = all_proofs.select_proofs(ln_amount)?;
+
+        let total_proofs = {
+            let selected_tokens =
+                (wallet_keyset.mint_url.to_owned(), selected_proofs.clone()).into();
+            let swap_result = self
+                .swap_tokens_to(wallet_keyset, &selected_tokens, ln_amount.into(), None)
+                .await?;
+
+            // the proofs for the melt are only kept in pending_melts until the result is stored,
+            // so recover_pending can restore them if the wallet stops in between
+            let mut tx = self.localstore.begin_tx().await?;
+            self.localstore
+                .delete_proofs(&mut tx, &selected_proofs)
+                .await?;
+            self.localstore
+                .add_stored_proofs(&mut tx, &swap_result.0)
+                .await?;
+            self.localstore
+                .add_pending_melt(
+                    &mut tx,
+                    &PendingMelt {
+                        mint_url: wallet_keyset.mint_url.to_owned(),
+                        quote: melt_quote.quote.to_owned(),
+                        proofs: swap_result.1.clone(),
                     },
-                    blinding_factor,
-                    secret,
-                ))
-            })
-            .collect::<Result<Vec<(_, _, _)>, MokshaWalletError>>()?;
+                )
+                .await?;
+            tx.commit().await?;
 
-        let signatures = match payment_method {
-            PaymentMethod::Bolt11 => {
-                let post_mint_resp = self
-                    .client
-                    .post_mint_bolt11(
-                        &wallet_keyset.mint_url,
-                        quote_id,
-                        blinded_messages
-                            .clone()
-                            .into_iter()
-                            .map(|(msg, _, _)| msg)
-                            .collect::<Vec<BlindedMessage>>(),
-                    )
-                    .await?;
-                post_mint_resp.signatures
-            }
-            PaymentMethod::BtcOnchain => {
-                let post_mint_resp = self
-                    .client
-                    .post_mint_onchain(
-                        &wallet_keyset.mint_url,
-                        quote_id,
-                        blinded_messages
-                            .clone()
-                            .into_iter()
-                            .map(|(msg, _, _)| msg)
-                            .collect::<Vec<BlindedMessage>>(),
-                    )
-                    .await?;
-                post_mint_resp.signatures
-            }
+            swap_result.1
         };
 
-        // step 3: unblind signatures
-        let current_keyset_id = wallet_keyset.keyset_id.to_string(); // FIXME
+        let (blank_start_index, fee_blind) = self
+            .create_blank(melt_quote.fee_reserve.into(), &wallet_keyset.keyset_id)
+            .await?;
 
-        let proofs = signatures
+        let msgs = fee_blind
             .iter()
-            .zip(blinded_messages)
-            .map(|(p, (_, priv_key, secret))| {
-                let key = wallet_keyset
-                    .public_keys
-                    .get(&p.amount)
-                    .expect("msg amount not found in mint keys");
-                let pub_alice = self.dhke.step3_alice(p.c_, priv_key, *key).unwrap();
-                Proof::new(p.amount, secret, pub_alice, current_keyset_id.clone())
+            .map(|(msg, _, _)| msg.clone())
+            .collect::<Vec<BlindedMessage>>();
+
+        let secrets = fee_blind
+            .iter()
+            .map(|(_, _, secret)| secret.clone())
+            .collect::<Vec<String>>();
+
+        let outputs = fee_blind
+            .iter()
+            .map(|(msg, blinding_factor, _)| (msg.clone(), blinding_factor.clone()))
+            .collect::<Vec<(BlindedMessage, BlindingFactor)>>();
+
+        let melt_result = self
+            .melt_token(
+                &wallet_keyset.mint_url,
+                melt_quote.to_owned().quote,
+                &total_proofs.proofs(),
+                msgs,
+            )
+            .await;
+        // the change is verified before anything is stored, so an invalid response leaves the
+        // proofs in the pending melts where recover_pending settles them
+        let change_proofs = match &melt_result {
+            Ok(response) => StoredProofs::with_derivation_indexes(
+                self.verify_change(
+                    wallet_keyset,
+                    &response.change,
+                    melt_quote.fee_reserve,
+                    secrets,
+                    outputs,
+                )?,
+                blank_start_index,
+            ),
+            Err(_) => StoredProofs::default(),
+        };
+
+        let mut tx = self.localstore.begin_tx().await?;
+        self.localstore
+            .delete_pending_melt_proofs(&mut tx, &total_proofs.proofs())
+            .await?;
+        match melt_result {
+            Ok(response) => {
+                if !response.paid {
+                    self.localstore
+                        .add_stored_proofs(&mut tx, &total_proofs)
+                        .await?;
+                }
+                self.localstore
+                    .add_stored_proofs(&mut tx, &change_proofs)
+                    .await?;
+                if response.paid {
+                    // the spent amount includes the fees that were not returned as change
+                    let amount = total_proofs.total_amount() - change_proofs.total_amount();
+                    self.localstore
+                        .add_transaction(
+                            &mut tx,
+                            &WalletTransaction::new(Operation::Melt, amount, wallet_keyset, None),
+                        )
+                        .await?;
+                }
+                tx.commit().await?;
+
+                Ok((response, change_proofs.total_amount()))
+            }
+            Err(e) => {
+                self.localstore
+                    .add_stored_proofs(&mut tx, &total_proofs)
+                    .await?;
+                tx.commit().await?;
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn get_melt_quote_btconchain(
+        &self,
+        mint_url: &Url,
+        address: String,
+        amount: u64,
+    ) -> Result<Vec<PostMeltQuoteBtcOnchainResponse>, MokshaWalletError> {
+        self.client
+            .post_melt_quote_onchain(mint_url, address, amount, CurrencyUnit::Sat)
+            .await
+    }
+
+    /// Pays all outputs with a single onchain transaction, which is cheaper than paying each
+    /// output separately
+    pub async fn pay_onchain_batch(
+        &self,
+        wallet_keyset: &WalletKeyset,
+        outputs: Vec<(String, u64)>,
+    ) -> Result<PostMeltBtcOnchainResponse, MokshaWalletError> {
+        let outputs = outputs
+            .into_iter()
+            .map(|(address, amount)| OnchainOutput { address, amount })
+            .collect();
+        let quotes = self
+            .client
+            .post_melt_quote_onchain_batch(&wallet_keyset.mint_url, outputs, CurrencyUnit::Sat)
+            .await?;
+        let quote = quotes.first().ok_or_else(|| {
+            MokshaWalletError::UnexpectedResponse("mint returned no melt quote".to_owned())
+        })?;
+        self.pay_onchain(wallet_keyset, quote, false).await
+    }
+
+    pub async fn pay_onchain(
+        &self,
+        wallet_keyset: &WalletKeyset,
+        melt_quote: &PostMeltQuoteBtcOnchainResponse,
+        force: bool,
+    ) -> Result<PostMeltBtcOnchainResponse, MokshaWalletError> {
+        let result = self.melt_onchain(wallet_keyset, melt_quote, force).await;
+        self.log_operation(
+            Operation::Melt,
+            serde_json::json!({
+                "mint_url": wallet_keyset.mint_url.as_str(),
+                "quote": melt_quote.quote,
+                "amount": melt_quote.amount,
+                "fee": melt_quote.fee,
+            }),
+            &result,
+            |response| serde_json::to_value(response).unwrap_or_default(),
+        )
+        .await;
+        result
+    }
+
+    async fn melt_onchain(
+        &self,
+        wallet_keyset: &WalletKeyset,
+        melt_quote: &PostMeltQuoteBtcOnchainResponse,
+        force: bool,
+    ) -> Result<PostMeltBtcOnchainResponse, MokshaWalletError> {
+        let ln_amount = melt_quote.amount + melt_quote.fee;
+        self.check_max_send_amount(ln_amount, force).await?;
+
+        let mut tx = self.localstore.begin_tx().await?;
+        let all_proofs = self.localstore.get_proofs(&mut tx).await?;
+        tx.commit().await?;
+
+        if ln_amount > all_proofs.total_amount() {
+            return Err(MokshaWalletError::NotEnoughTokens);
+        }
+        let selected_proofs = all_proofs.select_proofs(ln_amount)?;
+
+        let mut tx = self.localstore.begin_tx().await?;
+        let total_proofs = {
+            let selected_tokens =
+                (wallet_keyset.mint_url.to_owned(), selected_proofs.clone()).into();
+            let swap_result = self
+                .swap_tokens_to(wallet_keyset, &selected_tokens, ln_amount.into(), None)
+                .await?;
+            self.localstore
+                .delete_proofs(&mut tx, &selected_proofs)
+                .await?;
+            self.localstore
+                .add_stored_proofs(&mut tx, &swap_result.0)
+                .await?;
+
+            swap_result.1.proofs()
+        };
+
+        let melt_response = self
+            .client
+            .post_melt_onchain(
+                &wallet_keyset.mint_url,
+                total_proofs.clone(),
+                melt_quote.quote.clone(),
+            )
+            .await?;
+
+        if melt_response.state == MeltBtcOnchainState::Paid {
+            self.localstore
+                .delete_proofs(&mut tx, &total_proofs)
+                .await?;
+            self.localstore
+                .add_transaction(
+                    &mut tx,
+                    &WalletTransaction::new(
+                        Operation::Melt,
+                        total_proofs.total_amount(),
+                        wallet_keyset,
+                        None,
+                    ),
+                )
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(melt_response)
+    }
+
+    /// Derives the next secrets of the keyset. Returns the derivation index of the first secret
+    /// together with the secrets
+    async fn create_secrets(
+        &self,
+        keyset_id: &KeysetId,
+        amount: u32,
+    ) -> Result<(u32, Vec<(String, BlindingFactor)>), MokshaWalletError> {
+        let all_keysets = self.get_wallet_keysets().await?;
+        let keyset = all_keysets
+            .iter()
+            .find(|k| k.keyset_id == *keyset_id)
+            .expect("keyset not found create-secrets");
+
+        // the range is reserved before deriving, so concurrent operations never reuse an index
+        let mut tx = self.localstore.begin_tx().await?;
+        let start_index = self
+            .localstore
+            .reserve_keyset_indexes(&mut tx, keyset, amount)
+            .await? as u32;
+        tx.commit().await?;
+
+        let secret_range = self.secret.derive_range(keyset_id, start_index, amount)?;
+        Ok((start_index, secret_range))
+    }
+
+    pub async fn swap_tokens(
+        &self,
+        wallet_keyset: &WalletKeyset,
+        tokens: &TokenV3,
+        splt_amount: Amount,
+    ) -> Result<(TokenV3, TokenV3), MokshaWalletError> {
+        let (first, second) = self
+            .swap_tokens_to(wallet_keyset, tokens, splt_amount, None)
+            .await?;
+        Ok((
+            to_token(wallet_keyset, &first),
+            to_token(wallet_keyset, &second),
+        ))
+    }
+
+    /// Swaps like [`Wallet::swap_tokens`], but locks the second tokens to `n_sigs` of the pubkeys
+    /// if given
+    async fn swap_tokens_to(
+        &self,
+        wallet_keyset: &WalletKeyset,
+        tokens: &TokenV3,
+        splt_amount: Amount,
+        lock_to: Option<&P2PKSecret>,
+    ) -> Result<(StoredProofs, StoredProofs), MokshaWalletError> {
+        let inputs = serde_json::json!({
+            "mint_url": wallet_keyset.mint_url.as_str(),
+            "amount": splt_amount.0,
+            "proofs": proof_ids(&tokens.proofs()),
+        });
+        // logged before the mint is called, so reconcile can find swaps that were interrupted
+        let log_id = self.begin_operation(Operation::Swap, inputs).await?;
+        let result = self
+            .swap_proofs(log_id, wallet_keyset, tokens, splt_amount, lock_to)
+            .await;
+        self.finish_operation(log_id, &result, |(first, second)| {
+            serde_json::json!({
+                "change": proof_ids(&first.proofs()),
+                "proofs": proof_ids(&second.proofs()),
+            })
+        })
+        .await;
+        result
+    }
+
+    /// Re-checks the proofs of swaps that were interrupted, e.g. by a crash after the mint was
+    /// called but before the result was stored. Stored inputs of such swaps are swapped again if
+    /// they are still unspent and removed if the mint already spent them. Should be called on
+    /// startup before any other operation. Returns the number of reconciled swaps.
+    pub async fn reconcile(&self) -> Result<usize, MokshaWalletError> {
+        let keysets = self.get_wallet_keysets().await?;
+        let entries = self.get_operation_log(RECONCILE_LOG_ENTRIES).await?;
+        let mut reconciled = 0;
+
+        // failed swaps leave their inputs untouched, so only unfinished and successful ones are checked
+        for entry in entries
+            .iter()
+            .filter(|entry| entry.operation == Operation::Swap && entry.error.is_none())
+        {
+            let inputs: serde_json::Value = serde_json::from_str(&entry.inputs)?;
+            let input_ids = inputs["proofs"]
+                .as_array()
+                .map(|ids| ids.iter().filter_map(|id| id.as_str()).collect::<Vec<_>>())
+                .unwrap_or_default();
+
+            // inputs are removed when the result is stored, or were never stored when receiving
+            let proofs: Proofs = self
+                .get_proofs()
+                .await?
+                .proofs()
+                .into_iter()
+                .filter(|proof| {
+                    proof
+                        .y()
+                        .is_ok_and(|y| input_ids.contains(&y.to_string().as_str()))
+                })
+                .collect::<Vec<_>>()
+                .into();
+            let Some(keyset) = proofs.proofs().first().and_then(|proof| {
+                keysets.iter().find(|keyset| {
+                    keyset.keyset_id.to_string() == proof.keyset_id
+                        && Some(keyset.mint_url.as_str()) == inputs["mint_url"].as_str()
+                })
+            }) else {
+                continue;
+            };
+
+            let amount = inputs["amount"]
+                .as_u64()
+                .unwrap_or_default()
+                .min(proofs.total_amount());
+            let tokens: TokenV3 = (
+                keyset.mint_url.to_owned(),
+                keyset.currency_unit.clone(),
+                proofs.clone(),
+            )
+                .into();
+            let note = match self
+                .swap_tokens_to(keyset, &tokens, amount.into(), None)
+                .await
+            {
+                Ok((change, swapped)) => {
+                    let mut tx = self.localstore.begin_tx().await?;
+                    self.localstore.delete_proofs(&mut tx, &proofs).await?;
+                    self.localstore.add_stored_proofs(&mut tx, &change).await?;
+                    self.localstore.add_stored_proofs(&mut tx, &swapped).await?;
+                    tx.commit().await?;
+                    "interrupted, the unspent inputs were swapped again"
+                }
+                Err(MokshaWalletError::ProofAlreadyUsed(_)) => {
+                    let restored = self.restore_swap_outputs(&keysets, &entry.outputs).await?;
+                    let mut tx = self.localstore.begin_tx().await?;
+                    self.localstore.delete_proofs(&mut tx, &proofs).await?;
+                    self.localstore
+                        .add_stored_proofs(&mut tx, &restored)
+                        .await?;
+                    tx.commit().await?;
+                    "interrupted, the inputs were already spent and the outputs were restored"
+                }
+                // keep the entry, so the next run checks the proofs again
+                Err(err) => return Err(err),
+            };
+
+            if let Some(id) = entry.id {
+                let mut tx = self.localstore.begin_tx().await?;
+                self.localstore
+                    .update_log_entry(&mut tx, id, &entry.outputs, Some(note))
+                    .await?;
+                tx.commit().await?;
+            }
+            reconciled += 1;
+        }
+        Ok(reconciled)
+    }
+
+    /// Restores the proofs of bolt11 melts that were interrupted, e.g. by a crash after the proofs
+    /// were split but before the result of the melt was stored. Proofs the mint reports as unspent
+    /// are added to the wallet again, spent ones are dropped; the change of such a melt can be
+    /// recovered with [`Wallet::restore_tokens`]. Pending proofs are checked again on the next
+    /// call. Should be called on startup before any other operation. Returns the restored proofs.
+    pub async fn recover_pending(&self) -> Result<Proofs, MokshaWalletError> {
+        let mut tx = self.localstore.begin_tx().await?;
+        let pending_melts = self.localstore.get_pending_melts(&mut tx).await?;
+        tx.commit().await?;
+
+        let mut restored = vec![];
+        for pending_melt in pending_melts {
+            for chunk in pending_melt.proofs.0.chunks(CHECKSTATE_BATCH_SIZE) {
+                let ys = chunk
+                    .iter()
+                    .map(|stored| stored.proof.y())
+                    .collect::<Result<Vec<_>, _>>()?;
+                let states = self
+                    .client
+                    .post_checkstate(&pending_melt.mint_url, ys.clone())
+                    .await?
+                    .states;
+
+                let (mut unspent, mut settled) = (vec![], vec![]);
+                for (proof, y) in chunk.iter().zip(ys) {
+                    match states
+                        .iter()
+                        .find(|state| state.y == y)
+                        .map(|state| &state.state)
+                    {
+                        Some(SpendState::Unspent) => unspent.push(proof.clone()),
+                        Some(SpendState::Spent) => settled.push(proof.clone()),
+                        Some(SpendState::Pending) | None => {}
+                    }
+                }
+                let unspent = StoredProofs::new(unspent);
+                let settled = StoredProofs::new([settled, unspent.0.clone()].concat()).proofs();
+
+                let mut tx = self.localstore.begin_tx().await?;
+                self.localstore.add_stored_proofs(&mut tx, &unspent).await?;
+                self.localstore
+                    .delete_pending_melt_proofs(&mut tx, &settled)
+                    .await?;
+                tx.commit().await?;
+                restored.extend(unspent.proofs().proofs());
+            }
+        }
+        Ok(Proofs::new(restored))
+    }
+
+    /// Recovers the proofs of the keyset from the seed (nut09), e.g. after the local database
+    /// was lost. The secrets are derived in batches and the mint returns the signatures of those
+    /// it has signed. Proofs that are spent or already stored are skipped. Returns the restored
+    /// proofs.
+    pub async fn restore_tokens(
+        &self,
+        wallet_keyset: &WalletKeyset,
+    ) -> Result<Proofs, MokshaWalletError> {
+        let mut restored = vec![];
+        let mut start_index = 0;
+        loop {
+            let batch = self
+                .restore_outputs(
+                    wallet_keyset,
+                    (start_index..start_index + RESTORE_BATCH_SIZE).collect(),
+                )
+                .await?;
+            if batch.is_empty() {
+                break;
+            }
+            restored.extend(batch.0);
+            start_index += RESTORE_BATCH_SIZE;
+        }
+        let last_index = restored
+            .iter()
+            .filter_map(|stored| stored.derivation_index)
+            .map(u64::from)
+            .fold(wallet_keyset.last_index, u64::max);
+
+        let stored = self
+            .get_proofs()
+            .await?
+            .proofs()
+            .into_iter()
+            .map(|proof| proof.secret)
+            .collect::<HashSet<_>>();
+        restored.retain(|stored_proof| !stored.contains(&stored_proof.proof.secret));
+
+        let mut unspent = vec![];
+        for chunk in restored.chunks(CHECKSTATE_BATCH_SIZE) {
+            let ys = chunk
+                .iter()
+                .map(|stored| stored.proof.y())
+                .collect::<Result<Vec<_>, _>>()?;
+            let states = self
+                .client
+                .post_checkstate(&wallet_keyset.mint_url, ys.clone())
+                .await?
+                .states;
+            unspent.extend(
+                chunk
+                    .iter()
+                    .zip(ys)
+                    .filter(|(_, y)| {
+                        states
+                            .iter()
+                            .any(|state| state.y == *y && state.state == SpendState::Unspent)
+                    })
+                    .map(|(proof, _)| proof.clone()),
+            );
+        }
+
+        let unspent = StoredProofs::new(unspent);
+        let mut tx = self.localstore.begin_tx().await?;
+        self.localstore.add_stored_proofs(&mut tx, &unspent).await?;
+        // the restored indexes must not be derived again
+        if last_index > wallet_keyset.last_index {
+            self.localstore
+                .update_keyset_last_index(
+                    &mut tx,
+                    &WalletKeyset {
+                        last_index,
+                        ..wallet_keyset.clone()
+                    },
+                )
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(unspent.proofs())
+    }
+
+    async fn swap_proofs(
+        &self,
+        log_id: u64,
+        wallet_keyset: &WalletKeyset,
+        tokens: &TokenV3,
+        splt_amount: Amount,
+        lock_to: Option<&P2PKSecret>,
+    ) -> Result<(StoredProofs, StoredProofs), MokshaWalletError> {
+        let total_token_amount = tokens.total_amount();
+        let first_amount: Amount = (total_token_amount - splt_amount.0).into();
+        let (first_start_index, first_secrets) = self
+            .create_secrets(&wallet_keyset.keyset_id, first_amount.split().len() as u32)
+            .await?;
+        let first_outputs = self.create_blinded_messages(
+            &wallet_keyset.keyset_id,
+            first_amount,
+            first_secrets.clone(),
+        )?;
+
+        // ############################################################################
+
+        let second_amount = splt_amount.clone();
+        let (second_start_index, second_secrets) = match lock_to {
+            Some(lock) => (
+                None,
+                create_locked_secrets(lock, second_amount.split().len())?,
+            ),
+            None => {
+                let (start_index, secrets) = self
+                    .create_secrets(&wallet_keyset.keyset_id, second_amount.split().len() as u32)
+                    .await?;
+                (Some(start_index), secrets)
+            }
+        };
+        let second_outputs = self.create_blinded_messages(
+            &wallet_keyset.keyset_id,
+            second_amount,
+            second_secrets.clone(),
+        )?;
+
+        let mut total_outputs = vec![];
+        total_outputs.extend(get_blinded_msg(first_outputs.clone()));
+        total_outputs.extend(get_blinded_msg(second_outputs.clone()));
+
+        if tokens.total_amount() != total_outputs.total_amount() {
+            return Err(MokshaWalletError::InvalidProofs);
+        }
+
+        // the derivation indexes of the outputs are logged before the mint is called, so
+        // reconcile can restore them if the result of the swap gets lost. Locked outputs are
+        // random and can't be restored
+        let first_indexes = first_start_index..first_start_index + first_secrets.len() as u32;
+        let second_indexes = second_start_index
+            .map(|start_index| start_index..start_index + second_secrets.len() as u32)
+            .unwrap_or_default();
+        let mut tx = self.localstore.begin_tx().await?;
+        self.localstore
+            .update_log_entry(
+                &mut tx,
+                log_id,
+                &serde_json::json!({
+                    "keyset_id": wallet_keyset.keyset_id.to_string(),
+                    "derivation_indexes": first_indexes.chain(second_indexes).collect::<Vec<_>>(),
+                })
+                .to_string(),
+                None,
+            )
+            .await?;
+        tx.commit().await?;
+
+        let split_result = self
+            .client
+            .post_swap(&wallet_keyset.mint_url, tokens.proofs(), total_outputs)
+            .await?;
+
+        if split_result.signatures.is_empty() {
+            return Ok((StoredProofs::default(), StoredProofs::default()));
+        }
+
+        let len_first = first_secrets.len();
+        let secrets = [first_secrets, second_secrets].concat();
+        let outputs = [first_outputs, second_outputs].concat();
+
+        let secrets = secrets.into_iter().map(|(s, _)| s).collect::<Vec<String>>();
+
+        let proofs = self
+            .create_proofs_from_blinded_signatures(
+                wallet_keyset,
+                split_result.signatures,
+                secrets,
+                outputs,
+            )
+            .await?
+            .proofs();
+
+        let first_tokens = StoredProofs::with_derivation_indexes(
+            proofs[0..len_first].to_vec().into(),
+            first_start_index,
+        );
+        let second_proofs: Proofs = proofs[len_first..proofs.len()].to_vec().into();
+        let second_tokens = match second_start_index {
+            Some(start_index) => StoredProofs::with_derivation_indexes(second_proofs, start_index),
+            None => second_proofs.into(),
+        };
+
+        if tokens.total_amount() != first_tokens.total_amount() + second_tokens.total_amount() {
+            println!(
+                "Error in swap: input {:?} != output {:?} + {:?}",
+                tokens.total_amount(),
+                first_tokens.total_amount(),
+                second_tokens.total_amount()
+            );
+        }
+
+        Ok((first_tokens, second_tokens))
+    }
+
+    pub async fn get_mint_info(
+        &self,
+        mint_url: &Url,
+    ) -> Result<MintInfoResponse, MokshaWalletError> {
+        self.client.get_info(mint_url).await
+    }
+
+    /// Returns how many seconds the local clock at `now` is ahead of the clock of the mint.
+    /// A negative offset means the local clock is behind. `None` if the mint doesn't report its time
+    pub async fn check_time_sync(
+        &self,
+        mint_url: &Url,
+        now: u64,
+    ) -> Result<Option<i64>, MokshaWalletError> {
+        let info = self.client.get_info(mint_url).await?;
+        Ok(info.time.map(|mint_time| now as i64 - mint_time as i64))
+    }
+
+    async fn melt_token(
+        &self,
+        mint_url: &Url,
+        quote_id: String,
+        proofs: &Proofs,
+        fee_blinded_messages: Vec<BlindedMessage>,
+    ) -> Result<PostMeltBolt11Response, MokshaWalletError> {
+        let melt_response = self
+            .client
+            .post_melt_bolt11(mint_url, proofs.clone(), quote_id, fee_blinded_messages)
+            .await?;
+
+        if melt_response.paid {
+            let mut tx = self.localstore.begin_tx().await?;
+            self.localstore.delete_proofs(&mut tx, proofs).await?;
+            tx.commit().await?;
+        }
+        Ok(melt_response)
+    }
+
+    fn decode_invoice(payment_request: &str) -> Result<LNInvoice, MokshaWalletError> {
+        LNInvoice::from_str(payment_request)
+            .map_err(|err| MokshaWalletError::DecodeInvoice(payment_request.to_owned(), err))
+    }
+
+    fn get_invoice_amount(payment_request: &str) -> Result<Option<u64>, MokshaWalletError> {
+        let invoice = Self::decode_invoice(payment_request)?;
+        Ok(invoice.amount_milli_satoshis().map(|amount| amount / 1000))
+    }
+
+    pub async fn mint_tokens(
+        &self,
+        wallet_keyset: &WalletKeyset,
+        payment_method: &PaymentMethod,
+        amount: Amount,
+        quote_id: String,
+    ) -> Result<TokenV3, MokshaWalletError> {
+        let inputs = serde_json::json!({
+            "mint_url": wallet_keyset.mint_url.as_str(),
+            "payment_method": payment_method,
+            "amount": amount.0,
+            "quote": quote_id,
+        });
+        let result = self
+            .mint_proofs(wallet_keyset, payment_method, amount, quote_id)
+            .await;
+        self.log_operation(
+            Operation::Mint,
+            inputs,
+            &result,
+            |token| serde_json::json!({ "proofs": proof_ids(&token.proofs()) }),
+        )
+        .await;
+        result
+    }
+
+    /// Mints tokens for a paid quote and returns them as a token that is handed off right away.
+    /// The proofs are never added to the balance. If the token doesn't reach the receiver, the
+    /// proofs are still unspent and can be recovered with [`Wallet::restore_tokens`].
+    /// With `lock_to` the tokens are locked to the pubkey (NUT-11). The locked secrets are random,
+    /// so these tokens can't be restored from the seed.
+    pub async fn mint_and_send(
+        &self,
+        wallet_keyset: &WalletKeyset,
+        payment_method: &PaymentMethod,
+        amount: Amount,
+        quote_id: String,
+        lock_to: Option<&PublicKey>,
+    ) -> Result<TokenV3, MokshaWalletError> {
+        let inputs = serde_json::json!({
+            "mint_url": wallet_keyset.mint_url.as_str(),
+            "payment_method": payment_method,
+            "amount": amount.0,
+            "quote": quote_id,
+            "lock_to": lock_to.map(ToString::to_string),
+        });
+        let lock = lock_to
+            .map(|pubkey| P2PKSecret::new_multisig(&[*pubkey], 1))
+            .transpose()?;
+        let result = self
+            .mint_unstored(
+                wallet_keyset,
+                payment_method,
+                amount,
+                quote_id,
+                lock.as_ref(),
+            )
+            .await
+            .map(|proofs| to_token(wallet_keyset, &proofs));
+        self.log_operation(
+            Operation::Send,
+            inputs,
+            &result,
+            |token| serde_json::json!({ "proofs": proof_ids(&token.proofs()) }),
+        )
+        .await;
+        result
+    }
+
+    async fn mint_proofs(
+        &self,
+        wallet_keyset: &WalletKeyset,
+        payment_method: &PaymentMethod,
+        amount: Amount,
+        quote_id: String,
+    ) -> Result<TokenV3, MokshaWalletError> {
+        let proofs = self
+            .mint_unstored(wallet_keyset, payment_method, amount, quote_id, None)
+            .await?;
+        let tokens = to_token(wallet_keyset, &proofs);
+        let mut tx = self.localstore.begin_tx().await?;
+        self.localstore.add_stored_proofs(&mut tx, &proofs).await?;
+        self.localstore
+            .add_transaction(
+                &mut tx,
+                &WalletTransaction::new(
+                    Operation::Mint,
+                    tokens.total_amount(),
+                    wallet_keyset,
+                    None,
+                ),
+            )
+            .await?;
+        tx.commit().await?;
+
+        Ok(tokens)
+    }
+
+    /// Mints proofs for the quote without storing them. With `lock_to` the proofs are locked to
+    /// the P2PK secret instead of using secrets derived from the seed
+    async fn mint_unstored(
+        &self,
+        wallet_keyset: &WalletKeyset,
+        payment_method: &PaymentMethod,
+        amount: Amount,
+        quote_id: String,
+        lock_to: Option<&P2PKSecret>,
+    ) -> Result<StoredProofs, MokshaWalletError> {
+        // proofs of inactive keysets can still be spent, but no new ones are minted
+        if !wallet_keyset.active {
+            return Err(MokshaWalletError::KeysetInactive(
+                wallet_keyset.keyset_id.to_string(),
+            ));
+        }
+
+        if *payment_method == PaymentMethod::Bolt12 {
+            return Err(MokshaWalletError::UnsupportedPaymentMethod(
+                payment_method.clone(),
+            ));
+        }
+
+        let split_amount = amount.split();
+
+        let (start_index, secret_range) = match lock_to {
+            Some(lock) => (None, create_locked_secrets(lock, split_amount.len())?),
+            None => {
+                let (start_index, secrets) = self
+                    .create_secrets(&wallet_keyset.keyset_id, split_amount.len() as u32)
+                    .await?;
+                (Some(start_index), secrets)
+            }
+        };
+
+        let blinded_messages = split_amount
+            .into_iter()
+            .zip(secret_range)
+            .map(|(amount, (secret, blinding_factor))| {
+                let b_ = self.dhke.step1_alice(&secret, &blinding_factor)?;
+                Ok((
+                    BlindedMessage {
+                        amount,
+                        b_,
+                        id: wallet_keyset.keyset_id.to_string(), // FIXME use keyset_id
+                    },
+                    blinding_factor,
+                    secret,
+                ))
+            })
+            .collect::<Result<Vec<(_, _, _)>, MokshaWalletError>>()?;
+
+        let signatures = match payment_method {
+            PaymentMethod::Bolt11 => {
+                let post_mint_resp = self
+                    .client
+                    .post_mint_bolt11(
+                        &wallet_keyset.mint_url,
+                        quote_id,
+                        blinded_messages
+                            .clone()
+                            .into_iter()
+                            .map(|(msg, _, _)| msg)
+                            .collect::<Vec<BlindedMessage>>(),
+                    )
+                    .await?;
+                post_mint_resp.signatures
+            }
+            PaymentMethod::BtcOnchain => {
+                let post_mint_resp = self
+                    .client
+                    .post_mint_onchain(
+                        &wallet_keyset.mint_url,
+                        quote_id,
+                        blinded_messages
+                            .clone()
+                            .into_iter()
+                            .map(|(msg, _, _)| msg)
+                            .collect::<Vec<BlindedMessage>>(),
+                    )
+                    .await?;
+                post_mint_resp.signatures
+            }
+            PaymentMethod::Bolt12 => unreachable!("bolt12 is rejected before creating secrets"),
+        };
+
+        // step 3: unblind signatures
+        let (outputs, secrets): (Vec<_>, Vec<_>) = blinded_messages
+            .into_iter()
+            .map(|(msg, blinding_factor, secret)| ((msg, blinding_factor), secret))
+            .unzip();
+        let proofs = self
+            .create_proofs_from_blinded_signatures(wallet_keyset, signatures, secrets, outputs)
+            .await?;
+        Ok(match start_index {
+            Some(start_index) => StoredProofs::with_derivation_indexes(proofs, start_index),
+            None => proofs.into(),
+        })
+    }
+
+    pub async fn create_blank(
+        &self,
+        fee_reserve: Amount,
+        keyset_id: &KeysetId,
+    ) -> Result<(u32, Vec<(BlindedMessage, BlindingFactor, String)>), MokshaWalletError> {
+        if fee_reserve.0 == 0 {
+            return Ok((0, vec![]));
+        }
+
+        let count = fee_reserve.blank_outputs_count();
+        let (start_index, secret_range) = self.create_secrets(keyset_id, count as u32).await?;
+        let blinded_messages = secret_range
+            .into_iter()
+            .map(|(secret, blinding_factor)| {
+                let b_ = self.dhke.step1_alice(secret.clone(), &blinding_factor)?;
+                Ok((
+                    BlindedMessage {
+                        amount: 1,
+                        b_,
+                        id: keyset_id.to_string(),
+                    },
+                    blinding_factor,
+                    secret,
+                ))
+            })
+            .collect::<Result<Vec<(_, _, _)>, MokshaWalletError>>()?;
+
+        Ok((start_index, blinded_messages))
+    }
+
+    /// Derives the outputs of the indexes and returns the proofs of those the mint has signed
+    /// before (nut09)
+    async fn restore_outputs(
+        &self,
+        wallet_keyset: &WalletKeyset,
+        indexes: Vec<u32>,
+    ) -> Result<StoredProofs, MokshaWalletError> {
+        let secrets = indexes
+            .iter()
+            .map(|index| {
+                self.secret
+                    .derive_range(&wallet_keyset.keyset_id, *index, 1)
+                    .map(|mut secrets| secrets.remove(0))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // the mint stores the amount of each output, so any amount can be requested
+        let outputs = secrets
+            .iter()
+            .map(|(secret, blinding_factor)| {
+                Ok(BlindedMessage {
+                    amount: 1,
+                    b_: self.dhke.step1_alice(secret.clone(), blinding_factor)?,
+                    id: wallet_keyset.keyset_id.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>, MokshaWalletError>>()?;
+        if outputs.is_empty() {
+            return Ok(StoredProofs::default());
+        }
+
+        let response = self
+            .client
+            .post_restore(&wallet_keyset.mint_url, outputs.clone())
+            .await?;
+        let mut restored_indexes = vec![];
+        let mut signed = vec![];
+        let mut signatures = vec![];
+        for (output, signature) in response.outputs.iter().zip(response.signatures) {
+            let Some(position) = outputs.iter().position(|o| o.b_ == output.b_) else {
+                continue;
+            };
+            let (secret, blinding_factor) = secrets[position].clone();
+            restored_indexes.push(indexes[position]);
+            signed.push(((output.clone(), blinding_factor), secret));
+            signatures.push(signature);
+        }
+        let (outputs, secrets): (Vec<_>, Vec<_>) = signed.into_iter().unzip();
+        let proofs = self
+            .create_proofs_from_blinded_signatures(wallet_keyset, signatures, secrets, outputs)
+            .await?;
+        Ok(proofs
+            .proofs()
+            .into_iter()
+            .zip(restored_indexes)
+            .map(|(proof, index)| StoredProof {
+                proof,
+                derivation_index: Some(index),
+                label: None,
+            })
+            .collect::<Vec<_>>()
+            .into())
+    }
+
+    /// Restores the outputs of an interrupted swap from the derivation indexes that were logged
+    /// before the mint was called
+    async fn restore_swap_outputs(
+        &self,
+        keysets: &[WalletKeyset],
+        outputs: &str,
+    ) -> Result<StoredProofs, MokshaWalletError> {
+        let outputs: serde_json::Value = serde_json::from_str(outputs)?;
+        let Some(wallet_keyset) = keysets.iter().find(|keyset| {
+            Some(keyset.keyset_id.to_string().as_str()) == outputs["keyset_id"].as_str()
+        }) else {
+            return Ok(StoredProofs::default());
+        };
+        let indexes = outputs["derivation_indexes"]
+            .as_array()
+            .map(|indexes| {
+                indexes
+                    .iter()
+                    .filter_map(|index| index.as_u64())
+                    .map(|index| index as u32)
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.restore_outputs(wallet_keyset, indexes).await
+    }
+
+    fn create_blinded_messages(
+        &self,
+        keyset_id: &KeysetId,
+        amount: Amount,
+        secrets_factors: Vec<(String, BlindingFactor)>,
+    ) -> Result<Vec<(BlindedMessage, BlindingFactor)>, MokshaWalletError> {
+        let split_amount = amount.split();
+
+        split_amount
+            .into_iter()
+            .zip(secrets_factors)
+            .map(|(amount, (secret, blinding_factor))| {
+                let b_ = self.dhke.step1_alice(secret, &blinding_factor)?;
+                Ok((
+                    BlindedMessage {
+                        amount,
+                        b_,
+                        id: keyset_id.to_string(),
+                    },
+                    blinding_factor,
+                ))
+            })
+            .collect::<Result<Vec<(_, _)>, MokshaWalletError>>()
+    }
+
+    /// Checks that the change returned by the mint doesn't exceed the fee reserve and unblinds it.
+    /// Each signature belongs to the blank output at the same position and has to unblind with the
+    /// key of the wallet keyset for its amount.
+    fn verify_change(
+        &self,
+        wallet_keyset: &WalletKeyset,
+        change: &[BlindedSignature],
+        fee_reserve: u64,
+        secrets: Vec<String>,
+        outputs: Vec<(BlindedMessage, BlindingFactor)>,
+    ) -> Result<Proofs, MokshaWalletError> {
+        if change.len() > outputs.len() {
+            return Err(MokshaWalletError::InvalidChange(format!(
+                "received {} signatures for {} blank outputs",
+                change.len(),
+                outputs.len()
+            )));
+        }
+
+        let change_amount = change
+            .iter()
+            .try_fold(0_u64, |total, sig| total.checked_add(sig.amount))
+            .ok_or_else(|| MokshaWalletError::InvalidChange("change amount overflows".into()))?;
+        if change_amount > fee_reserve {
+            return Err(MokshaWalletError::InvalidChange(format!(
+                "change {change_amount} exceeds fee reserve {fee_reserve}"
+            )));
+        }
+
+        let keyset_id = wallet_keyset.keyset_id.to_string();
+        Ok(change
+            .iter()
+            .zip(secrets)
+            .zip(outputs)
+            .map(|((sig, secret), (_, blinding_factor))| {
+                let key = wallet_keyset
+                    .public_keys
+                    .get(&sig.amount)
+                    .filter(|_| sig.id == keyset_id)
+                    .ok_or_else(|| {
+                        MokshaWalletError::InvalidChange(format!(
+                            "signature for amount {} with keyset {} does not match wallet keyset {}",
+                            sig.amount, sig.id, keyset_id
+                        ))
+                    })?;
+                let c = self
+                    .dhke
+                    .step3_alice(sig.c_, blinding_factor, *key)
+                    .map_err(|e| {
+                        MokshaWalletError::InvalidChange(format!(
+                            "signature for amount {} can not be unblinded: {e}",
+                            sig.amount
+                        ))
+                    })?;
+                Ok(Proof::new(sig.amount, secret, c, keyset_id.clone()))
+            })
+            .collect::<Result<Vec<_>, MokshaWalletError>>()?
+            .into())
+    }
+
+    /// Unblinds the signatures with the keys of the keyset that signed them. The mint may sign
+    /// with another keyset than requested, so the keyset id of each signature is used and the
+    /// requested keyset is the fallback for signatures without a valid keyset id.
+    async fn create_proofs_from_blinded_signatures(
+        &self,
+        wallet_keyset: &WalletKeyset,
+        signatures: Vec<BlindedSignature>,
+        secrets: Vec<String>,
+        outputs: Vec<(BlindedMessage, BlindingFactor)>,
+    ) -> Result<Proofs, MokshaWalletError> {
+        let requested_keyset_id = wallet_keyset.keyset_id.to_string();
+
+        let mut keys = HashMap::from([(
+            requested_keyset_id.clone(),
+            wallet_keyset.public_keys.clone(),
+        )]);
+        let keyset_ids = signatures
+            .iter()
+            .map(|sig| match KeysetId::new(&sig.id) {
+                Ok(_) => sig.id.clone(),
+                Err(_) => requested_keyset_id.clone(),
+            })
+            .collect::<Vec<_>>();
+        for keyset_id in keyset_ids.iter() {
+            if !keys.contains_key(keyset_id) {
+                let public_keys = self
+                    .public_keys_by_id(&wallet_keyset.mint_url, keyset_id)
+                    .await?;
+                keys.insert(keyset_id.clone(), public_keys);
+            }
+        }
+
+        let blinding_factors = outputs
+            .into_iter()
+            .map(|(_, secret)| secret)
+            .collect::<Vec<BlindingFactor>>();
+
+        Ok(signatures
+            .iter()
+            .zip(keyset_ids)
+            .zip(blinding_factors)
+            .zip(secrets)
+            .map(|(((p, keyset_id), blinding_factor), secret)| {
+                let key = keys[&keyset_id]
+                    .get(&p.amount)
+                    .ok_or(MokshaWalletError::PubkeyNotFound)?;
+                let pub_alice = self
+                    .dhke
+                    .step3_alice(p.c_, blinding_factor.to_owned(), *key)?;
+                Ok(Proof::new(p.amount, secret, pub_alice, keyset_id))
+            })
+            .collect::<Result<Vec<_>, MokshaWalletError>>()?
+            .into())
+    }
+
+    /// Returns the public keys of a keyset of the mint from the localstore and fetches them from
+    /// the mint if the keyset is unknown
+    async fn public_keys_by_id(
+        &self,
+        mint_url: &Url,
+        keyset_id: &str,
+    ) -> Result<HashMap<u64, PublicKey>, MokshaWalletError> {
+        let stored = self
+            .get_wallet_keysets()
+            .await?
+            .into_iter()
+            .find(|k| k.mint_url == *mint_url && k.keyset_id.to_string() == keyset_id);
+        if let Some(keyset) = stored {
+            return Ok(keyset.public_keys);
+        }
+
+        self.client
+            .get_keys_by_id(mint_url, keyset_id.to_owned())
+            .await?
+            .keysets
+            .into_iter()
+            .find(|k| k.id == keyset_id)
+            .map(|k| k.keys)
+            .ok_or_else(|| MokshaWalletError::UnknownKeyset(keyset_id.to_owned()))
+    }
+
+    /// Returns the latest transactions of the history, newest first
+    pub async fn get_transactions(
+        &self,
+        limit: u64,
+    ) -> Result<Vec<WalletTransaction>, MokshaWalletError> {
+        let mut tx = self.localstore.begin_tx().await?;
+        let transactions = self.localstore.list_transactions(&mut tx, limit).await?;
+        tx.commit().await?;
+        Ok(transactions)
+    }
+
+    /// Returns the latest entries of the operation log, newest first
+    pub async fn get_operation_log(
+        &self,
+        limit: u64,
+    ) -> Result<Vec<OperationLogEntry>, MokshaWalletError> {
+        let mut tx = self.localstore.begin_tx().await?;
+        let entries = self.localstore.get_log_entries(&mut tx, limit).await?;
+        tx.commit().await?;
+        Ok(entries)
+    }
+
+    /// Appends the call to the operation log. Failed calls are logged with their error.
+    /// The log is best-effort: the result of the call is returned even if it can't be written.
+    async fn log_operation<T>(
+        &self,
+        operation: Operation,
+        inputs: serde_json::Value,
+        result: &Result<T, MokshaWalletError>,
+        outputs: impl FnOnce(&T) -> serde_json::Value,
+    ) {
+        let (outputs, error) = operation_result(result, outputs);
+        let entry = OperationLogEntry {
+            id: None,
+            operation,
+            inputs: inputs.to_string(),
+            outputs: outputs.to_string(),
+            error,
+            created_at: None,
+        };
+        let logged = async {
+            let mut tx = self.localstore.begin_tx().await?;
+            self.localstore
+                .add_log_entry(&mut tx, &entry, MAX_LOG_ENTRIES)
+                .await?;
+            tx.commit().await?;
+            Ok::<_, MokshaWalletError>(())
+        }
+        .await;
+        if let Err(err) = logged {
+            warn!("failed to log {operation:?} operation: {err}");
+        }
+    }
+
+    /// Appends a call without a result to the operation log and returns the id of the entry.
+    /// An entry that is never finished belongs to a call that was interrupted.
+    async fn begin_operation(
+        &self,
+        operation: Operation,
+        inputs: serde_json::Value,
+    ) -> Result<u64, MokshaWalletError> {
+        let entry = OperationLogEntry {
+            id: None,
+            operation,
+            inputs: inputs.to_string(),
+            outputs: serde_json::Value::Null.to_string(),
+            error: None,
+            created_at: None,
+        };
+        let mut tx = self.localstore.begin_tx().await?;
+        let id = self
+            .localstore
+            .add_log_entry(&mut tx, &entry, MAX_LOG_ENTRIES)
+            .await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    /// Stores the result of a call that was logged with `begin_operation`. Like `log_operation`
+    /// a failed write only emits a warning.
+    async fn finish_operation<T>(
+        &self,
+        id: u64,
+        result: &Result<T, MokshaWalletError>,
+        outputs: impl FnOnce(&T) -> serde_json::Value,
+    ) {
+        let (outputs, error) = operation_result(result, outputs);
+        let logged = async {
+            let mut tx = self.localstore.begin_tx().await?;
+            self.localstore
+                .update_log_entry(&mut tx, id, &outputs.to_string(), error.as_deref())
+                .await?;
+            tx.commit().await?;
+            Ok::<_, MokshaWalletError>(())
+        }
+        .await;
+        if let Err(err) = logged {
+            warn!("failed to log the result of operation {id}: {err}");
+        }
+    }
+
+    pub async fn get_proofs(&self) -> Result<Proofs, MokshaWalletError> {
+        let mut tx = self.localstore.begin_tx().await?;
+        let proofs = self.localstore.get_proofs(&mut tx).await?;
+        tx.commit().await?;
+        Ok(proofs)
+    }
+}
+
+fn operation_result<T>(
+    result: &Result<T, MokshaWalletError>,
+    outputs: impl FnOnce(&T) -> serde_json::Value,
+) -> (serde_json::Value, Option<String>) {
+    match result {
+        Ok(value) => (outputs(value), None),
+        Err(err) => (serde_json::Value::Null, Some(err.to_string())),
+    }
+}
+
+// FIXME implement for Vec<BlindedMessage, Secretkey>
+/// Sets the derivation index of proofs whose secrets were derived consecutively from `start_index`
+/// Y of each proof, which identifies it in the operation log without revealing the secret
+fn proof_ids(proofs: &Proofs) -> Vec<String> {
+    proofs
+        .proofs()
+        .iter()
+        .filter_map(|proof| proof.y().ok())
+        .map(|y| y.to_string())
+        .collect()
+}
+
+/// Creates P2PK secrets with the conditions of the lock and random nonces
+fn create_locked_secrets(
+    lock: &P2PKSecret,
+    count: usize,
+) -> Result<Vec<(String, BlindingFactor)>, MokshaWalletError> {
+    (0..count)
+        .map(|_| {
+            let secret = lock.with_new_nonce().to_secret()?;
+            let blinding_factor = SecretKey::new(&mut rand::thread_rng()).into();
+            Ok((secret, blinding_factor))
+        })
+        .collect()
+}
+
+fn to_token(wallet_keyset: &WalletKeyset, proofs: &StoredProofs) -> TokenV3 {
+    (
+        wallet_keyset.mint_url.to_owned(),
+        wallet_keyset.currency_unit.clone(),
+        proofs.proofs(),
+    )
+        .into()
+}
+
+fn get_blinded_msg(blinded_messages: Vec<(BlindedMessage, BlindingFactor)>) -> Vec<BlindedMessage> {
+    blinded_messages
+        .into_iter()
+        .map(|(msg, _)| msg)
+        .collect::<Vec<BlindedMessage>>()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::client::MockCashuClient;
+    use crate::error::MokshaWalletError;
+    use crate::lnurl::{LnurlPayRequest, LnurlWithdrawRequest};
+    use crate::localstore::sqlite::SqliteLocalStore;
+    use crate::localstore::{LocalStore, Operation, WalletKeyset};
+    use crate::wallet::{proof_ids, Bolt11ReceiveState, Wallet, WalletBuilder};
+
+    use moksha_core::blind::{BlindedMessage, BlindedSignature};
+    use moksha_core::dhke::{public_key_from_hex, Dhke};
+    use moksha_core::fixture::{read_fixture, read_fixture_as};
+    use moksha_core::keyset::{derive_secret_key, KeysetId, Keysets, MintKeyset};
+    use moksha_core::p2pk::P2PKSecret;
+    use moksha_core::primitives::{
+        CurrencyUnit, KeyResponse, KeysResponse, MintInfoResponse, Nut17, Nuts, PaymentMethod,
+        PostCheckStateResponse, PostMeltBolt11Response, PostMeltQuoteBolt11Response,
+        PostMintBolt11Response, PostMintQuoteBolt11Response, PostRestoreResponse, PostSwapResponse,
+        ProofState, SpendState,
+    };
+
+    use moksha_core::proof::{Proof, Proofs};
+    use moksha_core::spent_digest::SpentDigest;
+    use moksha_core::token::{Token, TokenV3};
+    use secp256k1::PublicKey;
+    use url::Url;
+
+    fn create_mock() -> MockCashuClient {
+        let keys = MintKeyset::new("mykey", "");
+        let key_response = KeyResponse {
+            keys: keys.public_keys.clone(),
+            id: keys.keyset_id.clone(),
+            unit: CurrencyUnit::Sat,
+        };
+        let keys_response = KeysResponse::new(key_response.clone());
+        let keys_by_id_response = keys_response.clone();
+        let keysets = Keysets::new(keys.keyset_id, CurrencyUnit::Sat, true);
+
+        let mut client = MockCashuClient::default();
+        client
+            .expect_get_keys()
+            .returning(move |_| Ok(keys_response.clone()));
+        client
+            .expect_get_keysets()
+            .returning(move |_| Ok(keysets.clone()));
+        client
+            .expect_get_keys_by_id()
+            .returning(move |_, _| Ok(keys_by_id_response.clone()));
+        client.expect_is_v1_supported().returning(move |_| Ok(true));
+        client
+    }
+
+    #[tokio::test]
+    async fn test_blank_blinded_messages_1000_sats() -> anyhow::Result<()> {
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let wallet_keyset = create_test_wallet_keyset()?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &wallet_keyset).await?;
+        tx.commit().await?;
+
+        let client = create_mock();
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+        let result = wallet
+            .create_blank(1000.into(), &KeysetId::new("00d31cecf59d18c0")?)
+            .await;
+        println!("{:?}", result);
+        assert!(result.is_ok());
+        let (_, result) = result.unwrap();
+        assert!(result.len() == 10);
+        assert!(result.first().unwrap().0.amount == 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blank_blinded_messages_zero_sats() -> anyhow::Result<()> {
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let wallet_keyset = create_test_wallet_keyset()?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &wallet_keyset).await?;
+        tx.commit().await?;
+
+        let client = create_mock();
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+        let result = wallet
+            .create_blank(0.into(), &KeysetId::new("00d31cecf59d18c0")?)
+            .await;
+        println!("{:?}", result);
+        assert!(result.is_ok());
+        assert!(result.unwrap().1.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blank_blinded_messages_serialize() -> anyhow::Result<()> {
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let wallet_keyset = create_test_wallet_keyset()?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &wallet_keyset).await?;
+        tx.commit().await?;
+
+        let client = create_mock();
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+
+        let result = wallet
+            .create_blank(4000.into(), &KeysetId::new("00d31cecf59d18c0")?)
+            .await?;
+        for (blinded_message, _, _) in result.1 {
+            let out = serde_json::to_string(&blinded_message)?;
+            assert!(!out.is_empty());
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mint_tokens() -> anyhow::Result<()> {
+        let mint_response =
+            read_fixture_as::<PostMintBolt11Response>("post_mint_response_20.json")?;
+
+        let mut client = create_mock();
+        client
+            .expect_post_mint_bolt11()
+            .returning(move |_, _, _| Ok(mint_response.clone()));
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let wallet_keyset = create_test_wallet_keyset()?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &wallet_keyset).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+
+        let result = wallet
+            .mint_tokens(
+                &wallet_keyset,
+                &PaymentMethod::Bolt11,
+                20.into(),
+                "hash".to_string(),
+            )
+            .await?;
+        assert_eq!(20, result.total_amount());
+        result.tokens.into_iter().for_each(|t| {
+            assert_eq!(wallet_keyset.mint_url, t.mint.expect("mint is empty"));
+        });
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bolt11_receive_state() -> anyhow::Result<()> {
+        let mint_response =
+            read_fixture_as::<PostMintBolt11Response>("post_mint_response_20.json")?;
+        let quote = PostMintQuoteBolt11Response {
+            quote: "quote".to_owned(),
+            payment_request: "lnbcrt200n1".to_owned(),
+            amount: 20,
+            paid: false,
+            expiry: Some(1_000),
+        };
+
+        let mut client = create_mock();
+        // the mint reports the invoice as paid on the second request
+        let requests = std::sync::atomic::AtomicUsize::new(0);
+        let unpaid = quote.clone();
+        client
+            .expect_get_mint_quote_bolt11()
+            .returning(move |_, _| {
+                let paid = requests.fetch_add(1, std::sync::atomic::Ordering::SeqCst) > 0;
+                Ok(PostMintQuoteBolt11Response {
+                    paid,
+                    ..unpaid.clone()
+                })
+            });
+        client
+            .expect_post_mint_bolt11()
+            .times(1)
+            .returning(move |_, _, _| Ok(mint_response.clone()));
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let wallet_keyset = create_test_wallet_keyset()?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &wallet_keyset).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+        let mint_url = &wallet_keyset.mint_url;
+
+        assert_eq!(
+            Bolt11ReceiveState::Waiting,
+            wallet
+                .get_bolt11_receive_state(mint_url, &quote.quote, 500)
+                .await?
+        );
+        // a paid invoice can be minted even after the quote expired
+        assert_eq!(
+            Bolt11ReceiveState::Paid,
+            wallet
+                .get_bolt11_receive_state(mint_url, &quote.quote, 2_000)
+                .await?
+        );
+
+        let minted = wallet
+            .mint_tokens(
+                &wallet_keyset,
+                &PaymentMethod::Bolt11,
+                quote.amount.into(),
+                quote.quote.clone(),
+            )
+            .await?;
+        assert_eq!(20, minted.total_amount());
+        assert_eq!(20, wallet.get_balance().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bolt11_receive_state_expired() -> anyhow::Result<()> {
+        let mut client = create_mock();
+        client.expect_get_mint_quote_bolt11().returning(|_, quote| {
+            Ok(PostMintQuoteBolt11Response {
+                quote,
+                payment_request: "lnbcrt200n1".to_owned(),
+                amount: 20,
+                paid: false,
+                expiry: Some(1_000),
+            })
+        });
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+
+        let mint_url = Url::parse("http://127.0.0.1:3338")?;
+        assert_eq!(
+            Bolt11ReceiveState::Expired,
+            wallet
+                .get_bolt11_receive_state(&mint_url, "quote", 1_001)
+                .await?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_time_sync() -> anyhow::Result<()> {
+        let mut client = create_mock();
+        let info = MintInfoResponse {
+            name: None,
+            pubkey: MintKeyset::new("mykey", "").mint_pubkey,
+            version: None,
+            description: None,
+            description_long: None,
+            contact: None,
+            motd: None,
+            time: Some(10_000),
+            nuts: Nuts::default(),
+        };
+        client
+            .expect_get_info()
+            .returning(move |_| Ok(info.clone()));
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+        let mint_url = Url::parse("http://localhost:8080")?;
+
+        assert_eq!(
+            Some(3_600),
+            wallet.check_time_sync(&mint_url, 13_600).await?
+        );
+        assert_eq!(Some(-600), wallet.check_time_sync(&mint_url, 9_400).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_mint_quote() -> anyhow::Result<()> {
+        let mut client = create_mock();
+        let info = MintInfoResponse {
+            name: None,
+            pubkey: MintKeyset::new("mykey", "").mint_pubkey,
+            version: None,
+            description: None,
+            description_long: None,
+            contact: None,
+            motd: None,
+            time: None,
+            nuts: Nuts {
+                nut17: Some(Nut17::bolt11_mint_quote(&[CurrencyUnit::Sat])),
+                ..Default::default()
+            },
+        };
+        client
+            .expect_get_info()
+            .returning(move |_| Ok(info.clone()));
+        let (sender, receiver) = tokio::sync::mpsc::channel(1);
+        client
+            .expect_subscribe_mint_quote()
+            .times(1)
+            .return_once(move |_, _| Ok(receiver));
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+
+        let mint_url = Url::parse("http://127.0.0.1:3338")?;
+        let mut updates = wallet
+            .subscribe_mint_quote(&mint_url, "quote", &CurrencyUnit::Sat)
+            .await?
+            .expect("subscription is supported");
+        sender
+            .send(PostMintQuoteBolt11Response {
+                quote: "quote".to_owned(),
+                payment_request: "lnbcrt200n1".to_owned(),
+                amount: 20,
+                paid: true,
+                expiry: Some(1_000),
+            })
+            .await?;
+        let update = updates.recv().await.expect("update not received");
+        assert_eq!(
+            Bolt11ReceiveState::Paid,
+            Bolt11ReceiveState::from_quote(&update, 1_001)
+        );
+
+        // the mint doesn't support subscriptions for usd, so the caller has to poll
+        assert!(wallet
+            .subscribe_mint_quote(&mint_url, "quote", &CurrencyUnit::Usd)
+            .await?
+            .is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mint_tokens_signed_with_other_keyset() -> anyhow::Result<()> {
+        // the wallet requests its stored keyset, but the mint signs with the keyset of create_mock
+        let mint_keyset = MintKeyset::new("mykey", "");
+        let signing_keyset = mint_keyset.clone();
+        let mut client = create_mock();
+        client
+            .expect_post_mint_bolt11()
+            .returning(move |_, _, outputs| {
+                let dhke = Dhke::new();
+                let signatures = outputs
+                    .iter()
+                    .map(|output| BlindedSignature {
+                        amount: output.amount,
+                        c_: dhke
+                            .step2_bob(output.b_, &signing_keyset.private_keys[&output.amount])
+                            .expect("signing failed"),
+                        id: signing_keyset.keyset_id.clone(),
+                    })
+                    .collect();
+                Ok(PostMintBolt11Response { signatures })
+            });
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let wallet_keyset = create_test_wallet_keyset()?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &wallet_keyset).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+
+        let result = wallet
+            .mint_tokens(
+                &wallet_keyset,
+                &PaymentMethod::Bolt11,
+                20.into(),
+                "hash".to_string(),
+            )
+            .await?;
+        assert_eq!(20, result.total_amount());
+        for proof in result.proofs().proofs() {
+            assert_eq!(mint_keyset.keyset_id, proof.keyset_id);
+            assert!(Dhke::new().verify(
+                mint_keyset.private_keys[&proof.amount],
+                proof.c,
+                proof.secret
+            )?);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mint_tokens_usd() -> anyhow::Result<()> {
+        let mint_response =
+            read_fixture_as::<PostMintBolt11Response>("post_mint_response_20.json")?;
+
+        let mut client = create_mock();
+        client
+            .expect_post_mint_quote_bolt11()
+            .withf(|_, amount, unit| *amount == 20 && *unit == CurrencyUnit::Usd)
+            .returning(|_, amount, _| {
+                Ok(PostMintQuoteBolt11Response {
+                    quote: "quote".to_string(),
+                    payment_request: "lnbcrt1".to_string(),
+                    amount,
+                    paid: false,
+                    expiry: None,
+                })
+            });
+        client
+            .expect_post_mint_bolt11()
+            .returning(move |_, _, _| Ok(mint_response.clone()));
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let wallet_keyset = WalletKeyset {
+            currency_unit: CurrencyUnit::Usd,
+            ..create_test_wallet_keyset()?
+        };
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &wallet_keyset).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+
+        let quote = wallet
+            .create_quote_bolt11(&wallet_keyset.mint_url, 20, CurrencyUnit::Usd)
+            .await?;
+        assert_eq!(20, quote.amount);
+
+        let result = wallet
+            .mint_tokens(
+                &wallet_keyset,
+                &PaymentMethod::Bolt11,
+                20.into(),
+                quote.quote,
+            )
+            .await?;
+        assert_eq!(Some(CurrencyUnit::Usd), result.currency_unit);
+        assert_eq!(CurrencyUnit::Usd, wallet.get_token_unit(&result).await?);
+        Ok(())
+    }
+
+    fn lnurl_withdraw_request() -> LnurlWithdrawRequest {
+        LnurlWithdrawRequest {
+            tag: "withdrawRequest".to_owned(),
+            callback: "https://service.com/withdraw/callback".to_owned(),
+            k1: "k1".to_owned(),
+            min_withdrawable: 1_000,
+            max_withdrawable: 100_000,
+            default_description: "moksha".to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mint_from_lnurl_withdraw() -> anyhow::Result<()> {
+        let mut client = create_mock();
+        client
+            .expect_get_lnurl_withdraw()
+            .withf(|url| url.as_str() == "https://service.com/withdraw")
+            .returning(|_| Ok(lnurl_withdraw_request()));
+        client
+            .expect_post_mint_quote_bolt11()
+            .withf(|_, amount, unit| *amount == 20 && *unit == CurrencyUnit::Sat)
+            .returning(|_, amount, _| {
+                Ok(PostMintQuoteBolt11Response {
+                    quote: "quote".to_string(),
+                    payment_request: "lnbcrt1".to_string(),
+                    amount,
+                    paid: false,
+                    expiry: None,
+                })
+            });
+        client
+            .expect_submit_lnurl_withdraw()
+            .withf(|request, pr| request.k1 == "k1" && pr == "lnbcrt1")
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+
+        let quote = wallet
+            .mint_from_lnurl_withdraw(
+                &Url::parse("http://127.0.0.1:3338")?,
+                "lnurlw://service.com/withdraw",
+                20,
+            )
+            .await?;
+        assert_eq!("quote", quote.quote);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mint_from_lnurl_withdraw_exceeds_limits() -> anyhow::Result<()> {
+        let mut client = create_mock();
+        client
+            .expect_get_lnurl_withdraw()
+            .returning(|_| Ok(lnurl_withdraw_request()));
+        client.expect_post_mint_quote_bolt11().never();
+        client.expect_submit_lnurl_withdraw().never();
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+
+        let result = wallet
+            .mint_from_lnurl_withdraw(
+                &Url::parse("http://127.0.0.1:3338")?,
+                "lnurlw://service.com/withdraw",
+                101,
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(MokshaWalletError::LnurlWithdrawLimits(101, 1, 100))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_tls_fingerprint() -> anyhow::Result<()> {
+        let mut client = create_mock();
+        let mut fingerprints = vec!["new", "new", "new", "old", "old"];
+        client
+            .expect_get_tls_fingerprint()
+            .times(5)
+            .returning(move |_| Ok(fingerprints.pop().map(ToOwned::to_owned)));
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+        let mint_url = Url::parse("https://mint.example.com")?;
+
+        // stored on the first connection
+        let result = wallet.check_tls_fingerprint(&mint_url).await?;
+        assert_eq!(Some("old".to_owned()), result.current);
+        assert!(!result.changed());
+        let mut tx = wallet.localstore.begin_tx().await?;
+        let stored = wallet
+            .localstore
+            .get_setting(&mut tx, "mint_tls_fingerprint:https://mint.example.com/")
+            .await?;
+        tx.commit().await?;
+        assert_eq!(Some("old".to_owned()), stored);
+
+        assert!(!wallet.check_tls_fingerprint(&mint_url).await?.changed());
+
+        let result = wallet.check_tls_fingerprint(&mint_url).await?;
+        assert_eq!(Some("new".to_owned()), result.current);
+        assert_eq!(Some("old".to_owned()), result.previous);
+
+        // reported again until the user accepts the new certificate
+        assert!(wallet.check_tls_fingerprint(&mint_url).await?.changed());
+        wallet.accept_tls_fingerprint(&mint_url, "new").await?;
+        assert!(!wallet.check_tls_fingerprint(&mint_url).await?.changed());
+        Ok(())
+    }
+
+    fn lnurl_pay_request() -> LnurlPayRequest {
+        LnurlPayRequest {
+            tag: "payRequest".to_owned(),
+            callback: "https://service.com/lnurlp/satoshi/callback".to_owned(),
+            min_sendable: 1_000,
+            max_sendable: 100_000,
+            metadata: r#"[["text/plain","moksha"]]"#.to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_lightning_address() -> anyhow::Result<()> {
+        // 21 sats, description hash of [["text/plain","moksha"]]
+        let invoice = "lnbcrt210n1pj48ugqhp5lqel9mmhhue5et7zywv4r2le0yagqf2hvjj302eww9ljw0ptsvuqpp5wkrhhdqa8ya4lwz9tnnqanvdmgqp6p33vjttzn0607y4v4hwef9qsp5qurswpc8qurswpc8qurswpc8qurswpc8qurswpc8qurswpc8qurs9qrsgqcqzysdrrq42aah6uqqhhn6llfvg0tnkrpv654fp75rknxaql3kd7hhlxxgna3zen7lz7ujzjqke0txculd42476efkq3fz85m6rcz7qjjqfsphwgaxz";
+        let mut client = create_mock();
+        client
+            .expect_get_lnurl_pay()
+            .withf(|url| url.as_str() == "https://service.com/.well-known/lnurlp/satoshi")
+            .returning(|_| Ok(lnurl_pay_request()));
+        client
+            .expect_get_lnurl_pay_invoice()
+            .withf(|_, amount_msat| *amount_msat == 21_000)
+            .times(1)
+            .returning(move |_, _| Ok(invoice.to_owned()));
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+
+        let result = wallet
+            .resolve_lightning_address("satoshi@service.com", 21)
+            .await?;
+        assert_eq!(invoice, result);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_lightning_address_errors() -> anyhow::Result<()> {
+        let mut client = create_mock();
+        client
+            .expect_get_lnurl_pay()
+            .withf(|url| url.host_str() == Some("service.com"))
+            .returning(|_| Ok(lnurl_pay_request()));
+        client
+            .expect_get_lnurl_pay()
+            .returning(|_| Err(MokshaWalletError::Lnurl("not found".to_owned())));
+        client.expect_get_lnurl_pay_invoice().never();
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+
+        let result = wallet
+            .resolve_lightning_address("satoshi@service.com", 101)
+            .await;
+        assert!(matches!(
+            result,
+            Err(MokshaWalletError::LnurlPayLimits(101, 1, 100))
+        ));
+
+        let result = wallet
+            .resolve_lightning_address("satoshi@example.com", 21)
+            .await;
+        assert!(matches!(
+            result,
+            Err(MokshaWalletError::LightningAddressNotFound(address, _)) if address == "satoshi@example.com"
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_secrets_concurrently() -> anyhow::Result<()> {
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let wallet_keyset = create_test_wallet_keyset()?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &wallet_keyset).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(create_mock())
+            .with_localstore(localstore)
+            .build()
+            .await?;
+        let keyset_id = wallet_keyset.keyset_id;
+        let (first, second) = tokio::join!(
+            wallet.create_secrets(&keyset_id, 3),
+            wallet.create_secrets(&keyset_id, 3),
+        );
+        let ((first_index, first_secrets), (second_index, second_secrets)) = (first?, second?);
+
+        let mut indexes = vec![first_index, second_index];
+        indexes.sort();
+        let start_index = wallet_keyset.last_index as u32 + 1;
+        assert_eq!(vec![start_index, start_index + 3], indexes);
+        assert!(first_secrets
+            .iter()
+            .all(|(secret, _)| second_secrets.iter().all(|(other, _)| other != secret)));
+        assert_eq!(
+            wallet_keyset.last_index + 6,
+            wallet.get_wallet_keysets().await?[0].last_index
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mint_tokens_stores_derivation_index() -> anyhow::Result<()> {
+        let mint_response =
+            read_fixture_as::<PostMintBolt11Response>("post_mint_response_20.json")?;
+
+        let mut client = create_mock();
+        client
+            .expect_post_mint_bolt11()
+            .returning(move |_, _, _| Ok(mint_response.clone()));
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let wallet_keyset = create_test_wallet_keyset()?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &wallet_keyset).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+        wallet
+            .mint_tokens(
+                &wallet_keyset,
+                &PaymentMethod::Bolt11,
+                20.into(),
+                "hash".to_string(),
+            )
+            .await?;
+
+        let start_index = wallet_keyset.last_index as u32 + 1;
+        let mut tx = wallet.localstore.begin_tx().await?;
+        let indexes = wallet
+            .localstore
+            .get_stored_proofs(&mut tx)
+            .await?
+            .0
+            .into_iter()
+            .map(|stored| stored.derivation_index)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![Some(start_index), Some(start_index + 1)], indexes);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mint_and_send() -> anyhow::Result<()> {
+        let mint_response =
+            read_fixture_as::<PostMintBolt11Response>("post_mint_response_20.json")?;
+
+        let mut client = create_mock();
+        client
+            .expect_post_mint_bolt11()
+            .returning(move |_, _, _| Ok(mint_response.clone()));
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let wallet_keyset = create_test_wallet_keyset()?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &wallet_keyset).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+        let token = wallet
+            .mint_and_send(
+                &wallet_keyset,
+                &PaymentMethod::Bolt11,
+                20.into(),
+                "hash".to_string(),
+                None,
+            )
+            .await?;
+
+        assert_eq!(20, token.total_amount());
+        assert_eq!(0, wallet.get_balance().await?);
+        assert!(wallet.get_transactions(10).await?.is_empty());
+        let log = wallet.get_operation_log(10).await?;
+        assert_eq!(Operation::Send, log[0].operation);
+        assert!(log[0].error.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mint_and_send_locked() -> anyhow::Result<()> {
+        let mint_response =
+            read_fixture_as::<PostMintBolt11Response>("post_mint_response_20.json")?;
+
+        let mut client = create_mock();
+        client
+            .expect_post_mint_bolt11()
+            .returning(move |_, _, _| Ok(mint_response.clone()));
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let wallet_keyset = create_test_wallet_keyset()?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &wallet_keyset).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+        let pubkey =
+            secp256k1::SecretKey::from_slice(&[1; 32])?.public_key(&secp256k1::Secp256k1::new());
+        let token = wallet
+            .mint_and_send(
+                &wallet_keyset,
+                &PaymentMethod::Bolt11,
+                20.into(),
+                "hash".to_string(),
+                Some(&pubkey),
+            )
+            .await?;
+
+        assert_eq!(20, token.total_amount());
+        for proof in token.proofs().proofs() {
+            let secret = P2PKSecret::from_secret(&proof.secret)?.expect("secret is not locked");
+            assert_eq!(pubkey, secret.pubkey);
+        }
+        assert_eq!(0, wallet.get_balance().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mint_tokens_inactive_keyset() -> anyhow::Result<()> {
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let wallet_keyset = WalletKeyset {
+            active: false,
+            ..create_test_wallet_keyset()?
+        };
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &wallet_keyset).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(create_mock())
+            .with_localstore(localstore)
+            .build()
+            .await?;
+
+        let result = wallet
+            .mint_tokens(
+                &wallet_keyset,
+                &PaymentMethod::Bolt11,
+                20.into(),
+                "hash".to_string(),
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(MokshaWalletError::KeysetInactive(id)) if id == "00d31cecf59d18c0"
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_tokens_inactive_keyset() -> anyhow::Result<()> {
+        let split_response = read_fixture_as::<PostSwapResponse>("post_swap_response_24_40.json")?;
+        let mut client = create_mock();
+        client
+            .expect_post_swap()
+            .times(1)
+            .returning(move |_, _, _| Ok(split_response.clone()));
+        let keyset = WalletKeyset {
+            active: false,
+            ..create_test_wallet_keyset()?
+        };
+        let tokens: TokenV3 = read_fixture("token_64.cashu")?.try_into()?;
+        let proofs: Proofs = tokens
+            .proofs()
+            .proofs()
+            .into_iter()
+            .map(|p| Proof {
+                keyset_id: keyset.keyset_id.to_string(),
+                ..p
+            })
+            .collect::<Vec<_>>()
+            .into();
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        localstore.add_proofs(&mut tx, &proofs).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+
+        // the proofs of the inactive keyset are spent, the change of the swap stays in the wallet
+        let result = wallet.send_tokens(&keyset, 20, false).await?;
+        let remaining = wallet.get_proofs().await?;
+        assert_eq!(64, result.total_amount() + remaining.total_amount());
+        assert_eq!(24, remaining.total_amount());
+        assert!(remaining
+            .proofs()
+            .iter()
+            .all(|proof| !proofs.proofs().contains(proof)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_swap() -> anyhow::Result<()> {
+        let split_response = read_fixture_as::<PostSwapResponse>("post_swap_response_24_40.json")?;
+        let mut client = create_mock();
+        client
+            .expect_post_swap()
+            .returning(move |_, _, _| Ok(split_response.clone()));
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let keyset = create_test_wallet_keyset()?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+
+        let tokens = read_fixture("token_64.cashu")?.try_into()?;
+        let result = wallet.swap_tokens(&keyset, &tokens, 20.into()).await?;
+
+        let first = result.0;
+
+        assert_eq!(CurrencyUnit::Sat, first.clone().currency_unit.unwrap());
+        assert_eq!(24, first.total_amount());
+
+        let second = result.1;
+
+        assert_eq!(CurrencyUnit::Sat, second.clone().currency_unit.unwrap());
+        assert_eq!(40, second.total_amount());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_can_redeem_unknown_keyset() -> anyhow::Result<()> {
+        let tokens: TokenV3 = read_fixture("token_60.cashu")?.try_into()?;
+        let wallet = WalletBuilder::new()
+            .with_client(create_mock())
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+
+        assert!(!wallet.can_redeem(&tokens).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_can_redeem() -> anyhow::Result<()> {
+        let mut tokens: TokenV3 = read_fixture("token_60.cashu")?.try_into()?;
+        let keyset_id = MintKeyset::new("mykey", "").keyset_id;
+        tokens.tokens = tokens
+            .tokens
+            .into_iter()
+            .map(|t| Token {
+                proofs: Proofs::new(
+                    t.proofs
+                        .proofs()
+                        .into_iter()
+                        .map(|p| Proof {
+                            keyset_id: keyset_id.clone(),
+                            ..p
+                        })
+                        .collect(),
+                ),
+                ..t
+            })
+            .collect();
+        let wallet = WalletBuilder::new()
+            .with_client(create_mock())
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+
+        assert!(wallet.can_redeem(&tokens).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_mints_from_list() -> anyhow::Result<()> {
+        let keys = MintKeyset::new("mykey", "");
+        let keys_response = KeysResponse::new(KeyResponse {
+            keys: keys.public_keys.clone(),
+            id: keys.keyset_id.clone(),
+            unit: CurrencyUnit::Sat,
+        });
+        let keysets = Keysets::new(keys.keyset_id, CurrencyUnit::Sat, true);
+
+        let valid = Url::parse("http://valid.mint")?;
+        let invalid = Url::parse("http://invalid.mint")?;
+        let mints = vec![
+            valid.to_string(),
+            "not a url".to_owned(),
+            invalid.to_string(),
+            valid.to_string(),
+        ];
+
+        let mut client = MockCashuClient::default();
+        client
+            .expect_get_mint_list()
+            .returning(move |_| Ok(mints.clone()));
+        client
+            .expect_is_v1_supported()
+            .returning(|url| match url.host_str() {
+                Some("valid.mint") => Ok(true),
+                _ => Err(MokshaWalletError::UnsupportedApiVersion),
+            });
+        client
+            .expect_get_keysets()
+            .returning(move |_| Ok(keysets.clone()));
+        client
+            .expect_get_keys_by_id()
+            .returning(move |_, _| Ok(keys_response.clone()));
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+
+        let result = wallet
+            .add_mints_from_list(&Url::parse("http://mints.example/mints.json")?)
+            .await?;
+        assert_eq!(vec![valid.clone()], result.added);
+        assert_eq!(vec![valid.clone()], result.skipped);
+        assert_eq!(1, result.failed.len());
+        assert_eq!(invalid, result.failed[0].0);
+        assert_eq!(vec!["not a url".to_owned()], result.invalid);
+        assert_eq!(vec![valid], wallet.get_mint_urls().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_mint_keysets_signed() -> anyhow::Result<()> {
+        let keys = MintKeyset::new("mykey", "");
+        let keys_response = KeysResponse::new(KeyResponse {
+            keys: keys.public_keys.clone(),
+            id: keys.keyset_id.clone(),
+            unit: CurrencyUnit::Sat,
+        });
+        let signed = Keysets::new(keys.keyset_id, CurrencyUnit::Sat, true)
+            .sign(&derive_secret_key("mykey")?);
+        let mut tampered = signed.clone();
+        tampered.keysets[0].active = false;
+        let info = MintInfoResponse {
+            name: None,
+            pubkey: keys.mint_pubkey,
+            version: None,
+            description: None,
+            description_long: None,
+            contact: None,
+            motd: None,
+            time: None,
+            nuts: Default::default(),
+        };
+
+        let mut client = MockCashuClient::default();
+        client.expect_is_v1_supported().returning(|_| Ok(true));
+        let mut calls = 0;
+        client.expect_get_keysets().returning(move |_| {
+            calls += 1;
+            match calls {
+                1 => Ok(signed.clone()),
+                2 => Ok(tampered.clone()),
+                _ => Ok(Keysets {
+                    signature: None,
+                    ..tampered.clone()
+                }),
+            }
+        });
+        client
+            .expect_get_keys_by_id()
+            .returning(move |_, _| Ok(keys_response.clone()));
+        client
+            .expect_get_info()
+            .times(1)
+            .returning(move |_| Ok(info.clone()));
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+
+        let mint_url = Url::parse("http://127.0.0.1:3338")?;
+        assert_eq!(1, wallet.add_mint_keysets(&mint_url).await?.len());
+        // the pubkey is pinned, so a tampered or unsigned keyset list is rejected
+        assert!(wallet.add_mint_keysets(&mint_url).await.is_err());
+        assert!(wallet.add_mint_keysets(&mint_url).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_mint_keysets_keys_changed() -> anyhow::Result<()> {
+        let keys = MintKeyset::new("mykey", "");
+        // the mint serves the keys of another private key under the same id
+        let changed_keys = MintKeyset::new("otherkey", "").public_keys;
+        let keysets = Keysets::new(keys.keyset_id.clone(), CurrencyUnit::Sat, true);
+
+        let mut client = MockCashuClient::default();
+        client.expect_is_v1_supported().returning(|_| Ok(true));
+        client
+            .expect_get_keysets()
+            .returning(move |_| Ok(keysets.clone()));
+        let mut calls = 0;
+        let original_keys = keys.public_keys.clone();
+        let keyset_id = keys.keyset_id.clone();
+        client.expect_get_keys_by_id().returning(move |_, _| {
+            calls += 1;
+            Ok(KeysResponse::new(KeyResponse {
+                keys: match calls {
+                    1 => original_keys.clone(),
+                    _ => changed_keys.clone(),
+                },
+                id: keyset_id.clone(),
+                unit: CurrencyUnit::Sat,
+            }))
+        });
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+
+        let mint_url = Url::parse("http://127.0.0.1:3338")?;
+        wallet.add_mint_keysets(&mint_url).await?;
+
+        let result = wallet.add_mint_keysets(&mint_url).await;
+        assert!(matches!(
+            result,
+            Err(MokshaWalletError::KeysetKeysChanged(id)) if id == keys.keyset_id
+        ));
+        // the stored keys are kept
+        let stored = wallet.get_wallet_keysets().await?;
+        assert_eq!(1, stored.len());
+        assert_eq!(keys.public_keys, stored[0].public_keys);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_mint_keysets_id_mismatch() -> anyhow::Result<()> {
+        let keys = MintKeyset::new("mykey", "");
+        let tampered_keys = MintKeyset::new("otherkey", "").public_keys;
+        let keysets = Keysets::new(keys.keyset_id.clone(), CurrencyUnit::Sat, true);
+
+        let mut client = MockCashuClient::default();
+        client.expect_is_v1_supported().returning(|_| Ok(true));
+        client
+            .expect_get_keysets()
+            .returning(move |_| Ok(keysets.clone()));
+        let keyset_id = keys.keyset_id.clone();
+        client.expect_get_keys_by_id().returning(move |_, _| {
+            Ok(KeysResponse::new(KeyResponse {
+                keys: tampered_keys.clone(),
+                id: keyset_id.clone(),
+                unit: CurrencyUnit::Sat,
+            }))
+        });
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+
+        let mint_url = Url::parse("http://127.0.0.1:3338")?;
+        let result = wallet.add_mint_keysets(&mint_url).await;
+        assert!(matches!(
+            result,
+            Err(MokshaWalletError::KeysetIdMismatch(id, _)) if id == keys.keyset_id
+        ));
+        assert!(wallet.get_wallet_keysets().await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_mint_keysets_for_unit() -> anyhow::Result<()> {
+        let keys = MintKeyset::new("mykey", "");
+        let keysets = Keysets::new(keys.keyset_id.clone(), CurrencyUnit::Sat, true);
+
+        let mut client = MockCashuClient::default();
+        client.expect_is_v1_supported().returning(|_| Ok(true));
+        client
+            .expect_get_keysets()
+            .returning(move |_| Ok(keysets.clone()));
+        let keyset_id = keys.keyset_id.clone();
+        let public_keys = keys.public_keys.clone();
+        client.expect_get_keys_by_id().returning(move |_, _| {
+            Ok(KeysResponse::new(KeyResponse {
+                keys: public_keys.clone(),
+                id: keyset_id.clone(),
+                unit: CurrencyUnit::Sat,
+            }))
+        });
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+
+        let mint_url = Url::parse("http://127.0.0.1:3338")?;
+        assert!(
+            wallet
+                .mint_supports_unit(&mint_url, &CurrencyUnit::Sat)
+                .await?
+        );
+        assert!(
+            !wallet
+                .mint_supports_unit(&mint_url, &CurrencyUnit::Usd)
+                .await?
+        );
+
+        let result = wallet
+            .add_mint_keysets_for_unit(&mint_url, Some(&CurrencyUnit::Usd))
+            .await;
+        assert!(matches!(
+            result,
+            Err(MokshaWalletError::UnitNotSupported(unit)) if unit == "usd"
+        ));
+        assert!(wallet.get_wallet_keysets().await?.is_empty());
+
+        let added = wallet
+            .add_mint_keysets_for_unit(&mint_url, Some(&CurrencyUnit::Sat))
+            .await?;
+        assert_eq!(1, added.len());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_claim_fee_estimate() -> anyhow::Result<()> {
+        let token = read_fixture("token_60.cashu")?.parse::<TokenV3>()?;
+        let mint_url = token.mint().expect("mint url not found");
+        let keyset_id = token.proofs().proofs()[0].keyset_id.clone();
+        assert_eq!(4, token.proofs().len());
+
+        let mut client = MockCashuClient::default();
+        client.expect_get_keysets().returning(move |_| {
+            let mut keysets = Keysets::new(keyset_id.clone(), CurrencyUnit::Sat, true);
+            keysets.keysets[0].input_fee_ppk = 100;
+            Ok(keysets)
+        });
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+
+        // 4 proofs * 100 ppk = 400 ppk, rounded up to 1 sat
+        assert_eq!(1, wallet.claim_fee_estimate(&mint_url, &token).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_expired_keysets() -> anyhow::Result<()> {
+        let token = read_fixture("token_60.cashu")?.parse::<TokenV3>()?;
+        let mint_url = token.mint().expect("mint url not found");
+        let keyset_id = token.proofs().proofs()[0].keyset_id.clone();
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.add_proofs(&mut tx, &token.proofs()).await?;
+        tx.commit().await?;
+
+        let mut client = MockCashuClient::default();
+        client.expect_get_keysets().returning(move |_| {
+            let mut keysets = Keysets::new(keyset_id.clone(), CurrencyUnit::Sat, false);
+            keysets.keysets[0].valid_to = Some(100);
+            // replaced as well, but the wallet holds no proofs of it
+            let mut unused = keysets.keysets[0].clone();
+            unused.id = "00ffd48b8f5ecf80".to_owned();
+            keysets.keysets.push(unused);
+            Ok(keysets)
+        });
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+
+        assert!(wallet.get_expired_keysets(&mint_url, 99).await?.is_empty());
+        assert_eq!(
+            vec![token.proofs().proofs()[0].keyset_id.clone()],
+            wallet.get_expired_keysets(&mint_url, 100).await?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_mint_keysets_offline() -> anyhow::Result<()> {
+        let keys = read_fixture_as::<KeysResponse>("keys_response.json")?;
+        let mint_url = Url::parse("http://127.0.0.1:3338")?;
+
+        // the mock fails on any request, so the wallet must not contact the mint
+        let wallet = WalletBuilder::new()
+            .with_client(MockCashuClient::default())
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+
+        let keysets = wallet.add_mint_keysets_offline(&mint_url, &keys).await?;
+        assert_eq!(1, keysets.len());
+        assert_eq!("00d31cecf59d18c0", keysets[0].keyset_id.to_string());
+
+        let mut token = read_fixture("token_60.cashu")?.parse::<TokenV3>()?;
+        assert!(!wallet.can_redeem_offline(&token).await?);
+        let proofs = token
+            .proofs()
+            .proofs()
+            .into_iter()
+            .map(|proof| Proof {
+                keyset_id: "00d31cecf59d18c0".to_owned(),
+                ..proof
+            })
+            .collect::<Vec<_>>();
+        token.tokens[0].proofs = proofs.into();
+        assert!(wallet.can_redeem_offline(&token).await?);
+
+        let mut tx = wallet.localstore.begin_tx().await?;
+        wallet
+            .localstore
+            .add_proofs(&mut tx, &token.proofs())
+            .await?;
+        tx.commit().await?;
+        assert_eq!(60, wallet.get_balance().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_mint_keysets_offline_invalid_id() -> anyhow::Result<()> {
+        let mut keys = read_fixture_as::<KeysResponse>("keys_response.json")?;
+        keys.keysets[0].id = "00ffd48b8f5ecf80".to_owned();
+
+        let wallet = WalletBuilder::new()
+            .with_client(MockCashuClient::default())
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+
+        let result = wallet
+            .add_mint_keysets_offline(&Url::parse("http://127.0.0.1:3338")?, &keys)
+            .await;
+        assert!(matches!(
+            result,
+            Err(MokshaWalletError::KeysetIdMismatch(id, _)) if id == "00ffd48b8f5ecf80"
+        ));
+        assert!(wallet.get_wallet_keysets().await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_receive_fee_exceeds_value() -> anyhow::Result<()> {
+        let token = read_fixture("token_60.cashu")?.parse::<TokenV3>()?;
+        let keyset_id = token.proofs().proofs()[0].keyset_id.clone();
+        // a single 4 sat proof
+        let dust = TokenV3 {
+            tokens: vec![Token {
+                mint: token.mint(),
+                proofs: Proofs::with_proof(token.proofs().proofs()[0].clone()),
+            }],
+            ..token.clone()
+        };
+        assert_eq!(4, dust.total_amount());
+
+        let mut client = MockCashuClient::default();
+        client.expect_get_keysets().returning(move |_| {
+            let mut keysets = Keysets::new(keyset_id.clone(), CurrencyUnit::Sat, true);
+            keysets.keysets[0].input_fee_ppk = 5_000;
+            Ok(keysets)
+        });
+        client.expect_post_swap().never();
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+        let keyset = WalletKeyset {
+            mint_url: token.mint().expect("mint url not found"),
+            ..create_test_wallet_keyset()?
+        };
+
+        let result = wallet.receive_tokens(&keyset, &dust, false).await;
+        assert!(matches!(
+            result,
+            Err(MokshaWalletError::FeeExceedsValue(5, 4))
+        ));
+
+        // 4 proofs cost 20 sats to claim, which is a third of the 60 sat token
+        wallet.set_max_receive_fee_percent(Some(30)).await?;
+        assert!(matches!(
+            wallet.check_receive_fee(&keyset.mint_url, &token).await,
+            Err(MokshaWalletError::FeeExceedsValue(20, 60))
+        ));
+        wallet.set_max_receive_fee_percent(Some(50)).await?;
+        assert!(wallet
+            .check_receive_fee(&keyset.mint_url, &token)
+            .await
+            .is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_token_unit() -> anyhow::Result<()> {
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let keyset = WalletKeyset {
+            currency_unit: CurrencyUnit::Usd,
+            ..create_test_wallet_keyset()?
+        };
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        tx.commit().await?;
+        let wallet = WalletBuilder::new()
+            .with_client(create_mock())
+            .with_localstore(localstore)
+            .build()
+            .await?;
+
+        let mut tokens: TokenV3 = read_fixture("token_60.cashu")?.try_into()?;
+        tokens.currency_unit = None;
+        assert_eq!(CurrencyUnit::Sat, wallet.get_token_unit(&tokens).await?);
+
+        tokens.tokens = tokens
+            .tokens
+            .into_iter()
+            .map(|t| Token {
+                proofs: Proofs::new(
+                    t.proofs
+                        .proofs()
+                        .into_iter()
+                        .map(|p| Proof {
+                            keyset_id: keyset.keyset_id.to_string(),
+                            ..p
+                        })
+                        .collect(),
+                ),
+                ..t
+            })
+            .collect();
+        assert_eq!(CurrencyUnit::Usd, wallet.get_token_unit(&tokens).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_tokens_from_label() -> anyhow::Result<()> {
+        let keyset = create_test_wallet_keyset()?;
+        let with_keyset = |token: TokenV3| -> Proofs {
+            token
+                .proofs()
+                .proofs()
+                .into_iter()
+                .map(|p| Proof {
+                    keyset_id: keyset.keyset_id.to_string(),
+                    ..p
+                })
+                .collect::<Vec<_>>()
+                .into()
+        };
+        let savings = with_keyset(read_fixture("token_64.cashu")?.try_into()?);
+        let untagged = with_keyset(read_fixture("token_60.cashu")?.try_into()?);
+
+        let split_response = read_fixture_as::<PostSwapResponse>("post_swap_response_24_40.json")?;
+        let mut client = create_mock();
+        let savings_secrets = savings
+            .proofs()
+            .into_iter()
+            .map(|p| p.secret)
+            .collect::<Vec<_>>();
+        client
+            .expect_post_swap()
+            .withf(move |_, proofs, _| {
+                proofs
+                    .proofs()
+                    .iter()
+                    .all(|p| savings_secrets.contains(&p.secret))
+            })
+            .times(1)
+            .returning(move |_, _, _| Ok(split_response.clone()));
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        localstore.add_proofs(&mut tx, &savings).await?;
+        localstore.add_proofs(&mut tx, &untagged).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+        wallet.set_proof_labels(&savings, Some("savings")).await?;
+
+        // untagged proofs are not used even though the balance is sufficient
+        let result = wallet
+            .send_tokens_from_label(&keyset, "savings", 100, false)
+            .await;
+        assert!(matches!(result, Err(MokshaWalletError::NotEnoughTokens)));
+        let result = wallet
+            .send_tokens_from_label(&keyset, "spending", 20, false)
+            .await;
+        assert!(matches!(result, Err(MokshaWalletError::NotEnoughTokens)));
+
+        let result = wallet
+            .send_tokens_from_label(&keyset, "savings", 20, false)
+            .await?;
+        assert_eq!(40, result.total_amount());
+
+        // the change keeps the label and the untagged proofs are untouched
+        let mut tx = wallet.localstore.begin_tx().await?;
+        let proofs = wallet.localstore.get_stored_proofs(&mut tx).await?;
+        assert_eq!(24, proofs.with_label("savings").total_amount());
+        assert_eq!(84, proofs.total_amount());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_tokens_exact_amount_without_swap() -> anyhow::Result<()> {
+        let keyset = create_test_wallet_keyset()?;
+        let tokens: TokenV3 = read_fixture("token_60.cashu")?.try_into()?; // 4,8,16,32
+        let proofs: Proofs = tokens
+            .proofs()
+            .proofs()
+            .into_iter()
+            .filter(|p| p.amount == 8 || p.amount == 16)
+            .map(|p| Proof {
+                keyset_id: keyset.keyset_id.to_string(),
+                ..p
+            })
+            .collect::<Vec<_>>()
+            .into();
+
+        let mut client = create_mock();
+        client.expect_post_swap().never();
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        localstore.add_proofs(&mut tx, &proofs).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+        let result = wallet.send_tokens(&keyset, 8, false).await?;
+
+        assert_eq!(8, result.total_amount());
+        assert_eq!(
+            vec![16],
+            wallet
+                .get_proofs()
+                .await?
+                .proofs()
+                .iter()
+                .map(|p| p.amount)
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_receive_tokens_from_unknown_mint() -> anyhow::Result<()> {
+        let tokens: TokenV3 = read_fixture("token_60.cashu")?.try_into()?;
+        let mut client = create_mock();
+        client.expect_post_swap().never();
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(SqliteLocalStore::with_in_memory().await?)
+            .build()
+            .await?;
+
+        let result = wallet.receive_tokens_from_known_mint(&tokens, false).await;
+        assert!(matches!(result, Err(MokshaWalletError::UnknownMint(_))));
+        assert!(wallet.get_mint_urls().await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_receive_tokens_from_known_mint_without_active_keyset() -> anyhow::Result<()> {
+        let tokens: TokenV3 = read_fixture("token_60.cashu")?.try_into()?;
+        let mut client = create_mock();
+        client.expect_post_swap().never();
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore
+            .upsert_keyset(
+                &mut tx,
+                &WalletKeyset {
+                    active: false,
+                    ..create_test_wallet_keyset()?
+                },
+            )
+            .await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+
+        let result = wallet.receive_tokens_from_known_mint(&tokens, false).await;
+        assert!(matches!(
+            result,
+            Err(MokshaWalletError::NoActiveKeyset(_, _))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_accounts_have_independent_balances() -> anyhow::Result<()> {
+        let keyset = create_test_wallet_keyset()?;
+        let tokens: TokenV3 = read_fixture("token_60.cashu")?.try_into()?;
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+
+        let default_wallet = WalletBuilder::new()
+            .with_client(create_mock())
+            .with_localstore(localstore.clone())
+            .build()
+            .await?;
+        let alice_store = localstore.with_account("alice");
+        let mut tx = alice_store.begin_tx().await?;
+        alice_store.upsert_keyset(&mut tx, &keyset).await?;
+        alice_store.add_proofs(&mut tx, &tokens.proofs()).await?;
+        tx.commit().await?;
+        let alice_wallet = WalletBuilder::new()
+            .with_client(create_mock())
+            .with_localstore(alice_store)
+            .build()
+            .await?;
+
+        assert_eq!(60, alice_wallet.get_balance().await?);
+        assert_eq!(0, default_wallet.get_balance().await?);
+        assert_eq!(1, alice_wallet.get_wallet_keysets().await?.len());
+        assert!(default_wallet.get_wallet_keysets().await?.is_empty());
+        assert_eq!(
+            vec!["alice".to_owned(), "default".to_owned()],
+            default_wallet.list_accounts().await?
+        );
+
+        let mut tx = default_wallet.localstore.begin_tx().await?;
+        let default_seed = default_wallet.localstore.get_seed(&mut tx).await?;
+        let alice_seed = default_wallet
+            .localstore
+            .get_seed_for_account(&mut tx, "alice")
+            .await?;
+        tx.commit().await?;
+        assert!(alice_seed.is_some());
+        assert_ne!(default_seed, alice_seed);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_tokens_locked() -> anyhow::Result<()> {
+        let keyset = create_test_wallet_keyset()?;
+        let tokens: TokenV3 = read_fixture("token_64.cashu")?.try_into()?;
+        let proofs: Proofs = tokens
+            .proofs()
+            .proofs()
+            .into_iter()
+            .map(|p| Proof {
+                keyset_id: keyset.keyset_id.to_string(),
+                ..p
+            })
+            .collect::<Vec<_>>()
+            .into();
+
+        let split_response = read_fixture_as::<PostSwapResponse>("post_swap_response_24_40.json")?;
+        let mut client = create_mock();
+        client
+            .expect_post_swap()
+            .times(1)
+            .returning(move |_, _, _| Ok(split_response.clone()));
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        localstore.add_proofs(&mut tx, &proofs).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+        let pubkey =
+            secp256k1::SecretKey::from_slice(&[1; 32])?.public_key(&secp256k1::Secp256k1::new());
+        // 32 would match exactly, but locked tokens always need a swap
+        let result = wallet
+            .send_tokens_locked(&keyset, 32, &[pubkey], 1, None, false)
+            .await?;
+
+        assert!(!result.proofs().is_empty());
+        for proof in result.proofs().proofs() {
+            let secret = P2PKSecret::from_secret(&proof.secret)?.expect("secret is not locked");
+            assert_eq!(pubkey, secret.pubkey);
+        }
+        for proof in wallet.get_proofs().await?.proofs() {
+            assert_eq!(None, P2PKSecret::from_secret(&proof.secret)?);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotate_p2pk_key() -> anyhow::Result<()> {
+        let keyset = create_test_wallet_keyset()?;
+        let tokens: TokenV3 = read_fixture("token_60.cashu")?.try_into()?;
+        let mut client = create_mock();
+        // only the proofs of 4 and 8 are migrated, signed with the old key
+        client
+            .expect_post_swap()
+            .times(1)
+            .withf(|_, proofs, _| {
+                proofs.total_amount() == 12
+                    && proofs
+                        .proofs()
+                        .iter()
+                        .all(|proof| proof.verify_spending_conditions(0).is_ok())
+            })
+            .returning(|_, _, outputs| {
+                Ok(PostSwapResponse {
+                    signatures: outputs
+                        .into_iter()
+                        .map(|output| BlindedSignature {
+                            amount: output.amount,
+                            c_: output.b_,
+                            id: "00d31cecf59d18c0".to_owned(),
+                        })
+                        .collect(),
+                })
+            });
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore.clone())
+            .build()
+            .await?;
+        let old_pubkey = wallet.p2pk_pubkey().await?;
+        let other_pubkey =
+            secp256k1::SecretKey::from_slice(&[1; 32])?.public_key(&secp256k1::Secp256k1::new());
+
+        // 4 and 8 are locked to the wallet, 16 needs another signature and 32 is not locked
+        let proofs: Proofs = tokens
+            .proofs()
+            .proofs()
+            .into_iter()
+            .map(|proof| {
+                let secret = match proof.amount {
+                    4 | 8 => P2PKSecret::new(old_pubkey).to_secret()?,
+                    16 => P2PKSecret::new_multisig(&[old_pubkey, other_pubkey], 2)?.to_secret()?,
+                    _ => proof.secret,
+                };
+                Ok(Proof {
+                    keyset_id: keyset.keyset_id.to_string(),
+                    secret,
+                    ..proof
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into();
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        localstore.add_proofs(&mut tx, &proofs).await?;
+        tx.commit().await?;
+
+        let new_pubkey = wallet.rotate_p2pk_key().await?;
+        assert_ne!(old_pubkey, new_pubkey);
+        assert_eq!(new_pubkey, wallet.p2pk_pubkey().await?);
+        assert_eq!(60, wallet.get_balance().await?);
+
+        let mut locked_amounts = HashMap::new();
+        for proof in wallet.get_proofs().await?.proofs() {
+            if let Some(secret) = P2PKSecret::from_secret(&proof.secret)? {
+                *locked_amounts.entry(secret.pubkey).or_insert(0) += proof.amount;
+            }
+        }
+        assert_eq!(Some(&12), locked_amounts.get(&new_pubkey));
+        assert_eq!(Some(&16), locked_amounts.get(&old_pubkey));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reclaim_tokens() -> anyhow::Result<()> {
+        let keyset = create_test_wallet_keyset()?;
+        let tokens: TokenV3 = read_fixture("token_60.cashu")?.try_into()?;
+        let mut client = create_mock();
+        // signed with the refund key, which is only valid after the locktime
+        client
+            .expect_post_swap()
+            .times(1)
+            .withf(|_, proofs, _| {
+                proofs.proofs().iter().all(|proof| {
+                    proof.verify_spending_conditions(99).is_err()
+                        && proof.verify_spending_conditions(100).is_ok()
+                })
+            })
+            .returning(|_, _, outputs| {
+                Ok(PostSwapResponse {
+                    signatures: outputs
+                        .into_iter()
+                        .map(|output| BlindedSignature {
+                            amount: output.amount,
+                            c_: output.b_,
+                            id: "00d31cecf59d18c0".to_owned(),
+                        })
+                        .collect(),
+                })
+            });
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        tx.commit().await?;
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+        let refund_pubkey = wallet.p2pk_pubkey().await?;
+        let other_pubkey =
+            secp256k1::SecretKey::from_slice(&[1; 32])?.public_key(&secp256k1::Secp256k1::new());
+
+        let lock = |locktime: Option<u64>| -> anyhow::Result<TokenV3> {
+            let proofs = tokens
+                .proofs()
+                .proofs()
+                .into_iter()
+                .map(|proof| {
+                    let mut secret = P2PKSecret::new(other_pubkey);
+                    if let Some(locktime) = locktime {
+                        secret = secret.with_locktime(locktime, &[refund_pubkey]);
+                    }
+                    Ok(Proof {
+                        secret: secret.to_secret()?,
+                        ..proof
+                    })
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            Ok((keyset.mint_url.clone(), Proofs::new(proofs)).into())
+        };
+
+        assert!(matches!(
+            wallet.reclaim_tokens(&keyset, &lock(None)?, 100).await,
+            Err(MokshaWalletError::NotReclaimable)
+        ));
+        let reclaimed = wallet
+            .reclaim_tokens(&keyset, &lock(Some(100))?, 100)
+            .await?;
+        assert_eq!(60, reclaimed.total_amount());
+        assert_eq!(60, wallet.get_balance().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reclaim_tokens_before_locktime() -> anyhow::Result<()> {
+        let keyset = create_test_wallet_keyset()?;
+        let tokens: TokenV3 = read_fixture("token_60.cashu")?.try_into()?;
+        let mut client = create_mock();
+        client.expect_post_swap().never();
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        tx.commit().await?;
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+        let refund_pubkey = wallet.p2pk_pubkey().await?;
+        let other_pubkey =
+            secp256k1::SecretKey::from_slice(&[1; 32])?.public_key(&secp256k1::Secp256k1::new());
+
+        let proofs = tokens
+            .proofs()
+            .proofs()
+            .into_iter()
+            .map(|proof| {
+                Ok(Proof {
+                    secret: P2PKSecret::new(other_pubkey)
+                        .with_locktime(100, &[refund_pubkey])
+                        .to_secret()?,
+                    ..proof
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let locked: TokenV3 = (keyset.mint_url.clone(), Proofs::new(proofs)).into();
+
+        assert!(matches!(
+            wallet.reclaim_tokens(&keyset, &locked, 99).await,
+            Err(MokshaWalletError::LocktimeNotReached(100))
+        ));
+        assert_eq!(0, wallet.get_balance().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_tokens_writes_operation_log() -> anyhow::Result<()> {
+        let keyset = create_test_wallet_keyset()?;
+        let tokens: TokenV3 = read_fixture("token_64.cashu")?.try_into()?;
+        let proofs: Proofs = tokens
+            .proofs()
+            .proofs()
+            .into_iter()
+            .map(|p| Proof {
+                keyset_id: keyset.keyset_id.to_string(),
+                ..p
+            })
+            .collect::<Vec<_>>()
+            .into();
+
+        let split_response = read_fixture_as::<PostSwapResponse>("post_swap_response_24_40.json")?;
+        let mut client = create_mock();
+        client
+            .expect_post_swap()
+            .returning(move |_, _, _| Ok(split_response.clone()));
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        localstore.add_proofs(&mut tx, &proofs).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+        let result = wallet.send_tokens(&keyset, 20, false).await?;
+
+        let entries = wallet.get_operation_log(10).await?;
+        assert_eq!(
+            vec![Operation::Send, Operation::Swap],
+            entries.iter().map(|e| e.operation).collect::<Vec<_>>()
+        );
+        let send = &entries[0];
+        assert_eq!(None, send.error);
+        let inputs: serde_json::Value = serde_json::from_str(&send.inputs)?;
+        assert_eq!(20, inputs["amount"]);
+        assert_eq!(
+            serde_json::json!(proof_ids(&proofs.select_proofs(20)?)),
+            inputs["proofs"]
+        );
+        let outputs: serde_json::Value = serde_json::from_str(&send.outputs)?;
+        assert_eq!(
+            serde_json::json!(proof_ids(&result.proofs())),
+            outputs["proofs"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_tokens_without_operation_log() -> anyhow::Result<()> {
+        let keyset = create_test_wallet_keyset()?;
+        let tokens: TokenV3 = read_fixture("token_60.cashu")?.try_into()?; // 4,8,16,32
+        let proofs: Proofs = tokens
+            .proofs()
+            .proofs()
+            .into_iter()
+            .map(|p| Proof {
+                keyset_id: keyset.keyset_id.to_string(),
+                ..p
+            })
+            .collect::<Vec<_>>()
+            .into();
+
+        let mut client = create_mock();
+        client.expect_post_swap().never();
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        localstore.add_proofs(&mut tx, &proofs).await?;
+        // the operation log can't be written
+        sqlx::query("DROP TABLE operation_log")
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+
+        // the proofs are removed from the wallet, so the token must still be returned
+        let result = wallet.send_tokens(&keyset, 60, false).await?;
+        assert_eq!(60, result.total_amount());
+        assert_eq!(0, wallet.get_balance().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_audit_balance() -> anyhow::Result<()> {
+        let keyset = create_test_wallet_keyset()?;
+        let tokens: TokenV3 = read_fixture("token_60.cashu")?.try_into()?;
+        let proofs: Proofs = tokens
+            .proofs()
+            .proofs()
+            .into_iter()
+            .map(|p| Proof {
+                keyset_id: keyset.keyset_id.to_string(),
+                ..p
+            })
+            .collect::<Vec<_>>()
+            .into();
+
+        // 4 is spent, 8 is pending, 32 is unknown to the mint and only 16 is unspent
+        let states = proofs
+            .proofs()
+            .into_iter()
+            .filter_map(|proof| {
+                let state = match proof.amount {
+                    4 => SpendState::Spent,
+                    8 => SpendState::Pending,
+                    16 => SpendState::Unspent,
+                    _ => return None,
+                };
+                Some(ProofState {
+                    y: proof.y().expect("invalid secret"),
+                    state,
+                    witness: None,
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut client = create_mock();
+        client
+            .expect_post_checkstate()
+            .times(2)
+            .returning(move |_, _| {
+                Ok(PostCheckStateResponse {
+                    states: states.clone(),
+                })
+            });
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        localstore.add_proofs(&mut tx, &proofs).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+
+        let audit = wallet.audit_balance(&keyset.mint_url, false).await?;
+        assert!(!audit.is_consistent());
+        assert_eq!(60, audit.local_balance);
+        assert_eq!(16, audit.verified_balance);
+        assert_eq!(4, audit.spent.total_amount());
+        assert_eq!(8, audit.pending.total_amount());
+        assert_eq!(32, audit.unknown.total_amount());
+        assert!(!audit.pruned);
+        assert_eq!(60, wallet.get_balance().await?);
+
+        let audit = wallet.audit_balance(&keyset.mint_url, true).await?;
+        assert!(audit.pruned);
+        assert_eq!(56, wallet.get_balance().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_balances() -> anyhow::Result<()> {
+        let first_keyset = create_test_wallet_keyset()?;
+        let second_keyset = WalletKeyset {
+            keyset_id: KeysetId::new("00ffd48b8f5ecf80")?,
+            mint_url: Url::parse("http://127.0.0.1:3339")?,
+            ..first_keyset.clone()
+        };
+        let usd_keyset = WalletKeyset {
+            keyset_id: KeysetId::new("00ffd48b8f5ecf81")?,
+            currency_unit: CurrencyUnit::Usd,
+            ..second_keyset.clone()
+        };
+        let tokens: TokenV3 = read_fixture("token_60.cashu")?.try_into()?;
+        // 4 and 8 belong to the first mint, 16 (sat) and 32 (usd) to the second one
+        let proofs: Proofs = tokens
+            .proofs()
+            .proofs()
+            .into_iter()
+            .map(|p| Proof {
+                keyset_id: match p.amount {
+                    4 | 8 => first_keyset.keyset_id.to_string(),
+                    16 => second_keyset.keyset_id.to_string(),
+                    _ => usd_keyset.keyset_id.to_string(),
+                },
+                ..p
+            })
+            .collect::<Vec<_>>()
+            .into();
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &first_keyset).await?;
+        localstore.upsert_keyset(&mut tx, &second_keyset).await?;
+        localstore.upsert_keyset(&mut tx, &usd_keyset).await?;
+        localstore.add_proofs(&mut tx, &proofs).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(create_mock())
+            .with_localstore(localstore)
+            .build()
+            .await?;
+
+        let balances = wallet.get_balances().await?;
+        assert_eq!(3, balances.len());
+        assert_eq!(
+            Some(&12),
+            balances.get(&(
+                first_keyset.mint_url.clone(),
+                first_keyset.keyset_id.clone()
+            ))
+        );
+        assert_eq!(
+            Some(&16),
+            balances.get(&(
+                second_keyset.mint_url.clone(),
+                second_keyset.keyset_id.clone()
+            ))
+        );
+        assert_eq!(
+            Some(&32),
+            balances.get(&(usd_keyset.mint_url.clone(), usd_keyset.keyset_id.clone()))
+        );
+        assert_eq!(
+            16,
+            wallet
+                .get_balance_for_mint(&second_keyset.mint_url, &CurrencyUnit::Sat)
+                .await?
+        );
+        assert_eq!(
+            32,
+            wallet
+                .get_balance_for_mint(&second_keyset.mint_url, &CurrencyUnit::Usd)
+                .await?
+        );
+        assert_eq!(
+            0,
+            wallet
+                .get_balance_for_mint(&Url::parse("http://127.0.0.1:3340")?, &CurrencyUnit::Sat)
+                .await?
+        );
+        assert_eq!(60, wallet.get_balance().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_tokens_records_transaction() -> anyhow::Result<()> {
+        let keyset = create_test_wallet_keyset()?;
+        let tokens: TokenV3 = read_fixture("token_64.cashu")?.try_into()?;
+        let proofs: Proofs = tokens
+            .proofs()
+            .proofs()
+            .into_iter()
+            .map(|p| Proof {
+                keyset_id: keyset.keyset_id.to_string(),
+                ..p
             })
-            .collect::<Vec<Proof>>()
+            .collect::<Vec<_>>()
             .into();
 
-        let tokens: TokenV3 = (wallet_keyset.mint_url.to_owned(), proofs).into();
-        let mut tx = self.localstore.begin_tx().await?;
-        self.localstore
-            .add_proofs(&mut tx, &tokens.proofs())
-            .await?;
+        let split_response = read_fixture_as::<PostSwapResponse>("post_swap_response_24_40.json")?;
+        let mut client = create_mock();
+        client
+            .expect_post_swap()
+            .returning(move |_, _, _| Ok(split_response.clone()));
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        localstore.add_proofs(&mut tx, &proofs).await?;
         tx.commit().await?;
 
-        Ok(tokens)
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+        assert!(wallet.get_transactions(10).await?.is_empty());
+        wallet.send_tokens(&keyset, 20, false).await?;
+
+        let transactions = wallet.get_transactions(10).await?;
+        assert_eq!(1, transactions.len());
+        assert_eq!(Operation::Send, transactions[0].operation);
+        assert_eq!(20, transactions[0].amount);
+        assert_eq!(keyset.mint_url, transactions[0].mint_url);
+        Ok(())
     }
 
-    pub async fn create_blank(
-        &self,
-        fee_reserve: Amount,
-        keyset_id: &KeysetId,
-    ) -> Result<Vec<(BlindedMessage, BlindingFactor, String)>, MokshaWalletError> {
-        if fee_reserve.0 == 0 {
-            return Ok(vec![]);
-        }
-
-        let fee_reserve_float = fee_reserve.0 as f64;
-        let count = (fee_reserve_float.log2().ceil() as u64).max(1);
-
-        let secret_range = self.create_secrets(keyset_id, count as u32).await?;
-        let blinded_messages = secret_range
+    async fn create_interrupted_swap_wallet(
+        client: MockCashuClient,
+    ) -> anyhow::Result<(Wallet<SqliteLocalStore, MockCashuClient>, Proofs)> {
+        let keyset = create_test_wallet_keyset()?;
+        let tokens: TokenV3 = read_fixture("token_64.cashu")?.try_into()?;
+        let proofs: Proofs = tokens
+            .proofs()
+            .proofs()
             .into_iter()
-            .map(|(secret, blinding_factor)| {
-                let b_ = self.dhke.step1_alice(secret.clone(), &blinding_factor)?;
-                Ok((
-                    BlindedMessage {
-                        amount: 1,
-                        b_,
-                        id: keyset_id.to_string(),
-                    },
-                    blinding_factor,
-                    secret,
-                ))
+            .map(|p| Proof {
+                keyset_id: keyset.keyset_id.to_string(),
+                ..p
             })
-            .collect::<Result<Vec<(_, _, _)>, MokshaWalletError>>()?;
+            .collect::<Vec<_>>()
+            .into();
 
-        Ok(blinded_messages)
-    }
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        localstore.add_proofs(&mut tx, &proofs).await?;
+        tx.commit().await?;
 
-    fn create_blinded_messages(
-        &self,
-        keyset_id: &KeysetId,
-        amount: Amount,
-        secrets_factors: Vec<(String, BlindingFactor)>,
-    ) -> Result<Vec<(BlindedMessage, BlindingFactor)>, MokshaWalletError> {
-        let split_amount = amount.split();
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
 
-        split_amount
-            .into_iter()
-            .zip(secrets_factors)
-            .map(|(amount, (secret, blinding_factor))| {
-                let b_ = self.dhke.step1_alice(secret, &blinding_factor)?;
-                Ok((
-                    BlindedMessage {
-                        amount,
-                        b_,
-                        id: keyset_id.to_string(),
-                    },
-                    blinding_factor,
-                ))
-            })
-            .collect::<Result<Vec<(_, _)>, MokshaWalletError>>()
+        // the process stopped after the swap was logged, before its result was stored
+        let log_id = wallet
+            .begin_operation(
+                Operation::Swap,
+                serde_json::json!({
+                    "mint_url": keyset.mint_url.as_str(),
+                    "amount": 40,
+                    "proofs": proof_ids(&proofs),
+                }),
+            )
+            .await?;
+        // 24 and 40 sats are split into 4 outputs
+        let (start_index, _) = wallet.create_secrets(&keyset.keyset_id, 4).await?;
+        let mut tx = wallet.localstore.begin_tx().await?;
+        wallet
+            .localstore
+            .update_log_entry(
+                &mut tx,
+                log_id,
+                &serde_json::json!({
+                    "keyset_id": keyset.keyset_id.to_string(),
+                    "derivation_indexes": (start_index..start_index + 4).collect::<Vec<_>>(),
+                })
+                .to_string(),
+                None,
+            )
+            .await?;
+        tx.commit().await?;
+        Ok((wallet, proofs))
     }
 
-    fn create_proofs_from_blinded_signatures(
-        &self,
-        keyset_id: &KeysetId,
-        pub_keys: &HashMap<u64, PublicKey>,
-        signatures: Vec<BlindedSignature>,
-        secrets: Vec<String>,
-        outputs: Vec<(BlindedMessage, BlindingFactor)>,
-    ) -> Result<Proofs, MokshaWalletError> {
-        let current_keyset_id = keyset_id.to_string(); // FIXME
-
-        let blinding_factors = outputs
-            .into_iter()
-            .map(|(_, secret)| secret)
-            .collect::<Vec<BlindingFactor>>();
-
-        Ok(signatures
-            .iter()
-            .zip(blinding_factors)
-            .zip(secrets)
-            .map(|((p, blinding_factor), secret)| {
-                let key = pub_keys
-                    .get(&p.amount)
-                    .ok_or(MokshaWalletError::PubkeyNotFound)?;
-                let pub_alice = self
-                    .dhke
-                    .step3_alice(p.c_, blinding_factor.to_owned(), *key)?;
-                Ok(Proof::new(
-                    p.amount,
-                    secret,
-                    pub_alice,
-                    current_keyset_id.clone(),
-                ))
-            })
-            .collect::<Result<Vec<_>, MokshaWalletError>>()?
-            .into())
+    #[tokio::test]
+    async fn test_reconcile_interrupted_swap() -> anyhow::Result<()> {
+        let mut client = create_mock();
+        // the wallet doesn't verify the signatures, so returning the blinded messages is enough
+        client
+            .expect_post_swap()
+            .times(1)
+            .returning(|_, _, outputs| {
+                Ok(PostSwapResponse {
+                    signatures: outputs
+                        .into_iter()
+                        .map(|output| BlindedSignature {
+                            amount: output.amount,
+                            c_: output.b_,
+                            id: "00d31cecf59d18c0".to_owned(),
+                        })
+                        .collect(),
+                })
+            });
+        let (wallet, proofs) = create_interrupted_swap_wallet(client).await?;
+
+        assert_eq!(1, wallet.reconcile().await?);
+        assert_eq!(64, wallet.get_balance().await?);
+        let stored_ids = proof_ids(&wallet.get_proofs().await?);
+        assert!(proof_ids(&proofs).iter().all(|id| !stored_ids.contains(id)));
+
+        // the entry is marked as reconciled and not checked again
+        assert_eq!(0, wallet.reconcile().await?);
+        Ok(())
     }
 
-    pub async fn get_proofs(&self) -> Result<Proofs, MokshaWalletError> {
-        let mut tx = self.localstore.begin_tx().await?;
-        let proofs = self.localstore.get_proofs(&mut tx).await?;
-        tx.commit().await?;
-        Ok(proofs)
+    #[tokio::test]
+    async fn test_reconcile_interrupted_swap_already_spent() -> anyhow::Result<()> {
+        let mut client = create_mock();
+        client.expect_post_swap().times(1).returning(|_, _, _| {
+            Err(MokshaWalletError::ProofAlreadyUsed(
+                "Proof already used 407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837"
+                    .to_owned(),
+            ))
+        });
+        // the mint signed the outputs of the interrupted swap
+        client
+            .expect_post_restore()
+            .times(1)
+            .returning(|_, outputs| {
+                let outputs = outputs
+                    .into_iter()
+                    .zip([8, 16, 8, 32])
+                    .map(|(output, amount)| BlindedMessage { amount, ..output })
+                    .collect::<Vec<_>>();
+                Ok(PostRestoreResponse {
+                    signatures: outputs
+                        .iter()
+                        .map(|output| BlindedSignature {
+                            amount: output.amount,
+                            c_: output.b_,
+                            id: "00d31cecf59d18c0".to_owned(),
+                        })
+                        .collect(),
+                    outputs,
+                })
+            });
+        let (wallet, proofs) = create_interrupted_swap_wallet(client).await?;
+
+        assert_eq!(1, wallet.reconcile().await?);
+        // the spent inputs are replaced by the restored outputs
+        assert_eq!(64, wallet.get_balance().await?);
+        let stored_ids = proof_ids(&wallet.get_proofs().await?);
+        assert!(proof_ids(&proofs).iter().all(|id| !stored_ids.contains(id)));
+        assert_eq!(0, wallet.reconcile().await?);
+        Ok(())
     }
-}
 
-// FIXME implement for Vec<BlindedMessage, Secretkey>
-fn get_blinded_msg(blinded_messages: Vec<(BlindedMessage, BlindingFactor)>) -> Vec<BlindedMessage> {
-    blinded_messages
-        .into_iter()
-        .map(|(msg, _)| msg)
-        .collect::<Vec<BlindedMessage>>()
-}
-
-#[cfg(test)]
-mod tests {
-    use std::collections::HashMap;
+    #[tokio::test]
+    async fn test_send_tokens_max_send_amount() -> anyhow::Result<()> {
+        let split_response = read_fixture_as::<PostSwapResponse>("post_swap_response_24_40.json")?;
+        let mut client = create_mock();
+        client
+            .expect_post_swap()
+            .returning(move |_, _, _| Ok(split_response.clone()));
 
-    use crate::client::MockCashuClient;
-    use crate::localstore::sqlite::SqliteLocalStore;
-    use crate::localstore::{LocalStore, WalletKeyset};
-    use crate::wallet::WalletBuilder;
+        let tokens: TokenV3 = read_fixture("token_64.cashu")?.try_into()?;
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let keyset = create_test_wallet_keyset()?;
+        let proofs = Proofs::new(
+            tokens
+                .proofs()
+                .proofs()
+                .into_iter()
+                .map(|p| Proof {
+                    keyset_id: keyset.keyset_id.to_string(),
+                    ..p
+                })
+                .collect(),
+        );
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        localstore.add_proofs(&mut tx, &proofs).await?;
+        tx.commit().await?;
 
-    use moksha_core::fixture::{read_fixture, read_fixture_as};
-    use moksha_core::keyset::{KeysetId, Keysets, MintKeyset};
-    use moksha_core::primitives::{
-        CurrencyUnit, KeyResponse, KeysResponse, PaymentMethod, PostMeltBolt11Response,
-        PostMeltQuoteBolt11Response, PostMintBolt11Response, PostSwapResponse,
-    };
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+        wallet.set_max_send_amount(Some(10)).await?;
+        assert_eq!(Some(10), wallet.get_max_send_amount().await?);
 
-    use moksha_core::token::TokenV3;
-    use secp256k1::PublicKey;
-    use url::Url;
+        let result = wallet.send_tokens(&keyset, 20, false).await;
+        assert!(matches!(
+            result,
+            Err(MokshaWalletError::MaxSendAmountExceeded(20, 10))
+        ));
+        assert_eq!(64, wallet.get_balance().await?);
 
-    fn create_mock() -> MockCashuClient {
-        let keys = MintKeyset::new("mykey", "");
-        let key_response = KeyResponse {
-            keys: keys.public_keys.clone(),
-            id: keys.keyset_id.clone(),
-            unit: CurrencyUnit::Sat,
-        };
-        let keys_response = KeysResponse::new(key_response.clone());
-        let keys_by_id_response = keys_response.clone();
-        let keysets = Keysets::new(keys.keyset_id, CurrencyUnit::Sat, true);
+        let result = wallet.send_tokens(&keyset, 20, true).await;
+        assert!(result.is_ok());
 
-        let mut client = MockCashuClient::default();
-        client
-            .expect_get_keys()
-            .returning(move |_| Ok(keys_response.clone()));
-        client
-            .expect_get_keysets()
-            .returning(move |_| Ok(keysets.clone()));
-        client
-            .expect_get_keys_by_id()
-            .returning(move |_, _| Ok(keys_by_id_response.clone()));
-        client.expect_is_v1_supported().returning(move |_| Ok(true));
-        client
+        wallet.set_max_send_amount(None).await?;
+        assert_eq!(None, wallet.get_max_send_amount().await?);
+        Ok(())
     }
 
     #[tokio::test]
-    async fn test_blank_blinded_messages_1000_sats() -> anyhow::Result<()> {
+    async fn test_export_tokens_roundtrip() -> anyhow::Result<()> {
+        let tokens: TokenV3 = read_fixture("token_64.cashu")?.try_into()?;
         let localstore = SqliteLocalStore::with_in_memory().await?;
-        let wallet_keyset = create_test_wallet_keyset()?;
+        let keyset = create_test_wallet_keyset()?;
+        let proofs = Proofs::new(
+            tokens
+                .proofs()
+                .proofs()
+                .into_iter()
+                .map(|p| Proof {
+                    keyset_id: keyset.keyset_id.to_string(),
+                    ..p
+                })
+                .collect(),
+        );
+
+        // the mint must receive exactly the proofs that were exported
+        let expected_proofs = proofs.clone();
+        let mut client = create_mock();
+        client
+            .expect_post_swap()
+            .withf(move |_, proofs, _| proofs == &expected_proofs)
+            .times(1)
+            .returning(|_, _, outputs| {
+                Ok(PostSwapResponse {
+                    signatures: outputs
+                        .into_iter()
+                        .map(|output| BlindedSignature {
+                            amount: output.amount,
+                            c_: output.b_,
+                            id: "00d31cecf59d18c0".to_owned(),
+                        })
+                        .collect(),
+                })
+            });
         let mut tx = localstore.begin_tx().await?;
-        localstore.upsert_keyset(&mut tx, &wallet_keyset).await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        localstore.add_proofs(&mut tx, &proofs).await?;
         tx.commit().await?;
 
-        let client = create_mock();
-
         let wallet = WalletBuilder::new()
             .with_client(client)
             .with_localstore(localstore)
             .build()
             .await?;
-        let result = wallet
-            .create_blank(1000.into(), &KeysetId::new("00d31cecf59d18c0")?)
-            .await;
-        println!("{:?}", result);
-        assert!(result.is_ok());
-        let result = result.unwrap();
-        assert!(result.len() == 10);
-        assert!(result.first().unwrap().0.amount == 1);
 
+        let exported = wallet.export_tokens().await?;
+        assert_eq!(1, exported.len());
+        assert_eq!(Some(keyset.mint_url.clone()), exported[0].mint());
+        assert_eq!(Some(CurrencyUnit::Sat), exported[0].currency_unit);
+        assert_eq!(64, exported[0].total_amount());
+        assert_eq!(64, wallet.get_balance().await?);
+
+        wallet.withdraw_tokens(&exported).await?;
+        assert_eq!(0, wallet.get_balance().await?);
+
+        let file = exported
+            .into_iter()
+            .map(String::try_from)
+            .collect::<Result<Vec<_>, _>>()?
+            .join("\n");
+        for line in file.lines() {
+            let token = line.parse::<TokenV3>()?;
+            wallet.receive_tokens(&keyset, &token, true).await?;
+        }
+        assert_eq!(64, wallet.get_balance().await?);
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_blank_blinded_messages_zero_sats() -> anyhow::Result<()> {
-        let localstore = SqliteLocalStore::with_in_memory().await?;
+    async fn test_get_balance() -> anyhow::Result<()> {
+        let fixture = read_fixture("token_60.cashu")?; // 60 tokens (4,8,16,32)
+        let fixture: TokenV3 = fixture.try_into()?;
+        let local_store = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = local_store.begin_tx().await?;
+        local_store.add_proofs(&mut tx, &fixture.proofs()).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(create_mock())
+            .with_localstore(local_store)
+            .build()
+            .await?;
+
+        let result = wallet.get_balance().await?;
+        assert_eq!(60, result);
+        Ok(())
+    }
+    // FIXME
+
+    #[tokio::test]
+    async fn test_pay_invoice() -> anyhow::Result<()> {
+        let fixture = read_fixture("token_60.cashu")?; // 60 tokens (4,8,16,32)
+
+        let local_store = SqliteLocalStore::with_in_memory().await?;
+        let fixture: TokenV3 = fixture.try_into()?;
+        let mut tx = local_store.begin_tx().await?;
+        local_store.add_proofs(&mut tx, &fixture.proofs()).await?;
         let wallet_keyset = create_test_wallet_keyset()?;
-        let mut tx = localstore.begin_tx().await?;
-        localstore.upsert_keyset(&mut tx, &wallet_keyset).await?;
+        local_store.upsert_keyset(&mut tx, &wallet_keyset).await?;
         tx.commit().await?;
 
-        let client = create_mock();
+        let melt_response =
+            read_fixture_as::<PostMeltBolt11Response>("post_melt_response_21.json")?; // 60 tokens (4,8,16,32)
+        let mut mock_client = create_mock();
+        mock_client
+            .expect_post_melt_bolt11()
+            .returning(move |_, _, _, _| Ok(melt_response.clone()));
+
+        let quote_response =
+            read_fixture_as::<PostMeltQuoteBolt11Response>("post_melt_quote_response.json")?;
+        mock_client
+            .expect_post_melt_quote_bolt11()
+            .returning(move |_, _, _, _| Ok(quote_response.clone()));
+
+        let swap_response = read_fixture_as::<PostSwapResponse>("post_swap_response_24_40.json")?;
+        mock_client
+            .expect_post_swap()
+            .returning(move |_, _, _| Ok(swap_response.clone()));
 
+        let mint_url = Url::parse("http://localhost:8080/").expect("invalid url");
         let wallet = WalletBuilder::new()
-            .with_client(client)
-            .with_localstore(localstore)
+            .with_client(mock_client)
+            .with_localstore(local_store)
             .build()
             .await?;
-        let result = wallet
-            .create_blank(0.into(), &KeysetId::new("00d31cecf59d18c0")?)
-            .await;
-        println!("{:?}", result);
-        assert!(result.is_ok());
-        assert!(result.unwrap().is_empty());
 
+        // FIXME fix mock response: Warning Error in swap: input 32 != output 24 + 40
+
+        // 21 sats
+        let invoice = "lnbcrt210n1pjg6mqhpp5pza5wzh0csjjuvfpjpv4zdjmg30vedj9ycv5tyfes9x7dp8axy0sdqqcqzzsxqyz5vqsp5vtxg4c5tw2s2zxxya2a7an0psn9mcfmlqctxzntm3sngnpyk3muq9qyyssqf8z5f90yu3wrmsufnnza25qjlnvc6ukdr094ckzn63ktcy6z5fw5mxf9skndpg2p4648gfjfvvx4qg2lqvlryyycg5k7x9h4dw70t4qq37pegm".to_string();
+
+        let quote = wallet
+            .get_melt_quote_bolt11(&mint_url, invoice.clone(), CurrencyUnit::Sat, None)
+            .await?;
+        let result = wallet
+            .pay_invoice(&wallet_keyset, &quote, invoice, false)
+            .await?;
+        assert!(result.0.paid);
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_blank_blinded_messages_serialize() -> anyhow::Result<()> {
-        let localstore = SqliteLocalStore::with_in_memory().await?;
+    async fn test_recover_pending_melt() -> anyhow::Result<()> {
+        let fixture: TokenV3 = read_fixture("token_60.cashu")?.try_into()?;
+        let local_store = SqliteLocalStore::with_in_memory().await?;
         let wallet_keyset = create_test_wallet_keyset()?;
-        let mut tx = localstore.begin_tx().await?;
-        localstore.upsert_keyset(&mut tx, &wallet_keyset).await?;
+        let mut tx = local_store.begin_tx().await?;
+        local_store.add_proofs(&mut tx, &fixture.proofs()).await?;
+        local_store.upsert_keyset(&mut tx, &wallet_keyset).await?;
         tx.commit().await?;
 
-        let client = create_mock();
-
+        let swap_response = read_fixture_as::<PostSwapResponse>("post_swap_response_24_40.json")?;
+        let mut mock_client = create_mock();
+        mock_client
+            .expect_post_swap()
+            .returning(move |_, _, _| Ok(swap_response.clone()));
+        // the wallet stops after the split was stored, while the mint is called
+        mock_client
+            .expect_post_melt_bolt11()
+            .returning(|_, _, _, _| panic!("wallet stopped"));
         let wallet = WalletBuilder::new()
-            .with_client(client)
-            .with_localstore(localstore)
+            .with_client(mock_client)
+            .with_localstore(local_store.clone())
             .build()
             .await?;
 
-        let result = wallet
-            .create_blank(4000.into(), &KeysetId::new("00d31cecf59d18c0")?)
+        let quote =
+            read_fixture_as::<PostMeltQuoteBolt11Response>("post_melt_quote_response.json")?;
+        let invoice = "lnbcrt210n1pjg6mqhpp5pza5wzh0csjjuvfpjpv4zdjmg30vedj9ycv5tyfes9x7dp8axy0sdqqcqzzsxqyz5vqsp5vtxg4c5tw2s2zxxya2a7an0psn9mcfmlqctxzntm3sngnpyk3muq9qyyssqf8z5f90yu3wrmsufnnza25qjlnvc6ukdr094ckzn63ktcy6z5fw5mxf9skndpg2p4648gfjfvvx4qg2lqvlryyycg5k7x9h4dw70t4qq37pegm".to_string();
+        let keyset = wallet_keyset.clone();
+        // the panic of the spawned task stands in for a crash of the process
+        let stopped = tokio::task::LocalSet::new()
+            .run_until(async move {
+                tokio::task::spawn_local(async move {
+                    wallet.pay_invoice(&keyset, &quote, invoice, false).await
+                })
+                .await
+            })
+            .await;
+        assert!(stopped.is_err());
+
+        // the mint never received the proofs of the melt
+        let mut mock_client = create_mock();
+        mock_client
+            .expect_post_checkstate()
+            .times(1)
+            .returning(|_, ys| {
+                Ok(PostCheckStateResponse {
+                    states: ys
+                        .into_iter()
+                        .map(|y| ProofState {
+                            y,
+                            state: SpendState::Unspent,
+                            witness: None,
+                        })
+                        .collect(),
+                })
+            });
+        let wallet = WalletBuilder::new()
+            .with_client(mock_client)
+            .with_localstore(local_store)
+            .build()
             .await?;
-        for (blinded_message, _, _) in result {
-            let out = serde_json::to_string(&blinded_message)?;
-            assert!(!out.is_empty());
-        }
+
+        let balance = wallet.get_balance().await?;
+        let restored = wallet.recover_pending().await?;
+        assert_eq!(40, restored.total_amount());
+        assert_eq!(balance + 40, wallet.get_balance().await?);
+        assert!(wallet.recover_pending().await?.is_empty());
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_mint_tokens() -> anyhow::Result<()> {
-        let mint_response =
-            read_fixture_as::<PostMintBolt11Response>("post_mint_response_20.json")?;
-
-        let mut client = create_mock();
-        client
-            .expect_post_mint_bolt11()
-            .returning(move |_, _, _| Ok(mint_response.clone()));
+    async fn test_pay_invoice_amountless() -> anyhow::Result<()> {
+        let fixture = read_fixture("token_60.cashu")?; // 60 tokens (4,8,16,32)
 
-        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let local_store = SqliteLocalStore::with_in_memory().await?;
+        let fixture: TokenV3 = fixture.try_into()?;
+        let mut tx = local_store.begin_tx().await?;
+        local_store.add_proofs(&mut tx, &fixture.proofs()).await?;
         let wallet_keyset = create_test_wallet_keyset()?;
-        let mut tx = localstore.begin_tx().await?;
-        localstore.upsert_keyset(&mut tx, &wallet_keyset).await?;
+        local_store.upsert_keyset(&mut tx, &wallet_keyset).await?;
         tx.commit().await?;
 
+        let melt_response =
+            read_fixture_as::<PostMeltBolt11Response>("post_melt_response_21.json")?;
+        let mut mock_client = create_mock();
+        mock_client
+            .expect_post_melt_bolt11()
+            .returning(move |_, _, _, _| Ok(melt_response.clone()));
+
+        let quote_response =
+            read_fixture_as::<PostMeltQuoteBolt11Response>("post_melt_quote_response.json")?;
+        mock_client
+            .expect_post_melt_quote_bolt11()
+            .withf(|_, _, _, amount| *amount == Some(21))
+            .returning(move |_, _, _, _| Ok(quote_response.clone()));
+
+        let swap_response = read_fixture_as::<PostSwapResponse>("post_swap_response_24_40.json")?;
+        mock_client
+            .expect_post_swap()
+            .returning(move |_, _, _| Ok(swap_response.clone()));
+
+        let mint_url = Url::parse("http://localhost:8080/").expect("invalid url");
         let wallet = WalletBuilder::new()
-            .with_client(client)
-            .with_localstore(localstore)
+            .with_client(mock_client)
+            .with_localstore(local_store)
             .build()
             .await?;
 
+        // invoice without amount
+        let invoice = "lnbcrt1pj48ugqdqsv9kk7atww3kx2umnpp5wtxkappzcsrlkmgfs6g0zyct0hkhashh7hsaxz7e65slq9fkx7fssp5qurswpc8qurswpc8qurswpc8qurswpc8qurswpc8qurswpc8qurs9qrsgqcqzysgmg2qkuder3zzdmcj8vx7fax3v7jd2wt6s398tya0v9rfg6mfqqnxqajag8fm520745ee9fdt4qpvppyp2zkzz652dfw7d9qpp6mz8sqv8zjmu".to_string();
+
+        let quote = wallet
+            .get_melt_quote_bolt11(&mint_url, invoice.clone(), CurrencyUnit::Sat, Some(21))
+            .await?;
+        assert_eq!(21, quote.amount);
         let result = wallet
-            .mint_tokens(
-                &wallet_keyset,
-                &PaymentMethod::Bolt11,
-                20.into(),
-                "hash".to_string(),
-            )
+            .pay_invoice(&wallet_keyset, &quote, invoice, false)
             .await?;
-        assert_eq!(20, result.total_amount());
-        result.tokens.into_iter().for_each(|t| {
-            assert_eq!(wallet_keyset.mint_url, t.mint.expect("mint is empty"));
-        });
+        assert!(result.0.paid);
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_swap() -> anyhow::Result<()> {
-        let split_response = read_fixture_as::<PostSwapResponse>("post_swap_response_24_40.json")?;
-        let mut client = create_mock();
-        client
-            .expect_post_swap()
-            .returning(move |_, _, _| Ok(split_response.clone()));
-        let localstore = SqliteLocalStore::with_in_memory().await?;
-        let keyset = create_test_wallet_keyset()?;
-        let mut tx = localstore.begin_tx().await?;
-        localstore.upsert_keyset(&mut tx, &keyset).await?;
+    async fn test_pay_invoice_fee_reserve_too_high() -> anyhow::Result<()> {
+        let fixture = read_fixture("token_60.cashu")?; // 60 tokens (4,8,16,32)
+
+        let local_store = SqliteLocalStore::with_in_memory().await?;
+        let fixture: TokenV3 = fixture.try_into()?;
+        let mut tx = local_store.begin_tx().await?;
+        local_store.add_proofs(&mut tx, &fixture.proofs()).await?;
+        let wallet_keyset = create_test_wallet_keyset()?;
+        local_store.upsert_keyset(&mut tx, &wallet_keyset).await?;
         tx.commit().await?;
 
+        let mut mock_client = create_mock();
+        mock_client.expect_post_swap().never();
+        mock_client.expect_post_melt_bolt11().never();
+
         let wallet = WalletBuilder::new()
-            .with_client(client)
-            .with_localstore(localstore)
+            .with_client(mock_client)
+            .with_localstore(local_store)
             .build()
             .await?;
+        wallet.set_max_fee_reserve_ratio(Some(0.5)).await?;
 
-        let tokens = read_fixture("token_64.cashu")?.try_into()?;
-        let result = wallet.swap_tokens(&keyset, &tokens, 20.into()).await?;
-
-        let first = result.0;
-
-        assert_eq!(CurrencyUnit::Sat, first.clone().currency_unit.unwrap());
-        assert_eq!(24, first.total_amount());
+        let invoice = "lnbcrt1pj48ugqdqsv9kk7atww3kx2umnpp5wtxkappzcsrlkmgfs6g0zyct0hkhashh7hsaxz7e65slq9fkx7fssp5qurswpc8qurswpc8qurswpc8qurswpc8qurswpc8qurswpc8qurs9qrsgqcqzysgmg2qkuder3zzdmcj8vx7fax3v7jd2wt6s398tya0v9rfg6mfqqnxqajag8fm520745ee9fdt4qpvppyp2zkzz652dfw7d9qpp6mz8sqv8zjmu".to_string();
+        let mut quote =
+            read_fixture_as::<PostMeltQuoteBolt11Response>("post_melt_quote_response.json")?;
+        quote.amount = 20;
+        quote.fee_reserve = 11;
 
-        let second = result.1;
+        let result = wallet
+            .pay_invoice(&wallet_keyset, &quote, invoice, false)
+            .await;
+        assert!(matches!(
+            result,
+            Err(MokshaWalletError::FeeReserveTooHigh(11, 20))
+        ));
 
-        assert_eq!(CurrencyUnit::Sat, second.clone().currency_unit.unwrap());
-        assert_eq!(40, second.total_amount());
+        assert!(wallet.check_fee_reserve(20, 10, false).await.is_ok());
+        assert!(wallet.check_fee_reserve(20, 11, true).await.is_ok());
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_get_balance() -> anyhow::Result<()> {
+    async fn test_pay_invoice_change_exceeds_fee_reserve() -> anyhow::Result<()> {
         let fixture = read_fixture("token_60.cashu")?; // 60 tokens (4,8,16,32)
-        let fixture: TokenV3 = fixture.try_into()?;
+
         let local_store = SqliteLocalStore::with_in_memory().await?;
+        let fixture: TokenV3 = fixture.try_into()?;
         let mut tx = local_store.begin_tx().await?;
         local_store.add_proofs(&mut tx, &fixture.proofs()).await?;
+        let wallet_keyset = create_test_wallet_keyset()?;
+        local_store.upsert_keyset(&mut tx, &wallet_keyset).await?;
         tx.commit().await?;
 
+        // fee reserve in quote is 0, but the mint returns 8 sats change
+        let melt_response = PostMeltBolt11Response {
+            change: vec![BlindedSignature {
+                amount: 8,
+                c_: public_key_from_hex(
+                    "037074c4f53e326ee14ed67125f387d160e0e729351471b69ad41f7d5d21071e15",
+                ),
+                id: wallet_keyset.keyset_id.to_string(),
+            }],
+            ..read_fixture_as::<PostMeltBolt11Response>("post_melt_response_21.json")?
+        };
+        let mut mock_client = create_mock();
+        mock_client
+            .expect_post_melt_bolt11()
+            .returning(move |_, _, _, _| Ok(melt_response.clone()));
+
+        let quote_response =
+            read_fixture_as::<PostMeltQuoteBolt11Response>("post_melt_quote_response.json")?;
+        mock_client
+            .expect_post_melt_quote_bolt11()
+            .returning(move |_, _, _, _| Ok(quote_response.clone()));
+
+        let swap_response = read_fixture_as::<PostSwapResponse>("post_swap_response_24_40.json")?;
+        mock_client
+            .expect_post_swap()
+            .returning(move |_, _, _| Ok(swap_response.clone()));
+
+        let mint_url = Url::parse("http://localhost:8080/").expect("invalid url");
         let wallet = WalletBuilder::new()
-            .with_client(create_mock())
+            .with_client(mock_client)
             .with_localstore(local_store)
             .build()
             .await?;
 
-        let result = wallet.get_balance().await?;
-        assert_eq!(60, result);
+        // 21 sats
+        let invoice = "lnbcrt210n1pjg6mqhpp5pza5wzh0csjjuvfpjpv4zdjmg30vedj9ycv5tyfes9x7dp8axy0sdqqcqzzsxqyz5vqsp5vtxg4c5tw2s2zxxya2a7an0psn9mcfmlqctxzntm3sngnpyk3muq9qyyssqf8z5f90yu3wrmsufnnza25qjlnvc6ukdr094ckzn63ktcy6z5fw5mxf9skndpg2p4648gfjfvvx4qg2lqvlryyycg5k7x9h4dw70t4qq37pegm".to_string();
+
+        let quote = wallet
+            .get_melt_quote_bolt11(&mint_url, invoice.clone(), CurrencyUnit::Sat, None)
+            .await?;
+        let result = wallet
+            .pay_invoice(&wallet_keyset, &quote, invoice, false)
+            .await;
+        assert!(matches!(result, Err(MokshaWalletError::InvalidChange(_))));
         Ok(())
     }
-    // FIXME
 
     #[tokio::test]
-    async fn test_pay_invoice() -> anyhow::Result<()> {
+    async fn test_pay_invoice_invalid_change_keeps_pending_melt() -> anyhow::Result<()> {
         let fixture = read_fixture("token_60.cashu")?; // 60 tokens (4,8,16,32)
 
         let local_store = SqliteLocalStore::with_in_memory().await?;
@@ -1075,8 +5687,17 @@ mod tests {
         local_store.upsert_keyset(&mut tx, &wallet_keyset).await?;
         tx.commit().await?;
 
-        let melt_response =
-            read_fixture_as::<PostMeltBolt11Response>("post_melt_response_21.json")?; // 60 tokens (4,8,16,32)
+        // the change is signed with a keyset the wallet didn't request
+        let melt_response = PostMeltBolt11Response {
+            change: vec![BlindedSignature {
+                amount: 0,
+                c_: public_key_from_hex(
+                    "037074c4f53e326ee14ed67125f387d160e0e729351471b69ad41f7d5d21071e15",
+                ),
+                id: "00ffffffffffffff".to_owned(),
+            }],
+            ..read_fixture_as::<PostMeltBolt11Response>("post_melt_response_21.json")?
+        };
         let mut mock_client = create_mock();
         mock_client
             .expect_post_melt_bolt11()
@@ -1086,7 +5707,7 @@ mod tests {
             read_fixture_as::<PostMeltQuoteBolt11Response>("post_melt_quote_response.json")?;
         mock_client
             .expect_post_melt_quote_bolt11()
-            .returning(move |_, _, _| Ok(quote_response.clone()));
+            .returning(move |_, _, _, _| Ok(quote_response.clone()));
 
         let swap_response = read_fixture_as::<PostSwapResponse>("post_swap_response_24_40.json")?;
         mock_client
@@ -1096,20 +5717,28 @@ mod tests {
         let mint_url = Url::parse("http://localhost:8080/").expect("invalid url");
         let wallet = WalletBuilder::new()
             .with_client(mock_client)
-            .with_localstore(local_store)
+            .with_localstore(local_store.clone())
             .build()
             .await?;
 
-        // FIXME fix mock response: Warning Error in swap: input 32 != output 24 + 40
-
         // 21 sats
         let invoice = "lnbcrt210n1pjg6mqhpp5pza5wzh0csjjuvfpjpv4zdjmg30vedj9ycv5tyfes9x7dp8axy0sdqqcqzzsxqyz5vqsp5vtxg4c5tw2s2zxxya2a7an0psn9mcfmlqctxzntm3sngnpyk3muq9qyyssqf8z5f90yu3wrmsufnnza25qjlnvc6ukdr094ckzn63ktcy6z5fw5mxf9skndpg2p4648gfjfvvx4qg2lqvlryyycg5k7x9h4dw70t4qq37pegm".to_string();
 
         let quote = wallet
-            .get_melt_quote_bolt11(&mint_url, invoice.clone(), CurrencyUnit::Sat)
+            .get_melt_quote_bolt11(&mint_url, invoice.clone(), CurrencyUnit::Sat, None)
             .await?;
-        let result = wallet.pay_invoice(&wallet_keyset, &quote, invoice).await?;
-        assert!(result.0.paid);
+        let result = wallet
+            .pay_invoice(&wallet_keyset, &quote, invoice, false)
+            .await;
+        assert!(matches!(result, Err(MokshaWalletError::InvalidChange(_))));
+
+        let mut tx = local_store.begin_tx().await?;
+        let pending_melts = local_store.get_pending_melts(&mut tx).await?;
+        let transactions = local_store.list_transactions(&mut tx, 10).await?;
+        tx.commit().await?;
+        // the proofs split off for the melt stay pending until recover_pending settles them
+        assert_eq!(40, pending_melts[0].proofs.total_amount());
+        assert!(transactions.is_empty());
         Ok(())
     }
 
@@ -1141,7 +5770,7 @@ mod tests {
             read_fixture_as::<PostMeltQuoteBolt11Response>("post_melt_quote_response.json")?;
         mock_client
             .expect_post_melt_quote_bolt11()
-            .returning(move |_, _, _| Ok(quote_response.clone()));
+            .returning(move |_, _, _, _| Ok(quote_response.clone()));
         let swap_response = read_fixture_as::<PostSwapResponse>("post_swap_response_24_40.json")?;
         mock_client
             .expect_post_swap()
@@ -1157,12 +5786,19 @@ mod tests {
         let invoice = "lnbcrt210n1pjg6mqhpp5pza5wzh0csjjuvfpjpv4zdjmg30vedj9ycv5tyfes9x7dp8axy0sdqqcqzzsxqyz5vqsp5vtxg4c5tw2s2zxxya2a7an0psn9mcfmlqctxzntm3sngnpyk3muq9qyyssqf8z5f90yu3wrmsufnnza25qjlnvc6ukdr094ckzn63ktcy6z5fw5mxf9skndpg2p4648gfjfvvx4qg2lqvlryyycg5k7x9h4dw70t4qq37pegm".to_string();
 
         let quote = wallet
-            .get_melt_quote_bolt11(&wallet_keyset.mint_url, invoice.clone(), CurrencyUnit::Sat)
+            .get_melt_quote_bolt11(
+                &wallet_keyset.mint_url,
+                invoice.clone(),
+                CurrencyUnit::Sat,
+                None,
+            )
             .await?;
 
         let wallet_keyset = create_test_wallet_keyset()?;
 
-        let result = wallet.pay_invoice(&wallet_keyset, &quote, invoice).await?;
+        let result = wallet
+            .pay_invoice(&wallet_keyset, &quote, invoice, false)
+            .await?;
         assert!(!result.0.paid);
         let mut tx = localstore.begin_tx().await?;
         assert_eq!(64, localstore.get_proofs(&mut tx).await?.total_amount());
@@ -1170,6 +5806,162 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_sync_spent_digest() -> anyhow::Result<()> {
+        let keyset = create_test_wallet_keyset()?;
+        let tokens: TokenV3 = read_fixture("token_60.cashu")?.try_into()?;
+        let proofs: Proofs = tokens
+            .proofs()
+            .proofs()
+            .into_iter()
+            .map(|p| Proof {
+                keyset_id: keyset.keyset_id.to_string(),
+                ..p
+            })
+            .collect::<Vec<_>>()
+            .into();
+        let y = |amount: u64| {
+            proofs
+                .proofs()
+                .into_iter()
+                .find(|proof| proof.amount == amount)
+                .and_then(|proof| proof.y().ok())
+                .expect("proof not found")
+        };
+
+        // the digest flags 4 and 8, but only 4 is spent
+        let (spent_y, unspent_y) = (y(4), y(8));
+        let mut client = create_mock();
+        client
+            .expect_get_spent_digest()
+            .times(1)
+            .returning(move |_| Ok(SpentDigest::new(&[spent_y, unspent_y])));
+        client
+            .expect_post_checkstate()
+            .times(1)
+            .withf(move |_, ys| ys.len() == 2 && ys.contains(&spent_y) && ys.contains(&unspent_y))
+            .returning(move |_, _| {
+                Ok(PostCheckStateResponse {
+                    states: vec![
+                        ProofState {
+                            y: spent_y,
+                            state: SpendState::Spent,
+                            witness: None,
+                        },
+                        ProofState {
+                            y: unspent_y,
+                            state: SpendState::Unspent,
+                            witness: None,
+                        },
+                    ],
+                })
+            });
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        localstore.add_proofs(&mut tx, &proofs).await?;
+        tx.commit().await?;
+
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+
+        let spent = wallet.sync_spent_digest(&keyset.mint_url).await?;
+        assert_eq!(1, spent.len());
+        assert_eq!(4, spent.total_amount());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_restore_tokens() -> anyhow::Result<()> {
+        let mint_keyset = MintKeyset::new("mykey", "");
+        let keyset = WalletKeyset::new(
+            &KeysetId::new(&mint_keyset.keyset_id)?,
+            &Url::parse("http://127.0.0.1:3338")?,
+            &CurrencyUnit::Sat,
+            0,
+            mint_keyset.public_keys.clone(),
+            true,
+        );
+
+        // the mint signed the outputs at index 3 and 5 of the first batch
+        let mut client = create_mock();
+        let mut batches = 0;
+        client
+            .expect_post_restore()
+            .times(2)
+            .returning(move |_, outputs| {
+                batches += 1;
+                if batches > 1 {
+                    return Ok(PostRestoreResponse::default());
+                }
+                let outputs = vec![
+                    BlindedMessage {
+                        amount: 8,
+                        ..outputs[3].clone()
+                    },
+                    BlindedMessage {
+                        amount: 2,
+                        ..outputs[5].clone()
+                    },
+                ];
+                let signatures = outputs
+                    .iter()
+                    .map(|output| BlindedSignature {
+                        amount: output.amount,
+                        c_: Dhke::new()
+                            .step2_bob(output.b_, &mint_keyset.private_keys[&output.amount])
+                            .expect("signing failed"),
+                        id: mint_keyset.keyset_id.clone(),
+                    })
+                    .collect();
+                Ok(PostRestoreResponse {
+                    outputs,
+                    signatures,
+                })
+            });
+        // the proof of index 3 is spent already
+        client.expect_post_checkstate().returning(|_, ys| {
+            Ok(PostCheckStateResponse {
+                states: ys
+                    .iter()
+                    .enumerate()
+                    .map(|(i, y)| ProofState {
+                        y: *y,
+                        state: match i {
+                            0 => SpendState::Spent,
+                            _ => SpendState::Unspent,
+                        },
+                        witness: None,
+                    })
+                    .collect(),
+            })
+        });
+
+        let localstore = SqliteLocalStore::with_in_memory().await?;
+        let mut tx = localstore.begin_tx().await?;
+        localstore.upsert_keyset(&mut tx, &keyset).await?;
+        tx.commit().await?;
+        let wallet = WalletBuilder::new()
+            .with_client(client)
+            .with_localstore(localstore)
+            .build()
+            .await?;
+        let keyset = wallet.get_wallet_keysets().await?[0].clone();
+
+        let restored = wallet.restore_tokens(&keyset).await?;
+        assert_eq!(2, restored.total_amount());
+        let mut tx = wallet.localstore.begin_tx().await?;
+        let stored = wallet.localstore.get_stored_proofs(&mut tx).await?;
+        assert_eq!(Some(5), stored.0[0].derivation_index);
+        assert_eq!(2, wallet.get_balance().await?);
+        assert_eq!(5, wallet.get_wallet_keysets().await?[0].last_index);
+        Ok(())
+    }
+
     fn create_test_wallet_keyset() -> anyhow::Result<WalletKeyset> {
         let pub_keys = read_fixture_as::<HashMap<u64, PublicKey>>("pub_keys.json")?;
         let keyset_id = KeysetId::new("00d31cecf59d18c0")?;